@@ -0,0 +1,82 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use interpreter::Interpreter;
+use lexer::Lexer;
+use parser::{Parser, Statement};
+
+fn parse(source: &str) -> Vec<Statement> {
+	let tokens = Lexer::new(String::from(source)).scan_tokens().unwrap();
+	Parser::new(tokens).parse().unwrap()
+}
+
+fn interpret(statements: &[Statement]) {
+	let mut interpreter = Interpreter::new();
+	interpreter.interpret(statements.to_vec()).unwrap();
+}
+
+fn lexer(c: &mut Criterion) {
+	let source = r#"
+	f fib(n) {
+		if n < 2 {
+			return n
+		}
+		return fib(n - 1) + fib(n - 2)
+	}
+	greeting = "hello " + "world"
+	total = (1 + 2.5) * 3 / 4 - 5 % 6 ^ 7
+	"#
+	.repeat(20);
+
+	c.bench_function("lex large file", |b| {
+		b.iter(|| Lexer::new(black_box(source.clone())).scan_tokens().unwrap())
+	});
+}
+
+fn parser(c: &mut Criterion) {
+	let depth = 200;
+	let source = format!("{}1{}", "(1 + ".repeat(depth), ")".repeat(depth));
+	let tokens = Lexer::new(source).scan_tokens().unwrap();
+
+	c.bench_function("parse deep expression", |b| {
+		b.iter(|| Parser::new(black_box(tokens.clone())).parse().unwrap())
+	});
+}
+
+fn interpreter(c: &mut Criterion) {
+	let while_loop = parse(
+		"i = 0
+		while i < 10000 {
+			i = i + 1
+		}",
+	);
+	c.bench_function("interpret while loop", |b| {
+		b.iter(|| interpret(&while_loop))
+	});
+
+	let recursion = parse(
+		"f fib(n) {
+			if n < 2 {
+				return n
+			}
+			return fib(n - 1) + fib(n - 2)
+		}
+		fib(15)",
+	);
+	c.bench_function("interpret recursive calls", |b| {
+		b.iter(|| interpret(&recursion))
+	});
+
+	let concatenation = parse(
+		r#"text = ""
+		i = 0
+		while i < 1000 {
+			text = text + "ilo"
+			i = i + 1
+		}"#,
+	);
+	c.bench_function("interpret string concatenation", |b| {
+		b.iter(|| interpret(&concatenation))
+	});
+}
+
+criterion_group!(benches, lexer, parser, interpreter);
+criterion_main!(benches);
@@ -0,0 +1,173 @@
+//! Runs every program of the `examples` directory and compares what it prints, followed by the
+//! errors it reports, with the `.expected` file next to it.
+//!
+//! `cargo test -p tests --test examples -- --bless` writes the `.expected` files from the current
+//! behavior instead, to add an example or accept a change of output.
+
+use std::{
+	cell::RefCell,
+	fs,
+	path::{Path, PathBuf},
+	process::ExitCode,
+	rc::Rc,
+};
+
+use error_manager::{set_color_choice, ColorChoice};
+use ilo::{run_in, Archive, Host, Interpreter, NullHost};
+
+/// Host recording what scripts print. Scripts get no input, and the time is always 0, so that
+/// their output does not depend on the environment.
+struct CapturingHost(Rc<RefCell<String>>);
+
+impl Host for CapturingHost {
+	fn print(&mut self, text: &str) {
+		let mut output = self.0.borrow_mut();
+		output.push_str(text);
+		output.push('\n');
+	}
+
+	fn ask(&mut self, _prompt: &str) -> String {
+		String::new()
+	}
+
+	fn ask_timeout(&mut self, _prompt: &str, _seconds: f64) -> Option<String> {
+		None
+	}
+
+	fn multi_select(&mut self, _prompt: &str, _options: &[String]) -> Option<Vec<usize>> {
+		None
+	}
+
+	fn read_line(&mut self) -> Option<Option<String>> {
+		None
+	}
+
+	fn read_all(&mut self) -> Option<String> {
+		None
+	}
+
+	fn time(&mut self) -> f64 {
+		0.0
+	}
+
+	fn clock(&mut self) -> f64 {
+		0.0
+	}
+
+	fn run_command(&mut self, _program: &str, _args: &[&str]) -> Option<String> {
+		None
+	}
+
+	fn run_shell(&mut self, _command: &str) -> Option<String> {
+		None
+	}
+
+	fn watch(&mut self, _path: &str) -> Option<Box<dyn Iterator<Item = String>>> {
+		None
+	}
+
+	fn download(&mut self, _url: &str, _destination: &str) -> Option<u64> {
+		None
+	}
+
+	fn create_archive(&mut self, _format: Archive, _source: &str, _destination: &str) -> bool {
+		false
+	}
+
+	fn extract_archive(&mut self, _format: Archive, _archive: &str, _destination: &str) -> bool {
+		false
+	}
+
+	fn read_file(&mut self, _path: &str) -> Option<String> {
+		None
+	}
+
+	fn file_exists(&mut self, _path: &str) -> bool {
+		false
+	}
+
+	fn remove_file(&mut self, _path: &str) -> bool {
+		false
+	}
+
+	fn copy_file(&mut self, _source: &str, _destination: &str) -> bool {
+		false
+	}
+
+	fn move_file(&mut self, _source: &str, _destination: &str) -> bool {
+		false
+	}
+
+	fn list_dir(&mut self, _path: &str, _recursive: bool) -> Option<Vec<String>> {
+		None
+	}
+
+	fn task_host(&self) -> Box<dyn Host + Send> {
+		Box::new(NullHost)
+	}
+}
+
+fn run_example(path: &Path) -> String {
+	let source = fs::read_to_string(path).expect("could not read the example");
+	let output = Rc::new(RefCell::new(String::new()));
+
+	let mut interpreter = Interpreter::new();
+	interpreter.set_host(Box::new(CapturingHost(output.clone())));
+	let diagnostics = run_in(&mut interpreter, &source).err().unwrap_or_default();
+
+	let mut result = output.take();
+	for diagnostic in diagnostics {
+		result.push_str(&format!("{diagnostic}\n"));
+	}
+	result
+}
+
+fn main() -> ExitCode {
+	let bless = std::env::args().any(|arg| arg == "--bless");
+	set_color_choice(ColorChoice::Never);
+
+	let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("../examples");
+	let mut examples: Vec<PathBuf> = fs::read_dir(&directory)
+		.expect("could not read the examples directory")
+		.map(|entry| entry.unwrap().path())
+		.filter(|path| path.extension().is_some_and(|extension| extension == "ilo"))
+		.collect();
+	examples.sort();
+
+	let mut failures = 0;
+	for example in &examples {
+		let name = example.file_name().unwrap().to_string_lossy();
+		let actual = run_example(example);
+		let expected_path = example.with_extension("expected");
+
+		if bless {
+			fs::write(&expected_path, &actual).expect("could not write the expected output");
+			println!("blessed {name}");
+			continue;
+		}
+
+		match fs::read_to_string(&expected_path) {
+			Ok(expected) if expected == actual => println!("example {name} ... ok"),
+			Ok(expected) => {
+				failures += 1;
+				println!("example {name} ... FAILED");
+				println!("--- expected\n{expected}--- actual\n{actual}---");
+			}
+			Err(_) => {
+				failures += 1;
+				println!("example {name} ... FAILED");
+				println!(
+					"missing {}, run with `--bless` to create it",
+					expected_path.display()
+				);
+			}
+		}
+	}
+
+	println!("\n{} examples, {failures} failed", examples.len());
+	if failures == 0 {
+		ExitCode::SUCCESS
+	} else {
+		ExitCode::FAILURE
+	}
+}
@@ -1,6 +1,9 @@
-use interpreter::Interpreter;
-use lexer::Lexer;
+use bytecode::{compile, disassemble, load, LoadError};
+use error_manager::{capture_errors, set_color_choice, ColorChoice};
+use interpreter::{Interpreter, Param, Statistics};
+use lexer::{Lexer, Symbol};
 use parser::Parser;
+use transpiler::{transpile, transpile_with_source_map};
 
 fn run(source: String) -> Result<String, ()> {
 	let mut lexer = Lexer::new(source);
@@ -14,6 +17,19 @@ fn run(source: String) -> Result<String, ()> {
 	interpreter.interpret(statements)
 }
 
+fn run_sandboxed(source: String) -> Result<String, ()> {
+	let mut lexer = Lexer::new(source);
+	let tokens = lexer.scan_tokens()?;
+
+	let mut parser = Parser::new(tokens);
+	let statements = parser.parse()?;
+
+	let mut interpreter = Interpreter::new();
+	interpreter.set_sandbox(true);
+
+	interpreter.interpret(statements)
+}
+
 fn ev(source: &str) -> String {
 	if let Ok(result) = run(String::from(source)) {
 		return result;
@@ -21,12 +37,16 @@ fn ev(source: &str) -> String {
 	String::from("err")
 }
 
+fn ev_sandboxed(source: &str) -> String {
+	if let Ok(result) = run_sandboxed(String::from(source)) {
+		return result;
+	}
+	String::from("err")
+}
+
 fn has_lexical_error(source: &str) -> bool {
 	let mut lexer = Lexer::new(String::from(source));
-	if let Ok(_) = lexer.scan_tokens() {
-		return false;
-	}
-	true
+	lexer.scan_tokens().is_err()
 }
 
 fn has_parsing_error(source: &str) -> bool {
@@ -35,10 +55,7 @@ fn has_parsing_error(source: &str) -> bool {
 
 	let mut parser = Parser::new(tokens);
 
-	if let Ok(_) = parser.parse() {
-		return false;
-	}
-	true
+	parser.parse().is_err()
 }
 
 #[test]
@@ -46,6 +63,17 @@ fn lexical_error() {
 	assert!(has_lexical_error("something;"));
 }
 
+#[test]
+fn symbols() {
+	let tokens = Lexer::new(String::from("total = total + other"))
+		.scan_tokens()
+		.unwrap();
+	assert_eq!(tokens[0].symbol(), tokens[2].symbol());
+	assert_ne!(tokens[0].symbol(), tokens[4].symbol());
+	assert_eq!("other", tokens[4].symbol().name());
+	assert_eq!(tokens[4].symbol(), Symbol::intern("other"));
+}
+
 #[test]
 fn comments() {
 	assert_eq!("", ev("// this is a comment"));
@@ -60,6 +88,26 @@ fn comments() {
 	assert!(has_lexical_error("/* this is an unterminated comment"));
 }
 
+#[test]
+fn scientific_notation() {
+	assert_eq!("1000000000", ev("1e9"));
+	assert_eq!("0.0025", ev("2.5e-3"));
+	assert_eq!("150", ev("1.5E+2"));
+	let tokens = Lexer::new(String::from("1e3 + 2")).scan_tokens().unwrap();
+	assert_eq!(5, tokens[1].column());
+	assert!(has_lexical_error("1e999"));
+}
+
+#[test]
+fn digit_separators() {
+	assert_eq!("1000000", ev("1_000_000"));
+	assert_eq!("1234.5678", ev("1_234.567_8"));
+	assert_eq!("1000", ev("1e0_3"));
+	for source in ["1_", "1__0", "1_.5", "2_e3"] {
+		assert!(has_lexical_error(source), "{source}");
+	}
+}
+
 #[test]
 fn math_expressions() {
 	assert_eq!("2", ev("1+1"));
@@ -222,7 +270,129 @@ fn string_expressions() {
 	assert_eq!("err", ev(r#""hello " * true"#));
 	assert_eq!("err", ev(r#""hello " + true"#));
 	assert_eq!("err", ev(r#""hello " + 3"#));
-	assert_eq!(true, has_lexical_error(r#""unterminated string"#));
+	assert!(has_lexical_error(r#""unterminated string"#));
+
+	// Appending to a string variable
+	assert_eq!(
+		"ab ab ab (ab ab ab )",
+		ev(r#"text = ""
+		i = 0
+		while i < 3 {
+			text = text + "ab" + " "
+			i = i + 1
+		}
+		text = text + "(" + text + ")"
+		text"#)
+	);
+	assert_eq!(
+		"a",
+		ev(r#"text = "a"
+		f change() {
+			text = "changed"
+			return ""
+		}
+		text = text + change()
+		text"#)
+	);
+	assert_eq!(
+		"err",
+		ev(r#"text = "a"
+	text = text + 3"#)
+	);
+}
+
+#[test]
+fn constants() {
+	assert_eq!("3.141592653589793", ev("PI"));
+	assert_eq!("2.718281828459045", ev("E"));
+	assert_eq!("true", ev("INFINITY > 1e308"));
+	assert_eq!("6.283185307179586", ev("r = 1\n2 * PI * r"));
+	assert_eq!("3", ev("PI = 3\nPI"));
+	assert_eq!("err", ev(r#"PI = "pi""#));
+}
+
+#[test]
+fn json() {
+	// ilo strings cannot contain double quotes, which `quoted` puts instead of single quotes
+	let run = |source: &str| {
+		let mut interpreter = Interpreter::new();
+		interpreter.define_typed_native("quoted", vec![Param::String], |args| match &args[0] {
+			interpreter::Value::String(text) => {
+				interpreter::Value::String(text.replace('\'', "\""))
+			}
+			_ => unreachable!(),
+		});
+		let tokens = Lexer::new(String::from(source)).scan_tokens().unwrap();
+		interpreter
+			.interpret(Parser::new(tokens).parse().unwrap())
+			.unwrap_or_else(|()| String::from("err"))
+	};
+
+	assert_eq!(
+		r#"[1, "a", [true, empty], -0.5]"#,
+		run(r#"json_parse(quoted(" [1, 'a', [true, null], -5e-1] "))"#)
+	);
+	assert_eq!(
+		"é\n😀/",
+		run(r#"json_parse(quoted("'\u00e9\n\ud83d\ude00\/'"))"#)
+	);
+	assert_eq!("empty", run(r#"type(json_parse("null"))"#));
+	for invalid in [
+		"{}",
+		"[1,]",
+		"01",
+		".5",
+		"1.",
+		"1e",
+		"tru",
+		"'a",
+		"'\\ud83d'",
+		"[1] 2",
+		"",
+	] {
+		let source = format!("json_parse(quoted(\"{invalid}\"))");
+		assert_eq!("err", run(&source), "{invalid}");
+	}
+	let (result, errors) = capture_errors(|| run("json_parse(\"[1,]\")"));
+	assert_eq!("err", result);
+	assert_eq!(
+		"`json_parse` could not parse the JSON (unexpected `]`)",
+		errors[0].message()
+	);
+	assert_eq!("err", run("json_parse(1)"));
+	let nested = |depth| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+	assert_eq!(
+		nested(64).replace("][", "], ["),
+		run(&format!("json_parse(\"{}\")", nested(64)))
+	);
+	let (result, errors) = capture_errors(|| run(&format!("json_parse(\"{}\")", nested(100_000))));
+	assert_eq!("err", result);
+	assert_eq!(
+		"`json_parse` could not parse the JSON (arrays cannot be nested more than 64 levels deep)",
+		errors[0].message()
+	);
+
+	assert_eq!(
+		r#"[1,"a\"b\\",null,[],-0.5]"#,
+		run(r#"n = empty(number)
+json_stringify([1, quoted("a'b\"), n, [], -1 / 2], false)"#)
+	);
+	assert_eq!(
+		"[\n\t1,\n\t[\n\t\t2\n\t]\n]",
+		run("json_stringify([1, [2]], true)")
+	);
+	assert_eq!("\"\\n\"", run("json_stringify(\"\n\", true)"));
+	assert_eq!("null", run("json_stringify(INFINITY, false)"));
+	assert_eq!("err", run("json_stringify(out, false)"));
+	assert_eq!("err", run("json_stringify(1)"));
+	let (result, errors) = capture_errors(|| {
+		run("l = []\ni = 0\nwhile i < 100 {\n\tl = [l]\n\ti++\n}\njson_stringify(l, false)")
+	});
+	assert_eq!("err", result);
+	assert_eq!(
+		"`json_stringify` could not convert the value (lists cannot be nested more than 64 levels deep)",
+		errors[0].message()
+	);
 }
 
 #[test]
@@ -244,15 +414,307 @@ fn native_functions() {
 		out(54)"
 	));
 
+	// Type (`type`)
+	assert_eq!("number", ev("type(1)"));
+	assert_eq!("string", ev(r#"type("a")"#));
+	assert_eq!("boolean", ev("type(true)"));
+	assert_eq!("list", ev("type([1])"));
+	assert_eq!("function(2)", ev("type((a, b) -> a + b)"));
+	assert_eq!("function(1)", ev("type(size)"));
+	assert_eq!("number", ev("n = empty(number)\ntype(n)"));
+	assert_eq!("empty", ev("f nothing() {\n}\ntype(nothing())"));
+	assert_eq!("Color", ev("enum Color { Red }\ntype(Color.Red)"));
+
+	// Arguments (`args`)
+	assert_eq!("[]", ev("args()"));
+	let mut interpreter = Interpreter::new();
+	interpreter.set_arguments(vec![String::from("-v"), String::from("a b")]);
+	let tokens = Lexer::new(String::from("args()")).scan_tokens().unwrap();
+	assert_eq!(
+		Ok(String::from(r#"["-v", "a b"]"#)),
+		interpreter.interpret(Parser::new(tokens).parse().unwrap())
+	);
+
 	// Size (`size`)
 	assert_eq!("11", ev(r#"size("hello world")"#));
-	assert_eq!("0", ev("size(3)"));
-	assert_eq!("0", ev("size(true)"));
+	assert_eq!("5", ev("s = \"héllo\"\nsize(s)"));
+	assert_eq!("err", ev("size(3)"));
+	assert_eq!("err", ev("size(true)"));
+
+	// Math (`abs`, `floor`, `ceil`, `round` and `sqrt`)
+	assert_eq!("2.5", ev("abs(-2.5)"));
+	assert_eq!("-3", ev("floor(-2.5)"));
+	assert_eq!("-2", ev("ceil(-2.5)"));
+	assert_eq!("3", ev("round(2.5)"));
+	assert_eq!("1.4142135623730951", ev("sqrt(2)"));
+	assert_eq!("err", ev("sqrt(-1)"));
+	assert_eq!("err", ev(r#"abs("-1")"#));
+	assert_eq!("err", ev("floor(true)"));
+	assert_eq!("err", ev("sqrt([4])"));
+
+	// Case (`upper` and `lower`)
+	assert_eq!("HELLO, WORLD", ev(r#"upper("Hello, world")"#));
+	assert_eq!("ÉCOLE STRASSE", ev(r#"upper("école straße")"#));
+	assert_eq!("ilo", ev(r#"lower("ILO")"#));
+	assert_eq!("όσος", ev(r#"lower("ΌΣΟΣ")"#));
+	assert_eq!("err", ev("upper(1)"));
+
+	// Search (`index_of` and `last_index_of`)
+	assert_eq!("2", ev(r#"index_of("a=b=c", "b")"#));
+	assert_eq!("1", ev(r#"index_of("a=b=c", "=")"#));
+	assert_eq!("3", ev(r#"last_index_of("a=b=c", "=")"#));
+	assert_eq!("1", ev(r#"index_of("été", "t")"#));
+	assert_eq!("2", ev(r#"last_index_of("été", "é")"#));
+	assert_eq!(
+		"true",
+		ev(r#"i = index_of("abc", "d")
+i?"#)
+	);
+	assert_eq!(
+		"true",
+		ev(r#"i = last_index_of("abc", "d")
+i?"#)
+	);
+	assert_eq!("err", ev(r#"index_of("abc", 1)"#));
+
+	// Padding (`pad_start` and `pad_end`)
+	assert_eq!("007", ev(r#"pad_start("7", 3, "0")"#));
+	assert_eq!("ab  ", ev(r#"pad_end("ab", 4, " ")"#));
+	assert_eq!("-=-ab", ev(r#"pad_start("ab", 5, "-=")"#));
+	assert_eq!("été.", ev(r#"pad_end("été", 4, ".")"#));
+	assert_eq!("long", ev(r#"pad_start("long", 2, " ")"#));
+	assert_eq!("ab", ev(r#"pad_end("ab", 4, "")"#));
+	assert_eq!("err", ev(r#"pad_start(7, 3, "0")"#));
+	assert_eq!("err", ev(r#"pad_start("a", 1e20, "x")"#));
+	assert_eq!("err", ev(r#"pad_end("a", 1e20, "é")"#));
+	assert_eq!("a", ev(r#"pad_start("a", -3, "x")"#));
+	assert_eq!("a", ev(r#"pad_end("a", -3, "x")"#));
+
+	// Signatures (the arguments of natives are checked before the call)
+	let statements = |source: &str| {
+		let tokens = Lexer::new(String::from(source)).scan_tokens().unwrap();
+		Parser::new(tokens).parse().unwrap()
+	};
+	let (result, errors) =
+		capture_errors(|| Interpreter::new().interpret(statements("a = 1\nsize(a)")));
+	assert!(result.is_err());
+	assert_eq!(
+		vec!["Type error at line 2, column 7: Argument 1 of `size` should be a string or a list (found number 1)."],
+		errors.iter().map(ToString::to_string).collect::<Vec<_>>()
+	);
+	let mut interpreter = Interpreter::new();
+	interpreter.define_typed_native("twice", vec![Param::Number], |args| match args[0] {
+		interpreter::Value::Number(number) => interpreter::Value::Number(number * 2.0),
+		_ => unreachable!(),
+	});
+	assert_eq!(
+		Ok(String::from("4")),
+		interpreter.interpret(statements("twice(2)"))
+	);
+	let (result, errors) =
+		capture_errors(|| interpreter.interpret(statements("f g(a) {\n\treturn a\n}\ntwice(g)")));
+	assert!(result.is_err());
+	assert_eq!(
+		"Argument 1 of `twice` should be a number (found function(1))",
+		errors[0].message()
+	);
+
+	// Styling (`style`)
+	assert_eq!("hello", ev(r#"style("hello", "bold red")"#));
+	assert_eq!("hello", ev(r#"style("hello", "")"#));
+	assert_eq!("hello", ev(r#"style("hello", "sparkly")"#));
+	assert_eq!("err", ev(r#"style(3, "red")"#));
+
+	// Durations (`clock_ms`, `timer_start`, `timer_elapsed`, `format_duration` and `duration`)
+	assert_eq!(
+		"true",
+		ev("start = clock_ms()
+			end = clock_ms()
+			end >= start")
+	);
+	assert_eq!(
+		"true",
+		ev("timer = timer_start()
+			elapsed = timer_elapsed(timer)
+			elapsed >= 0 and elapsed < 60000")
+	);
+	assert_eq!("err", ev(r#"timer_elapsed("timer")"#));
+	assert_eq!("0ms", ev("format_duration(0)"));
+	assert_eq!("250ms", ev("format_duration(250.4)"));
+	assert_eq!("1.5s", ev("format_duration(1500)"));
+	assert_eq!("59.99s", ev("format_duration(59990)"));
+	assert_eq!("2m 0s", ev("format_duration(120000)"));
+	assert_eq!("1h 2m 3s", ev("format_duration(3723000)"));
+	assert_eq!("err", ev("format_duration(-1)"));
+	assert_eq!("err", ev("duration(INFINITY)"));
+	assert_eq!("err", ev(r#"format_duration("1s")"#));
+	assert_eq!("1h 2m 3s", ev("duration(3723000)"));
+	assert_eq!("err", ev("duration(true)"));
 
 	// Command execution (`cmd`)
 	assert_eq!("hello world", ev(r#"cmd("echo -n hello world")"#));
 	assert_eq!("", ev(r#"cmd("")"#));
-	assert_eq!("", ev("cmd(4)"));
+	assert_eq!("err", ev("cmd(4)"));
+	assert_eq!("a b-c", ev(r#"cmd("printf '%s-%s' 'a b' c")"#));
+	assert_eq!("it's-", ev(r#"cmd("printf %s-%s it\'s ''")"#));
+	assert_eq!("2\n", ev(r#"shell("printf 'a\nb\n' | wc -l | tr -d ' '")"#));
+	assert_eq!("err", ev("shell(4)"));
+
+	// Glob patterns (`glob_match`)
+	for (pattern, path) in [
+		("src/*.ilo", "src/main.ilo"),
+		("src/**/*.ilo", "src/main.ilo"),
+		("src/**/*.ilo", "src/a/b/main.ilo"),
+		("**", "a/b"),
+		("?.txt", "a.txt"),
+		("[a-c]x[!y]", "bxz"),
+		("[^a]", "b"),
+		("[ab", "[ab"),
+		("", ""),
+	] {
+		let source = format!(r#"glob_match("{pattern}", "{path}")"#);
+		assert_eq!("true", ev(&source), "{source}");
+	}
+	for (pattern, path) in [
+		("src/*.ilo", "src/a/main.ilo"),
+		("src/**/*.ilo", "tests/main.ilo"),
+		("*.ilo", "main.ilo.bak"),
+		("?", "/"),
+		("?.txt", "ab.txt"),
+		("[a-c]", "d"),
+		("[!a]", "a"),
+		("a*", ""),
+	] {
+		let source = format!(r#"glob_match("{pattern}", "{path}")"#);
+		assert_eq!("false", ev(&source), "{source}");
+	}
+	// each wildcard tries every split of the path, which must not take exponential time
+	let path = "a".repeat(40);
+	for pattern in ["*a*a*a*a*a*a*a*b", "**a**a**a**a**a**a**a**b"] {
+		let source = format!(r#"glob_match("{pattern}", "{path}")"#);
+		assert_eq!("false", ev(&source), "{source}");
+	}
+	assert_eq!("err", ev(r#"glob_match("*", 3)"#));
+
+	// Watching files (`watch`)
+	let directory = std::env::temp_dir().join(format!("ilo_watch_{}", std::process::id()));
+	std::fs::create_dir_all(&directory).unwrap();
+	let writer = {
+		let file = directory.join("changed.txt");
+		std::thread::spawn(move || {
+			std::thread::sleep(std::time::Duration::from_millis(300));
+			std::fs::write(file, "changed").unwrap();
+		})
+	};
+	assert_eq!(
+		"true",
+		ev(&format!(
+			r#"found = false
+			f changed(path) {{
+				found = path.size() > 0
+				return false
+			}}
+			watch("{}", changed)
+			found"#,
+			directory.display()
+		))
+	);
+	writer.join().unwrap();
+	std::fs::remove_dir_all(directory).unwrap();
+	assert_eq!("", ev(r#"watch("/does/not/exist", out)"#));
+	assert_eq!("err", ev("watch(3, out)"));
+	assert_eq!("err", ev(r#"watch(".", time)"#));
+
+	// Archives (`zip`, `unzip`, `tar` and `untar`)
+	let directory = std::env::temp_dir().join(format!("ilo_archive_{}", std::process::id()));
+	std::fs::create_dir_all(directory.join("files/nested")).unwrap();
+	std::fs::write(directory.join("files/a.txt"), "a").unwrap();
+	std::fs::write(directory.join("files/nested/b.txt"), "b").unwrap();
+	for (create, extract, archive) in [
+		("zip", "unzip", "files.zip"),
+		("tar", "untar", "files.tar.gz"),
+	] {
+		let (source, archive, output) = (
+			directory.join("files"),
+			directory.join(archive),
+			directory.join(format!("{create}_output")),
+		);
+		assert_eq!(
+			"true",
+			ev(&format!(
+				r#"{create}("{}", "{}") and {extract}("{}", "{}")"#,
+				source.display(),
+				archive.display(),
+				archive.display(),
+				output.display()
+			))
+		);
+		assert_eq!(
+			"b",
+			std::fs::read_to_string(output.join("files/nested/b.txt")).unwrap()
+		);
+		assert_eq!(
+			"false",
+			ev(&format!(
+				r#"{extract}("{}", "{}")"#,
+				source.display(),
+				output.display()
+			))
+		);
+		// no archive is left behind when it cannot be created
+		let missing = directory.join("missing.archive");
+		assert_eq!(
+			"false",
+			ev(&format!(
+				r#"{create}("/does/not/exist", "{}")"#,
+				missing.display()
+			))
+		);
+		assert!(!missing.exists());
+		assert_eq!("err", ev(&format!("{create}(1, 2)")));
+	}
+	std::fs::remove_dir_all(directory).unwrap();
+
+	// Downloads (`download`)
+	let server = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+	let address = server.local_addr().unwrap();
+	let responder = std::thread::spawn(move || {
+		use std::io::{BufRead, BufReader, Write};
+		let responses = [
+			"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+			"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+		];
+		// the responses come first, so that no other connection is awaited after them
+		for (response, stream) in responses.into_iter().zip(server.incoming()) {
+			let mut stream = stream.unwrap();
+			let mut request = BufReader::new(&stream);
+			let mut line = String::new();
+			while line != "\r\n" {
+				line.clear();
+				request.read_line(&mut line).unwrap();
+			}
+			stream.write_all(response.as_bytes()).unwrap();
+		}
+	});
+	let destination = std::env::temp_dir().join(format!("ilo_download_{}", std::process::id()));
+	assert_eq!(
+		"5",
+		ev(&format!(
+			r#"download("http://{address}/file", "{}")"#,
+			destination.display()
+		))
+	);
+	assert_eq!("hello", std::fs::read_to_string(&destination).unwrap());
+	assert_eq!(
+		"true",
+		ev(&format!(
+			r#"download("http://{address}/missing", "{}")?"#,
+			destination.display()
+		))
+	);
+	responder.join().unwrap();
+	std::fs::remove_file(destination).unwrap();
+	assert_eq!("err", ev(r#"download(3, "file")?"#));
 
 	// Equality
 	assert_eq!(
@@ -272,6 +734,17 @@ fn native_functions() {
 	// Display
 	assert_eq!("f time(0 arguments) { [native code] }", ev("time"));
 	assert_eq!("f out(1 argument) { [native code] }", ev("out"));
+	assert_eq!("f ask(1 to 2 arguments) { [native code] }", ev("ask"));
+	assert_eq!("err", ev(r#"ask("name", 1, 2)"#));
+	let tokens = Lexer::new(String::from(r#"ask("name", 1, 2)"#))
+		.scan_tokens()
+		.unwrap();
+	let statements = Parser::new(tokens).parse().unwrap();
+	let (_, errors) = capture_errors(|| Interpreter::new().interpret(statements));
+	assert_eq!(
+		"Expected 1 to 2 arguments for function(2), but found 3",
+		errors[0].message()
+	);
 }
 
 #[test]
@@ -459,6 +932,33 @@ fn functions() {
 		"f f() {
 		}",
 	);
+
+	// Returning from nested blocks leaves the function scope
+	assert_eq!(
+		"err",
+		ev(r#"f find(limit) {
+			while true {
+				if limit == 3 {
+					return limit
+				}
+			}
+		}
+		find(3)
+		limit"#)
+	);
+
+	// Arguments shadow outer variables only during the call
+	assert_eq!(
+		"true",
+		ev(r#"n = 1
+		f depth(n) {
+			if n < 3 {
+				return depth(n + 1)
+			}
+			return n
+		}
+		depth(n) == 3 and n == 1"#)
+	);
 }
 
 #[test]
@@ -524,6 +1024,258 @@ fn global_variables() {
 	);
 }
 
+#[test]
+fn lambdas() {
+	assert_eq!(
+		"8",
+		ev("double = (n) -> n * 2
+			double(4)")
+	);
+	assert_eq!(
+		"3 42",
+		ev(r#"add = (a, b) -> a + b
+			answer = () -> 42
+			string(add(1, 2)) + " " + string(answer())"#)
+	);
+	assert_eq!("2", ev("((x) -> x + 1)(1)"));
+	// parentheses without `->` are still groupings
+	assert_eq!(
+		"6",
+		ev("a = 2
+(a) * 3")
+	);
+	assert_eq!(
+		"f lambda(1 argument) {}",
+		ev("double = (n) -> n * 2
+double")
+	);
+	// lambdas are only equal to themselves
+	assert_eq!(
+		"true false",
+		ev(r#"one = () -> 1
+			other = () -> 1
+			string(one == one) + " " + string(one == other)"#)
+	);
+	assert_eq!(
+		"err",
+		ev("double = (n) -> n * 2
+double(1, 2)")
+	);
+	assert!(has_parsing_error("(a, ) -> a"));
+	assert!(has_parsing_error("(n) ->"));
+}
+
+#[test]
+fn higher_order_functions() {
+	// `f` is a name unless it declares a function
+	let apply = "f apply(f, value) {
+			return f(value)
+		}
+		";
+	assert_eq!("8", ev(&format!("{apply}apply((n) -> n * 2, 4)")));
+	assert_eq!(
+		"3",
+		ev(&format!(
+			"{apply}f inc(n) {{\nreturn n + 1\n}}\napply(inc, 2)"
+		))
+	);
+	assert_eq!("4", ev("f = (n) -> n * n\nf(2)"));
+	assert_eq!("err", ev(&format!("{apply}apply((a, b) -> a + b, 1)")));
+	let tokens = Lexer::new(format!("{apply}apply((a, b) -> a + b, 1)"))
+		.scan_tokens()
+		.unwrap();
+	let statements = Parser::new(tokens).parse().unwrap();
+	let (_, errors) = capture_errors(|| Interpreter::new().interpret(statements));
+	assert_eq!(
+		"Expected 2 arguments for function(2), but found 1",
+		errors[0].message()
+	);
+}
+
+#[test]
+fn closures() {
+	let adder = "f adder(n) {
+			return (x) -> x + n
+		}
+		";
+	assert_eq!("3", ev(&format!("{adder}add2 = adder(2)\nadd2(1)")));
+	assert_eq!(
+		"7",
+		ev(&format!(
+			"{adder}add2 = adder(2)\nadd5 = adder(5)\nadd2(add5(0))"
+		))
+	);
+	assert_eq!(
+		"7",
+		ev("f compose(f, g) {
+				return (x) -> g(f(x))
+			}
+			compose((x) -> x + 2, (x) -> x + 5)(0)")
+	);
+	// the parameters hide the captured variables of the same name
+	assert_eq!(
+		"10",
+		ev("f make(x) {
+				return (x) -> x * 2
+			}
+			make(1)(5)")
+	);
+	// lambdas created in blocks of the script keep their variables too
+	assert_eq!(
+		"2",
+		ev("g = () -> 0
+			{
+				inner = 2
+				g = () -> inner
+			}
+			g()")
+	);
+	assert_eq!("err", ev("{\n\tinner = 2\n}\ninner"));
+	// declared functions still use the variables of their caller
+	assert_eq!(
+		"2",
+		ev("f outer() {
+				count = 0
+				f inc() {
+					count = count + 1
+				}
+				inc()
+				inc()
+				return count
+			}
+			outer()")
+	);
+	// and keep the variables of the function declaring them once it has returned
+	assert_eq!(
+		"3",
+		ev("f adder(n) {
+				f add(x) {
+					return x + n
+				}
+				return add
+			}
+			add2 = adder(2)
+			add2(1)")
+	);
+}
+
+#[test]
+fn variadic_functions() {
+	let sum = "f sum(values...) {
+			total = 0
+			i = 0
+			while i < size(values) {
+				total += values[i]
+				i++
+			}
+			return total
+		}
+		";
+	assert_eq!("0", ev(&format!("{sum}sum()")));
+	assert_eq!("6", ev(&format!("{sum}sum(1, 2, 3)")));
+	assert_eq!(
+		"[\"b\", 1]",
+		ev(r#"f rest(first, others...) {
+				return others
+			}
+			string(rest("a", "b", 1))"#)
+	);
+	assert_eq!("f sum(at least 0 arguments) {}", ev(&format!("{sum}sum")));
+	assert_eq!("err", ev("f g(a, b...) {\n}\ng()"));
+	assert!(has_parsing_error("f g(a..., b) {\n}"));
+}
+
+#[test]
+fn compound_assignments() {
+	assert_eq!(
+		"4",
+		ev("count = 1
+			count += 2
+			count *= 10
+			count -= 5
+			count /= 5
+			count ^= 2
+			count %= 7
+			count")
+	);
+	assert_eq!(
+		"abcabc",
+		ev(r#"s = "ab"
+			s += "c"
+			s *= 2
+			s"#)
+	);
+	assert_eq!("err", ev("missing += 1"));
+	assert_eq!(
+		"err",
+		ev(r#"s = "ab"
+		s -= "a""#)
+	);
+	assert!(has_parsing_error("a += empty(number)"));
+	assert!(has_parsing_error("a += 1 2"));
+}
+
+#[test]
+fn multiple_assignments() {
+	assert_eq!("[1, 2, 3]", ev("a, b, c = 1, 2, 3\n[a, b, c]"));
+	assert_eq!(
+		"[2, 1]",
+		ev("a, b = 1, 2
+			a, b = b, a
+			[a, b]")
+	);
+	assert_eq!(
+		"[3, \"x\"]",
+		ev("n = 1
+			n, s = n + 2, \"x\"
+			[n, s]")
+	);
+	assert_eq!("true", ev("n = 1\nn, s = empty, \"x\"\nn?"));
+	assert_eq!("err", ev("a = true\na, b = 1, 2"));
+	assert_eq!("err", ev("a, b = 1, missing"));
+	assert!(has_parsing_error("a, b = 1"));
+	assert!(has_parsing_error("a, b = 1, 2, 3"));
+	assert!(has_parsing_error("a, a = 1, 2"));
+	assert!(has_parsing_error("a, 1 = 1, 2"));
+	assert!(has_parsing_error("a, b"));
+}
+
+#[test]
+fn increments() {
+	assert_eq!(
+		"2",
+		ev("i = 1
+			i++
+			i++
+			i--
+			i")
+	);
+	// functions change the variables they see, like with assignments
+	assert_eq!(
+		"3",
+		ev("i = 1
+			f bump() {
+				i++
+			}
+			bump()
+			bump()
+			i")
+	);
+	assert_eq!("err", ev("missing++"));
+	assert_eq!(
+		"err",
+		ev(r#"s = "a"
+		s++"#)
+	);
+	assert_eq!(
+		"err",
+		ev("n = empty(number)
+		n--")
+	);
+	assert!(has_parsing_error("i = 1\ni++ 1"));
+	assert!(has_parsing_error("i = 1\nout(i++)"));
+}
+
 #[test]
 fn empty_variables() {
 	assert_eq!(
@@ -589,7 +1341,38 @@ fn empty_variables() {
 	assert!(has_parsing_error("var = empty("));
 	assert!(has_parsing_error("var = empty(test)"));
 	assert!(has_parsing_error("var = empty(number"));
-	assert!(has_parsing_error("var = empty(string)"));
+	assert!(has_parsing_error("var = empty(list)"));
+}
+
+#[test]
+fn empty_strings() {
+	assert_eq!("", ev("var = empty(string)\nvar"));
+	assert_eq!("true", ev("var = empty(string)\nvar?"));
+	assert_eq!("false", ev("var = \"\"\nvar?"));
+	assert_eq!("false", ev("var = empty(string)\nvar == \"\""));
+	assert_eq!("hi", ev("var = empty(string)\nvar = \"hi\"\nvar"));
+	assert_eq!("true", ev("var = \"hi\"\nvar = empty\nvar?"));
+	assert_eq!("err", ev("var = empty(string)\nvar = 3"));
+	assert_eq!("true", ev("var = empty(number)\nstring(var)?"));
+}
+
+#[test]
+fn multiline_expressions() {
+	assert_eq!("9", ev("a = (1 +\n\t2) * (\n\t3\n)\na"));
+	assert_eq!(
+		"6",
+		ev("f sum(a, b, c) {\n\treturn a + b + c\n}\nsum(\n\t1,\n\t2,\n\t3\n)")
+	);
+	assert_eq!(
+		"3",
+		ev("f add(\n\ta,\n\tb\n) {\n\treturn a + b\n}\nadd(1, 2)")
+	);
+	assert_eq!(
+		"b",
+		ev("x = 2\ny = (match x {\n\t1 -> \"a\"\n\t2 -> \"b\"\n})\ny")
+	);
+	assert!(has_parsing_error("a = (1 +\n2"));
+	assert!(has_parsing_error("a = 1 +\n2"));
 }
 
 #[test]
@@ -748,3 +1531,2455 @@ fn while_statements() {
 
 	assert!(has_parsing_error("while true out(4)"));
 }
+
+#[test]
+fn repeat_statements() {
+	// the body runs at least once, and the condition is checked after it
+	assert_eq!("1", ev("i = 0\nrepeat {\n\ti++\n} until true\ni"));
+	assert_eq!("5", ev("i = 0\nrepeat {\n\ti++\n} until i == 5\ni"));
+	assert_eq!(
+		"3",
+		ev("i = 0\nrepeat {\n\ti++\n\tif i < 3 {\n\t\tcontinue\n\t}\n\tbreak\n} until false\ni")
+	);
+	assert_eq!(
+		"4",
+		ev("n = 0\nouter: repeat {\n\trepeat {\n\t\tn++\n\t\tif n == 4 {\n\t\t\tbreak outer\n\t\t}\n\t} until false\n} until false\nn")
+	);
+
+	assert!(has_parsing_error("repeat {\n\tout(1)\n}"));
+	assert!(has_parsing_error("repeat out(1) until true"));
+
+	let tokens = Lexer::new(String::from("i = 0\nrepeat {\n\ti++\n} until i > 2"))
+		.scan_tokens()
+		.unwrap();
+	assert!(transpile(&Parser::new(tokens).parse().unwrap())
+		.ends_with("let i = 0;\ndo {\n\ti++;\n} while ((i > 2) === false);\n"));
+}
+
+#[test]
+fn sandbox() {
+	assert_eq!("2", ev_sandboxed("1 + 1"));
+	assert_eq!("", ev_sandboxed(r#"out("output")"#));
+	assert_eq!("f cmd(1 argument) { [native code] }", ev_sandboxed("cmd"));
+
+	assert_eq!("err", ev_sandboxed(r#"cmd("echo -n hello world")"#));
+	assert_eq!(
+		"err",
+		ev_sandboxed(
+			r#"run = cmd
+			run("ls")"#
+		)
+	);
+	assert_eq!("err", ev_sandboxed(r#"shell("ls | wc -l")"#));
+	assert_eq!("err", ev_sandboxed(r#"watch(".", out)"#));
+	assert_eq!(
+		"err",
+		ev_sandboxed(r#"download("http://example.com", "page.html")"#)
+	);
+	assert_eq!("err", ev_sandboxed(r#"unzip("files.zip", ".")"#));
+	assert_eq!("err", ev_sandboxed(r#"import "utils.ilo""#));
+	assert_eq!("err", ev_sandboxed(r#"remove_file("a.txt")"#));
+	assert_eq!("err", ev_sandboxed(r#"move_file("a.txt", "b.txt")"#));
+	assert_eq!("err", ev_sandboxed(r#"file_exists("missing.txt")"#));
+	assert_eq!("err", ev_sandboxed(r#"file_exists("Cargo.toml")"#));
+	assert_eq!("err", ev_sandboxed(r#"list_dir(".", false)"#));
+	assert_eq!("err", ev_sandboxed(r#"glob("**/*.rs")"#));
+	assert_eq!("true", ev_sandboxed(r#"glob_match("*.rs", "a.rs")"#));
+}
+
+#[test]
+fn colors() {
+	set_color_choice(ColorChoice::Always);
+	assert_eq!(
+		"\x1b[1;31mhello\x1b[0m",
+		ev(r#"style("hello", "bold red")"#)
+	);
+	assert_eq!("hello", ev(r#"style("hello", "")"#));
+
+	set_color_choice(ColorChoice::Never);
+	assert_eq!("hello", ev(r#"style("hello", "bold red")"#));
+}
+
+#[test]
+fn bytecode() {
+	use lexer::{Token, TokenType};
+	use parser::{Expr, Statement};
+
+	let source = r#"f fibo(n) {
+		if n == 0 or n == 1 {
+			return 1
+		} else {
+			return fibo(n - 1) + fibo(n - 2)
+		}
+	}
+	f all(values...) {
+		return values
+	}
+	a = empty(number)
+	a = fibo(5) * -2
+	outer: while a < 0 {
+		a = a + 10
+		if a > 100 {
+			break outer
+		}
+	}
+	b = empty(number)
+	b?.fibo()
+	a++
+	"a" + "b"
+	a"#;
+	let tokens = Lexer::new(String::from(source)).scan_tokens().unwrap();
+	let statements = Parser::new(tokens).parse().unwrap();
+
+	let bytes = compile("fibo.ilo", &statements);
+	let chunk = load(&bytes).unwrap();
+	assert_eq!("fibo.ilo", chunk.source_path);
+	assert_eq!(statements, chunk.statements);
+	assert_eq!(
+		Ok(String::from("5")),
+		Interpreter::new().interpret(chunk.statements)
+	);
+
+	assert_eq!(Err(LoadError::InvalidHeader), load(b"a = 3"));
+	assert_eq!(
+		Err(LoadError::UnexpectedEnd),
+		load(&bytes[..bytes.len() - 1])
+	);
+	let mut wrong_version = bytes.clone();
+	wrong_version[4] += 1;
+	assert!(matches!(
+		load(&wrong_version),
+		Err(LoadError::UnsupportedVersion(_))
+	));
+
+	// changed files cannot run code that the parser rejects
+	let compiled = |source: &str| {
+		let tokens = Lexer::new(String::from(source)).scan_tokens().unwrap();
+		compile("bad.ilo", &Parser::new(tokens).parse().unwrap())
+	};
+	// replaces the type of the token with this lexeme, written before its length and lexeme
+	let retag = |mut bytes: Vec<u8>, lexeme: &str, tag: u8| {
+		let mut encoded = (lexeme.len() as u32).to_le_bytes().to_vec();
+		encoded.extend_from_slice(lexeme.as_bytes());
+		let position = bytes
+			.windows(encoded.len())
+			.position(|window| window == encoded)
+			.unwrap();
+		bytes[position - 1] = tag;
+		bytes
+	};
+	let comma = 0x04;
+	assert_eq!(
+		Err(LoadError::InvalidCode("invalid binary operator")),
+		load(&retag(compiled("out(1 + 2)"), "+", comma))
+	);
+	assert_eq!(
+		Err(LoadError::InvalidCode("invalid literal")),
+		load(&retag(compiled("out(true)"), "true", comma))
+	);
+	assert_eq!(
+		Err(LoadError::InvalidCode("invalid unary operator")),
+		load(&retag(compiled("out(-a)"), "-", comma))
+	);
+	let token = |token_type: TokenType, lexeme: &str| Token::new(token_type, lexeme.into(), 1, 1);
+	let loop_control = Statement::Break {
+		keyword: token(TokenType::Break, "break"),
+		label: None,
+	};
+	assert!(matches!(
+		load(&compile("bad.ilo", &[loop_control])),
+		Err(LoadError::InvalidCode(_))
+	));
+	let import = Statement::Import {
+		keyword: token(TokenType::Import, "import"),
+		path: token(TokenType::NumberLiteral(1.0), "1"),
+		alias: None,
+	};
+	assert!(matches!(
+		load(&compile("bad.ilo", &[import])),
+		Err(LoadError::InvalidCode(_))
+	));
+	// `a * (b + c)` cannot lose its parentheses
+	let operation = |left, operator: &str, right| Expr::Binary {
+		left_expr: Box::new(left),
+		operator: token(
+			if operator == "+" {
+				TokenType::Plus
+			} else {
+				TokenType::Star
+			},
+			operator,
+		),
+		right_expr: Box::new(right),
+	};
+	let variable = |name: &str| Expr::Variable {
+		name: token(TokenType::Identifier, name),
+	};
+	let expr = operation(
+		variable("a"),
+		"*",
+		operation(variable("b"), "+", variable("c")),
+	);
+	assert!(matches!(
+		load(&compile("bad.ilo", &[Statement::Expr { expr }])),
+		Err(LoadError::InvalidCode(_))
+	));
+
+	// code is nested as deeply as the parser accepts, and no more
+	let nested = |depth: usize| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+	assert!(load(&compiled(&nested(60))).is_ok());
+	let mut deep = compile("deep.ilo", &[]);
+	let length = deep.len();
+	// one expression statement (0x01) of groupings (0x23) nested far too deeply to be read
+	deep[length - 4..].copy_from_slice(&1u32.to_le_bytes());
+	deep.push(0x01);
+	deep.extend([0x23; 100_000]);
+	assert_eq!(Err(LoadError::TooDeep), load(&deep));
+
+	let tokens = Lexer::new(String::from("x = 1\nwhile x < 3 {\n\tx = x + 1\n}"))
+		.scan_tokens()
+		.unwrap();
+	let bytes = compile("w.ilo", &Parser::new(tokens).parse().unwrap());
+	assert_eq!(
+		"; compiled from `w.ilo` (format version 5)
+0012  line 1    ASSIGNMENT x
+0029  line 1      PRIMARY 1
+0048  line 2    WHILE
+004a  line 2      BINARY <
+004b  line 2        VARIABLE x
+0078  line 2        PRIMARY 3
+0097              BLOCK 1 statement
+009c  line 3        ASSIGNMENT x
+00b3  line 3          BINARY +
+00b4  line 3            VARIABLE x
+00e1  line 3            PRIMARY 1
+",
+		disassemble(&bytes).unwrap()
+	);
+	// the offsets are those of the opcodes in the compiled file
+	assert_eq!(0x02, bytes[0x12]);
+	assert_eq!(0x05, bytes[0x48]);
+	assert_eq!(Err(LoadError::InvalidHeader), disassemble(b"a = 3"));
+}
+
+#[test]
+fn javascript_transpilation() {
+	let js = |source: &str| {
+		let tokens = Lexer::new(String::from(source)).scan_tokens().unwrap();
+		transpile(&Parser::new(tokens).parse().unwrap())
+	};
+
+	assert_eq!("console.log(1 + (2 * 3));\n", js("out(1 + 2 * 3)"));
+	assert_eq!("(-2) ** 2;\n", js("-2 ^ 2"));
+	assert_eq!("-(-2);\n", js("- -2"));
+	assert_eq!(
+		"console.log(Math.PI + Infinity);\n",
+		js("out(PI + INFINITY)")
+	);
+	assert_eq!("Math.floor(Math.sqrt(10));\n", js("floor(sqrt(10))"));
+	assert!(js("a = args()").contains("process.argv.slice(2)"));
+	assert_eq!("\"a\\\\\".repeat(3);\n", js(r#""a\" * 3"#));
+	assert_eq!(
+		"let add = ((a, b) => a + b);\n",
+		js("add = (a, b) -> a + b")
+	);
+	assert_eq!(
+		"let [a, b] = [1, 2];\nlet c;\n[b, c] = [a, b];\n",
+		js("a, b = 1, 2\nb, c = a, b")
+	);
+	assert_eq!(
+		"let a = null;\na = 3;\n{\n\ta = 4;\n\tlet b = (a === 4) && true;\n}\n",
+		js("a = empty(number)
+		a = 3
+		{
+			a = 4
+			b = a == 4 and true
+		}")
+	);
+	assert_eq!(
+		"let var_ = 1;\nwhile (var_ === true) {\n\tvar_ = !var_;\n}\n",
+		js("var = 1
+		while var {
+			var = !var
+		}")
+	);
+	assert_eq!(
+		"function double(n) {\n\treturn n + n;\n}\nif (double(2) > 3) {\n} else if (false) {\n} else {\n\tconsole.log(\"no\");\n}\n",
+		js(r#"f double(n) {
+			return n + n
+		}
+		if double(2) > 3 {
+		} else if false {
+		} else {
+			out("no")
+		}"#)
+	);
+	assert_eq!(
+		"for (let c of \"ab\") {\n\tconsole.log(c);\n}\n",
+		js(r#"for c in "ab" {
+			out(c)
+		}"#)
+	);
+	assert_eq!(
+		"a: while (true) {\n\twhile (true) {\n\t\tbreak a;\n\t}\n\tcontinue;\n}\n",
+		js("a: while true {
+			while true {
+				break a
+			}
+			continue
+		}")
+	);
+	assert_eq!(
+		"function g() {\n\tconst $ilo_deferred = [];\n\ttry {\n\t\t$ilo_deferred.push(() => {\n\t\t\tconsole.log(1);\n\t\t});\n\t} finally {\n\t\t$ilo_deferred.reverse().forEach((deferred) => deferred());\n\t}\n}\n",
+		js("f g() {
+			defer out(1)
+		}")
+	);
+	let tokens = Lexer::new(String::from("x = 5 % 2\nif x > 0 {\n\tout(x)\n}"))
+		.scan_tokens()
+		.unwrap();
+	let (javascript, source_map) =
+		transpile_with_source_map(&Parser::new(tokens).parse().unwrap(), "a.ilo", "a.js");
+	// the `$ilo_mod` helper and the blank line after it are not mapped
+	assert!(
+		javascript.ends_with("\n\nlet x = $ilo_mod(5, 2);\nif (x > 0) {\n\tconsole.log(x);\n}\n")
+	);
+	assert_eq!(
+		r#"{"version":3,"file":"a.js","sources":["a.ilo"],"names":[],"mappings":";;;;;AAAA;AACG;AACD;AADC"}"#,
+		source_map
+	);
+	assert!(js("a = 5 % 2").starts_with("function $ilo_mod(left, right) {"));
+	assert!(js("a = b * 2").ends_with("let a = $ilo_mul(b, 2);\n"));
+	assert!(js("ask(\"name\")").ends_with("$ilo_ask(\"name\");\n"));
+	assert!(js("ask(\"name\", 5)").ends_with("$ilo_ask(\"name\", 5);\n"));
+	assert_eq!(
+		"(($receiver) => $receiver === null ? null : g($receiver, 1))(a);\n",
+		js("a?.g(1)")
+	);
+}
+
+#[test]
+fn facade() {
+	let options = ilo::RunOptions::default();
+
+	assert!(matches!(ilo::run("1 + 2", &options), Ok(ilo::Value::Number(n)) if n == 3.0));
+	assert_eq!("hey", ilo::run(r#""hey""#, &options).unwrap().to_string());
+
+	let diagnostics = ilo::run("a = 1\nb = a + c", &options).unwrap_err();
+	assert_eq!(1, diagnostics.len());
+	assert_eq!(ilo::ErrorType::NameError, diagnostics[0].error_type());
+	assert_eq!(2, diagnostics[0].line());
+
+	let diagnostics = ilo::run("a = (1\nb = )", &options).unwrap_err();
+	assert!(diagnostics
+		.iter()
+		.all(|d| d.error_type() == ilo::ErrorType::ParsingError));
+
+	let sandbox = ilo::RunOptions { sandbox: true };
+	assert!(ilo::run(r#"cmd("ls")"#, &sandbox).is_err());
+
+	let mut interpreter = ilo::Interpreter::new();
+	ilo::run_in(&mut interpreter, "a = 4").unwrap();
+	assert_eq!(
+		"8",
+		ilo::run_in(&mut interpreter, "a * 2").unwrap().to_string()
+	);
+
+	// Packs of natives, checked like the natives of ilo
+	struct Greetings;
+	impl ilo::NativePack for Greetings {
+		fn name(&self) -> &str {
+			"greetings"
+		}
+
+		fn define(&self, interpreter: &mut ilo::Interpreter) {
+			interpreter.define_typed_native("greet", vec![Param::String], |args| {
+				ilo::Value::String(format!("hello {}", args[0]))
+			});
+		}
+	}
+	let mut interpreter = ilo::Interpreter::new();
+	interpreter.load_pack(&Greetings);
+	assert_eq!(
+		"hello ilo",
+		ilo::run_in(&mut interpreter, r#"greet("ilo")"#)
+			.unwrap()
+			.to_string()
+	);
+	let diagnostics = ilo::run_in(&mut interpreter, "greet(1, 2)").unwrap_err();
+	assert_eq!(ilo::ErrorType::TypeError, diagnostics[0].error_type());
+}
+
+#[test]
+fn playground() {
+	let result = ilo_wasm::run("out(\"hello\")\nout(ask(\"name?\"))\nout(1 + 1)");
+	assert_eq!("hello\nname?\n\n2\n", result.output());
+	assert!(result.errors().is_empty());
+
+	let result = ilo_wasm::run("out(1)\nn = 0 - 1\nout(\"a\" * n)");
+	assert_eq!("1\n", result.output());
+	assert_eq!(
+		vec!["Runtime error at line 3, column 9: Multiplication (`*`) between a string and a number requires a positive integer (found `-1`)."],
+		result.errors()
+	);
+	assert_eq!(1, ilo_wasm::run(r#"cmd("ls")"#).errors().len());
+}
+
+#[test]
+fn c_bindings() {
+	use ilo_ffi::*;
+	use std::ffi::{c_void, CStr, CString};
+
+	extern "C" fn twice(args: *const IloValue, count: usize, user_data: *mut c_void) -> IloValue {
+		let args = unsafe { std::slice::from_raw_parts(args, count) };
+		let calls = unsafe { &mut *(user_data as *mut i32) };
+		*calls += 1;
+		IloValue {
+			value_type: IloValueType::Number,
+			boolean: false,
+			number: args[0].number * 2.0,
+			string: std::ptr::null(),
+		}
+	}
+
+	let source = |text: &str| CString::new(text).unwrap();
+	let mut calls = 0;
+	unsafe {
+		let interpreter = ilo_new_interpreter();
+		let mut result = std::mem::zeroed::<IloValue>();
+
+		assert!(ilo_eval(
+			interpreter,
+			source("a = \"hello\"").as_ptr(),
+			&mut result
+		));
+		assert!(ilo_last_error(interpreter).is_null());
+		assert!(ilo_get_global(
+			interpreter,
+			source("a").as_ptr(),
+			&mut result
+		));
+		assert_eq!(IloValueType::String, result.value_type);
+		assert_eq!("hello", CStr::from_ptr(result.string).to_str().unwrap());
+		assert!(!ilo_get_global(
+			interpreter,
+			source("b").as_ptr(),
+			&mut result
+		));
+
+		ilo_register_native(
+			interpreter,
+			source("twice").as_ptr(),
+			1,
+			twice,
+			&mut calls as *mut i32 as *mut c_void,
+		);
+		assert!(ilo_eval(
+			interpreter,
+			source("twice(21)").as_ptr(),
+			&mut result
+		));
+		assert_eq!(IloValueType::Number, result.value_type);
+		assert_eq!(42.0, result.number);
+
+		assert!(!ilo_eval(
+			interpreter,
+			source("twice(1, 2)").as_ptr(),
+			&mut result
+		));
+		assert_eq!(
+			"Type error at line 1, column 11: Expected 1 argument for function(1), but found 2.",
+			CStr::from_ptr(ilo_last_error(interpreter))
+				.to_str()
+				.unwrap()
+		);
+
+		ilo_free_interpreter(interpreter);
+	}
+	assert_eq!(1, calls);
+}
+
+#[test]
+fn hosts() {
+	use interpreter::{Archive, Host, NullHost};
+	use std::{cell::RefCell, rc::Rc};
+
+	struct RecordingHost(Rc<RefCell<Vec<String>>>);
+
+	impl Host for RecordingHost {
+		fn print(&mut self, text: &str) {
+			self.0.borrow_mut().push(text.to_string());
+		}
+		fn ask(&mut self, prompt: &str) -> String {
+			format!("answer to {prompt}")
+		}
+		fn ask_timeout(&mut self, prompt: &str, seconds: f64) -> Option<String> {
+			(seconds >= 1.0).then(|| format!("quick answer to {prompt}"))
+		}
+		fn multi_select(&mut self, prompt: &str, options: &[String]) -> Option<Vec<usize>> {
+			(prompt == "pick").then(|| (0..options.len()).step_by(2).collect())
+		}
+		fn read_line(&mut self) -> Option<Option<String>> {
+			None
+		}
+		fn read_all(&mut self) -> Option<String> {
+			Some(String::from("all"))
+		}
+		fn time(&mut self) -> f64 {
+			42.0
+		}
+		fn clock(&mut self) -> f64 {
+			1500.0
+		}
+		fn run_command(&mut self, program: &str, args: &[&str]) -> Option<String> {
+			Some(format!("{program} {}", args.join(",")))
+		}
+		fn run_shell(&mut self, command: &str) -> Option<String> {
+			Some(format!("sh {command}"))
+		}
+		fn watch(&mut self, path: &str) -> Option<Box<dyn Iterator<Item = String>>> {
+			(path == "src").then(|| {
+				Box::new(
+					["src/a.ilo", "src/b.ilo", "src/c.ilo"]
+						.map(String::from)
+						.into_iter(),
+				) as Box<dyn Iterator<Item = String>>
+			})
+		}
+		fn download(&mut self, _url: &str, _destination: &str) -> Option<u64> {
+			None
+		}
+		fn create_archive(&mut self, _: Archive, _: &str, _: &str) -> bool {
+			false
+		}
+		fn extract_archive(&mut self, _: Archive, _: &str, _: &str) -> bool {
+			false
+		}
+		fn read_file(&mut self, path: &str) -> Option<String> {
+			(path == "greet.ilo")
+				.then(|| String::from("f greet(name) {\n\treturn \"hi \" + name\n}"))
+		}
+		fn file_exists(&mut self, path: &str) -> bool {
+			path == "greet.ilo"
+		}
+		fn remove_file(&mut self, _path: &str) -> bool {
+			false
+		}
+		fn copy_file(&mut self, source: &str, _destination: &str) -> bool {
+			source == "greet.ilo"
+		}
+		fn move_file(&mut self, _source: &str, _destination: &str) -> bool {
+			false
+		}
+		fn list_dir(&mut self, path: &str, recursive: bool) -> Option<Vec<String>> {
+			let entries = ["a.ilo", "lib", "lib/b.ilo", "notes.txt"].map(String::from);
+			(path == "src").then(|| {
+				entries
+					.into_iter()
+					.filter(|entry| recursive || !entry.contains('/'))
+					.collect()
+			})
+		}
+		fn task_host(&self) -> Box<dyn Host + Send> {
+			Box::new(NullHost)
+		}
+	}
+
+	let printed = Rc::new(RefCell::new(vec![]));
+	let mut interpreter = Interpreter::new();
+	interpreter.set_host(Box::new(RecordingHost(printed.clone())));
+	let mut run = |source: &str| {
+		let tokens = Lexer::new(String::from(source)).scan_tokens().unwrap();
+		interpreter
+			.interpret(Parser::new(tokens).parse().unwrap())
+			.unwrap_or_else(|()| String::from("err"))
+	};
+
+	assert_eq!("", run(r#"out(ask("name"))"#));
+	assert_eq!("quick answer to name", run(r#"ask("name", 1)"#));
+	assert_eq!("true", run(r#"ask("name", 0.5)?"#));
+	assert_eq!("true", run(r#"number(ask("name", 0.5))?"#));
+	assert_eq!("false", run(r#"ask("name")?"#));
+	assert_eq!("err", run(r#"ask("name", 0)"#));
+	assert_eq!("err", run(r#"ask("name", -1)"#));
+	assert_eq!(
+		r#"["a", true]"#,
+		run(r#"multi_select("pick", ["a", 1, true])"#)
+	);
+	assert_eq!("[]", run(r#"multi_select("other", ["a"])"#));
+	assert_eq!("42", run("time()"));
+	assert_eq!("1500", run("clock_ms()"));
+	assert_eq!("1000", run("timer_elapsed(500)"));
+	assert_eq!("all", run("read_all()"));
+	assert_eq!("ls -a,-l", run(r#"cmd("ls  -a -l")"#));
+	assert_eq!("ls my files,", run(r#"cmd("ls 'my files' ''")"#));
+	assert_eq!("sh ls | wc -l", run(r#"shell("ls | wc -l")"#));
+	assert_eq!("", run("read_line()"));
+	assert_eq!("hi ilo", run("import \"greet.ilo\"\ngreet(\"ilo\")"));
+	assert_eq!("true", run(r#"file_exists("greet.ilo")"#));
+	assert_eq!("true", run(r#"copy_file("greet.ilo", "hi.ilo")"#));
+	assert_eq!("false", run(r#"remove_file("hi.ilo")"#));
+	assert_eq!(
+		r#"["a.ilo", "lib", "notes.txt"]"#,
+		run(r#"list_dir("src", false)"#)
+	);
+	assert_eq!(
+		r#"["src/a.ilo", "src/lib/b.ilo"]"#,
+		run(r#"glob("src/**/*.ilo")"#)
+	);
+	assert_eq!("[]", run(r#"glob("tests/*.ilo")"#));
+	assert_eq!("[]", run(r#"list_dir("tests", true)"#));
+	assert_eq!(
+		vec![
+			"answer to name",
+			"error: `multi_select` could not ask the user to choose",
+			"error: `read_line` could not read the standard input",
+			"error: `remove_file` could not remove `hi.ilo`",
+			"error: `list_dir` could not read the directory `tests`"
+		],
+		*printed.borrow()
+	);
+
+	printed.borrow_mut().clear();
+	run("inspect(\"say \\ hi\n\")");
+	run("inspect(1.5)");
+	run("inspect(1 > 2)");
+	run("inspect(empty)");
+	run("a = 1\na = empty\ninspect(a)");
+	run("b = true\nb = empty\ninspect(b)");
+	run("c = empty(string)\ninspect(c)");
+	run("inspect(out)");
+	run("inspect([1, \"a\"])");
+	assert_eq!(
+		vec![
+			r#"string "say \\ hi\n""#,
+			"number 1.5",
+			"boolean false",
+			"empty",
+			"empty(number)",
+			"empty(boolean)",
+			"empty(string)",
+			"f out(1 argument) { [native code] }",
+			r#"list [1, "a"]"#
+		],
+		*printed.borrow()
+	);
+
+	printed.borrow_mut().clear();
+	run(r#"f changed(path) {
+			out(path)
+			return path != "src/b.ilo"
+		}
+		watch("src", changed)
+		watch("tests", changed)
+		watch("src", out)"#);
+	assert_eq!(
+		vec![
+			"src/a.ilo",
+			"src/b.ilo",
+			"error: `watch` could not watch `tests`",
+			"src/a.ilo",
+			"src/b.ilo",
+			"src/c.ilo"
+		],
+		*printed.borrow()
+	);
+
+	let mut interpreter = Interpreter::new();
+	interpreter.set_host(Box::new(NullHost));
+	let tokens = Lexer::new(String::from(r#"out("lost")"#))
+		.scan_tokens()
+		.unwrap();
+	assert_eq!(
+		Ok(String::new()),
+		interpreter.interpret(Parser::new(tokens).parse().unwrap())
+	);
+	// without a user to ask, like in the sandbox of untrusted scripts, nothing is chosen
+	interpreter.set_sandbox(true);
+	let tokens = Lexer::new(String::from(r#"multi_select("pick", ["a", "b"])"#))
+		.scan_tokens()
+		.unwrap();
+	assert_eq!(
+		Ok(String::from("[]")),
+		interpreter.interpret(Parser::new(tokens).parse().unwrap())
+	);
+}
+
+#[test]
+fn tasks() {
+	assert_eq!(
+		"20",
+		ev("base = 10
+			f double() {
+				return base * 2
+			}
+			f triple() {
+				return base * 3
+			}
+			first = spawn(double)
+			second = spawn(triple)
+			wait(second) - wait(first) + base")
+	);
+	// tasks get a copy of the variables
+	assert_eq!(
+		"3",
+		ev("n = 1
+			f change() {
+				n = 2
+				return n
+			}
+			task = spawn(change)
+			wait(task) + n")
+	);
+	// the functions called by the task are copied too
+	assert_eq!(
+		"hello world",
+		ev(r#"f greet(name) {
+				return "hello " + name
+			}
+			f task() {
+				return greet("world")
+			}
+			wait(spawn(task))"#)
+	);
+	assert_eq!(
+		"true",
+		ev("started = time()
+			f slow() {
+				cmd(\"sleep 0.5\")
+			}
+			a = spawn(slow)
+			b = spawn(slow)
+			wait(a)
+			wait(b)
+			time() - started < 900000000")
+	);
+
+	assert_eq!(
+		"err",
+		ev(r#"f failing() {
+				return 1 + "a"
+			}
+			wait(spawn(failing))"#)
+	);
+	assert_eq!(
+		"err",
+		ev("f task() {
+				return 1
+			}
+			t = spawn(task)
+			wait(t)
+			wait(t) + 1")
+	);
+	assert_eq!("err", ev("wait(3) + 1"));
+	assert_eq!("err", ev("spawn(time) + 1"));
+	assert_eq!("err", ev("f task(a) {\n}\nspawn(task) + 1"));
+	assert_eq!(
+		"err",
+		ev_sandboxed("f task() {\ncmd(\"ls\")\n}\nwait(spawn(task))")
+	);
+
+	// tasks get the fuel left, the arguments and the interrupt flag of the script
+	let forever = "f forever() {\n\twhile true {\n\t}\n}\nwait(spawn(forever))";
+	let run_task = |interpreter: &mut Interpreter, source: &str| {
+		let tokens = Lexer::new(String::from(source)).scan_tokens().unwrap();
+		capture_errors(|| interpreter.interpret(Parser::new(tokens).parse().unwrap()))
+	};
+	let mut interpreter = Interpreter::new();
+	interpreter.set_fuel(Some(1000));
+	let (result, errors) = run_task(&mut interpreter, forever);
+	assert!(result.is_err());
+	assert_eq!("The task waited for failed", errors[0].message());
+
+	let mut interpreter = Interpreter::new();
+	interpreter.set_arguments(vec![String::from("-v")]);
+	assert_eq!(
+		Ok(String::from(r#"["-v"]"#)),
+		run_task(
+			&mut interpreter,
+			"f task() {\n\treturn args()\n}\nwait(spawn(task))"
+		)
+		.0
+	);
+
+	let mut interpreter = Interpreter::new();
+	let flag = interpreter.interrupt_flag();
+	// stands for Ctrl-C being pressed while the task runs
+	std::thread::spawn(move || {
+		std::thread::sleep(std::time::Duration::from_millis(100));
+		flag.store(true, std::sync::atomic::Ordering::Relaxed);
+	});
+	assert!(run_task(&mut interpreter, forever).0.is_err());
+}
+
+#[test]
+fn piped_input() {
+	use std::{
+		io::Write,
+		process::{Command, Stdio},
+	};
+
+	// the test runs itself again with its input piped, as the standard host reads the real one
+	if std::env::var_os("ILO_PIPED_INPUT").is_some() {
+		let source =
+			"first = read_line()\nblank = read_line()\nend = read_line()\n[first, blank?, end?]";
+		println!("result: {}", ev(source));
+		return;
+	}
+	let mut child = Command::new(std::env::current_exe().unwrap())
+		.args(["piped_input", "--exact", "--nocapture"])
+		.env("ILO_PIPED_INPUT", "1")
+		.stdin(Stdio::piped())
+		.stdout(Stdio::piped())
+		.spawn()
+		.unwrap();
+	child.stdin.take().unwrap().write_all(b"first\n\n").unwrap();
+	let output = child.wait_with_output().unwrap();
+	assert!(String::from_utf8(output.stdout)
+		.unwrap()
+		.contains(r#"result: ["first", false, true]"#));
+}
+
+#[test]
+fn interruptions() {
+	use std::{cell::RefCell, rc::Rc, sync::atomic::Ordering};
+
+	let run_interrupted = |source: &str| {
+		let tokens = Lexer::new(String::from(source)).scan_tokens().unwrap();
+		let mut interpreter = Interpreter::new();
+		let flag = interpreter.interrupt_flag();
+		// stands for Ctrl-C being pressed
+		interpreter.define_native("interrupt", 0, move |_| {
+			flag.store(true, Ordering::Relaxed);
+			interpreter::Value::Empty
+		});
+		let calls = Rc::new(RefCell::new(0));
+		let counter = calls.clone();
+		interpreter.define_native("count", 0, move |_| {
+			*counter.borrow_mut() += 1;
+			interpreter::Value::Empty
+		});
+		let result = interpreter.interpret(Parser::new(tokens).parse().unwrap());
+		let calls = *calls.borrow();
+		(result.is_err(), calls)
+	};
+
+	assert_eq!(
+		(true, 1),
+		run_interrupted(
+			"on_interrupt(count)
+			interrupt()
+			out(\"never reached\")"
+		)
+	);
+	// the handler is called once, and stops too if interrupted again
+	assert_eq!(
+		(true, 1),
+		run_interrupted(
+			"f cleanup() {
+				count()
+				interrupt()
+				count()
+			}
+			on_interrupt(cleanup)
+			while true {
+				interrupt()
+			}"
+		)
+	);
+	assert_eq!((true, 0), run_interrupted("interrupt()\ncount()"));
+	assert_eq!((false, 1), run_interrupted("on_interrupt(count)\ncount()"));
+	assert_eq!("err", ev("on_interrupt(out)"));
+}
+
+#[test]
+fn profiling() {
+	let tokens = Lexer::new(String::from(
+		"f inner(n) {
+			return n * 2
+		}
+		f outer() {
+			total = 0
+			for i in 1...3 {
+				total = total + inner(i)
+			}
+			return total
+		}
+		out(outer())
+		inner(1)",
+	))
+	.scan_tokens()
+	.unwrap();
+	let mut interpreter = Interpreter::new();
+	assert_eq!(None, interpreter.folded_stacks());
+	interpreter.enable_profiling();
+	interpreter
+		.interpret(Parser::new(tokens).parse().unwrap())
+		.unwrap();
+	assert_eq!(
+		Some(String::from(
+			"script 4\nscript;inner 1\nscript;outer 6\nscript;outer;inner 3\n"
+		)),
+		interpreter.folded_stacks()
+	);
+}
+
+#[test]
+fn statistics() {
+	let tokens = Lexer::new(String::from(
+		"f greet(name) {
+			return \"hi \" + name
+		}
+		i = 0
+		while i < 3 {
+			out(greet(string(i)))
+			i = i + 1
+		}",
+	))
+	.scan_tokens()
+	.unwrap();
+	let mut interpreter = Interpreter::new();
+	assert_eq!(None, interpreter.statistics());
+	interpreter.enable_statistics();
+	interpreter
+		.interpret(Parser::new(tokens).parse().unwrap())
+		.unwrap();
+	assert_eq!(
+		Some(Statistics {
+			statements: 12,
+			function_calls: 3,
+			native_calls: 3,
+			strings: 6,
+			max_scope_depth: 3,
+		}),
+		interpreter.statistics()
+	);
+}
+
+#[test]
+fn environment_snapshots() {
+	use interpreter::ScopeKind;
+
+	let snapshot = |source: &str| {
+		let tokens = Lexer::new(String::from(source)).scan_tokens().unwrap();
+		let mut interpreter = Interpreter::new();
+		interpreter.set_snapshot_on_error(true);
+		let failed = interpreter
+			.interpret(Parser::new(tokens).parse().unwrap())
+			.is_err();
+		assert!(failed);
+		interpreter
+			.take_error_snapshot()
+			.unwrap()
+			.into_iter()
+			.map(|scope| {
+				let variables: Vec<String> = scope
+					.variables
+					.iter()
+					.map(|(name, value)| format!("{name} = {}", value.inspect()))
+					.collect();
+				(scope.kind, variables.join(", "))
+			})
+			.collect::<Vec<_>>()
+	};
+
+	assert_eq!(
+		vec![
+			(
+				ScopeKind::Global,
+				"n = number 1, g = f g(1 argument) {}".to_string()
+			),
+			(ScopeKind::Function, "n = string \"a\"".to_string()),
+			(ScopeKind::Block, "b = boolean true".to_string()),
+		],
+		snapshot(
+			"n = 1
+			f g(n) {
+				if true {
+					b = true
+					return -n
+				}
+			}
+			g(\"a\")"
+		)
+	);
+	// the scopes are saved at the first error, before the deferred code runs
+	assert_eq!(
+		vec![(ScopeKind::Global, "a = number 1".to_string())],
+		snapshot("a = 1\ndefer -true\n1 + true\nb = 2")
+	);
+
+	let mut interpreter = Interpreter::new();
+	interpreter.interpret(vec![]).unwrap();
+	assert!(interpreter.take_error_snapshot().is_none());
+	assert_eq!(1, interpreter.environment_snapshot().len());
+}
+
+#[test]
+fn limits() {
+	let run_limited = |source: &str| {
+		let tokens = Lexer::new(String::from(source)).scan_tokens().unwrap();
+		let mut interpreter = Interpreter::new();
+		interpreter.set_fuel(Some(10_000));
+		interpreter.set_max_string_length(Some(100));
+		interpreter
+			.interpret(Parser::new(tokens).parse().unwrap())
+			.unwrap_or(String::from("err"))
+	};
+
+	assert_eq!("err", run_limited("while true {\n}"));
+	assert_eq!(
+		"499500",
+		run_limited(
+			"i = 0
+			s = 0
+			while i < 1000 {
+				s = s + i
+				i = i + 1
+			}
+			s"
+		)
+	);
+	assert_eq!("err", run_limited(r#""a" * 101"#));
+	assert_eq!("err", run_limited(r#""a" * 1000000000000000000000"#));
+	assert_eq!(
+		"err",
+		run_limited(&format!(r#""{}" + "a""#, "a".repeat(100)))
+	);
+	assert_eq!(
+		"err",
+		run_limited(
+			r#"s = "a"
+			while true {
+				s = s + s
+			}"#
+		)
+	);
+	assert_eq!("100", run_limited(r#"size("a" * 100)"#));
+	assert_eq!("", run_limited(r#""" * 1000000000000000000000"#));
+	assert_eq!("err", run_limited(r#"pad_start("a", 101, "x")"#));
+	assert_eq!("100", run_limited(r#"size(pad_end("a", 100, "x"))"#));
+
+	assert_eq!(
+		"err",
+		ev("f loop(n) {
+			return loop(n + 1)
+		}
+		loop(0)")
+	);
+	assert_eq!(
+		"200",
+		ev("f count(n) {
+			if n == 200 {
+				return n
+			}
+			return count(n + 1)
+		}
+		count(0)")
+	);
+
+	assert!(has_parsing_error(&format!(
+		"{}1{}",
+		"(".repeat(100),
+		")".repeat(100)
+	)));
+	assert!(has_parsing_error(&format!("{}1", "-".repeat(100))));
+	assert_eq!("1", ev(&format!("{}1{}", "(".repeat(40), ")".repeat(40))));
+	assert_eq!(
+		Ok(vec![]),
+		Parser::new(vec![]).parse(),
+		"tokens that do not end with EOF should be accepted"
+	);
+
+	assert_eq!("err", ev("a = [1]\na = empty"));
+	assert_eq!("", ev("a = empty(number)\na = empty\na"));
+}
+
+fn expression(source: &str) -> parser::Expr {
+	let tokens = Lexer::new(String::from(source)).scan_tokens().unwrap();
+	match Parser::new(tokens).parse().unwrap().remove(0) {
+		parser::Statement::Expr { expr } => expr,
+		_ => panic!("`{source}` is not an expression"),
+	}
+}
+
+#[test]
+fn checker() {
+	let check = |source: &str| {
+		let tokens = Lexer::new(String::from(source)).scan_tokens().unwrap();
+		let statements = Parser::new(tokens).parse().unwrap();
+		let (result, errors) =
+			capture_errors(|| ilo::checker_for(&Interpreter::new()).check(&statements));
+		assert_eq!(result.is_err(), !errors.is_empty());
+		errors
+			.iter()
+			.map(|error| (error.line(), error.message().to_string()))
+			.collect::<Vec<_>>()
+	};
+
+	assert_eq!(
+		vec![(
+			2,
+			String::from(
+				"Variable `a` already exists, but has a different type (tried to replace a number with a string)"
+			)
+		)],
+		check("a = 1\na = \"s\"")
+	);
+	assert_eq!(
+		vec![(
+			2,
+			String::from("Condition of `if` statement should be a boolean expression")
+		)],
+		check("a = 1 + 2\nif a {\n}")
+	);
+	assert_eq!(
+		vec![(
+			1,
+			String::from(
+				"Addition (`+`) can only be performed between two numbers or two strings (found a number and a string)"
+			)
+		)],
+		check("b = 1 + \"s\"")
+	);
+	assert_eq!(3, check("a = true\nb = -a\nc = a < 2\nd = 3()").len());
+	assert_eq!(
+		vec![(3, String::from("Identifier `g` has already been declared"))],
+		check("f g(a) {\n}\nf g(a, b) {\n}")
+	);
+
+	// the types propagate through operators and empty values
+	assert_eq!(1, check("s = \"a\" * 3 + \"b\"\ns = 1").len());
+	assert_eq!(1, check("n = empty(number)\nn = empty\nn = true").len());
+	assert_eq!(0, check("s = \"a\"\ns = empty").len());
+	assert_eq!(1, check("l = [1]\nl = empty").len());
+	assert_eq!(1, check("l = [1]\nl = \"s\"").len());
+	assert_eq!(1, check("l = [1] + 1").len());
+	assert_eq!(1, check("a = 1\nout(a[0])").len());
+	assert_eq!(1, check("l = [1]\nl[\"a\"] = 2").len());
+	assert!(check("n = empty(number)\nout(n?[0])\nl = [1]\nout(l[0] + 1)").is_empty());
+	// the characters of strings are strings
+	assert_eq!(1, check("c = \"ab\"[0]\nc = 1").len());
+
+	// values whose type is unknown are accepted
+	assert!(check("f g(a) {\n\ta = 1\n\ta = 2\n\treturn a + 1\n}\nb = g(1)\nb = \"s\"").is_empty());
+	assert!(check("a = 1\nf g() {\n\ta = \"s\"\n}").is_empty());
+	assert!(check("if true {\n\ta = 1\n} else {\n\ta = \"s\"\n}").is_empty());
+	assert!(check("f g(a) {\n\ta = 1\n\ta = \"s\"\n}").len() == 1);
+
+	// calls are checked against the declarations and the natives
+	assert_eq!(
+		vec![(
+			4,
+			String::from("Expected 2 arguments for function(2), but found 1")
+		)],
+		check("f add(a, b) {\n\treturn a + b\n}\nadd(1)")
+	);
+	assert_eq!(
+		vec![(
+			1,
+			String::from("Expected 1 argument for function(1), but found 0")
+		)],
+		check("out()")
+	);
+	assert!(check("f g(a) {\n\treturn a(1, 2)\n}\nsize(\"a\")").is_empty());
+
+	// constant expressions that always fail
+	assert_eq!(
+		vec![(
+			1,
+			String::from(
+				"Multiplication (`*`) between a string and a number requires a positive integer (found `-2`)"
+			)
+		)],
+		check("a = \"a\" * -(1 + 1)")
+	);
+	assert_eq!(1, check("a = \"a\" * 1.5").len());
+	assert_eq!(1, check("a = \"a\" - \"b\"").len());
+	assert_eq!(1, check("a = \"a\" + 1").len());
+	assert_eq!(1, check("a = -true").len());
+	assert!(check("a = \"a\" * (4 / 2)").is_empty());
+	let tokens = Lexer::new(String::from("a = 1 / (2 - 2)\nb = 1 % 0"))
+		.scan_tokens()
+		.unwrap();
+	let statements = Parser::new(tokens).parse().unwrap();
+	let (result, warnings) = capture_errors(|| checker::Checker::new().check(&statements));
+	assert!(result.is_ok());
+	assert_eq!(
+		vec![
+			"Warning at line 1, column 7: Division by zero, the result is not a finite number.",
+			"Warning at line 2, column 7: Modulo by zero, the result is not a finite number."
+		],
+		warnings.iter().map(ToString::to_string).collect::<Vec<_>>()
+	);
+	assert_eq!(
+		Some(checker::Constant::String(String::from("abab"))),
+		checker::evaluate_constant(&expression("\"ab\" * (1 + 1)"))
+	);
+	assert_eq!(None, checker::evaluate_constant(&expression("\"ab\" * a")));
+
+	// names that cannot be defined when they are used
+	assert_eq!(
+		vec![(
+			1,
+			String::from("Undefined symbol `secnd` (did you mean `second`?)")
+		)],
+		check("out(secnd)\nsecond = 1")
+	);
+	assert!(check("f g() {\n\treturn later\n}\nlater = 1\ng()").is_empty());
+	assert_eq!(1, check("f g() {\n\treturn never\n}").len());
+	assert_eq!(1, check("{\n\tinner = 1\n}\ninner").len());
+	// imported files can define any name
+	assert!(check("import \"utils.ilo\"\nout(helper(1))").is_empty());
+	assert_eq!(
+		vec![(
+			2,
+			String::from("Increment (`++`) must be applied to a number variable (found a boolean)")
+		)],
+		check("done = false\ndone++")
+	);
+	assert_eq!(1, check("double = (n) -> n * 2\ndouble(1, 2)").len());
+	assert_eq!(
+		vec![(
+			3,
+			String::from("Expected at least 1 argument for function(1...), but found 0")
+		)],
+		check("f g(a, b...) {\n}\ng()")
+	);
+	assert!(check("f g(a, b...) {\n}\ng(1, 2, 3)").is_empty());
+	assert!(check("ask(\"name\", 1)").is_empty());
+	assert_eq!(1, check("ask()").len());
+	assert!(check("add = (a, b) -> a + b + later\nlater = 1").is_empty());
+
+	// errors are found before running, so nothing is printed
+	assert!(ilo::run("out(1)\na = 1\na = \"s\"", &ilo::RunOptions::default()).is_err());
+
+	// without warnings, like `ilo check --types`
+	let tokens = Lexer::new(String::from("a = number(\"x\")\nb = 1 / 0\nc = a + \"s\""))
+		.scan_tokens()
+		.unwrap();
+	let statements = Parser::new(tokens).parse().unwrap();
+	for (warnings, lines) in [(true, vec![1, 2, 3]), (false, vec![3])] {
+		let mut types_checker = checker::Checker::new();
+		types_checker.set_warnings(warnings);
+		let (result, errors) = capture_errors(|| types_checker.check(&statements));
+		assert!(result.is_err());
+		assert_eq!(
+			lines,
+			errors.iter().map(|error| error.line()).collect::<Vec<_>>()
+		);
+	}
+}
+
+#[test]
+fn unreachable_code() {
+	let warnings = |source: &str| {
+		let tokens = Lexer::new(String::from(source)).scan_tokens().unwrap();
+		let statements = Parser::new(tokens).parse().unwrap();
+		let (result, warnings) = capture_errors(|| checker::lint(&statements, false));
+		assert_eq!(Ok(()), result);
+		warnings
+			.iter()
+			.map(|warning| {
+				assert_eq!(error_manager::ErrorType::Warning, warning.error_type());
+				warning.line()
+			})
+			.collect::<Vec<_>>()
+	};
+
+	assert_eq!(
+		vec![3],
+		warnings(
+			"f g() {
+				return 1
+				out(2)
+				out(3)
+			}"
+		)
+	);
+	assert_eq!(vec![4], warnings("while true {\n\tout(1)\n}\nout(2)"));
+	assert_eq!(
+		vec![4],
+		warnings("repeat {\n\tout(1)\n} until false\nout(2)")
+	);
+	assert!(warnings("repeat {\n\tbreak\n} until false\nout(2)").is_empty());
+	assert_eq!(
+		vec![7],
+		warnings(
+			"f g(a) {
+				if a {
+					return 1
+				} else {
+					return 2
+				}
+				out(3)
+			}"
+		)
+	);
+	assert_eq!(
+		vec![4],
+		warnings(
+			"f g() {
+				return 1
+				{
+					out(2)
+				}
+			}"
+		)
+	);
+	assert!(warnings("while 1 < 2 {\n}\nout(1)").is_empty());
+	assert_eq!(vec![3], warnings("while 1 < 2 {\n\tbreak\n\tout(1)\n}"));
+	assert!(warnings("while true {\n\tbreak\n}\nout(1)").is_empty());
+	assert!(warnings(
+		"outer: while true {
+			while true {
+				break outer
+			}
+		}
+		out(1)"
+	)
+	.is_empty());
+	assert_eq!(
+		vec![6],
+		warnings(
+			"while true {
+				while true {
+					break
+				}
+			}
+			out(1)"
+		)
+	);
+	assert!(warnings("f g(a) {\n\tif a {\n\t\treturn 1\n\t}\n\tout(2)\n}").is_empty());
+	// a `match` never completes when all its arms return, and one of them is `default`
+	assert_eq!(
+		vec![9],
+		warnings(
+			"f g(a) {
+				match a {
+					1 {
+						return 1
+					} default {
+						return 2
+					}
+				}
+				out(3)
+			}"
+		)
+	);
+	assert!(
+		warnings("f g(a) {\n\tmatch a {\n\t\t1 {\n\t\t\treturn 1\n\t\t}\n\t}\n\tout(2)\n}")
+			.is_empty()
+	);
+	assert!(
+		warnings("while true {\n\tmatch 1 {\n\t\t1 {\n\t\t\tbreak\n\t\t}\n\t}\n}\nout(1)")
+			.is_empty()
+	);
+	assert_eq!(
+		"Warning at line 2, column 1: Unreachable code: the previous statement always returns, breaks, continues or loops forever.",
+		capture_errors(|| {
+			checker::lint(&Parser::new(Lexer::new(String::from("return 1\nout(2)")).scan_tokens().unwrap()).parse().unwrap(), false)
+		})
+		.1[0]
+		.to_string()
+	);
+}
+
+#[test]
+fn shadowing_warnings() {
+	let warnings = |source: &str| {
+		let tokens = Lexer::new(String::from(source)).scan_tokens().unwrap();
+		let statements = Parser::new(tokens).parse().unwrap();
+		let (result, warnings) = capture_errors(|| checker::lint(&statements, false));
+		assert_eq!(Ok(()), result);
+		warnings
+			.iter()
+			.map(|warning| (warning.line(), warning.message().to_string()))
+			.collect::<Vec<_>>()
+	};
+
+	assert_eq!(
+		vec![
+			(
+				3,
+				String::from(
+					"Parameter `n` hides the variable of the same name defined outside of `count`"
+				)
+			),
+			(
+				6,
+				String::from(
+					"Assignment to `a` modifies the variable defined outside of `count` instead of creating a local one"
+				)
+			),
+		],
+		warnings(
+			"n = 5
+			a = 0
+			f count(n) {
+				if (n > 1) {
+					count(n - 1)
+					a = n
+					a = n + 1
+				}
+				out(n)
+			}"
+		)
+	);
+
+	// assignments to variables of outer blocks, and to locals, are expected
+	assert!(warnings(
+		"i = 0
+		while i < 3 {
+			i = i + 1
+		}
+		f g(x) {
+			y = x
+			y = 2
+			x = 3
+		}"
+	)
+	.is_empty());
+}
+
+#[test]
+fn strict_mode() {
+	let errors = |source: &str, strict: bool| {
+		let tokens = Lexer::new(String::from(source)).scan_tokens().unwrap();
+		let statements = Parser::new(tokens).parse().unwrap();
+		let (result, errors) = capture_errors(|| {
+			let linted = checker::lint(&statements, strict);
+			let mut checker = checker::Checker::new();
+			checker.set_strict(strict);
+			checker.define_global("out", checker::Type::Function(1));
+			linted.and(checker.check(&statements))
+		});
+		assert_eq!(result.is_err(), strict && !errors.is_empty());
+		errors
+			.iter()
+			.map(|error| (error.error_type(), error.line()))
+			.collect::<Vec<_>>()
+	};
+	let strict_errors = |lines: &[i64]| {
+		lines
+			.iter()
+			.map(|line| (error_manager::ErrorType::StrictError, *line))
+			.collect::<Vec<_>>()
+	};
+
+	// allowed, but discouraged
+	let source = "a = 1\na = empty\nout(a == \"1\")\nout(1 / 0)";
+	assert_eq!(
+		vec![(error_manager::ErrorType::Warning, 4)],
+		errors(source, false)
+	);
+	assert_eq!(strict_errors(&[2, 3, 4]), errors(source, true));
+
+	// lints are promoted too
+	assert_eq!(
+		strict_errors(&[3]),
+		errors("f g() {\n\treturn 1\n\tout(2)\n}", true)
+	);
+
+	// typed empties and comparisons with `empty` are allowed
+	assert!(errors(
+		"a = empty(number)\nb = empty(boolean)\nout(a == empty)\nout(b != true)",
+		true
+	)
+	.is_empty());
+}
+
+#[test]
+fn method_calls() {
+	assert_eq!("11", ev(r#""hello world".size()"#));
+	assert_eq!("hello", ev(r#""hello".style("bold")"#));
+	assert_eq!(
+		"5",
+		ev("f add(a, b) {
+				return a + b
+			}
+			f double(n) {
+				return n * 2
+			}
+			x = 1
+			x.add(1.5).double()")
+	);
+	assert_eq!("2", ev(r#"("a" + "b").size()"#));
+	// the call is applied before unary operators
+	assert_eq!("-2", ev("a = \"ab\"\n-a.size()"));
+
+	// without parentheses, `.` reads the variant of an enum
+	assert_eq!("err", ev("a = \"ab\"\na.size"));
+	assert!(has_parsing_error("a.()"));
+	assert!(has_parsing_error("a.size(1"));
+	assert_eq!("err", ev("a = 1\na.a()"));
+}
+
+#[test]
+fn safe_calls() {
+	let source = |receiver: &str| {
+		format!(
+			"f double(n) {{
+				out(\"called\")
+				return n * 2
+			}}
+			{receiver}
+			a?.double()"
+		)
+	};
+	assert_eq!("4", ev(&source("a = 2")));
+	assert_eq!("", ev(&source("a = empty(number)")));
+	assert_eq!("", ev(&source("a = empty(boolean)")));
+
+	// nothing is evaluated after an empty receiver
+	assert_eq!("", ev("a = empty(number)\na?.missing(missing)"));
+	assert_eq!("", ev("a = empty(number)\na?.size()?.missing()"));
+	assert_eq!("5", ev(r#""hello"?.size()"#));
+	assert_eq!("err", ev("a = 1\na?.missing()"));
+	assert_eq!("err", ev("a = 1\na?.size(2)"));
+
+	assert!(has_parsing_error("a?.size"));
+	assert!(has_parsing_error("a?.(1)"));
+}
+
+#[test]
+fn loop_control() {
+	assert_eq!(
+		"3",
+		ev("i = 0
+			while true {
+				i = i + 1
+				if i == 3 {
+					break
+				}
+			}
+			i")
+	);
+	assert_eq!(
+		"4",
+		ev("i = 0
+			odd = 0
+			while i < 8 {
+				i = i + 1
+				if i % 2 == 0 {
+					continue
+				}
+				odd = odd + 1
+			}
+			odd")
+	);
+	assert_eq!(
+		"6",
+		ev("found = 0
+			i = 0
+			outer: while i < 5 {
+				i = i + 1
+				j = 0
+				while j < 5 {
+					j = j + 1
+					if i * j == 6 {
+						found = i * j
+						break outer
+					}
+					if j > i {
+						continue outer
+					}
+				}
+			}
+			found")
+	);
+	// the scopes of the blocks left early are closed
+	let mut interpreter = Interpreter::new();
+	let tokens = Lexer::new(String::from(
+		"while true {\n\t{\n\t\tlocal = 1\n\t\tbreak\n\t}\n}",
+	))
+	.scan_tokens()
+	.unwrap();
+	interpreter
+		.interpret(Parser::new(tokens).parse().unwrap())
+		.unwrap();
+	assert!(!interpreter
+		.variables()
+		.iter()
+		.any(|(name, _)| name == "local"));
+
+	assert!(has_parsing_error("break"));
+	assert!(has_parsing_error(
+		"while true {\n\tf g() {\n\t\tbreak\n\t}\n}"
+	));
+	assert!(has_parsing_error("while true {\n\tbreak outer\n}"));
+	assert!(has_parsing_error(
+		"a: while true {\n\ta: while true {\n\t}\n}"
+	));
+	assert!(has_parsing_error("a: out(1)"));
+	assert!(has_parsing_error("while true {\n\tbreak 1\n}"));
+}
+
+#[test]
+fn deferred_code() {
+	assert_eq!(
+		"cba",
+		ev(r#"log = ""
+			f g() {
+				defer {
+					log = log + "a"
+				}
+				if true {
+					defer {
+						log = log + "b"
+					}
+				}
+				log = log + "c"
+				return log
+			}
+			g()
+			log"#)
+	);
+	// the return value is computed before the deferred code runs
+	assert_eq!(
+		"1",
+		ev("n = 1
+			f g() {
+				defer {
+					n = 2
+				}
+				return n
+			}
+			g()")
+	);
+	// deferred code runs when the function or the script fails, even if other deferred code fails
+	let mut interpreter = Interpreter::new();
+	let tokens = Lexer::new(String::from(
+		"cleaned = false\ndone = false\nf g() {\n\tdefer {\n\t\tcleaned = true\n\t}\n\tdefer -true\n\t1 + true\n}\ndefer {\n\tdone = true\n}\ng()",
+	))
+	.scan_tokens()
+	.unwrap();
+	assert!(interpreter
+		.interpret(Parser::new(tokens).parse().unwrap())
+		.is_err());
+	for (name, value) in interpreter.variables() {
+		if name == "cleaned" || name == "done" {
+			assert_eq!("true", value.to_string());
+		}
+	}
+
+	assert!(has_parsing_error(
+		"f g() {\n\tdefer {\n\t\treturn 1\n\t}\n}"
+	));
+	assert!(has_parsing_error(
+		"while true {\n\tdefer {\n\t\tbreak\n\t}\n}"
+	));
+	assert!(!has_parsing_error(
+		"defer {\n\tf g() {\n\t\treturn 1\n\t}\n}"
+	));
+}
+
+#[test]
+fn attempt_statements() {
+	// the handler runs only when the body fails, with the error as a string
+	assert_eq!(
+		"Type error at line 3, column 7: Unary minus (`-`) must be applied to a number (found `true`).",
+		ev("e = \"none\"\nattempt {\n\ta = -true\n} rescue error {\n\te = error\n}\ne")
+	);
+	assert_eq!(
+		"1",
+		ev("r = 0\nattempt {\n\tr = 1\n} rescue error {\n\tr = 2\n}\nr")
+	);
+	// errors in called functions are rescued too, and the variables of the body are left
+	assert_eq!(
+		"rescued",
+		ev("f g() {\n\t{\n\t\treturn 1 + true\n\t}\n}\nr = \"\"\nattempt {\n\tb = 1\n\tg()\n} rescue error {\n\tr = \"rescued\"\n}\nr")
+	);
+	assert_eq!(
+		"err",
+		ev("attempt {\n\tr = 1\n} rescue error {\n\tr = 2\n}\nerror")
+	);
+	// errors in the handler are not rescued
+	assert_eq!(
+		"err",
+		ev("attempt {\n\t-true\n} rescue error {\n\t-false\n}")
+	);
+	// `return` and `break` go through the `attempt`
+	assert_eq!(
+		"2",
+		ev("f g() {\n\tattempt {\n\t\treturn 2\n\t} rescue error {\n\t\treturn 3\n\t}\n}\ng()")
+	);
+	// scripts raise their own errors with `fail`
+	assert_eq!(
+		"Runtime error at line 3, column 5: invalid config 2.",
+		ev("f load(version) {\n\tif version > 1 {\n\t\tfail \"invalid config \" + string(version)\n\t}\n}\ne = \"\"\nattempt {\n\tload(2)\n} rescue error {\n\te = error\n}\ne")
+	);
+	assert_eq!("err", ev("fail \"stop\"\nout(1)"));
+	assert!(has_parsing_error("fail"));
+	assert!(has_parsing_error("attempt {\n\tout(1)\n}"));
+	assert!(has_parsing_error(
+		"attempt {\n\tout(1)\n} rescue {\n\tout(2)\n}"
+	));
+
+	// the execution limit cannot be rescued
+	let tokens = Lexer::new(String::from(
+		"attempt {\n\twhile true {\n\t}\n} rescue error {\n\tout(error)\n}",
+	))
+	.scan_tokens()
+	.unwrap();
+	let mut interpreter = Interpreter::new();
+	interpreter.set_fuel(Some(10));
+	let (result, errors) =
+		capture_errors(|| interpreter.interpret(Parser::new(tokens).parse().unwrap()));
+	assert!(result.is_err());
+	assert_eq!("Execution limit reached", errors[0].message());
+}
+
+#[test]
+fn assert_statements() {
+	let failure = |source: &str| {
+		let tokens = Lexer::new(String::from(source)).scan_tokens().unwrap();
+		let (result, errors) =
+			capture_errors(|| Interpreter::new().interpret(Parser::new(tokens).parse().unwrap()));
+		assert!(result.is_err());
+		errors[0].to_string()
+	};
+	assert_eq!("2", ev("a = 2\nassert a == 2\na"));
+	assert_eq!(
+		"Runtime error at line 2, column 1: Assertion failed.",
+		failure("a = 1\nassert a == 2")
+	);
+	assert_eq!(
+		"Runtime error at line 2, column 1: Assertion failed: a is 1.",
+		failure("a = 1\nassert a == 2, \"a is \" + string(a)")
+	);
+	// only `true` passes
+	assert_eq!("err", ev("assert 1"));
+	assert!(has_parsing_error("assert"));
+	assert!(has_parsing_error("assert true, \"a\" out(1)"));
+}
+#[test]
+fn files() {
+	let directory = std::env::temp_dir().join(format!("ilo_files_{}", std::process::id()));
+	std::fs::create_dir_all(&directory).unwrap();
+	let path = |name: &str| directory.join(name).display().to_string();
+	std::fs::write(path("a.txt"), "a").unwrap();
+
+	let run = |source: String| ev(&source.replace('\\', "/"));
+	assert_eq!("true", run(format!(r#"file_exists("{}")"#, path("a.txt"))));
+	assert_eq!("false", run(format!(r#"file_exists("{}")"#, path("b.txt"))));
+	assert_eq!(
+		"true",
+		run(format!(
+			r#"copy_file("{}", "{}")"#,
+			path("a.txt"),
+			path("b.txt")
+		))
+	);
+	assert_eq!("a", std::fs::read_to_string(path("b.txt")).unwrap());
+	assert_eq!(
+		"true",
+		run(format!(
+			r#"move_file("{}", "{}")"#,
+			path("b.txt"),
+			path("c.txt")
+		))
+	);
+	assert!(!directory.join("b.txt").exists());
+	assert_eq!("a", std::fs::read_to_string(path("c.txt")).unwrap());
+	assert_eq!("true", run(format!(r#"remove_file("{}")"#, path("c.txt"))));
+	assert!(!directory.join("c.txt").exists());
+	assert_eq!("false", run(format!(r#"remove_file("{}")"#, path("c.txt"))));
+	assert_eq!(
+		"false",
+		run(format!(
+			r#"move_file("{}", "{}")"#,
+			path("c.txt"),
+			path("d.txt")
+		))
+	);
+	assert_eq!("err", ev("copy_file(1, 2)"));
+
+	std::fs::create_dir_all(directory.join("lib")).unwrap();
+	for name in ["a.ilo", "lib/b.ilo", "lib/c.txt"] {
+		std::fs::write(path(name), "").unwrap();
+	}
+	assert_eq!(
+		r#"["a.ilo", "a.txt", "lib"]"#,
+		run(format!(r#"list_dir("{}", false)"#, path("")))
+	);
+	assert_eq!(
+		r#"["a.ilo", "a.txt", "lib", "lib/b.ilo", "lib/c.txt"]"#,
+		run(format!(r#"list_dir("{}", true)"#, path("")))
+	);
+	assert_eq!(
+		format!(r#"["{}", "{}"]"#, path("a.ilo"), path("lib/b.ilo")).replace('\\', "/"),
+		run(format!(r#"glob("{}")"#, path("**/*.ilo")))
+	);
+	assert_eq!(
+		format!(r#"["{}"]"#, path("lib/c.txt")).replace('\\', "/"),
+		run(format!(r#"glob("{}")"#, path("lib/c.txt")))
+	);
+
+	std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn imports() {
+	use error_manager::set_current_file;
+
+	let directory = std::env::temp_dir().join(format!("ilo_imports_{}", std::process::id()));
+	std::fs::create_dir_all(directory.join("lib")).unwrap();
+	for (path, source) in [
+		("constants.ilo", "ratio = 2"),
+		(
+			"lib/math.ilo",
+			"import \"../constants.ilo\"\nf scale(x) {\n\treturn x * ratio\n}",
+		),
+		("lib/peek.ilo", "seen = secret"),
+		("lib/broken.ilo", "a = (1"),
+		("cycle.ilo", "import \"./lib/../cycle_back.ilo\""),
+		("cycle_back.ilo", "import \"cycle.ilo\""),
+	] {
+		std::fs::write(directory.join(path), source).unwrap();
+	}
+	set_current_file(Some(directory.join("main.ilo").display().to_string()));
+
+	assert_eq!("6", ev("import \"lib/math.ilo\"\nscale(3)"));
+	assert_eq!("2", ev("import \"lib/math.ilo\"\nratio"));
+	assert_eq!(
+		"4",
+		ev("import \"constants.ilo\"\nimport \"lib/math.ilo\"\nratio = 4\nratio")
+	);
+	// imported files do not see the variables of the importing script
+	assert_eq!("err", ev("secret = 1\nimport \"lib/peek.ilo\""));
+	assert_eq!("err", ev("import \"lib/broken.ilo\""));
+	assert_eq!("err", ev("import \"missing.ilo\""));
+	assert_eq!("err", ev("import \"cycle.ilo\""));
+	assert_eq!("err", ev("import \"main.ilo\""));
+	assert_eq!("err", ev("ratio = true\nimport \"constants.ilo\""));
+
+	// imported with a name, the variables of the file are read from the module
+	assert_eq!("6", ev("import \"lib/math.ilo\" as math\nmath.scale(3)"));
+	assert_eq!(
+		"4",
+		ev("import \"lib/math.ilo\" as math\nscale = math.scale\nscale(2)")
+	);
+	assert_eq!(
+		"true",
+		ev("import \"constants.ilo\" as a\nimport \"./constants.ilo\" as b\na == b")
+	);
+	assert_eq!(
+		"1",
+		ev("ratio = 1\nimport \"constants.ilo\" as constants\nconstants.ratio\nratio")
+	);
+	assert_eq!("err", ev("import \"lib/math.ilo\" as math\nscale(3)"));
+	assert_eq!("err", ev("import \"lib/math.ilo\" as math\nmath.missing"));
+	// the module stays known to the next inputs of the REPL
+	let mut interpreter = Interpreter::new();
+	assert!(ilo::run_in(&mut interpreter, "import \"lib/math.ilo\" as math").is_ok());
+	assert_eq!(
+		Ok(interpreter::Value::Number(6.0)),
+		ilo::run_in(&mut interpreter, "math.scale(3)")
+	);
+	set_current_file(None);
+	std::fs::remove_dir_all(directory).unwrap();
+
+	assert!(has_parsing_error("import utils"));
+	assert!(has_parsing_error("import \"utils.ilo\" 2"));
+	assert!(has_parsing_error("if true {\n\timport \"utils.ilo\"\n}"));
+	assert!(has_parsing_error("import \"utils.ilo\" as"));
+	assert!(has_parsing_error("import \"utils.ilo\" as \"utils\""));
+}
+
+#[test]
+fn for_loops() {
+	assert_eq!(
+		"h-e-l-l-o-",
+		ev(r#"result = ""
+			for c in "hello" {
+				result = result + c + "-"
+			}
+			result"#)
+	);
+	// characters are Unicode scalar values, not bytes
+	assert_eq!(
+		"3",
+		ev(r#"count = 0
+			for c in "héé" {
+				count = count + 1
+			}
+			count"#)
+	);
+	assert_eq!(
+		"é",
+		ev(r#"last = ""
+			for c in "aé" {
+				last = c
+			}
+			last"#)
+	);
+	assert_eq!(
+		"ac",
+		ev(r#"result = ""
+			letters: for c in "abcd" {
+				for d in "x" {
+					if c == "b" {
+						continue letters
+					}
+					if c == "d" {
+						break letters
+					}
+				}
+				result = result + c
+			}
+			result"#)
+	);
+	// the variable only exists in the loop
+	assert_eq!(
+		"1",
+		ev(r#"c = 1
+			for c in "ab" {
+				c = c + "!"
+			}
+			c"#)
+	);
+	assert_eq!(
+		"",
+		ev(r#"for c in "" {
+	out(c)
+}"#)
+	);
+
+	assert_eq!("err", ev("for c in 12 {\n}"));
+	assert_eq!(
+		"err",
+		ev("n = 1\nf g() {\n\treturn n\n}\nfor c in g() {\n}")
+	);
+	assert!(has_parsing_error("for in \"a\" {\n}"));
+	assert!(has_parsing_error("for c \"a\" {\n}"));
+	assert!(has_parsing_error("for c in \"a\"\n"));
+}
+
+#[test]
+fn for_ranges() {
+	// number of iterations and sum of the numbers
+	let range = |range: &str| {
+		let loop_source = |update: &str| {
+			format!("result = 0\nfor i in {range} {{\n\tresult = {update}\n}}\nresult")
+		};
+		(
+			ev(&loop_source("result + 1")),
+			ev(&loop_source("result + i")),
+		)
+	};
+	let expected = |count: &str, sum: &str| (count.to_string(), sum.to_string());
+
+	assert_eq!(expected("4", "10"), range("1...4"));
+	assert_eq!(expected("3", "12"), range("0...10 step 4"));
+	assert_eq!(expected("3", "6"), range("3...1"));
+	assert_eq!(expected("2", "14"), range("10...0 step -6"));
+	assert_eq!(expected("0", "0"), range("1...4 step -1"));
+	assert_eq!(expected("1", "1"), range("1...1"));
+	assert_eq!(expected("2", "1.5"), range("0.5...1 step 0.5"));
+
+	assert_eq!(
+		"30",
+		ev("sum = 0
+			for i in 0...10 step 2 {
+				sum = sum + i
+			}
+			sum")
+	);
+	assert_eq!(
+		"3",
+		ev("count = 0
+			n = 3
+			for i in n * 3...n step 0 - n {
+				count = count + 1
+			}
+			count")
+	);
+	assert_eq!(
+		"6",
+		ev("count = 0
+			for i in 1...100 {
+				if i > 6 {
+					break
+				}
+				count = i
+			}
+			count")
+	);
+
+	assert_eq!("err", ev("for i in 1...\"a\" {\n}"));
+	assert_eq!("err", ev("for i in 1...4 step 0 {\n}"));
+	assert_eq!("err", ev("n = 0\nfor i in 1...4 step n {\n}"));
+	assert_eq!("err", ev("for i in 1...4 {\n\ti = i + \"a\"\n}"));
+	assert!(has_parsing_error("for i in 1... {\n}"));
+	assert!(has_parsing_error("for i in 1...4 step {\n}"));
+}
+
+#[test]
+fn emptiness_operator() {
+	assert_eq!("true", ev("a = empty(number)\na?"));
+	assert_eq!("true", ev("a = empty(boolean)\na?"));
+	assert_eq!("false", ev("a = 3\na?"));
+	assert_eq!("false", ev("a = false\na?"));
+	assert_eq!(
+		"false",
+		ev(r#"a = ""
+		a?"#)
+	);
+	assert_eq!("true", ev("a = 3\na = empty\na?"));
+	assert_eq!("false", ev("a = empty(number)\n!a?"));
+	assert_eq!(
+		"true",
+		ev("a = empty(number)
+			b = 2
+			a? and !b?")
+	);
+	assert_eq!("false", ev(r#""a".size()?"#));
+	assert_eq!("false", ev("(1 + 2)?"));
+
+	assert_eq!("err", ev("missing?"));
+	assert!(has_parsing_error("?a"));
+	assert_eq!(
+		"let a = null;\nconsole.log((a === null));\n",
+		transpile(
+			&Parser::new(
+				Lexer::new(String::from("a = empty(number)\nout(a?)"))
+					.scan_tokens()
+					.unwrap()
+			)
+			.parse()
+			.unwrap()
+		)
+	);
+}
+
+#[test]
+fn conversions() {
+	assert_eq!("3.5", ev(r#"number(" 3.5 ") + 0"#));
+	assert_eq!("-2", ev(r#"number("-2")"#));
+	assert_eq!("1", ev("number(true)"));
+	assert_eq!("0", ev("number(false)"));
+	assert_eq!("4", ev("number(4)"));
+	assert_eq!("12", ev("string(1) + string(2)"));
+	assert_eq!("0.5true", ev("string(0.5) + string(true)"));
+	assert_eq!("", ev("a = empty(number)\nstring(a)"));
+	assert_eq!("true", ev(r#"boolean("true")"#));
+	assert_eq!("false", ev("boolean(0)"));
+	assert_eq!("true", ev("boolean(1) and boolean(true)"));
+	assert_eq!("true", ev("a = empty(number)\nboolean(a)?"));
+
+	// impossible conversions give an empty value, or an error in strict mode
+	let convert = |source: &str, strict: bool| {
+		let tokens = Lexer::new(String::from(source)).scan_tokens().unwrap();
+		let mut interpreter = Interpreter::new();
+		interpreter.set_strict(strict);
+		let (result, _) =
+			capture_errors(|| interpreter.interpret(Parser::new(tokens).parse().unwrap()));
+		result.unwrap_or(String::from("err"))
+	};
+	for source in [
+		r#"number("abc")?"#,
+		r#"number("")?"#,
+		r#"number("1e999")?"#,
+		r#"boolean("yes")?"#,
+		"boolean(2)?",
+		"string(out)?",
+	] {
+		assert_eq!("true", convert(source, false), "{source}");
+		assert_eq!("err", convert(source, true), "{source}");
+	}
+
+	// conversions of constants that always fail are found before running
+	let (_, warnings) = capture_errors(|| {
+		let tokens = Lexer::new(String::from("a = number(\"abc\")\nb = boolean(3)"))
+			.scan_tokens()
+			.unwrap();
+		checker::Checker::new().check(&Parser::new(tokens).parse().unwrap())
+	});
+	assert_eq!(
+		vec![
+			"`\"abc\"` cannot be converted to a number, the result is empty",
+			"`3` cannot be converted to a boolean, the result is empty"
+		],
+		warnings
+			.iter()
+			.map(|warning| warning.message())
+			.collect::<Vec<_>>()
+	);
+	assert_eq!("err", ev("a = 1\na = string(a)"));
+	assert!(has_parsing_error("number"));
+	assert!(has_parsing_error("number(1"));
+	assert!(has_parsing_error("string()"));
+}
+
+#[test]
+fn match_statements() {
+	let source = r#"f describe(value) {
+		result = "other"
+		match value {
+			1, 2 {
+				result = "small"
+			} "x" {
+				result = "x"
+			}
+			number {
+				result = "number"
+			}
+			empty -> out("empty")
+		}
+		return result
+	}
+	n = empty(number)
+	describe(1) + describe(2) + describe("x") + describe(7) + describe(n) + describe(true)"#;
+	assert_eq!("smallsmallxnumberotherother", ev(source));
+	// the first matching arm runs, and a `match` without `default` may run no arm
+	assert_eq!(
+		"a",
+		ev(r#"log = ""
+			match 1 {
+				1 {
+					log = log + "a"
+				} number {
+					log = log + "b"
+				}
+			}
+			match "s" {
+				boolean {
+					log = log + "c"
+				}
+			}
+			log"#)
+	);
+
+	// as a value, a `match` gives the value of its arm, and fails when no arm matches
+	assert_eq!(
+		"not found",
+		ev(r#"code = 404
+			label = match code { 200 -> "ok", 404 -> "not found", default -> "error" }
+			label"#)
+	);
+	assert_eq!(
+		"3",
+		ev("a = 1 + match true { false -> 1, default -> 2 }\na")
+	);
+	assert_eq!("err", ev("a = match 3 { 1 -> 1 }"));
+
+	assert!(has_parsing_error("match 1 {\n\t1 -> out(1) 2 -> out(2)\n}"));
+
+	// an arm with a guard runs only when the guard is `true`, otherwise the next arms are tried
+	let size = r#"f size(n) {
+		return match n {
+			0 -> "none"
+			1, 2, 3 -> "few"
+			number if n > 10 -> "many"
+			default if n < 0 -> "negative"
+			default -> "some"
+		}
+	}
+	size(0) + " " + size(2) + " " + size(7) + " " + size(11) + " " + size(-1)"#;
+	assert_eq!("none few some many negative", ev(size));
+	assert_eq!(
+		"big",
+		ev("n = 12\nr = \"\"\nmatch n {\n\tnumber if n > 10 {\n\t\tr = \"big\"\n\t}\n\tdefault {\n\t\tr = \"small\"\n\t}\n}\nr")
+	);
+	assert!(has_parsing_error("match 1 {\n\t1 if -> out(1)\n}"));
+	assert!(has_parsing_error("match 1 {\n\t1 -> out(1)"));
+	assert!(has_parsing_error(
+		"a = match 1 {\n\t1 {\n\t\tout(1)\n\t}\n}"
+	));
+	assert!(has_parsing_error("match 1 {\n\t1 out(1)\n}"));
+
+	let tokens = Lexer::new(String::from(source)).scan_tokens().unwrap();
+	let statements = Parser::new(tokens).parse().unwrap();
+	assert_eq!(
+		statements,
+		load(&compile("match.ilo", &statements)).unwrap().statements
+	);
+}
+
+#[test]
+fn enums() {
+	assert_eq!(
+		"Color.green",
+		ev("enum Color { red, green, blue }\nColor.green")
+	);
+	assert_eq!(
+		"enum Color { red, green }",
+		ev("enum Color {\n\tred\n\tgreen\n}\nColor")
+	);
+	// variants are only equal to themselves
+	assert_eq!(
+		"true false false",
+		ev(r#"enum Color { red, green }
+			enum Light { red }
+			c = Color.red
+			string(c == Color.red) + " " + string(c == Light.red) + " " + string(c == "Color.red")"#)
+	);
+	assert_eq!(
+		"go",
+		ev(r#"enum Light { red, green }
+			match Light.green {
+				Light.red -> "stop"
+				Light.green -> "go"
+			}"#)
+	);
+	assert_eq!("err", ev("enum Color { red }\nColor.blue"));
+	assert_eq!("err", ev("a = 1\na.b"));
+	assert_eq!(
+		"Color.blue",
+		ev("enum Color { red }\nenum Color { blue }\nColor.blue")
+	);
+	assert_eq!("err", ev("Color = 1\nenum Color { blue }"));
+	assert!(has_parsing_error("enum Color { red, red }"));
+	assert!(has_parsing_error("enum Color { }"));
+	assert!(has_parsing_error("enum { red }"));
+
+	let js = |source: &str| {
+		let tokens = Lexer::new(String::from(source)).scan_tokens().unwrap();
+		transpile(&Parser::new(tokens).parse().unwrap())
+	};
+	assert!(js("enum Color { red, green }\nmatch Color.red {\n\tColor.red, string -> out(1)\n}")
+		.ends_with(
+			"const Color = $ilo_enum(\"Color\", [\"red\", \"green\"]);\n{\n\tconst $ilo_match = Color.red;\n\tif (($ilo_match === Color.red) || (typeof $ilo_match === \"string\")) {\n\t\tconsole.log(1);\n\t}\n}\n"
+		));
+	assert!(js("a = match 1 { 1 -> true, default -> false }").ends_with(
+		"let a = (($ilo_match) => $ilo_match === 1 ? true : true ? false : $ilo_unmatched($ilo_match))(1);\n"
+	));
+}
+
+#[test]
+fn match_checks() {
+	let check = |source: &str| {
+		let tokens = Lexer::new(String::from(source)).scan_tokens().unwrap();
+		let statements = Parser::new(tokens).parse().unwrap();
+		let (_, errors) =
+			capture_errors(|| ilo::checker_for(&Interpreter::new()).check(&statements));
+		errors
+			.iter()
+			.map(|error| {
+				(
+					error.error_type(),
+					error.line(),
+					error.message().to_string(),
+				)
+			})
+			.collect::<Vec<_>>()
+	};
+	use error_manager::ErrorType::{TypeError, Warning};
+
+	assert_eq!(
+		vec![
+			(
+				Warning,
+				3,
+				String::from("Pattern `1` is already handled by a previous arm")
+			),
+			(
+				Warning,
+				5,
+				String::from(
+					"Unreachable arm: the `default` arm before it matches every value"
+				)
+			),
+		],
+		check("match 2 {\n\t1 -> out(1)\n\t2, 1 -> out(2)\n\tdefault -> out(3)\n\tnumber -> out(4)\n}")
+	);
+	assert_eq!(
+		vec![(
+			Warning,
+			3,
+			String::from("`match` does not handle `Color.blue` (add its arm or a `default` arm)")
+		)],
+		check("enum Color { red, green, blue }\nc = Color.red\nmatch c {\n\tColor.red, Color.green -> out(1)\n}")
+	);
+	assert_eq!(
+		vec![(
+			TypeError,
+			2,
+			String::from(
+				"`match` does not handle `Color.green`, `Color.blue` (add their arms or a `default` arm)"
+			)
+		)],
+		check("enum Color { red, green, blue }\na = match Color.red { Color.red -> 1 }")
+	);
+	assert_eq!(
+		vec![(
+			TypeError,
+			1,
+			String::from(
+				"`match` used as a value needs a `default` arm, as some values are handled by none of its arms"
+			)
+		)],
+		check("a = match 1 { 1 -> 1 }")
+	);
+	assert_eq!(
+		vec![(
+			TypeError,
+			2,
+			String::from("Enum `Color` has no variant `purple`")
+		)],
+		check("enum Color { red }\nout(Color.purple)")
+	);
+	assert_eq!(1, check("a = 1\nout(a.b)").len());
+	// the arms give the type of the value, and statements without `default` need no arm
+	assert_eq!(
+		1,
+		check("a = match 1 { 1 -> \"a\", default -> \"b\" }\na = 2").len()
+	);
+	assert!(check("b = true\na = match b { true -> 1, false -> 2, empty -> 3 }").is_empty());
+	assert!(check("match 1 {\n\t2 -> out(2)\n}").is_empty());
+	// the patterns of an arm with a guard do not handle the values for the next arms
+	assert!(check("a = 1\nmatch a {\n\tdefault if a > 1 -> out(1)\n\t1 -> out(2)\n}").is_empty());
+	assert_eq!(
+		1,
+		check("a = match 1 { 1 -> 1, default if true -> 2 }").len()
+	);
+
+	// enums defined before checking are known to the checker
+	let mut interpreter = Interpreter::new();
+	let tokens = Lexer::new(String::from("enum Color { red }"))
+		.scan_tokens()
+		.unwrap();
+	interpreter
+		.interpret(Parser::new(tokens).parse().unwrap())
+		.unwrap();
+	let tokens = Lexer::new(String::from("Color.blue"))
+		.scan_tokens()
+		.unwrap();
+	let statements = Parser::new(tokens).parse().unwrap();
+	let (result, _) = capture_errors(|| ilo::checker_for(&interpreter).check(&statements));
+	assert!(result.is_err());
+}
+
+#[test]
+fn lists() {
+	assert_eq!("[1, 2, \"a\", true]", ev("[1, 1 + 1, \"a\", true]"));
+	assert_eq!("[]", ev("[]"));
+	assert_eq!(
+		"[[1], empty(number), \"\"]",
+		ev("n = empty(number)\n[[1], n, \"\"]")
+	);
+	assert_eq!("[1, 2]", ev("[\n\t1,\n\t2,\n]"));
+	assert_eq!("[1]", ev("string([1])"));
+
+	// lists are equal when their elements are
+	assert_eq!("true", ev("[1, [\"a\"]] == [1, [\"a\"]]"));
+	assert_eq!("false", ev("[1, 2] == [2, 1]"));
+	assert_eq!("false", ev("[1] == [1, 1]"));
+	assert_eq!("false", ev("[1] == 1"));
+
+	assert_eq!("3", ev("size([1, [2, 3], 4])"));
+	assert_eq!("0", ev("[].size()"));
+	assert_eq!("err", ev("size(1)"));
+	assert_eq!("err", ev("[1] + 1"));
+
+	assert!(has_parsing_error("[1, 2"));
+	assert!(has_parsing_error("[1 2]"));
+	assert!(has_parsing_error("[,]"));
+
+	let tokens = Lexer::new(String::from("a = [1, [\"b\"]]\nsize(a)"))
+		.scan_tokens()
+		.unwrap();
+	let statements = Parser::new(tokens).parse().unwrap();
+	assert_eq!(
+		statements,
+		load(&compile("lists.ilo", &statements)).unwrap().statements
+	);
+	// `===` compares arrays by reference, so lists are compared by a helper
+	let tokens = Lexer::new(String::from("a = [1, [\"b\"]]\nb = a == [1]\nc = a != 1"))
+		.scan_tokens()
+		.unwrap();
+	assert!(transpile(&Parser::new(tokens).parse().unwrap())
+		.ends_with("let a = [1, [\"b\"]];\nlet b = $ilo_equals(a, [1]);\nlet c = a !== 1;\n"));
+}
+
+#[test]
+fn list_indexing() {
+	assert_eq!("1", ev("xs = [1, 2, 3]\nxs[0]"));
+	assert_eq!("3", ev("xs = [1, 2, 3]\nxs[-1]"));
+	assert_eq!("b", ev("[\"a\", [\"b\"]][1][0]"));
+	assert_eq!("[1, 5]", ev("xs = [1, 2]\nxs[1] = 5\nxs"));
+	assert_eq!(
+		"[[1, \"x\"], 3]",
+		ev("xs = [[1, 2], 3]\nxs[0][-1] = \"x\"\nxs")
+	);
+	// the other copies of a list keep their elements
+	assert_eq!("[1, 2]", ev("xs = [1, 2]\nys = xs\nxs[0] = 3\nys"));
+	assert_eq!(
+		"[1]",
+		ev("xs = [1]\nf change(list) {\n\tlist[0] = 2\n}\nchange(xs)\nxs")
+	);
+
+	// errors are reported at the index
+	let errors = |source: &str| {
+		let tokens = Lexer::new(String::from(source)).scan_tokens().unwrap();
+		let statements = Parser::new(tokens).parse().unwrap();
+		let (_, errors) = capture_errors(|| Interpreter::new().interpret(statements));
+		errors.iter().map(ToString::to_string).collect::<Vec<_>>()
+	};
+	assert_eq!(
+		vec!["Runtime error at line 2, column 4: Index 2 is out of the bounds of a list of 2 elements."],
+		errors("xs = [1, 2]\nxs[2]")
+	);
+	assert_eq!(
+		vec!["Runtime error at line 2, column 4: Index -3 is out of the bounds of a list of 2 elements."],
+		errors("xs = [1, 2]\nxs[-3] = 1")
+	);
+	assert_eq!(
+		vec!["Runtime error at line 1, column 5: Index of a list must be a whole number (found 0.5)."],
+		errors("[1][0.5]")
+	);
+	assert_eq!(
+		vec![
+			"Type error at line 2, column 2: Only the elements of lists can be assigned with `[]` (found number 1)."
+		],
+		errors("a = 1\na[0] = 2")
+	);
+	assert_eq!("err", ev("[1][\"0\"]"));
+
+	// `?[` gives an empty value instead of failing
+	assert_eq!("", ev("n = empty(number)\nn?[0]"));
+	assert_eq!("true", ev("xs = [1]\nxs?[1]?"));
+	assert_eq!("1", ev("xs = [1]\nxs?[-1]"));
+	assert_eq!("err", ev("true?[0]"));
+
+	assert!(has_parsing_error("xs[0"));
+	assert!(has_parsing_error("xs?[0] = 1"));
+	assert!(has_parsing_error("[1][0] = 1"));
+	assert!(has_parsing_error("f()[0] = 1"));
+	assert!(has_parsing_error("1 = 1"));
+
+	let tokens = Lexer::new(String::from("xs = [[1]]\nxs[0][0] = xs?[0]"))
+		.scan_tokens()
+		.unwrap();
+	let statements = Parser::new(tokens).parse().unwrap();
+	assert_eq!(
+		statements,
+		load(&compile("index.ilo", &statements)).unwrap().statements
+	);
+	assert!(transpile(&statements)
+		.ends_with("let xs = [[1]];\n$ilo_set($ilo_index(xs, 0), 0, $ilo_safe_index(xs, 0));\n"));
+}
+
+#[test]
+fn string_indexing() {
+	assert_eq!("a", ev("\"abc\"[0]"));
+	assert_eq!("c", ev("s = \"abc\"\ns[-1]"));
+	assert_eq!("é", ev("\"héllo\"[1]"));
+	assert_eq!("", ev("\"abc\"?[3]"));
+	assert_eq!("b", ev("[\"abc\"][0][1]"));
+
+	// errors are reported at the bracket
+	let tokens = Lexer::new(String::from("s = \"ab\"\ns[2]"))
+		.scan_tokens()
+		.unwrap();
+	let statements = Parser::new(tokens).parse().unwrap();
+	let (_, errors) = capture_errors(|| Interpreter::new().interpret(statements));
+	assert_eq!(
+		vec!["Runtime error at line 2, column 2: Index 2 is out of the bounds of a string of 2 characters."],
+		errors.iter().map(ToString::to_string).collect::<Vec<_>>()
+	);
+	assert_eq!("err", ev("\"ab\"[0.5]"));
+	// strings cannot be changed
+	assert_eq!("err", ev("s = \"ab\"\ns[0] = \"c\""));
+}
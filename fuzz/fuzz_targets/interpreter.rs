@@ -0,0 +1,24 @@
+#![no_main]
+
+use error_manager::capture_errors;
+use interpreter::{Interpreter, NullHost};
+use lexer::Lexer;
+use libfuzzer_sys::fuzz_target;
+use parser::Parser;
+
+fuzz_target!(|data: &[u8]| {
+	let Ok(source) = std::str::from_utf8(data) else {
+		return;
+	};
+	let _ = capture_errors(|| {
+		let tokens = Lexer::new(source.to_string()).scan_tokens()?;
+		let statements = Parser::new(tokens).parse()?;
+
+		let mut interpreter = Interpreter::new();
+		interpreter.set_sandbox(true);
+		interpreter.set_host(Box::new(NullHost));
+		interpreter.set_fuel(Some(10_000));
+		interpreter.set_max_string_length(Some(1 << 16));
+		interpreter.interpret(statements)
+	});
+});
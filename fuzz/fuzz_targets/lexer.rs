@@ -0,0 +1,10 @@
+#![no_main]
+
+use error_manager::capture_errors;
+use lexer::Lexer;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+	let source = String::from_utf8_lossy(data).into_owned();
+	let _ = capture_errors(|| Lexer::new(source).scan_tokens());
+});
@@ -0,0 +1,27 @@
+#![no_main]
+
+use error_manager::capture_errors;
+use lexer::{Token, TokenType};
+use libfuzzer_sys::fuzz_target;
+use parser::Parser;
+
+/// Token of a type chosen by the fuzzer, which can be any type of the lexer, so that the parser
+/// receives sequences that the lexer would never produce
+fn token(token_type: TokenType, index: usize) -> Token {
+	let lexeme = match &token_type {
+		TokenType::Identifier => String::from(if index % 2 == 0 { "a" } else { "b" }),
+		TokenType::NumberLiteral(number) => number.to_string(),
+		TokenType::StringLiteral(string) => format!("\"{string}\""),
+		token_type => format!("{token_type:?}").to_lowercase(),
+	};
+	Token::new(token_type, lexeme, 1, index as i64 + 1)
+}
+
+fuzz_target!(|token_types: Vec<TokenType>| {
+	let tokens = token_types
+		.into_iter()
+		.enumerate()
+		.map(|(index, token_type)| token(token_type, index))
+		.collect();
+	let _ = capture_errors(|| Parser::new(tokens).parse());
+});
@@ -0,0 +1,1388 @@
+//! Compilation of parsed scripts to `.iloc` files.
+//!
+//! A compiled file starts with a header (the `ILOC` magic bytes, the format version and the
+//! path of the original source file), followed by the statements of the script, written as a
+//! pre-order stream of opcodes: each statement or expression is an opcode followed by its
+//! operands (tokens, nested nodes, or counts for lists of nodes).
+//!
+//! Loading a compiled file gives back the exact statements the parser produced, so lexing and
+//! parsing can be skipped. As compiled files can be changed by hand, the loader also rejects
+//! the code that the parser would have rejected and the interpreter relies on never running.
+//! `disassemble` lists the opcodes of a compiled file for humans.
+
+use lexer::{Symbol, Token, TokenType};
+use parser::{Expr, Iterable, MatchArm, Pattern, Statement, MAX_NESTING};
+use std::fmt::Display;
+
+const MAGIC: &[u8; 4] = b"ILOC";
+const FORMAT_VERSION: u8 = 5;
+
+const OP_EXPR: u8 = 0x01;
+const OP_ASSIGNMENT: u8 = 0x02;
+const OP_BLOCK: u8 = 0x03;
+const OP_IF: u8 = 0x04;
+const OP_WHILE: u8 = 0x05;
+const OP_FUNCTION_DECLARATION: u8 = 0x06;
+const OP_RETURN: u8 = 0x07;
+const OP_BREAK: u8 = 0x08;
+const OP_CONTINUE: u8 = 0x09;
+const OP_FOR: u8 = 0x0a;
+const OP_DEFER: u8 = 0x0b;
+const OP_MATCH: u8 = 0x0c;
+const OP_ENUM: u8 = 0x0d;
+const OP_INDEX_ASSIGNMENT: u8 = 0x0e;
+const OP_INCREMENT: u8 = 0x0f;
+const OP_REPEAT: u8 = 0x10;
+const OP_ATTEMPT: u8 = 0x11;
+const OP_FAIL: u8 = 0x12;
+const OP_ASSERT: u8 = 0x13;
+const OP_IMPORT: u8 = 0x14;
+const OP_MULTIPLE_ASSIGNMENT: u8 = 0x15;
+
+const OP_PRIMARY: u8 = 0x20;
+const OP_UNARY: u8 = 0x21;
+const OP_BINARY: u8 = 0x22;
+const OP_GROUPING: u8 = 0x23;
+const OP_VARIABLE: u8 = 0x24;
+const OP_CALL: u8 = 0x25;
+const OP_SAFE_CALL: u8 = 0x26;
+const OP_POSTFIX: u8 = 0x27;
+const OP_CONVERSION: u8 = 0x28;
+const OP_MATCH_EXPR: u8 = 0x29;
+const OP_FIELD: u8 = 0x2a;
+const OP_LIST: u8 = 0x2b;
+const OP_INDEX: u8 = 0x2c;
+const OP_LAMBDA: u8 = 0x2d;
+
+const PATTERN_VALUE: u8 = 0x00;
+const PATTERN_TYPE: u8 = 0x01;
+const PATTERN_DEFAULT: u8 = 0x02;
+
+const TOKEN_NUMBER_LITERAL: u8 = 0x80;
+const TOKEN_STRING_LITERAL: u8 = 0x81;
+
+/// Defines the tags of the token types that don’t hold a value
+macro_rules! simple_token_tags {
+	($($variant:ident = $tag:literal,)*) => {
+		fn simple_token_tag(token_type: &TokenType) -> u8 {
+			match token_type {
+				$(TokenType::$variant => $tag,)*
+				TokenType::NumberLiteral(_) | TokenType::StringLiteral(_) => {
+					unreachable!("literal tokens hold a value")
+				}
+			}
+		}
+
+		fn simple_token_type(tag: u8) -> Option<TokenType> {
+			match tag {
+				$($tag => Some(TokenType::$variant),)*
+				_ => None,
+			}
+		}
+	};
+}
+
+simple_token_tags! {
+	LeftBrace = 0x00,
+	RightBrace = 0x01,
+	LeftBracket = 0x02,
+	RightBracket = 0x03,
+	Comma = 0x04,
+	Colon = 0x05,
+	Interrogation = 0x06,
+	LeftParen = 0x07,
+	RightParen = 0x08,
+	Arrow = 0x09,
+	Bang = 0x0a,
+	BangEqual = 0x0b,
+	Caret = 0x0c,
+	CaretEqual = 0x0d,
+	Dot = 0x0e,
+	DotDotDot = 0x0f,
+	Equal = 0x10,
+	EqualEqual = 0x11,
+	Greater = 0x12,
+	GreaterEqual = 0x13,
+	Less = 0x14,
+	LessEqual = 0x15,
+	Minus = 0x16,
+	MinusEqual = 0x17,
+	MinusMinus = 0x18,
+	Percent = 0x19,
+	PercentEqual = 0x1a,
+	Plus = 0x1b,
+	PlusEqual = 0x1c,
+	PlusPlus = 0x1d,
+	Slash = 0x1e,
+	SlashEqual = 0x1f,
+	Star = 0x20,
+	StarEqual = 0x21,
+	Identifier = 0x22,
+	And = 0x23,
+	Boolean = 0x24,
+	Break = 0x25,
+	Continue = 0x26,
+	Default = 0x27,
+	Else = 0x28,
+	Empty = 0x29,
+	False = 0x2a,
+	For = 0x2b,
+	Function = 0x2c,
+	If = 0x2d,
+	In = 0x2e,
+	Match = 0x2f,
+	Number = 0x30,
+	Or = 0x31,
+	Return = 0x32,
+	String = 0x33,
+	True = 0x34,
+	While = 0x35,
+	EOL = 0x36,
+	EOF = 0x37,
+	InterrogationDot = 0x38,
+	Defer = 0x39,
+	Enum = 0x3a,
+	InterrogationBracket = 0x3b,
+	Repeat = 0x3c,
+	Until = 0x3d,
+	Attempt = 0x3e,
+	Rescue = 0x3f,
+	Fail = 0x40,
+	Assert = 0x41,
+	Import = 0x42,
+	As = 0x43,
+}
+
+/// A compiled script
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+	/// Path of the file the script was compiled from, used in diagnostics
+	pub source_path: String,
+	pub statements: Vec<Statement>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LoadError {
+	InvalidHeader,
+	UnsupportedVersion(u8),
+	UnexpectedEnd,
+	InvalidOpcode(u8),
+	InvalidTokenType(u8),
+	InvalidString,
+	/// Code nested more deeply than the parser accepts
+	TooDeep,
+	/// Code that the parser cannot produce, with the rule it breaks
+	InvalidCode(&'static str),
+}
+
+impl Display for LoadError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::InvalidHeader => write!(f, "not a compiled ilo file"),
+			Self::UnsupportedVersion(version) => write!(
+				f,
+				"compiled with an unsupported format version ({version}, expected {FORMAT_VERSION}), the script must be compiled again"
+			),
+			Self::UnexpectedEnd => write!(f, "unexpected end of file"),
+			Self::InvalidOpcode(opcode) => write!(f, "invalid opcode `{opcode:#04x}`"),
+			Self::InvalidTokenType(tag) => write!(f, "invalid token type `{tag:#04x}`"),
+			Self::InvalidString => write!(f, "invalid UTF-8 string"),
+			Self::TooDeep => write!(f, "code nested more than {MAX_NESTING} levels deep"),
+			Self::InvalidCode(rule) => write!(f, "invalid code: {rule}"),
+		}
+	}
+}
+
+pub fn compile(source_path: &str, statements: &[Statement]) -> Vec<u8> {
+	let mut writer = Writer {
+		bytes: vec![],
+		listing: None,
+		depth: 0,
+	};
+
+	writer.bytes.extend_from_slice(MAGIC);
+	writer.byte(FORMAT_VERSION);
+	writer.string(source_path);
+
+	writer.statements(statements);
+
+	writer.bytes
+}
+
+pub fn load(bytes: &[u8]) -> Result<Chunk, LoadError> {
+	let mut reader = Reader {
+		bytes,
+		current: 0,
+		nesting: 0,
+		loops: vec![],
+		deferred: false,
+	};
+
+	if reader.take(MAGIC.len()).or(Err(LoadError::InvalidHeader))? != MAGIC {
+		return Err(LoadError::InvalidHeader);
+	}
+	let version = reader.byte()?;
+	if version != FORMAT_VERSION {
+		return Err(LoadError::UnsupportedVersion(version));
+	}
+	let source_path = reader.string()?;
+
+	let statements = reader.statements()?;
+
+	Ok(Chunk {
+		source_path,
+		statements,
+	})
+}
+
+/// Lists the opcodes of a compiled file, one per line with its offset and the line of the
+/// source code it comes from. The operands of nested nodes are indented below their opcode.
+pub fn disassemble(bytes: &[u8]) -> Result<String, LoadError> {
+	let chunk = load(bytes)?;
+	// compiling the statements again writes the same bytes, while keeping track of the opcodes
+	let mut writer = Writer {
+		bytes: vec![],
+		listing: Some(vec![]),
+		depth: 0,
+	};
+	writer.bytes.extend_from_slice(MAGIC);
+	writer.byte(FORMAT_VERSION);
+	writer.string(&chunk.source_path);
+	writer.statements(&chunk.statements);
+
+	let mut listing = format!(
+		"; compiled from `{}` (format version {FORMAT_VERSION})\n",
+		chunk.source_path
+	);
+	for instruction in writer.listing.unwrap_or_default() {
+		let line = match instruction.line {
+			Some(line) => format!("line {line}"),
+			None => String::new(),
+		};
+		let text = format!(
+			"{:04x}  {line:<9} {}{} {}",
+			instruction.offset,
+			"  ".repeat(instruction.depth),
+			instruction.name,
+			instruction.operand
+		);
+		listing.push_str(text.trim_end());
+		listing.push('\n');
+	}
+	Ok(listing)
+}
+
+/// Opcode listed by `disassemble`
+struct Instruction {
+	offset: usize,
+	/// Number of nodes the opcode is nested in
+	depth: usize,
+	name: &'static str,
+	operand: String,
+	line: Option<i64>,
+}
+
+struct Writer {
+	bytes: Vec<u8>,
+	/// Opcodes written so far, when disassembling
+	listing: Option<Vec<Instruction>>,
+	depth: usize,
+}
+
+impl Writer {
+	fn byte(&mut self, byte: u8) {
+		self.bytes.push(byte);
+	}
+
+	fn count(&mut self, count: usize) {
+		self.bytes.extend_from_slice(&(count as u32).to_le_bytes());
+	}
+
+	fn integer(&mut self, integer: i64) {
+		self.bytes.extend_from_slice(&integer.to_le_bytes());
+	}
+
+	fn string(&mut self, string: &str) {
+		self.count(string.len());
+		self.bytes.extend_from_slice(string.as_bytes());
+	}
+
+	fn token(&mut self, token: &Token) {
+		match token.token_type() {
+			TokenType::NumberLiteral(number) => {
+				self.byte(TOKEN_NUMBER_LITERAL);
+				self.bytes.extend_from_slice(&number.to_le_bytes());
+			}
+			TokenType::StringLiteral(string) => {
+				self.byte(TOKEN_STRING_LITERAL);
+				self.string(&string);
+			}
+			token_type => self.byte(simple_token_tag(&token_type)),
+		}
+		self.string(token.lexeme());
+		self.integer(token.line());
+		self.integer(token.column());
+	}
+
+	fn optional_token(&mut self, token: Option<&Token>) {
+		match token {
+			Some(token) => {
+				self.byte(1);
+				self.token(token);
+			}
+			None => self.byte(0),
+		}
+	}
+
+	fn tokens(&mut self, tokens: &[Token]) {
+		self.count(tokens.len());
+		for token in tokens {
+			self.token(token);
+		}
+	}
+
+	fn exprs(&mut self, exprs: &[Expr]) {
+		self.count(exprs.len());
+		for expr in exprs {
+			self.expr(expr);
+		}
+	}
+
+	fn statements(&mut self, statements: &[Statement]) {
+		self.count(statements.len());
+		for statement in statements {
+			self.statement(statement);
+		}
+	}
+
+	/// Arms of a `match`: their count, then the patterns, the guard and the body of each arm
+	fn arms<T>(&mut self, arms: &[MatchArm<T>], mut body: impl FnMut(&mut Self, &T)) {
+		self.count(arms.len());
+		for arm in arms {
+			self.count(arm.patterns.len());
+			for pattern in &arm.patterns {
+				match pattern {
+					Pattern::Value(expr) => {
+						self.byte(PATTERN_VALUE);
+						self.expr(expr);
+					}
+					Pattern::Type(token) => {
+						self.byte(PATTERN_TYPE);
+						self.token(token);
+					}
+					Pattern::Default(token) => {
+						self.byte(PATTERN_DEFAULT);
+						self.token(token);
+					}
+				}
+			}
+			match &arm.guard {
+				Some(guard) => {
+					self.byte(1);
+					self.expr(guard);
+				}
+				None => self.byte(0),
+			}
+			body(self, &arm.body);
+		}
+	}
+
+	/// Lists the opcode about to be written, when disassembling
+	fn list(&mut self, name: &'static str, operand: String, line: Option<i64>) {
+		if let Some(listing) = self.listing.as_mut() {
+			listing.push(Instruction {
+				offset: self.bytes.len(),
+				depth: self.depth,
+				name,
+				operand,
+				line,
+			});
+		}
+	}
+
+	fn statement(&mut self, statement: &Statement) {
+		if self.listing.is_some() {
+			let (name, operand) = describe_statement(statement);
+			let line = match statement {
+				Statement::Block { .. } => None,
+				_ => Some(statement.first_token().line()),
+			};
+			self.list(name, operand, line);
+		}
+		self.depth += 1;
+		self.statement_operands(statement);
+		self.depth -= 1;
+	}
+
+	fn statement_operands(&mut self, statement: &Statement) {
+		match statement {
+			Statement::Expr { expr } => {
+				self.byte(OP_EXPR);
+				self.expr(expr);
+			}
+			Statement::Assignment { ident, value } => {
+				self.byte(OP_ASSIGNMENT);
+				self.token(ident);
+				self.expr(value);
+			}
+			Statement::MultipleAssignment { idents, values } => {
+				self.byte(OP_MULTIPLE_ASSIGNMENT);
+				self.tokens(idents);
+				self.exprs(values);
+			}
+			Statement::Increment { ident, operator } => {
+				self.byte(OP_INCREMENT);
+				self.token(ident);
+				self.token(operator);
+			}
+			Statement::IndexAssignment {
+				object,
+				bracket,
+				index,
+				value,
+			} => {
+				self.byte(OP_INDEX_ASSIGNMENT);
+				self.expr(object);
+				self.token(bracket);
+				self.expr(index);
+				self.expr(value);
+			}
+			Statement::Block { statements } => {
+				self.byte(OP_BLOCK);
+				self.statements(statements);
+			}
+			Statement::If {
+				condition,
+				then,
+				otherwise,
+			} => {
+				self.byte(OP_IF);
+				self.expr(condition);
+				self.statement(then);
+				match otherwise {
+					Some(otherwise) => {
+						self.byte(1);
+						self.statement(otherwise);
+					}
+					None => self.byte(0),
+				}
+			}
+			Statement::While {
+				label,
+				condition,
+				body,
+			} => {
+				self.byte(OP_WHILE);
+				self.optional_token(label.as_ref());
+				self.expr(condition);
+				self.statement(body);
+			}
+			Statement::Repeat {
+				label,
+				keyword,
+				body,
+				condition,
+			} => {
+				self.byte(OP_REPEAT);
+				self.optional_token(label.as_ref());
+				self.token(keyword);
+				self.statement(body);
+				self.expr(condition);
+			}
+			Statement::Attempt {
+				keyword,
+				body,
+				variable,
+				handler,
+			} => {
+				self.byte(OP_ATTEMPT);
+				self.token(keyword);
+				self.statement(body);
+				self.token(variable);
+				self.statement(handler);
+			}
+			Statement::For {
+				label,
+				variable,
+				iterable,
+				body,
+			} => {
+				self.byte(OP_FOR);
+				self.optional_token(label.as_ref());
+				self.token(variable);
+				match iterable {
+					Iterable::Value(expr) => {
+						self.byte(0);
+						self.expr(expr);
+					}
+					Iterable::Range {
+						start,
+						operator,
+						end,
+						step,
+					} => {
+						self.byte(1);
+						self.expr(start);
+						self.token(operator);
+						self.expr(end);
+						match step {
+							Some(step) => {
+								self.byte(1);
+								self.expr(step);
+							}
+							None => self.byte(0),
+						}
+					}
+				}
+				self.statement(body);
+			}
+			Statement::FunctionDeclaration {
+				ident,
+				params,
+				variadic,
+				body,
+			} => {
+				self.byte(OP_FUNCTION_DECLARATION);
+				self.token(ident);
+				self.tokens(params);
+				self.byte(u8::from(*variadic));
+				self.statements(body);
+			}
+			Statement::Return { expr } => {
+				self.byte(OP_RETURN);
+				self.expr(expr);
+			}
+			Statement::Fail { keyword, message } => {
+				self.byte(OP_FAIL);
+				self.token(keyword);
+				self.expr(message);
+			}
+			Statement::Assert {
+				keyword,
+				condition,
+				message,
+			} => {
+				self.byte(OP_ASSERT);
+				self.token(keyword);
+				self.expr(condition);
+				match message {
+					Some(message) => {
+						self.byte(1);
+						self.expr(message);
+					}
+					None => self.byte(0),
+				}
+			}
+			Statement::Import {
+				keyword,
+				path,
+				alias,
+			} => {
+				self.byte(OP_IMPORT);
+				self.token(keyword);
+				self.token(path);
+				self.optional_token(alias.as_ref());
+			}
+			Statement::Break { keyword, label } => {
+				self.byte(OP_BREAK);
+				self.token(keyword);
+				self.optional_token(label.as_ref());
+			}
+			Statement::Continue { keyword, label } => {
+				self.byte(OP_CONTINUE);
+				self.token(keyword);
+				self.optional_token(label.as_ref());
+			}
+			Statement::Defer { keyword, body } => {
+				self.byte(OP_DEFER);
+				self.token(keyword);
+				self.statement(body);
+			}
+			Statement::Match {
+				keyword,
+				value,
+				arms,
+			} => {
+				self.byte(OP_MATCH);
+				self.token(keyword);
+				self.expr(value);
+				self.arms(arms, Self::statement);
+			}
+			Statement::Enum { ident, variants } => {
+				self.byte(OP_ENUM);
+				self.token(ident);
+				self.tokens(variants);
+			}
+		}
+	}
+
+	fn expr(&mut self, expr: &Expr) {
+		if self.listing.is_some() {
+			let (name, operand) = describe_expr(expr);
+			self.list(name, operand, Some(expr.first_token().line()));
+		}
+		self.depth += 1;
+		self.expr_operands(expr);
+		self.depth -= 1;
+	}
+
+	fn expr_operands(&mut self, expr: &Expr) {
+		match expr {
+			Expr::Primary { value } => {
+				self.byte(OP_PRIMARY);
+				self.token(value);
+			}
+			Expr::Unary { operator, expr } => {
+				self.byte(OP_UNARY);
+				self.token(operator);
+				self.expr(expr);
+			}
+			Expr::Conversion {
+				target,
+				expr,
+				closing_paren,
+			} => {
+				self.byte(OP_CONVERSION);
+				self.token(target);
+				self.expr(expr);
+				self.token(closing_paren);
+			}
+			Expr::Postfix { expr, operator } => {
+				self.byte(OP_POSTFIX);
+				self.expr(expr);
+				self.token(operator);
+			}
+			Expr::Binary {
+				left_expr,
+				operator,
+				right_expr,
+			} => {
+				self.byte(OP_BINARY);
+				self.expr(left_expr);
+				self.token(operator);
+				self.expr(right_expr);
+			}
+			Expr::Grouping { expr } => {
+				self.byte(OP_GROUPING);
+				self.expr(expr);
+			}
+			Expr::Variable { name } => {
+				self.byte(OP_VARIABLE);
+				self.token(name);
+			}
+			Expr::Call {
+				callee,
+				closing_paren,
+				arguments,
+			} => {
+				self.byte(OP_CALL);
+				self.expr(callee);
+				self.token(closing_paren);
+				self.exprs(arguments);
+			}
+			Expr::SafeCall {
+				receiver,
+				name,
+				closing_paren,
+				arguments,
+			} => {
+				self.byte(OP_SAFE_CALL);
+				self.expr(receiver);
+				self.token(name);
+				self.token(closing_paren);
+				self.exprs(arguments);
+			}
+			Expr::Match {
+				keyword,
+				value,
+				arms,
+			} => {
+				self.byte(OP_MATCH_EXPR);
+				self.token(keyword);
+				self.expr(value);
+				self.arms(arms, Self::expr);
+			}
+			Expr::Field { object, name } => {
+				self.byte(OP_FIELD);
+				self.expr(object);
+				self.token(name);
+			}
+			Expr::Index {
+				object,
+				bracket,
+				index,
+			} => {
+				self.byte(OP_INDEX);
+				self.expr(object);
+				self.token(bracket);
+				self.expr(index);
+			}
+			Expr::List {
+				opening_bracket,
+				elements,
+			} => {
+				self.byte(OP_LIST);
+				self.token(opening_bracket);
+				self.exprs(elements);
+			}
+			Expr::Lambda {
+				opening_paren,
+				params,
+				body,
+			} => {
+				self.byte(OP_LAMBDA);
+				self.token(opening_paren);
+				self.tokens(params);
+				self.expr(body);
+			}
+		}
+	}
+}
+
+/// Name of the opcode of a statement, and a summary of its operands
+fn describe_statement(statement: &Statement) -> (&'static str, String) {
+	let label = |label: &Option<Token>| match label {
+		Some(label) => label.lexeme().to_string(),
+		None => String::new(),
+	};
+	// written like in the source code, before the loop
+	let loop_label = |label: &Option<Token>| match label {
+		Some(label) => format!("{}: ", label.lexeme()),
+		None => String::new(),
+	};
+	match statement {
+		Statement::Expr { .. } => ("EXPR", String::new()),
+		Statement::Assignment { ident, .. } => ("ASSIGNMENT", ident.lexeme().to_string()),
+		Statement::MultipleAssignment { idents, .. } => (
+			"MULTIPLE_ASSIGNMENT",
+			idents
+				.iter()
+				.map(Token::lexeme)
+				.collect::<Vec<_>>()
+				.join(", "),
+		),
+		Statement::Increment { ident, operator } => (
+			"INCREMENT",
+			format!("{}{}", ident.lexeme(), operator.lexeme()),
+		),
+		Statement::IndexAssignment { .. } => ("INDEX_ASSIGNMENT", String::new()),
+		Statement::Block { statements } => ("BLOCK", plural(statements.len(), "statement")),
+		Statement::If { otherwise, .. } => (
+			"IF",
+			if otherwise.is_some() { "else" } else { "" }.to_string(),
+		),
+		Statement::While { label: name, .. } => ("WHILE", loop_label(name)),
+		Statement::Repeat { label: name, .. } => ("REPEAT", loop_label(name)),
+		Statement::Attempt { variable, .. } => ("ATTEMPT", variable.lexeme().to_string()),
+		Statement::For {
+			label: name,
+			variable,
+			iterable,
+			..
+		} => {
+			let iterable = match iterable {
+				Iterable::Value(_) => "value",
+				Iterable::Range { .. } => "range",
+			};
+			(
+				"FOR",
+				format!("{}{} in {iterable}", loop_label(name), variable.lexeme()),
+			)
+		}
+		Statement::FunctionDeclaration {
+			ident,
+			params,
+			variadic,
+			..
+		} => (
+			"FUNCTION_DECLARATION",
+			format!(
+				"{}({}{})",
+				ident.lexeme(),
+				params
+					.iter()
+					.map(Token::lexeme)
+					.collect::<Vec<_>>()
+					.join(", "),
+				if *variadic { "..." } else { "" }
+			),
+		),
+		Statement::Return { .. } => ("RETURN", String::new()),
+		Statement::Fail { .. } => ("FAIL", String::new()),
+		Statement::Assert { .. } => ("ASSERT", String::new()),
+		Statement::Import { path, alias, .. } => (
+			"IMPORT",
+			match alias {
+				Some(alias) => format!("{} as {}", path.lexeme(), alias.lexeme()),
+				None => path.lexeme().to_string(),
+			},
+		),
+		Statement::Break { label: name, .. } => ("BREAK", label(name)),
+		Statement::Continue { label: name, .. } => ("CONTINUE", label(name)),
+		Statement::Defer { .. } => ("DEFER", String::new()),
+		Statement::Match { arms, .. } => ("MATCH", plural(arms.len(), "arm")),
+		Statement::Enum { ident, variants } => (
+			"ENUM",
+			format!(
+				"{} {{ {} }}",
+				ident.lexeme(),
+				variants
+					.iter()
+					.map(Token::lexeme)
+					.collect::<Vec<_>>()
+					.join(", ")
+			),
+		),
+	}
+}
+
+/// Name of the opcode of an expression, and a summary of its operands
+fn describe_expr(expr: &Expr) -> (&'static str, String) {
+	match expr {
+		Expr::Primary { value } => ("PRIMARY", value.lexeme().to_string()),
+		Expr::Unary { operator, .. } => ("UNARY", operator.lexeme().to_string()),
+		Expr::Conversion { target, .. } => ("CONVERSION", target.lexeme().to_string()),
+		Expr::Postfix { operator, .. } => ("POSTFIX", operator.lexeme().to_string()),
+		Expr::Binary { operator, .. } => ("BINARY", operator.lexeme().to_string()),
+		Expr::Grouping { .. } => ("GROUPING", String::new()),
+		Expr::Variable { name } => ("VARIABLE", name.lexeme().to_string()),
+		Expr::Call { arguments, .. } => ("CALL", plural(arguments.len(), "argument")),
+		Expr::SafeCall {
+			name, arguments, ..
+		} => (
+			"SAFE_CALL",
+			format!(
+				"{} with {}",
+				name.lexeme(),
+				plural(arguments.len(), "argument")
+			),
+		),
+		Expr::Match { arms, .. } => ("MATCH_EXPR", plural(arms.len(), "arm")),
+		Expr::Field { name, .. } => ("FIELD", name.lexeme().to_string()),
+		Expr::List { elements, .. } => ("LIST", plural(elements.len(), "element")),
+		Expr::Index { bracket, .. } => ("INDEX", bracket.lexeme().to_string()),
+		Expr::Lambda { params, .. } => (
+			"LAMBDA",
+			format!(
+				"({})",
+				params
+					.iter()
+					.map(Token::lexeme)
+					.collect::<Vec<_>>()
+					.join(", ")
+			),
+		),
+	}
+}
+
+fn plural(count: usize, noun: &str) -> String {
+	format!("{count} {noun}{}", if count == 1 { "" } else { "s" })
+}
+
+/// Precedence of a binary operator, higher for the operators binding more tightly, or `None` if
+/// the token is not one
+fn precedence(operator: &Token) -> Option<u8> {
+	Some(match operator.token_type() {
+		TokenType::Or => 1,
+		TokenType::And => 2,
+		TokenType::BangEqual | TokenType::EqualEqual => 3,
+		TokenType::Greater | TokenType::GreaterEqual | TokenType::Less | TokenType::LessEqual => 4,
+		TokenType::Minus | TokenType::Plus => 5,
+		TokenType::Percent => 6,
+		TokenType::Slash | TokenType::Star => 7,
+		TokenType::Caret => 8,
+		_ => return None,
+	})
+}
+
+/// Reads the statements back, keeping track of the context the parser checks them in
+struct Reader<'a> {
+	bytes: &'a [u8],
+	current: usize,
+	/// Number of statements and expressions being read inside each other, counted like the
+	/// parser does
+	nesting: usize,
+	/// Labels of the loops around the statement being read, in the current function
+	loops: Vec<Option<Symbol>>,
+	/// Whether the statement being read is deferred, and cannot return
+	deferred: bool,
+}
+
+impl Reader<'_> {
+	fn take(&mut self, length: usize) -> Result<&[u8], LoadError> {
+		if self.bytes.len() - self.current < length {
+			return Err(LoadError::UnexpectedEnd);
+		}
+		self.current += length;
+		Ok(&self.bytes[self.current - length..self.current])
+	}
+
+	fn byte(&mut self) -> Result<u8, LoadError> {
+		Ok(self.take(1)?[0])
+	}
+
+	fn count(&mut self) -> Result<usize, LoadError> {
+		let bytes = self.take(4)?;
+		Ok(u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+	}
+
+	fn integer(&mut self) -> Result<i64, LoadError> {
+		let bytes = self.take(8)?;
+		Ok(i64::from_le_bytes(bytes.try_into().unwrap()))
+	}
+
+	fn string(&mut self) -> Result<String, LoadError> {
+		let length = self.count()?;
+		let bytes = self.take(length)?;
+		String::from_utf8(bytes.to_vec()).or(Err(LoadError::InvalidString))
+	}
+
+	fn token(&mut self) -> Result<Token, LoadError> {
+		let token_type = match self.byte()? {
+			TOKEN_NUMBER_LITERAL => {
+				let bytes = self.take(8)?;
+				TokenType::NumberLiteral(f64::from_le_bytes(bytes.try_into().unwrap()))
+			}
+			TOKEN_STRING_LITERAL => TokenType::StringLiteral(self.string()?),
+			tag => simple_token_type(tag).ok_or(LoadError::InvalidTokenType(tag))?,
+		};
+		let lexeme = self.string()?;
+		let line = self.integer()?;
+		let column = self.integer()?;
+
+		Ok(Token::new(token_type, lexeme, line, column))
+	}
+
+	fn optional_token(&mut self) -> Result<Option<Token>, LoadError> {
+		Ok(match self.byte()? {
+			0 => None,
+			_ => Some(self.token()?),
+		})
+	}
+
+	fn tokens(&mut self) -> Result<Vec<Token>, LoadError> {
+		let count = self.count()?;
+		let mut tokens = vec![];
+		for _ in 0..count {
+			tokens.push(self.token()?);
+		}
+		Ok(tokens)
+	}
+
+	fn statements(&mut self) -> Result<Vec<Statement>, LoadError> {
+		let count = self.count()?;
+		let mut statements = vec![];
+		for _ in 0..count {
+			statements.push(self.statement()?);
+		}
+		Ok(statements)
+	}
+
+	/// Reads a node one nesting level deeper, or rejects the code if it is nested too deeply
+	fn nested<T>(
+		&mut self,
+		read: impl FnOnce(&mut Self) -> Result<T, LoadError>,
+	) -> Result<T, LoadError> {
+		if self.nesting == MAX_NESTING {
+			return Err(LoadError::TooDeep);
+		}
+		self.nesting += 1;
+		let result = read(self);
+		self.nesting -= 1;
+		result
+	}
+
+	fn peek(&self) -> Option<u8> {
+		self.bytes.get(self.current).copied()
+	}
+
+	fn statement(&mut self) -> Result<Statement, LoadError> {
+		self.nested(Self::unnested_statement)
+	}
+
+	/// Reads the body of a statement, which is not a level of its own when it is a block or an
+	/// expression, as the parser reads them directly
+	fn body(&mut self) -> Result<Statement, LoadError> {
+		match self.peek() {
+			Some(OP_BLOCK | OP_EXPR) => self.unnested_statement(),
+			_ => self.statement(),
+		}
+	}
+
+	/// Reads the body of a loop, where `break` and `continue` can be used
+	fn loop_body(&mut self, label: Option<&Token>) -> Result<Statement, LoadError> {
+		let label = label.map(Token::symbol);
+		if label.is_some() && self.loops.contains(&label) {
+			return Err(LoadError::InvalidCode(
+				"label already used by an enclosing loop",
+			));
+		}
+		self.loops.push(label);
+		let body = self.body();
+		self.loops.pop();
+		body
+	}
+
+	/// Reads a `break` or `continue`, which must be in a loop with its label, if any
+	fn loop_control(&mut self) -> Result<(Token, Option<Token>), LoadError> {
+		let (keyword, label) = (self.token()?, self.optional_token()?);
+		let in_loop = match &label {
+			Some(label) => self.loops.contains(&Some(label.symbol())),
+			None => !self.loops.is_empty(),
+		};
+		if !in_loop {
+			return Err(LoadError::InvalidCode(
+				"`break` and `continue` must be in a loop with their label",
+			));
+		}
+		Ok((keyword, label))
+	}
+
+	fn unnested_statement(&mut self) -> Result<Statement, LoadError> {
+		Ok(match self.byte()? {
+			OP_EXPR => Statement::Expr { expr: self.expr()? },
+			OP_ASSIGNMENT => Statement::Assignment {
+				ident: self.token()?,
+				value: self.expr()?,
+			},
+			OP_MULTIPLE_ASSIGNMENT => Statement::MultipleAssignment {
+				idents: self.tokens()?,
+				values: self.exprs()?,
+			},
+			OP_INCREMENT => {
+				let ident = self.token()?;
+				let operator = self.token()?;
+				if !matches!(
+					operator.token_type(),
+					TokenType::PlusPlus | TokenType::MinusMinus
+				) {
+					return Err(LoadError::InvalidCode("invalid increment operator"));
+				}
+				Statement::Increment { ident, operator }
+			}
+			OP_INDEX_ASSIGNMENT => {
+				let object = self.expr()?;
+				// the list changed must be in a variable
+				let mut list = &object;
+				while let Expr::Index { object, .. } = list {
+					list = object;
+				}
+				if !matches!(list, Expr::Variable { .. }) {
+					return Err(LoadError::InvalidCode(
+						"only the lists in variables can be changed by index",
+					));
+				}
+				Statement::IndexAssignment {
+					object: Box::new(object),
+					bracket: self.token()?,
+					index: Box::new(self.expr()?),
+					value: self.expr()?,
+				}
+			}
+			OP_BLOCK => Statement::Block {
+				statements: self.statements()?,
+			},
+			OP_IF => Statement::If {
+				condition: self.expr()?,
+				then: Box::new(self.body()?),
+				otherwise: match (self.byte()?, self.peek()) {
+					(0, _) => None,
+					// an `else if` is one level deeper
+					(_, Some(OP_IF)) => Some(Box::new(self.statement()?)),
+					_ => Some(Box::new(self.body()?)),
+				},
+			},
+			OP_WHILE => {
+				let label = self.optional_token()?;
+				Statement::While {
+					condition: self.expr()?,
+					body: Box::new(self.loop_body(label.as_ref())?),
+					label,
+				}
+			}
+			OP_REPEAT => {
+				let label = self.optional_token()?;
+				Statement::Repeat {
+					keyword: self.token()?,
+					body: Box::new(self.loop_body(label.as_ref())?),
+					condition: self.expr()?,
+					label,
+				}
+			}
+			OP_ATTEMPT => Statement::Attempt {
+				keyword: self.token()?,
+				body: Box::new(self.body()?),
+				variable: self.token()?,
+				handler: Box::new(self.body()?),
+			},
+			OP_FOR => {
+				let label = self.optional_token()?;
+				Statement::For {
+					variable: self.token()?,
+					iterable: match self.byte()? {
+						0 => Iterable::Value(self.expr()?),
+						_ => Iterable::Range {
+							start: Box::new(self.expr()?),
+							operator: self.token()?,
+							end: Box::new(self.expr()?),
+							step: match self.byte()? {
+								0 => None,
+								_ => Some(Box::new(self.expr()?)),
+							},
+						},
+					},
+					body: Box::new(self.loop_body(label.as_ref())?),
+					label,
+				}
+			}
+			OP_FUNCTION_DECLARATION => {
+				let (ident, params, variadic) = (self.token()?, self.tokens()?, self.byte()? != 0);
+				// loops around the declaration cannot be controlled from the function body
+				let loops = std::mem::take(&mut self.loops);
+				let deferred = std::mem::replace(&mut self.deferred, false);
+				let body = self.statements();
+				self.loops = loops;
+				self.deferred = deferred;
+				Statement::FunctionDeclaration {
+					ident,
+					params,
+					variadic,
+					body: body?,
+				}
+			}
+			OP_RETURN if self.deferred => {
+				return Err(LoadError::InvalidCode(
+					"`return` cannot be used in deferred code",
+				))
+			}
+			OP_RETURN => Statement::Return { expr: self.expr()? },
+			OP_FAIL => Statement::Fail {
+				keyword: self.token()?,
+				message: self.expr()?,
+			},
+			OP_ASSERT => Statement::Assert {
+				keyword: self.token()?,
+				condition: self.expr()?,
+				message: match self.byte()? {
+					0 => None,
+					_ => Some(self.expr()?),
+				},
+			},
+			OP_IMPORT if self.nesting > 1 => {
+				return Err(LoadError::InvalidCode(
+					"`import` can only be used at the top level of the script",
+				))
+			}
+			OP_IMPORT => {
+				let (keyword, path) = (self.token()?, self.token()?);
+				if !matches!(path.token_type(), TokenType::StringLiteral(_)) {
+					return Err(LoadError::InvalidCode(
+						"the path of an import must be a string",
+					));
+				}
+				Statement::Import {
+					keyword,
+					path,
+					alias: self.optional_token()?,
+				}
+			}
+			OP_BREAK => {
+				let (keyword, label) = self.loop_control()?;
+				Statement::Break { keyword, label }
+			}
+			OP_CONTINUE => {
+				let (keyword, label) = self.loop_control()?;
+				Statement::Continue { keyword, label }
+			}
+			OP_DEFER => {
+				let keyword = self.token()?;
+				// the deferred code runs once the loops around it are over
+				let loops = std::mem::take(&mut self.loops);
+				let deferred = std::mem::replace(&mut self.deferred, true);
+				let body = self.body();
+				self.loops = loops;
+				self.deferred = deferred;
+				Statement::Defer {
+					keyword,
+					body: Box::new(body?),
+				}
+			}
+			OP_MATCH => Statement::Match {
+				keyword: self.token()?,
+				value: self.expr()?,
+				arms: self.arms(Self::statement)?,
+			},
+			OP_ENUM => Statement::Enum {
+				ident: self.token()?,
+				variants: self.tokens()?,
+			},
+			opcode => return Err(LoadError::InvalidOpcode(opcode)),
+		})
+	}
+
+	fn expr(&mut self) -> Result<Expr, LoadError> {
+		self.nested(|reader| reader.operand(0))
+	}
+
+	/// Reads an expression at the same nesting level, which must bind at least as tightly as
+	/// the minimum precedence if it is an operation. Operands on the right bind more tightly
+	/// than their operator, as the parser builds operations from left to right, which bounds
+	/// their nesting.
+	fn operand(&mut self, minimum: u8) -> Result<Expr, LoadError> {
+		if self.peek() != Some(OP_BINARY) {
+			return self.unnested_expr();
+		}
+		self.current += 1;
+		let left_expr = self.operand(minimum)?;
+		let operator = self.token()?;
+		match precedence(&operator) {
+			Some(precedence) if precedence >= minimum => Ok(Expr::Binary {
+				left_expr: Box::new(left_expr),
+				operator,
+				right_expr: Box::new(self.operand(precedence + 1)?),
+			}),
+			Some(_) => Err(LoadError::InvalidCode(
+				"operations must be grouped in parentheses against the precedence of operators",
+			)),
+			None => Err(LoadError::InvalidCode("invalid binary operator")),
+		}
+	}
+
+	/// Reads the expression that is called, indexed or checked, which cannot be an operation
+	/// without parentheses
+	fn chained(&mut self) -> Result<Expr, LoadError> {
+		match self.peek() {
+			Some(OP_BINARY | OP_UNARY) => Err(LoadError::InvalidCode(
+				"operations must be grouped in parentheses to be called, indexed or checked",
+			)),
+			_ => self.unnested_expr(),
+		}
+	}
+
+	fn unnested_expr(&mut self) -> Result<Expr, LoadError> {
+		Ok(match self.byte()? {
+			OP_PRIMARY => {
+				let value = self.token()?;
+				if !matches!(
+					value.token_type(),
+					TokenType::True
+						| TokenType::False | TokenType::NumberLiteral(_)
+						| TokenType::StringLiteral(_)
+						| TokenType::Boolean
+						| TokenType::Number
+						| TokenType::String
+						| TokenType::Empty
+				) {
+					return Err(LoadError::InvalidCode("invalid literal"));
+				}
+				Expr::Primary { value }
+			}
+			OP_UNARY => {
+				let operator = self.token()?;
+				if !matches!(operator.token_type(), TokenType::Minus | TokenType::Bang) {
+					return Err(LoadError::InvalidCode("invalid unary operator"));
+				}
+				Expr::Unary {
+					operator,
+					expr: Box::new(self.expr()?),
+				}
+			}
+			OP_CONVERSION => Expr::Conversion {
+				target: self.token()?,
+				expr: Box::new(self.expr()?),
+				closing_paren: self.token()?,
+			},
+			OP_POSTFIX => {
+				let expr = self.chained()?;
+				let operator = self.token()?;
+				if operator.token_type() != TokenType::Interrogation {
+					return Err(LoadError::InvalidCode("invalid postfix operator"));
+				}
+				Expr::Postfix {
+					expr: Box::new(expr),
+					operator,
+				}
+			}
+			OP_GROUPING => Expr::Grouping {
+				expr: Box::new(self.expr()?),
+			},
+			OP_VARIABLE => Expr::Variable {
+				name: self.token()?,
+			},
+			OP_CALL => Expr::Call {
+				callee: Box::new(self.chained()?),
+				closing_paren: self.token()?,
+				arguments: self.exprs()?,
+			},
+			OP_SAFE_CALL => Expr::SafeCall {
+				receiver: Box::new(self.chained()?),
+				name: self.token()?,
+				closing_paren: self.token()?,
+				arguments: self.exprs()?,
+			},
+			OP_MATCH_EXPR => Expr::Match {
+				keyword: self.token()?,
+				value: Box::new(self.expr()?),
+				arms: self.arms(Self::expr)?,
+			},
+			OP_FIELD => Expr::Field {
+				object: Box::new(self.chained()?),
+				name: self.token()?,
+			},
+			OP_INDEX => Expr::Index {
+				object: Box::new(self.chained()?),
+				bracket: self.token()?,
+				index: Box::new(self.expr()?),
+			},
+			OP_LIST => Expr::List {
+				opening_bracket: self.token()?,
+				elements: self.exprs()?,
+			},
+			OP_LAMBDA => Expr::Lambda {
+				opening_paren: self.token()?,
+				params: self.tokens()?,
+				body: Box::new(self.expr()?),
+			},
+			opcode => return Err(LoadError::InvalidOpcode(opcode)),
+		})
+	}
+
+	fn arms<T>(
+		&mut self,
+		mut body: impl FnMut(&mut Self) -> Result<T, LoadError>,
+	) -> Result<Vec<MatchArm<T>>, LoadError> {
+		let count = self.count()?;
+		let mut arms = vec![];
+		for _ in 0..count {
+			let pattern_count = self.count()?;
+			let mut patterns = vec![];
+			for _ in 0..pattern_count {
+				patterns.push(match self.byte()? {
+					PATTERN_VALUE => Pattern::Value(self.expr()?),
+					PATTERN_TYPE => Pattern::Type(self.token()?),
+					PATTERN_DEFAULT => Pattern::Default(self.token()?),
+					tag => return Err(LoadError::InvalidOpcode(tag)),
+				});
+			}
+			let guard = match self.byte()? {
+				0 => None,
+				_ => Some(self.expr()?),
+			};
+			arms.push(MatchArm {
+				patterns,
+				guard,
+				body: body(self)?,
+			});
+		}
+		Ok(arms)
+	}
+
+	fn exprs(&mut self) -> Result<Vec<Expr>, LoadError> {
+		let count = self.count()?;
+		let mut exprs = vec![];
+		for _ in 0..count {
+			exprs.push(self.expr()?);
+		}
+		Ok(exprs)
+	}
+}
@@ -0,0 +1,122 @@
+use lexer::TokenType;
+use parser::Expr;
+
+/// Value of an expression that does not depend on variables or calls
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+	Boolean(bool),
+	Number(f64),
+	String(String),
+}
+
+/// Computes the value of a constant expression the way the interpreter would, or returns `None`
+/// if the expression is not constant or would fail when evaluated
+pub fn evaluate_constant(expr: &Expr) -> Option<Constant> {
+	match expr {
+		Expr::Primary { value } => match value.token_type() {
+			TokenType::True => Some(Constant::Boolean(true)),
+			TokenType::False => Some(Constant::Boolean(false)),
+			TokenType::NumberLiteral(number) => Some(Constant::Number(number)),
+			TokenType::StringLiteral(string) => Some(Constant::String(string)),
+			_ => None,
+		},
+		Expr::Grouping { expr } => evaluate_constant(expr),
+		Expr::Unary { operator, expr } => match (operator.token_type(), evaluate_constant(expr)?) {
+			(TokenType::Bang, Constant::Boolean(value)) => Some(Constant::Boolean(!value)),
+			(TokenType::Minus, Constant::Number(value)) => Some(Constant::Number(-value)),
+			_ => None,
+		},
+		Expr::Conversion { target, expr, .. } => {
+			let value = evaluate_constant(expr)?;
+			match (target.token_type(), value) {
+				(TokenType::String, Constant::Boolean(boolean)) => {
+					Some(Constant::String(boolean.to_string()))
+				}
+				(TokenType::String, Constant::Number(number)) => Some(Constant::String(
+					if number == 0.0 { 0.0 } else { number }.to_string(),
+				)),
+				(TokenType::Number, Constant::Boolean(boolean)) => {
+					Some(Constant::Number(if boolean { 1.0 } else { 0.0 }))
+				}
+				(TokenType::Number, Constant::String(string)) => string
+					.trim()
+					.parse::<f64>()
+					.ok()
+					.filter(|number| number.is_finite())
+					.map(Constant::Number),
+				(TokenType::Boolean, Constant::Number(0.0)) => Some(Constant::Boolean(false)),
+				(TokenType::Boolean, Constant::Number(1.0)) => Some(Constant::Boolean(true)),
+				(TokenType::Boolean, Constant::String(string)) => match string.as_str() {
+					"true" => Some(Constant::Boolean(true)),
+					"false" => Some(Constant::Boolean(false)),
+					_ => None,
+				},
+				(TokenType::Boolean, value @ Constant::Boolean(_))
+				| (TokenType::Number, value @ Constant::Number(_))
+				| (TokenType::String, value @ Constant::String(_)) => Some(value),
+				_ => None,
+			}
+		}
+		// constants are never empty
+		Expr::Postfix { expr, .. } => {
+			evaluate_constant(expr)?;
+			Some(Constant::Boolean(false))
+		}
+		Expr::Binary {
+			left_expr,
+			operator,
+			right_expr,
+		} => {
+			let left = evaluate_constant(left_expr)?;
+			match operator.token_type() {
+				// the right side is only evaluated when needed
+				TokenType::And if left != Constant::Boolean(true) => {
+					return Some(Constant::Boolean(false))
+				}
+				TokenType::Or if left == Constant::Boolean(true) => return Some(left),
+				TokenType::And | TokenType::Or => {
+					let right = evaluate_constant(right_expr)?;
+					return Some(Constant::Boolean(right == Constant::Boolean(true)));
+				}
+				_ => {}
+			}
+
+			let right = evaluate_constant(right_expr)?;
+			let value = match (operator.token_type(), left, right) {
+				(TokenType::EqualEqual, left, right) => Constant::Boolean(left == right),
+				(TokenType::BangEqual, left, right) => Constant::Boolean(left != right),
+				(token_type, Constant::Number(left), Constant::Number(right)) => match token_type {
+					TokenType::Greater => Constant::Boolean(left > right),
+					TokenType::GreaterEqual => Constant::Boolean(left >= right),
+					TokenType::Less => Constant::Boolean(left < right),
+					TokenType::LessEqual => Constant::Boolean(left <= right),
+					TokenType::Plus => Constant::Number(left + right),
+					TokenType::Minus => Constant::Number(left - right),
+					TokenType::Star => Constant::Number(left * right),
+					TokenType::Slash => Constant::Number(left / right),
+					TokenType::Percent => Constant::Number(left.rem_euclid(right)),
+					TokenType::Caret => Constant::Number(left.powf(right)),
+					_ => return None,
+				},
+				(TokenType::Plus, Constant::String(left), Constant::String(right)) => {
+					Constant::String(left + &right)
+				}
+				(TokenType::Star, Constant::String(left), Constant::Number(right))
+					if right >= 0.0 && right.round() == right && right <= u16::MAX as f64 =>
+				{
+					Constant::String(left.repeat(right as usize))
+				}
+				_ => return None,
+			};
+			Some(value)
+		}
+		Expr::Variable { .. }
+		| Expr::Call { .. }
+		| Expr::SafeCall { .. }
+		| Expr::Match { .. }
+		| Expr::Field { .. }
+		| Expr::List { .. }
+		| Expr::Index { .. }
+		| Expr::Lambda { .. } => None,
+	}
+}
@@ -0,0 +1,1238 @@
+//! Static checking of parsed scripts.
+//!
+//! The checker follows the types that the interpreter gives to variables (boolean, number,
+//! string, and functions with their arity) through assignments, calls and operators, and reports
+//! the type errors that would stop the script before it runs, including calls with the wrong
+//! number of arguments. Only errors that are certain are
+//! reported: values whose type cannot be known (function parameters, results of calls, variables
+//! of the caller seen from a function body) are accepted everywhere.
+//!
+//! Linting reports code that can run but is likely a mistake, such as unreachable code or
+//! functions modifying variables defined outside of them, as warnings.
+//!
+//! In strict mode, warnings are reported as errors, `empty` must be given a type, and values of
+//! different types cannot be compared with `==` or `!=`.
+
+#![allow(clippy::result_unit_err)]
+
+mod constant;
+mod shadowing;
+mod unreachable;
+
+pub use constant::{evaluate_constant, Constant};
+
+use error_manager::{report_error, ErrorDetails, ErrorType};
+use lexer::{Symbol, Token, TokenType};
+use parser::{Expr, Iterable, MatchArm, Pattern, Statement};
+use std::{
+	collections::{HashMap, HashSet},
+	fmt,
+};
+
+/// Type of a value, as far as it can be known before running the script
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Type {
+	Boolean,
+	Number,
+	String,
+	List,
+	/// Function with its number of arguments
+	Function(usize),
+	/// Function whose last argument collects the extra values, with its minimum number of
+	/// arguments
+	VariadicFunction(usize),
+	/// The `empty` keyword, whose type comes from the variable it is assigned to
+	Empty,
+	/// Enum declared with the given name
+	Enum(Symbol),
+	/// Variant of the enum with the given name
+	Variant(Symbol),
+	Unknown,
+}
+
+impl fmt::Display for Type {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Boolean => write!(f, "a boolean"),
+			Self::Number => write!(f, "a number"),
+			Self::String => write!(f, "a string"),
+			Self::List => write!(f, "a list"),
+			Self::Function(arity) => write!(
+				f,
+				"a function with {arity} argument{}",
+				if *arity == 1 { "" } else { "s" }
+			),
+			Self::VariadicFunction(minimum) => write!(
+				f,
+				"a function with at least {minimum} argument{}",
+				if *minimum == 1 { "" } else { "s" }
+			),
+			Self::Empty => write!(f, "`empty`"),
+			Self::Enum(name) => write!(f, "the enum `{}`", name.name()),
+			Self::Variant(name) => write!(f, "a variant of `{}`", name.name()),
+			Self::Unknown => write!(f, "an unknown value"),
+		}
+	}
+}
+
+struct Scope {
+	variables: HashMap<Symbol, Type>,
+	/// Variables of the scopes below a function scope belong to the caller, which is only known
+	/// when running the script
+	function: bool,
+}
+
+impl Scope {
+	fn new(function: bool) -> Self {
+		Self {
+			variables: HashMap::new(),
+			function,
+		}
+	}
+}
+
+pub struct Checker {
+	scopes: Vec<Scope>,
+	/// Names defined anywhere in the checked script, which a function body can use when they are
+	/// defined by its caller
+	defined_names: HashSet<Symbol>,
+	/// Variants of the enums declared so far
+	enums: HashMap<Symbol, Vec<Symbol>>,
+	/// Whether the script imports files without a name, whose variables are not known, so that
+	/// undefined variables cannot be reported
+	imports: bool,
+	has_error: bool,
+	strict: bool,
+	/// Whether warnings are reported, and not only errors
+	warnings: bool,
+}
+
+impl Checker {
+	pub fn new() -> Self {
+		Self {
+			scopes: vec![Scope::new(false)],
+			defined_names: HashSet::new(),
+			enums: HashMap::new(),
+			imports: false,
+			has_error: false,
+			strict: false,
+			warnings: true,
+		}
+	}
+
+	/// Enables strict mode, see the crate documentation
+	pub fn set_strict(&mut self, strict: bool) {
+		self.strict = strict;
+	}
+
+	/// Enables the warnings (the default). Without them, only the errors that would stop the
+	/// script are reported, like by `ilo check --types`.
+	pub fn set_warnings(&mut self, warnings: bool) {
+		self.warnings = warnings;
+	}
+
+	/// Declares a variable that exists before the script runs, such as a native function
+	pub fn define_global(&mut self, name: &str, variable_type: Type) {
+		self.scopes[0]
+			.variables
+			.insert(Symbol::intern(name), variable_type);
+	}
+
+	/// Declares an enum that exists before the script runs, with the names of its variants
+	pub fn define_enum(&mut self, name: &str, variants: &[String]) {
+		let name = Symbol::intern(name);
+		self.define_global(&name.name(), Type::Enum(name));
+		self.enums.insert(
+			name,
+			variants
+				.iter()
+				.map(|variant| Symbol::intern(variant))
+				.collect(),
+		);
+	}
+
+	/// Checks the statements, reporting every error found
+	pub fn check(&mut self, statements: &[Statement]) -> Result<(), ()> {
+		self.has_error = false;
+		collect_defined_names(statements, &mut self.defined_names);
+		self.imports |= statements
+			.iter()
+			.any(|statement| matches!(statement, Statement::Import { alias: None, .. }));
+		for statement in statements {
+			self.check_statement(statement);
+		}
+
+		if self.has_error {
+			Err(())
+		} else {
+			Ok(())
+		}
+	}
+
+	fn report(&mut self, error_type: ErrorType, token: &Token, message: String) {
+		if error_type == ErrorType::Warning && !self.warnings {
+			return;
+		}
+		let error_type = match error_type {
+			ErrorType::Warning if self.strict => ErrorType::StrictError,
+			error_type => error_type,
+		};
+		if error_type != ErrorType::Warning {
+			self.has_error = true;
+		}
+		report_error(ErrorDetails::new(
+			error_type,
+			message,
+			token.line(),
+			token.column(),
+		));
+	}
+
+	fn report_type_error(&mut self, token: &Token, message: String) {
+		self.report(ErrorType::TypeError, token, message);
+	}
+
+	/// Reports the use of a variable that cannot exist when it is used. In a function body, the
+	/// variable could be defined by the caller, so only names defined nowhere are reported.
+	fn check_undefined(&mut self, name: &Token) {
+		if self.imports {
+			return;
+		}
+		let in_function = self.scopes.iter().any(|scope| scope.function);
+		if in_function && self.defined_names.contains(&name.symbol()) {
+			return;
+		}
+
+		let mut message = format!("Undefined symbol `{}`", name.lexeme());
+		if self.defined_names.contains(&name.symbol()) {
+			message.push_str(" (it is only defined in other scopes)");
+		} else if let Some(suggestion) = self.closest_name(name.lexeme()) {
+			message.push_str(&format!(" (did you mean `{suggestion}`?)"));
+		}
+		self.report(ErrorType::NameError, name, message);
+	}
+
+	/// Defined name that is the most likely to have been misspelled as `name`
+	fn closest_name(&self, name: &str) -> Option<String> {
+		let names = self
+			.scopes
+			.iter()
+			.flat_map(|scope| scope.variables.keys())
+			.chain(self.defined_names.iter());
+		names
+			.map(|candidate| candidate.name())
+			.map(|candidate| (edit_distance(name, &candidate), candidate))
+			.filter(|(distance, _)| *distance <= (name.chars().count() / 3).max(1))
+			.min()
+			.map(|(_, candidate)| candidate)
+	}
+
+	/// Type of a variable and whether it is defined in the current scope, or `None` if it is not
+	/// defined. Variables found past a function scope may have been replaced by those of the
+	/// caller, so their type is unknown.
+	fn lookup(&self, name: Symbol) -> Option<(Type, bool)> {
+		let mut crossed_function = false;
+		for (depth, scope) in self.scopes.iter().rev().enumerate() {
+			if let Some(variable_type) = scope.variables.get(&name) {
+				if crossed_function {
+					return Some((Type::Unknown, false));
+				}
+				return Some((*variable_type, depth == 0));
+			}
+			crossed_function |= scope.function;
+		}
+		None
+	}
+
+	fn define(&mut self, name: Symbol, variable_type: Type) {
+		if let Some(scope) = self.scopes.last_mut() {
+			scope.variables.insert(name, variable_type);
+		}
+	}
+
+	fn check_block(&mut self, statements: &[Statement]) {
+		self.scopes.push(Scope::new(false));
+		for statement in statements {
+			self.check_statement(statement);
+		}
+		self.scopes.pop();
+	}
+
+	fn check_statement(&mut self, statement: &Statement) {
+		match statement {
+			Statement::Expr { expr }
+			| Statement::Return { expr }
+			| Statement::Fail { message: expr, .. } => {
+				self.check_expr(expr);
+			}
+			Statement::Assert {
+				condition, message, ..
+			} => {
+				self.check_expr(condition);
+				if let Some(message) = message {
+					self.check_expr(message);
+				}
+			}
+			Statement::Assignment { ident, value } => self.check_assignment(ident, value),
+			Statement::MultipleAssignment { idents, values } => {
+				let value_types: Vec<Type> = values
+					.iter()
+					.map(|value| self.check_assigned_value(value))
+					.collect();
+				for (ident, value_type) in idents.iter().zip(value_types) {
+					self.check_assigned_type(ident, value_type);
+				}
+			}
+			Statement::Increment { ident, operator } => self.check_increment(ident, operator),
+			Statement::IndexAssignment {
+				object,
+				bracket,
+				index,
+				value,
+			} => {
+				self.check_index(object, bracket, index);
+				self.check_expr(value);
+			}
+			Statement::Block { statements } => self.check_block(statements),
+			Statement::If {
+				condition,
+				then,
+				otherwise,
+			} => {
+				let condition_type = self.check_expr(condition);
+				if !matches!(condition_type, Type::Boolean | Type::Unknown) {
+					self.report_type_error(
+						condition.first_token(),
+						"Condition of `if` statement should be a boolean expression".to_string(),
+					);
+				}
+				self.check_statement(then);
+				if let Some(otherwise) = otherwise {
+					self.check_statement(otherwise);
+				}
+			}
+			Statement::While {
+				condition, body, ..
+			} => {
+				self.check_expr(condition);
+				self.check_statement(body);
+			}
+			Statement::Repeat {
+				body, condition, ..
+			} => {
+				self.check_statement(body);
+				self.check_expr(condition);
+			}
+			Statement::Attempt {
+				body,
+				variable,
+				handler,
+				..
+			} => {
+				self.check_statement(body);
+				// the error is given to the handler as a string
+				self.scopes.push(Scope::new(false));
+				self.define(variable.symbol(), Type::String);
+				self.check_statement(handler);
+				self.scopes.pop();
+			}
+			Statement::For {
+				variable,
+				iterable,
+				body,
+				..
+			} => {
+				let variable_type = self.check_iterable(iterable);
+				self.scopes.push(Scope::new(false));
+				self.define(variable.symbol(), variable_type);
+				self.check_statement(body);
+				self.scopes.pop();
+			}
+			Statement::FunctionDeclaration {
+				ident,
+				params,
+				variadic,
+				body,
+			} => self.check_function_declaration(ident, params, *variadic, body),
+			Statement::Defer { body, .. } => self.check_statement(body),
+			Statement::Match {
+				keyword,
+				value,
+				arms,
+			} => {
+				self.check_match(keyword, value, arms, false, |checker, body| {
+					checker.check_statement(body);
+					Type::Unknown
+				});
+			}
+			Statement::Enum { ident, variants } => self.check_enum_declaration(ident, variants),
+			Statement::Import {
+				alias: Some(alias), ..
+			} => self.define(alias.symbol(), Type::Unknown),
+			Statement::Break { .. }
+			| Statement::Continue { .. }
+			| Statement::Import { alias: None, .. } => {}
+		}
+	}
+
+	fn check_enum_declaration(&mut self, ident: &Token, variants: &[Token]) {
+		// like functions, enums can be declared again, replacing their variants
+		match self.lookup(ident.symbol()) {
+			Some((Type::Enum(_) | Type::Unknown, _)) | None => {
+				self.define(ident.symbol(), Type::Enum(ident.symbol()));
+				self.enums
+					.insert(ident.symbol(), variants.iter().map(Token::symbol).collect());
+			}
+			Some(_) => self.report_type_error(
+				ident,
+				format!("Identifier `{}` has already been declared", ident.lexeme()),
+			),
+		}
+	}
+
+	/// Checks the patterns and the arms of a `match`, and returns the type of its value when all
+	/// the arms give the same one. Patterns that can never be the first to match are reported as
+	/// warnings, and so are the variants of an enum left out of a `match` statement. A `match`
+	/// used as a value must handle every value it can be given. The patterns of an arm with a
+	/// guard may not match, so they handle no value for the arms after it.
+	fn check_match<T>(
+		&mut self,
+		keyword: &Token,
+		value: &Expr,
+		arms: &[MatchArm<T>],
+		expression: bool,
+		mut check_body: impl FnMut(&mut Self, &T) -> Type,
+	) -> Type {
+		let value_type = self.check_expr(value);
+		let mut matched: Vec<MatchedPattern> = vec![];
+		let mut has_default = false;
+		let mut arm_types = vec![];
+		for arm in arms {
+			if has_default {
+				self.report(
+					ErrorType::Warning,
+					arm.patterns[0].first_token(),
+					"Unreachable arm: the `default` arm before it matches every value".to_string(),
+				);
+			}
+			for pattern in &arm.patterns {
+				let pattern_matched = match pattern {
+					Pattern::Default(_) => {
+						has_default |= arm.guard.is_none();
+						None
+					}
+					Pattern::Type(token) => Some(MatchedPattern::Type(token.token_type())),
+					Pattern::Value(expr) => {
+						let pattern_type = self.check_expr(expr);
+						match (pattern_type, expr) {
+							(Type::Variant(enumeration), Expr::Field { name, .. }) => {
+								Some(MatchedPattern::Variant(enumeration, name.symbol()))
+							}
+							_ => evaluate_constant(expr).map(MatchedPattern::Constant),
+						}
+					}
+				};
+				if let Some(pattern_matched) = pattern_matched {
+					if matched.contains(&pattern_matched) {
+						self.report(
+							ErrorType::Warning,
+							pattern.first_token(),
+							format!(
+								"Pattern `{}` is already handled by a previous arm",
+								pattern_matched
+							),
+						);
+					} else if arm.guard.is_none() {
+						matched.push(pattern_matched);
+					}
+				}
+			}
+			if let Some(guard) = &arm.guard {
+				self.check_expr(guard);
+			}
+			arm_types.push(check_body(self, &arm.body));
+		}
+
+		if !has_default {
+			self.check_match_exhaustive(keyword, value_type, &matched, expression);
+		}
+
+		match arm_types.split_first() {
+			Some((first, rest)) if rest.iter().all(|arm_type| arm_type == first) => *first,
+			_ => Type::Unknown,
+		}
+	}
+
+	/// Reports the variants of an enum that a `match` without a `default` arm leaves out. A
+	/// `match` used as a value that can be given an unhandled value is a type error.
+	fn check_match_exhaustive(
+		&mut self,
+		keyword: &Token,
+		value_type: Type,
+		matched: &[MatchedPattern],
+		expression: bool,
+	) {
+		// the enum is the one of the value, or the one of the variants in the patterns
+		let enumeration = match value_type {
+			Type::Variant(enumeration) => Some(enumeration),
+			Type::Unknown => matched.iter().find_map(|pattern| match pattern {
+				MatchedPattern::Variant(enumeration, _) => Some(*enumeration),
+				_ => None,
+			}),
+			_ => None,
+		};
+		if let Some(enumeration) = enumeration {
+			let missing: Vec<String> = self
+				.enums
+				.get(&enumeration)
+				.into_iter()
+				.flatten()
+				.filter(|variant| {
+					!matched.contains(&MatchedPattern::Variant(enumeration, **variant))
+				})
+				.map(|variant| format!("`{}.{}`", enumeration.name(), variant.name()))
+				.collect();
+			if !missing.is_empty() {
+				self.report(
+					if expression {
+						ErrorType::TypeError
+					} else {
+						ErrorType::Warning
+					},
+					keyword,
+					format!(
+						"`match` does not handle {} (add {} or a `default` arm)",
+						missing.join(", "),
+						if missing.len() == 1 {
+							"its arm"
+						} else {
+							"their arms"
+						}
+					),
+				);
+			}
+			return;
+		}
+
+		let has = |pattern: MatchedPattern| matched.contains(&pattern);
+		let booleans_handled = has(MatchedPattern::Type(TokenType::Boolean))
+			|| (has(MatchedPattern::Constant(Constant::Boolean(true)))
+				&& has(MatchedPattern::Constant(Constant::Boolean(false))));
+		let exhaustive = value_type == Type::Boolean
+			&& booleans_handled
+			&& has(MatchedPattern::Type(TokenType::Empty));
+		if expression && !exhaustive {
+			self.report_type_error(
+				keyword,
+				"`match` used as a value needs a `default` arm, as some values are handled by none of its arms"
+					.to_string(),
+			);
+		}
+	}
+
+	/// Checks what a `for` loop iterates over, and returns the type of its elements
+	fn check_iterable(&mut self, iterable: &Iterable) -> Type {
+		match iterable {
+			Iterable::Value(expr) => {
+				let iterable_type = self.check_expr(expr);
+				if !matches!(iterable_type, Type::String | Type::Unknown) {
+					self.report_type_error(
+						expr.first_token(),
+						format!(
+							"Only strings can be iterated over with `for` (found {iterable_type})"
+						),
+					);
+				}
+				Type::String
+			}
+			Iterable::Range {
+				start,
+				operator,
+				end,
+				step,
+			} => {
+				for bound in [Some(start), Some(end), step.as_ref()]
+					.into_iter()
+					.flatten()
+				{
+					let bound_type = self.check_expr(bound);
+					if !matches!(bound_type, Type::Number | Type::Unknown) {
+						self.report_type_error(
+							bound.first_token(),
+							format!(
+								"The bounds and the step of a range must be numbers (found {bound_type})"
+							),
+						);
+					}
+				}
+				if let Some(Constant::Number(step)) = step.as_deref().and_then(evaluate_constant) {
+					if step == 0.0 || !step.is_finite() {
+						self.report(
+							ErrorType::RuntimeError,
+							operator,
+							format!("The step of a range must be a finite number other than 0 (found `{step}`)"),
+						);
+					}
+				}
+				Type::Number
+			}
+		}
+	}
+
+	fn check_assignment(&mut self, ident: &Token, value: &Expr) {
+		let value_type = self.check_assigned_value(value);
+		self.check_assigned_type(ident, value_type);
+	}
+
+	fn check_assigned_value(&mut self, value: &Expr) -> Type {
+		let value_type = self.check_expr(value);
+		if self.strict && value_type == Type::Empty {
+			self.report(
+				ErrorType::StrictError,
+				value.first_token(),
+				"`empty` must be given a type in strict mode, like `empty(boolean)`, `empty(number)` or `empty(string)`"
+					.to_string(),
+			);
+		}
+		value_type
+	}
+
+	fn check_assigned_type(&mut self, ident: &Token, value_type: Type) {
+		match self.lookup(ident.symbol()) {
+			Some((Type::Unknown, in_current_scope)) => {
+				// after the assignment, the variable necessarily has the type of the value
+				if in_current_scope && !matches!(value_type, Type::Empty) {
+					self.define(ident.symbol(), value_type);
+				}
+			}
+			Some((current_type, _)) => {
+				let compatible = match value_type {
+					Type::Empty => {
+						matches!(current_type, Type::Boolean | Type::Number | Type::String)
+					}
+					Type::Unknown => true,
+					_ => value_type == current_type,
+				};
+				if !compatible {
+					self.report_type_error(
+						ident,
+						format!(
+							"Variable `{}` already exists, but has a different type (tried to replace {current_type} with {value_type})",
+							ident.lexeme()
+						),
+					);
+				}
+			}
+			None => {
+				// declaring a variable as `empty` without a type is a runtime error, and the
+				// variable is not created
+				if value_type != Type::Empty {
+					self.define(ident.symbol(), value_type);
+				}
+			}
+		}
+	}
+
+	fn check_increment(&mut self, ident: &Token, operator: &Token) {
+		let variable_type = self.check_expr(&Expr::Variable {
+			name: ident.clone(),
+		});
+		if variable_type != Type::Number && variable_type != Type::Unknown {
+			let name = if operator.token_type() == TokenType::PlusPlus {
+				"Increment"
+			} else {
+				"Decrement"
+			};
+			self.report_type_error(
+				operator,
+				format!(
+					"{name} (`{}`) must be applied to a number variable (found {variable_type})",
+					operator.lexeme()
+				),
+			);
+		}
+	}
+
+	fn check_function_declaration(
+		&mut self,
+		ident: &Token,
+		params: &[Token],
+		variadic: bool,
+		body: &[Statement],
+	) {
+		let function_type = if variadic {
+			Type::VariadicFunction(params.len() - 1)
+		} else {
+			Type::Function(params.len())
+		};
+		match self.lookup(ident.symbol()) {
+			Some((Type::Unknown, _)) | None => self.define(ident.symbol(), function_type),
+			Some((current_type, _)) if current_type == function_type => {}
+			Some(_) => self.report_type_error(
+				ident,
+				format!("Identifier `{}` has already been declared", ident.lexeme()),
+			),
+		}
+
+		// like in the interpreter, the parameters and the body share the scope of the call
+		self.scopes.push(Scope::new(true));
+		for param in params {
+			self.define(param.symbol(), Type::Unknown);
+		}
+		for statement in body {
+			self.check_statement(statement);
+		}
+		self.scopes.pop();
+	}
+
+	fn check_expr(&mut self, expr: &Expr) -> Type {
+		match expr {
+			Expr::Primary { value } => match value.token_type() {
+				TokenType::True | TokenType::False | TokenType::Boolean => Type::Boolean,
+				TokenType::NumberLiteral(_) | TokenType::Number => Type::Number,
+				TokenType::StringLiteral(_) | TokenType::String => Type::String,
+				TokenType::Empty => Type::Empty,
+				_ => Type::Unknown,
+			},
+			Expr::Grouping { expr } => self.check_expr(expr),
+			Expr::Conversion {
+				target,
+				expr: value,
+				..
+			} => self.check_conversion(expr, target, value),
+			Expr::Variable { name } => match self.lookup(name.symbol()) {
+				Some((variable_type, _)) => variable_type,
+				None => {
+					self.check_undefined(name);
+					Type::Unknown
+				}
+			},
+			Expr::Unary { operator, expr } => self.check_unary(operator, expr),
+			Expr::Postfix { expr, .. } => {
+				self.check_expr(expr);
+				Type::Boolean
+			}
+			Expr::Binary {
+				left_expr,
+				operator,
+				right_expr,
+			} => self.check_binary(left_expr, operator, right_expr),
+			Expr::Call {
+				callee,
+				closing_paren,
+				arguments,
+			} => {
+				let callee_type = self.check_expr(callee);
+				for argument in arguments {
+					self.check_expr(argument);
+				}
+				self.check_call(callee_type, closing_paren, arguments.len());
+				Type::Unknown
+			}
+			Expr::SafeCall {
+				receiver,
+				name,
+				closing_paren,
+				arguments,
+			} => {
+				self.check_expr(receiver);
+				let callee_type = self.check_expr(&Expr::Variable { name: name.clone() });
+				for argument in arguments {
+					self.check_expr(argument);
+				}
+				// the receiver is the first argument
+				self.check_call(callee_type, closing_paren, arguments.len() + 1);
+				Type::Unknown
+			}
+			Expr::Match {
+				keyword,
+				value,
+				arms,
+			} => self.check_match(keyword, value, arms, true, Self::check_expr),
+			Expr::Field { object, name } => self.check_field(object, name),
+			Expr::List { elements, .. } => {
+				for element in elements {
+					self.check_expr(element);
+				}
+				Type::List
+			}
+			Expr::Index {
+				object,
+				bracket,
+				index,
+			} => self.check_index(object, bracket, index),
+			Expr::Lambda { params, body, .. } => {
+				// like the body of a declared function, with the scope of the call
+				self.scopes.push(Scope::new(true));
+				for param in params {
+					self.define(param.symbol(), Type::Unknown);
+				}
+				self.check_expr(body);
+				self.scopes.pop();
+				Type::Function(params.len())
+			}
+		}
+	}
+
+	/// Elements of lists can have any type, so only the list and the index are checked, while
+	/// the characters of strings are strings
+	fn check_index(&mut self, object: &Expr, bracket: &Token, index: &Expr) -> Type {
+		let object_type = self.check_expr(object);
+		let safe = bracket.token_type() == TokenType::InterrogationBracket;
+		let indexable = match object_type {
+			Type::List | Type::String | Type::Unknown => true,
+			// with `?[`, values that can be empty may give an empty value instead of failing
+			Type::Empty | Type::Boolean | Type::Number => safe,
+			_ => false,
+		};
+		if !indexable {
+			self.report_type_error(
+				bracket,
+				format!("Only lists and strings can be indexed with `[]` (found {object_type})"),
+			);
+		}
+
+		let index_type = self.check_expr(index);
+		if !matches!(index_type, Type::Number | Type::Unknown) {
+			self.report(
+				ErrorType::RuntimeError,
+				index.first_token(),
+				format!(
+					"Index of a {} must be a number (found {index_type})",
+					if object_type == Type::String {
+						"string"
+					} else {
+						"list"
+					}
+				),
+			);
+		}
+		match object_type {
+			// `?[` can give an empty value
+			Type::String if !safe => Type::String,
+			_ => Type::Unknown,
+		}
+	}
+
+	fn check_field(&mut self, object: &Expr, name: &Token) -> Type {
+		match self.check_expr(object) {
+			Type::Enum(enumeration) => {
+				let has_variant = self
+					.enums
+					.get(&enumeration)
+					.is_some_and(|variants| variants.contains(&name.symbol()));
+				if !has_variant {
+					self.report_type_error(
+						name,
+						format!(
+							"Enum `{}` has no variant `{}`",
+							enumeration.name(),
+							name.lexeme()
+						),
+					);
+				}
+				Type::Variant(enumeration)
+			}
+			Type::Unknown => Type::Unknown,
+			object_type => {
+				self.report_type_error(
+					name,
+					format!(
+						"Only the variants of an enum and the variables of a module can be read with `.` (found {object_type})"
+					),
+				);
+				Type::Unknown
+			}
+		}
+	}
+
+	fn check_call(&mut self, callee_type: Type, closing_paren: &Token, argument_count: usize) {
+		match callee_type {
+			Type::Function(arity) if arity != argument_count => self.report_type_error(
+				closing_paren,
+				format!(
+					"Expected {} argument{} for function({arity}), but found {}",
+					arity,
+					if arity == 1 { "" } else { "s" },
+					argument_count
+				),
+			),
+			Type::VariadicFunction(minimum) if argument_count < minimum => self.report_type_error(
+				closing_paren,
+				format!(
+					"Expected at least {} argument{} for function({minimum}...), but found {}",
+					minimum,
+					if minimum == 1 { "" } else { "s" },
+					argument_count
+				),
+			),
+			Type::Function(_) | Type::VariadicFunction(_) | Type::Unknown => {}
+			_ => self.report_type_error(closing_paren, "Expression not callable".to_string()),
+		}
+	}
+
+	/// Conversions that always fail give an empty value, and are reported as warnings
+	fn check_conversion(&mut self, conversion: &Expr, target: &Token, value: &Expr) -> Type {
+		let value_type = self.check_expr(value);
+		let always_fails = matches!(value_type, Type::Function(_) | Type::VariadicFunction(_))
+			|| (evaluate_constant(value).is_some() && evaluate_constant(conversion).is_none());
+		if always_fails {
+			self.report(
+				ErrorType::Warning,
+				target,
+				format!(
+					"{} cannot be converted to a {}, the result is empty",
+					match evaluate_constant(value) {
+						Some(Constant::String(string)) => format!("`{string:?}`"),
+						Some(Constant::Number(number)) => format!("`{number}`"),
+						Some(Constant::Boolean(boolean)) => format!("`{boolean}`"),
+						None => value_type.to_string(),
+					},
+					target.lexeme()
+				),
+			);
+		}
+
+		match target.token_type() {
+			TokenType::Boolean => Type::Boolean,
+			TokenType::Number => Type::Number,
+			_ => Type::String,
+		}
+	}
+
+	fn check_unary(&mut self, operator: &Token, expr: &Expr) -> Type {
+		let expr_type = self.check_expr(expr);
+		let (expected, description) = match operator.token_type() {
+			TokenType::Bang => (
+				Type::Boolean,
+				"Unary not (`!`) must be applied to a boolean",
+			),
+			_ => (
+				Type::Number,
+				"Unary minus (`-`) must be applied to a number",
+			),
+		};
+		if expr_type != expected && expr_type != Type::Unknown {
+			self.report_type_error(operator, format!("{description} (found {expr_type})"));
+		}
+		expected
+	}
+
+	fn check_binary(&mut self, left_expr: &Expr, operator: &Token, right_expr: &Expr) -> Type {
+		let left_type = self.check_expr(left_expr);
+		let right_type = self.check_expr(right_expr);
+
+		match operator.token_type() {
+			TokenType::And | TokenType::Or => Type::Boolean,
+			TokenType::BangEqual | TokenType::EqualEqual => {
+				if self.strict && !comparable(left_type, right_type) {
+					self.report(
+						ErrorType::StrictError,
+						operator,
+						format!(
+							"Values of different types cannot be compared with `{}` in strict mode (found {left_type} and {right_type})",
+							operator.lexeme()
+						),
+					);
+				}
+				Type::Boolean
+			}
+			TokenType::Greater
+			| TokenType::GreaterEqual
+			| TokenType::Less
+			| TokenType::LessEqual => {
+				if [left_type, right_type]
+					.iter()
+					.any(|operand| !matches!(operand, Type::Number | Type::Unknown))
+				{
+					self.report_type_error(
+						operator,
+						format!(
+							"Comparison can only be performed between two numbers (found {left_type} and {right_type})"
+						),
+					);
+				}
+				Type::Boolean
+			}
+			_ => self.check_math_operation(left_type, operator, right_type, right_expr),
+		}
+	}
+
+	/// Mirrors the interpreter: numbers can be combined with numbers, strings can be added to
+	/// strings, and multiplied by positive integers. Other combinations of strings and numbers
+	/// are runtime errors, reported here when they are certain to happen.
+	fn check_math_operation(
+		&mut self,
+		left_type: Type,
+		operator: &Token,
+		right_type: Type,
+		right_expr: &Expr,
+	) -> Type {
+		let is_plus = operator.token_type() == TokenType::Plus;
+		let is_star = operator.token_type() == TokenType::Star;
+		let valid_operand =
+			|operand: Type| matches!(operand, Type::Number | Type::String | Type::Unknown);
+
+		let is_type_error = !valid_operand(left_type)
+			|| !valid_operand(right_type)
+			|| (left_type == Type::Number && right_type == Type::String);
+		if is_type_error {
+			self.report_type_error(
+				operator,
+				format!(
+					"{} (`{}`) can only be performed between two numbers{} (found {left_type} and {right_type})",
+					match operator.token_type() {
+						TokenType::Plus => "Addition",
+						TokenType::Minus => "Substraction",
+						TokenType::Star => "Multiplication",
+						TokenType::Slash => "Division",
+						TokenType::Percent => "Modulo",
+						_ => "Exponentiation",
+					},
+					operator.lexeme(),
+					if is_plus {
+						" or two strings"
+					} else if is_star {
+						" or a string and a number"
+					} else {
+						""
+					},
+				),
+			);
+			return Type::Unknown;
+		}
+
+		match (left_type, right_type) {
+			(Type::String, Type::String) if !is_plus => self.report(
+				ErrorType::RuntimeError,
+				operator,
+				"Only addition (`+`) can be used between two strings".to_string(),
+			),
+			(Type::String, Type::Number) if !is_star => self.report(
+				ErrorType::RuntimeError,
+				operator,
+				"Only multiplication (`*`) can be used between a string and a number".to_string(),
+			),
+			(Type::String, Type::Number) => {
+				if let Some(Constant::Number(count)) = evaluate_constant(right_expr) {
+					if count < 0.0 || count.round() != count {
+						self.report(
+							ErrorType::RuntimeError,
+							operator,
+							format!("Multiplication (`*`) between a string and a number requires a positive integer (found `{count}`)"),
+						);
+					}
+				}
+			}
+			(Type::Number | Type::Unknown, Type::Number) => {
+				let divisor = match operator.token_type() {
+					TokenType::Slash => "Division",
+					TokenType::Percent => "Modulo",
+					_ => "",
+				};
+				if !divisor.is_empty()
+					&& evaluate_constant(right_expr) == Some(Constant::Number(0.0))
+				{
+					self.report(
+						ErrorType::Warning,
+						operator,
+						format!("{divisor} by zero, the result is not a finite number"),
+					);
+				}
+			}
+			_ => {}
+		}
+
+		match (left_type, right_type) {
+			(Type::Number, _) => Type::Number,
+			(Type::String, _) if is_plus || is_star => Type::String,
+			// only numbers can be used with the other operators
+			_ if !is_plus && !is_star => Type::Number,
+			_ => Type::Unknown,
+		}
+	}
+}
+
+impl Default for Checker {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// Whether values of the two types can be compared for equality in strict mode. `empty` is
+/// compared with booleans, numbers and strings to know if they are empty.
+fn comparable(left: Type, right: Type) -> bool {
+	match (left, right) {
+		(Type::Unknown, _) | (_, Type::Unknown) => true,
+		(Type::Empty, other) | (other, Type::Empty) => {
+			matches!(
+				other,
+				Type::Boolean | Type::Number | Type::String | Type::Empty
+			)
+		}
+		(
+			Type::Function(_) | Type::VariadicFunction(_),
+			Type::Function(_) | Type::VariadicFunction(_),
+		) => true,
+		_ => left == right,
+	}
+}
+
+/// What a pattern of a `match` handles, to find the patterns handled by a previous arm
+#[derive(PartialEq)]
+enum MatchedPattern {
+	Constant(Constant),
+	/// Enum and name of a variant
+	Variant(Symbol, Symbol),
+	/// Values of a type, or the empty values
+	Type(TokenType),
+}
+
+impl fmt::Display for MatchedPattern {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Constant(Constant::Boolean(boolean)) => write!(f, "{boolean}"),
+			Self::Constant(Constant::Number(number)) => write!(f, "{number}"),
+			Self::Constant(Constant::String(string)) => write!(f, "{string:?}"),
+			Self::Variant(enumeration, name) => {
+				write!(f, "{}.{}", enumeration.name(), name.name())
+			}
+			Self::Type(token_type) => write!(
+				f,
+				"{}",
+				match token_type {
+					TokenType::Boolean => "boolean",
+					TokenType::Number => "number",
+					TokenType::String => "string",
+					_ => "empty",
+				}
+			),
+		}
+	}
+}
+
+/// Adds the names of the variables, parameters and functions defined in the statements
+fn collect_defined_names(statements: &[Statement], names: &mut HashSet<Symbol>) {
+	for statement in statements {
+		match statement {
+			Statement::Assignment { ident, .. } => {
+				names.insert(ident.symbol());
+			}
+			Statement::MultipleAssignment { idents, .. } => {
+				names.extend(idents.iter().map(Token::symbol));
+			}
+			Statement::Block { statements } => collect_defined_names(statements, names),
+			Statement::If {
+				then, otherwise, ..
+			} => {
+				collect_defined_names(core::slice::from_ref(then.as_ref()), names);
+				if let Some(otherwise) = otherwise {
+					collect_defined_names(core::slice::from_ref(otherwise.as_ref()), names);
+				}
+			}
+			Statement::While { body, .. }
+			| Statement::Repeat { body, .. }
+			| Statement::Defer { body, .. } => {
+				collect_defined_names(core::slice::from_ref(body.as_ref()), names)
+			}
+			Statement::For { variable, body, .. } => {
+				names.insert(variable.symbol());
+				collect_defined_names(core::slice::from_ref(body.as_ref()), names)
+			}
+			Statement::Attempt {
+				body,
+				variable,
+				handler,
+				..
+			} => {
+				names.insert(variable.symbol());
+				collect_defined_names(core::slice::from_ref(body.as_ref()), names);
+				collect_defined_names(core::slice::from_ref(handler.as_ref()), names);
+			}
+			Statement::FunctionDeclaration {
+				ident,
+				params,
+				body,
+				..
+			} => {
+				names.insert(ident.symbol());
+				names.extend(params.iter().map(Token::symbol));
+				collect_defined_names(body, names);
+			}
+			Statement::Match { arms, .. } => {
+				for arm in arms {
+					collect_defined_names(core::slice::from_ref(&arm.body), names);
+				}
+			}
+			Statement::Enum { ident, .. }
+			| Statement::Import {
+				alias: Some(ident), ..
+			} => {
+				names.insert(ident.symbol());
+			}
+			Statement::Expr { .. }
+			| Statement::Increment { .. }
+			| Statement::IndexAssignment { .. }
+			| Statement::Return { .. }
+			| Statement::Fail { .. }
+			| Statement::Assert { .. }
+			| Statement::Import { alias: None, .. }
+			| Statement::Break { .. }
+			| Statement::Continue { .. } => {}
+		}
+	}
+}
+
+/// Number of characters to insert, remove or replace to go from one string to the other
+fn edit_distance(from: &str, to: &str) -> usize {
+	let to: Vec<char> = to.chars().collect();
+	let mut previous_row: Vec<usize> = (0..=to.len()).collect();
+	for (i, from_char) in from.chars().enumerate() {
+		let mut row = vec![i + 1];
+		for (j, to_char) in to.iter().enumerate() {
+			let substitution = previous_row[j] + usize::from(from_char != *to_char);
+			row.push(substitution.min(previous_row[j + 1] + 1).min(row[j] + 1));
+		}
+		previous_row = row;
+	}
+	previous_row[to.len()]
+}
+
+/// Reports warnings about code that runs but is likely a mistake. In strict mode, they are
+/// reported as errors.
+pub fn lint(statements: &[Statement], strict: bool) -> Result<(), ()> {
+	let mut warnings = Warnings {
+		strict,
+		reported: false,
+	};
+	unreachable::check_block(statements, &mut warnings);
+	shadowing::ShadowingLint::new(&mut warnings).check_block(statements);
+
+	if strict && warnings.reported {
+		Err(())
+	} else {
+		Ok(())
+	}
+}
+
+/// Reports the warnings of the lints
+struct Warnings {
+	strict: bool,
+	reported: bool,
+}
+
+impl Warnings {
+	fn report(&mut self, token: &Token, message: String) {
+		self.reported = true;
+		report_error(ErrorDetails::new(
+			if self.strict {
+				ErrorType::StrictError
+			} else {
+				ErrorType::Warning
+			},
+			message,
+			token.line(),
+			token.column(),
+		));
+	}
+}
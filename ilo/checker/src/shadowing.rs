@@ -0,0 +1,186 @@
+use crate::Warnings;
+use lexer::{Symbol, Token};
+use parser::Statement;
+use std::collections::HashSet;
+
+struct Scope {
+	names: HashSet<Symbol>,
+	/// Name of the function whose call creates this scope
+	function: Option<String>,
+	/// Outer variables already reported as modified by the function, to report them only once
+	reported: HashSet<Symbol>,
+}
+
+impl Scope {
+	fn new(function: Option<String>) -> Self {
+		Self {
+			names: HashSet::new(),
+			function,
+			reported: HashSet::new(),
+		}
+	}
+}
+
+/// Warns about function parameters that hide variables defined outside of the function, and
+/// about assignments in a function that modify such variables instead of creating a local one
+pub struct ShadowingLint<'a> {
+	scopes: Vec<Scope>,
+	warnings: &'a mut Warnings,
+}
+
+impl<'a> ShadowingLint<'a> {
+	pub fn new(warnings: &'a mut Warnings) -> Self {
+		Self {
+			scopes: vec![Scope::new(None)],
+			warnings,
+		}
+	}
+
+	pub fn check_block(&mut self, statements: &[Statement]) {
+		for statement in statements {
+			self.check_statement(statement);
+		}
+	}
+
+	/// Index of the scope where a name is defined
+	fn find(&self, name: Symbol) -> Option<usize> {
+		self.scopes
+			.iter()
+			.rposition(|scope| scope.names.contains(&name))
+	}
+
+	/// Index of the scope of the innermost function being checked
+	fn function_scope(&self) -> Option<usize> {
+		self.scopes
+			.iter()
+			.rposition(|scope| scope.function.is_some())
+	}
+
+	fn check_nested(&mut self, statements: &[Statement], function: Option<String>) {
+		self.scopes.push(Scope::new(function));
+		self.check_block(statements);
+		self.scopes.pop();
+	}
+
+	fn check_statement(&mut self, statement: &Statement) {
+		match statement {
+			Statement::Assignment { ident, .. } => self.check_assignment(ident),
+			Statement::MultipleAssignment { idents, .. } => {
+				for ident in idents {
+					self.check_assignment(ident);
+				}
+			}
+			Statement::Block { statements } => self.check_nested(statements, None),
+			Statement::If {
+				then, otherwise, ..
+			} => {
+				self.check_statement(then);
+				if let Some(otherwise) = otherwise {
+					self.check_statement(otherwise);
+				}
+			}
+			Statement::While { body, .. }
+			| Statement::Repeat { body, .. }
+			| Statement::Defer { body, .. } => self.check_statement(body),
+			Statement::Match { arms, .. } => {
+				for arm in arms {
+					self.check_statement(&arm.body);
+				}
+			}
+			Statement::Enum { ident, .. }
+			| Statement::Import {
+				alias: Some(ident), ..
+			} => {
+				if self.find(ident.symbol()).is_none() {
+					self.define(ident.symbol());
+				}
+			}
+			Statement::For { variable, body, .. } => {
+				// the variable is local to the loop
+				self.scopes.push(Scope::new(None));
+				self.define(variable.symbol());
+				self.check_statement(body);
+				self.scopes.pop();
+			}
+			Statement::Attempt {
+				body,
+				variable,
+				handler,
+				..
+			} => {
+				self.check_statement(body);
+				// the error is local to the handler
+				self.scopes.push(Scope::new(None));
+				self.define(variable.symbol());
+				self.check_statement(handler);
+				self.scopes.pop();
+			}
+			Statement::FunctionDeclaration {
+				ident,
+				params,
+				body,
+				..
+			} => {
+				if self.find(ident.symbol()).is_none() {
+					self.define(ident.symbol());
+				}
+
+				self.scopes
+					.push(Scope::new(Some(ident.lexeme().to_string())));
+				for param in params {
+					if self.find(param.symbol()).is_some() {
+						self.warnings.report(
+							param,
+							format!(
+								"Parameter `{}` hides the variable of the same name defined outside of `{}`",
+								param.lexeme(),
+								ident.lexeme()
+							),
+						);
+					}
+					self.define(param.symbol());
+				}
+				self.check_block(body);
+				self.scopes.pop();
+			}
+			// incrementing a number or changing an element of a list does not create a variable
+			Statement::Expr { .. }
+			| Statement::Increment { .. }
+			| Statement::IndexAssignment { .. }
+			| Statement::Return { .. }
+			| Statement::Fail { .. }
+			| Statement::Assert { .. }
+			| Statement::Import { alias: None, .. }
+			| Statement::Break { .. }
+			| Statement::Continue { .. } => {}
+		}
+	}
+
+	fn check_assignment(&mut self, ident: &Token) {
+		let Some(defined_in) = self.find(ident.symbol()) else {
+			self.define(ident.symbol());
+			return;
+		};
+		let Some(function_scope) = self.function_scope() else {
+			return;
+		};
+		if defined_in < function_scope
+			&& self.scopes[function_scope].reported.insert(ident.symbol())
+		{
+			self.warnings.report(
+				ident,
+				format!(
+					"Assignment to `{}` modifies the variable defined outside of `{}` instead of creating a local one",
+					ident.lexeme(),
+					self.scopes[function_scope].function.as_deref().unwrap_or_default()
+				),
+			);
+		}
+	}
+
+	fn define(&mut self, name: Symbol) {
+		if let Some(scope) = self.scopes.last_mut() {
+			scope.names.insert(name);
+		}
+	}
+}
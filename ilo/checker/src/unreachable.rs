@@ -0,0 +1,154 @@
+use crate::Warnings;
+use lexer::{Symbol, Token, TokenType};
+use parser::{Expr, Pattern, Statement};
+
+/// Reports the first statement of each block that comes after a statement that never completes,
+/// and returns whether the block itself never completes
+pub fn check_block(statements: &[Statement], warnings: &mut Warnings) -> bool {
+	let mut terminated = false;
+	for statement in statements {
+		if terminated {
+			if let Some(token) = first_token(statement) {
+				warnings.report(
+					token,
+					"Unreachable code: the previous statement always returns, breaks, continues or loops forever"
+						.to_string(),
+				);
+			}
+			// the rest of the block is unreachable too, and is not checked further
+			return true;
+		}
+		terminated = check_statement(statement, warnings);
+	}
+	terminated
+}
+
+/// Checks the blocks inside a statement, and returns whether the statement never completes
+fn check_statement(statement: &Statement, warnings: &mut Warnings) -> bool {
+	match statement {
+		Statement::Return { .. }
+		| Statement::Fail { .. }
+		| Statement::Break { .. }
+		| Statement::Continue { .. } => true,
+		Statement::Expr { .. }
+		| Statement::Assignment { .. }
+		| Statement::MultipleAssignment { .. }
+		| Statement::Increment { .. }
+		| Statement::IndexAssignment { .. }
+		| Statement::Assert { .. }
+		| Statement::Import { .. }
+		| Statement::Enum { .. } => false,
+		Statement::Block { statements } => check_block(statements, warnings),
+		Statement::If {
+			then, otherwise, ..
+		} => {
+			let then_terminates = check_statement(then, warnings);
+			let otherwise_terminates = otherwise
+				.as_deref()
+				.is_some_and(|otherwise| check_statement(otherwise, warnings));
+			then_terminates && otherwise_terminates
+		}
+		Statement::While {
+			label,
+			condition,
+			body,
+		} => {
+			check_statement(body, warnings);
+			is_literal(condition, TokenType::True)
+				&& !breaks_out(body, label.as_ref().map(Token::symbol), true)
+		}
+		Statement::Repeat {
+			label,
+			body,
+			condition,
+			..
+		} => {
+			check_statement(body, warnings);
+			is_literal(condition, TokenType::False)
+				&& !breaks_out(body, label.as_ref().map(Token::symbol), true)
+		}
+		Statement::For { body, .. } => {
+			check_statement(body, warnings);
+			false
+		}
+		Statement::FunctionDeclaration { body, .. } => {
+			check_block(body, warnings);
+			false
+		}
+		// without a `default` arm, the value may match no arm
+		Statement::Match { arms, .. } => {
+			let mut all_terminate = true;
+			for arm in arms {
+				all_terminate &= check_statement(&arm.body, warnings);
+			}
+			all_terminate
+				&& arms
+					.iter()
+					.filter(|arm| arm.guard.is_none())
+					.flat_map(|arm| &arm.patterns)
+					.any(|pattern| matches!(pattern, Pattern::Default(_)))
+		}
+		// the handler runs only when the body fails
+		Statement::Attempt { body, handler, .. } => {
+			let body_terminates = check_statement(body, warnings);
+			let handler_terminates = check_statement(handler, warnings);
+			body_terminates && handler_terminates
+		}
+		// deferred code runs later, so the statement itself always completes
+		Statement::Defer { body, .. } => {
+			check_statement(body, warnings);
+			false
+		}
+	}
+}
+
+/// Whether a `break` in the statement leaves the loop with the given label, `innermost` telling
+/// if that loop is the innermost one around the statement
+fn breaks_out(statement: &Statement, label: Option<Symbol>, innermost: bool) -> bool {
+	match statement {
+		Statement::Break { label: None, .. } => innermost,
+		Statement::Break {
+			label: Some(target),
+			..
+		} => label == Some(target.symbol()),
+		Statement::Block { statements } => statements
+			.iter()
+			.any(|statement| breaks_out(statement, label, innermost)),
+		Statement::If {
+			then, otherwise, ..
+		} => {
+			breaks_out(then, label, innermost)
+				|| otherwise
+					.as_deref()
+					.is_some_and(|otherwise| breaks_out(otherwise, label, innermost))
+		}
+		Statement::While { body, .. }
+		| Statement::Repeat { body, .. }
+		| Statement::For { body, .. } => breaks_out(body, label, false),
+		Statement::Match { arms, .. } => arms
+			.iter()
+			.any(|arm| breaks_out(&arm.body, label, innermost)),
+		Statement::Attempt { body, handler, .. } => {
+			breaks_out(body, label, innermost) || breaks_out(handler, label, innermost)
+		}
+		// a function body cannot leave the loops around its declaration
+		_ => false,
+	}
+}
+
+/// Whether the expression is the literal `true` or `false`, maybe in parentheses
+fn is_literal(expr: &Expr, literal: TokenType) -> bool {
+	match expr {
+		Expr::Primary { value } => value.token_type() == literal,
+		Expr::Grouping { expr } => is_literal(expr, literal),
+		_ => false,
+	}
+}
+
+/// Blocks do not have a token of their own, so the first token of their first statement is used
+fn first_token(statement: &Statement) -> Option<&Token> {
+	match statement {
+		Statement::Block { statements } => statements.iter().find_map(first_token),
+		_ => Some(statement.first_token()),
+	}
+}
@@ -1,10 +1,107 @@
-use std::fmt::Display;
+#![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+
+use alloc::{
+	format,
+	string::{String, ToString},
+	vec,
+	vec::Vec,
+};
+use core::fmt::Display;
+#[cfg(feature = "std")]
+use std::{
+	cell::RefCell,
+	env,
+	io::{stdout, IsTerminal},
+};
+
+struct State {
+	/// Path of the file being run, displayed in errors when set
+	current_file: Option<String>,
+	color_choice: ColorChoice,
+	/// Errors reported inside `capture_errors`, which are not displayed
+	captured_errors: Option<Vec<ErrorDetails>>,
+}
+
+const INITIAL_STATE: State = State {
+	current_file: None,
+	color_choice: ColorChoice::Auto,
+	captured_errors: None,
+};
+
+#[cfg(feature = "std")]
+thread_local! {
+	static STATE: RefCell<State> = const { RefCell::new(INITIAL_STATE) };
+}
+
+/// Without `std` there are no threads to keep the state apart, so it is shared
+#[cfg(not(feature = "std"))]
+static STATE: spin::Mutex<State> = spin::Mutex::new(INITIAL_STATE);
+
+fn with_state<T>(f: impl FnOnce(&mut State) -> T) -> T {
+	#[cfg(feature = "std")]
+	return STATE.with(|state| f(&mut state.borrow_mut()));
+	#[cfg(not(feature = "std"))]
+	f(&mut STATE.lock())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorChoice {
+	/// Colors are used when the output is a terminal and the `NO_COLOR` environment variable
+	/// is not set
+	Auto,
+	Always,
+	Never,
+}
+
+pub fn set_color_choice(choice: ColorChoice) {
+	with_state(|state| state.color_choice = choice);
+}
+
+pub fn colors_enabled() -> bool {
+	match with_state(|state| state.color_choice) {
+		ColorChoice::Always => true,
+		ColorChoice::Never => false,
+		#[cfg(feature = "std")]
+		ColorChoice::Auto => {
+			env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()) && stdout().is_terminal()
+		}
+		// there is no terminal to detect
+		#[cfg(not(feature = "std"))]
+		ColorChoice::Auto => false,
+	}
+}
+
+/// Wraps `text` in the given ANSI SGR codes (e.g. `"1;31"` for bold red) if colors are
+/// enabled
+pub fn paint(text: &str, codes: &str) -> String {
+	if colors_enabled() {
+		format!("\x1b[{codes}m{text}\x1b[0m")
+	} else {
+		text.to_string()
+	}
+}
+
+/// Sets the file that subsequent errors are reported in. `None` is used for sources that do
+/// not come from a file, such as the REPL.
+pub fn set_current_file(file: Option<String>) {
+	with_state(|state| state.current_file = file);
+}
+
+/// File that errors are currently reported in, if any
+pub fn current_file() -> Option<String> {
+	with_state(|state| state.current_file.clone())
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct ErrorDetails {
 	error_type: ErrorType,
 	message: String,
 	line: i64,
 	column: i64,
+	/// File the error happened in, set when the error is reported
+	file: Option<String>,
 }
 
 impl ErrorDetails {
@@ -14,39 +111,130 @@ impl ErrorDetails {
 			message,
 			line,
 			column,
+			file: None,
 		}
 	}
+
+	pub fn error_type(&self) -> ErrorType {
+		self.error_type
+	}
+
+	pub fn message(&self) -> &str {
+		self.message.as_ref()
+	}
+
+	pub fn line(&self) -> i64 {
+		self.line
+	}
+
+	pub fn column(&self) -> i64 {
+		self.column
+	}
+
+	pub fn file(&self) -> Option<&str> {
+		self.file.as_deref()
+	}
+
+	fn location(&self) -> String {
+		format!(
+			"{}at line {}, column {}",
+			match self.file {
+				Some(ref file) => format!("in {file} "),
+				None => String::new(),
+			},
+			self.line,
+			self.column
+		)
+	}
+}
+
+impl Display for ErrorDetails {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		write!(
+			f,
+			"{} {}: {}.",
+			self.error_type,
+			self.location(),
+			self.message
+		)
+	}
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ErrorType {
 	LexicalError,
 	ParsingError,
 	RuntimeError,
 	TypeError,
+	/// Use of a name that is not defined, found before running
+	NameError,
+	/// Problem that does not prevent the script from running
+	Warning,
+	/// Warning, or code that is allowed but discouraged, reported as an error in strict mode
+	StrictError,
 }
 
 impl Display for ErrorType {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
 		write!(
 			f,
-			"{} error",
+			"{}",
 			match self {
-				Self::LexicalError => "Lexical",
-				Self::ParsingError => "Syntax",
-				Self::RuntimeError => "Runtime",
-				Self::TypeError => "Type",
+				Self::LexicalError => "Lexical error",
+				Self::ParsingError => "Syntax error",
+				Self::RuntimeError => "Runtime error",
+				Self::TypeError => "Type error",
+				Self::NameError => "Name error",
+				Self::Warning => "Warning",
+				Self::StrictError => "Strict mode error",
 			},
 		)
 	}
 }
 
-pub fn report_error(error_details: ErrorDetails) {
-	display_error(error_details);
+/// Runs `f`, collecting the errors reported while it runs instead of displaying them
+pub fn capture_errors<T>(f: impl FnOnce() -> T) -> (T, Vec<ErrorDetails>) {
+	let previous = with_state(|state| state.captured_errors.replace(vec![]));
+	let result = f();
+	let errors = with_state(|state| core::mem::replace(&mut state.captured_errors, previous));
+	(result, errors.unwrap_or_default())
+}
+
+/// Displays an error, or collects it when called inside `capture_errors`. Without `std`,
+/// errors can only be collected.
+pub fn report_error(mut error_details: ErrorDetails) {
+	let error_details = with_state(|state| {
+		error_details.file = state.current_file.clone();
+		match state.captured_errors.as_mut() {
+			Some(errors) => {
+				errors.push(error_details);
+				None
+			}
+			None => Some(error_details),
+		}
+	});
+
+	#[cfg(feature = "std")]
+	if let Some(error_details) = error_details {
+		display_error(error_details);
+	}
+	#[cfg(not(feature = "std"))]
+	drop(error_details);
 }
 
+#[cfg(feature = "std")]
 fn display_error(error_details: ErrorDetails) {
 	println!(
-		"{} at line {}, column {}: {}.",
-		error_details.error_type, error_details.line, error_details.column, error_details.message
+		"{} {}: {}.",
+		paint(
+			&error_details.error_type.to_string(),
+			if error_details.error_type == ErrorType::Warning {
+				"1;33"
+			} else {
+				"1;31"
+			}
+		),
+		paint(&error_details.location(), "2"),
+		error_details.message
 	);
 }
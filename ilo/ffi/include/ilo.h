@@ -0,0 +1,55 @@
+#ifndef ILO_H
+#define ILO_H
+
+#include <stdbool.h>
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/*
+ * Strings given by the interpreter (in values and errors) are owned by it, and stay valid
+ * until the next call using the same interpreter.
+ */
+
+typedef struct IloInterpreter IloInterpreter;
+
+typedef enum {
+	ILO_EMPTY = 0,
+	ILO_BOOLEAN = 1,
+	ILO_NUMBER = 2,
+	ILO_STRING = 3,
+	ILO_FUNCTION = 4,
+} IloValueType;
+
+/* Only the field matching `value_type` is meaningful */
+typedef struct {
+	IloValueType value_type;
+	bool boolean;
+	double number;
+	const char *string;
+} IloValue;
+
+/* The returned string, if any, is copied before the callback is called again */
+typedef IloValue (*IloNative)(const IloValue *args, size_t count, void *user_data);
+
+IloInterpreter *ilo_new_interpreter(void);
+void ilo_free_interpreter(IloInterpreter *interpreter);
+
+/* Returns whether the script succeeded, and writes the value of its last statement in
+ * `result` if it is not NULL */
+bool ilo_eval(IloInterpreter *interpreter, const char *source, IloValue *result);
+/* Errors of the last evaluation, one per line, or NULL if it succeeded */
+const char *ilo_last_error(const IloInterpreter *interpreter);
+
+/* Returns false if the variable is not defined */
+bool ilo_get_global(IloInterpreter *interpreter, const char *name, IloValue *result);
+void ilo_register_native(IloInterpreter *interpreter, const char *name, size_t arity,
+	IloNative callback, void *user_data);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
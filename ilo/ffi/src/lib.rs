@@ -0,0 +1,223 @@
+//! C bindings of ilo, to embed the interpreter in applications written in other languages.
+//! The declarations are in `include/ilo.h`.
+//!
+//! Strings given by the interpreter (in values and errors) are owned by it, and stay valid
+//! until the next call using the same interpreter.
+
+use ilo::{Interpreter, Value};
+use std::{
+	ffi::{c_char, c_void, CStr, CString},
+	ptr,
+};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IloValueType {
+	Empty = 0,
+	Boolean = 1,
+	Number = 2,
+	String = 3,
+	Function = 4,
+}
+
+/// Value exchanged with C: only the field matching `value_type` is meaningful. Functions are
+/// given as their displayed representation in `string`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct IloValue {
+	pub value_type: IloValueType,
+	pub boolean: bool,
+	pub number: f64,
+	pub string: *const c_char,
+}
+
+/// Native function implemented in C. The returned string, if any, is copied before the
+/// callback is called again.
+pub type IloNative =
+	extern "C" fn(args: *const IloValue, count: usize, user_data: *mut c_void) -> IloValue;
+
+pub struct IloInterpreter {
+	interpreter: Interpreter,
+	/// Strings given to C since the last call
+	strings: Vec<CString>,
+	/// Errors of the last evaluation, one per line
+	errors: Option<CString>,
+}
+
+fn c_string(text: &str) -> CString {
+	CString::new(text.replace('\0', "")).unwrap()
+}
+
+fn to_c(value: &Value, strings: &mut Vec<CString>) -> IloValue {
+	let mut c_value = IloValue {
+		value_type: IloValueType::Empty,
+		boolean: false,
+		number: 0.0,
+		string: ptr::null(),
+	};
+	match value {
+		Value::Boolean(boolean) => {
+			c_value.value_type = IloValueType::Boolean;
+			c_value.boolean = *boolean;
+		}
+		Value::Number(number) => {
+			c_value.value_type = IloValueType::Number;
+			c_value.number = *number;
+		}
+		Value::String(_)
+		| Value::List(_)
+		| Value::Enum { .. }
+		| Value::Variant { .. }
+		| Value::Module { .. }
+		| Value::Function { .. }
+		| Value::NativeFunction { .. } => {
+			// lists, enums, variants and modules are given as they are displayed
+			c_value.value_type = match value {
+				Value::String(_)
+				| Value::List(_)
+				| Value::Enum { .. }
+				| Value::Variant { .. }
+				| Value::Module { .. } => IloValueType::String,
+				_ => IloValueType::Function,
+			};
+			let string = c_string(&value.to_string());
+			c_value.string = string.as_ptr();
+			strings.push(string);
+		}
+		Value::Empty | Value::EmptyBoolean | Value::EmptyNumber | Value::EmptyString => {}
+	}
+	c_value
+}
+
+/// # Safety
+///
+/// `value.string` must be null or point to a valid C string when `value_type` is `String`.
+unsafe fn from_c(value: &IloValue) -> Value {
+	match value.value_type {
+		IloValueType::Boolean => Value::Boolean(value.boolean),
+		IloValueType::Number => Value::Number(value.number),
+		IloValueType::String if !value.string.is_null() => {
+			Value::String(CStr::from_ptr(value.string).to_string_lossy().into_owned())
+		}
+		IloValueType::String => Value::String(String::new()),
+		IloValueType::Empty | IloValueType::Function => Value::Empty,
+	}
+}
+
+/// Creates an interpreter, to be freed with `ilo_free_interpreter`
+#[no_mangle]
+pub extern "C" fn ilo_new_interpreter() -> *mut IloInterpreter {
+	Box::into_raw(Box::new(IloInterpreter {
+		interpreter: Interpreter::new(),
+		strings: vec![],
+		errors: None,
+	}))
+}
+
+/// # Safety
+///
+/// `interpreter` must have been created by `ilo_new_interpreter` and not freed yet, or be null.
+#[no_mangle]
+pub unsafe extern "C" fn ilo_free_interpreter(interpreter: *mut IloInterpreter) {
+	if !interpreter.is_null() {
+		drop(Box::from_raw(interpreter));
+	}
+}
+
+/// Runs a script, keeping the variables of previous evaluations. Returns whether it succeeded,
+/// and writes the value of its last statement in `result` if it is not null. The errors are
+/// then available with `ilo_last_error`.
+///
+/// # Safety
+///
+/// `interpreter` must be a live interpreter, `source` a valid C string, and `result` null or
+/// writable.
+#[no_mangle]
+pub unsafe extern "C" fn ilo_eval(
+	interpreter: *mut IloInterpreter,
+	source: *const c_char,
+	result: *mut IloValue,
+) -> bool {
+	let interpreter = &mut *interpreter;
+	interpreter.strings.clear();
+	interpreter.errors = None;
+
+	let source = CStr::from_ptr(source).to_string_lossy();
+	match ilo::run_in(&mut interpreter.interpreter, &source) {
+		Ok(value) => {
+			if !result.is_null() {
+				*result = to_c(&value, &mut interpreter.strings);
+			}
+			true
+		}
+		Err(diagnostics) => {
+			let errors: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+			interpreter.errors = Some(c_string(&errors.join("\n")));
+			false
+		}
+	}
+}
+
+/// Errors of the last call to `ilo_eval`, one per line, or null if it succeeded
+///
+/// # Safety
+///
+/// `interpreter` must be a live interpreter.
+#[no_mangle]
+pub unsafe extern "C" fn ilo_last_error(interpreter: *const IloInterpreter) -> *const c_char {
+	match &(*interpreter).errors {
+		Some(errors) => errors.as_ptr(),
+		None => ptr::null(),
+	}
+}
+
+/// Reads a global variable into `result`. Returns `false` if it is not defined.
+///
+/// # Safety
+///
+/// `interpreter` must be a live interpreter, `name` a valid C string, and `result` writable.
+#[no_mangle]
+pub unsafe extern "C" fn ilo_get_global(
+	interpreter: *mut IloInterpreter,
+	name: *const c_char,
+	result: *mut IloValue,
+) -> bool {
+	let interpreter = &mut *interpreter;
+	interpreter.strings.clear();
+
+	let name = CStr::from_ptr(name).to_string_lossy();
+	match interpreter.interpreter.get_variable(&name) {
+		Some(value) => {
+			*result = to_c(&value, &mut interpreter.strings);
+			true
+		}
+		None => false,
+	}
+}
+
+/// Defines a native function taking `arity` arguments, which calls `callback` with
+/// `user_data`. It replaces any global variable with the same name.
+///
+/// # Safety
+///
+/// `interpreter` must be a live interpreter and `name` a valid C string. `user_data` must stay
+/// valid as long as the interpreter is used.
+#[no_mangle]
+pub unsafe extern "C" fn ilo_register_native(
+	interpreter: *mut IloInterpreter,
+	name: *const c_char,
+	arity: usize,
+	callback: IloNative,
+	user_data: *mut c_void,
+) {
+	let interpreter = &mut *interpreter;
+	let name = CStr::from_ptr(name).to_string_lossy();
+	interpreter
+		.interpreter
+		.define_native(&name, arity, move |args| {
+			let mut strings = vec![];
+			let c_args: Vec<IloValue> = args.iter().map(|arg| to_c(arg, &mut strings)).collect();
+			let result = callback(c_args.as_ptr(), c_args.len(), user_data);
+			from_c(&result)
+		});
+}
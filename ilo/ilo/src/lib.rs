@@ -0,0 +1,95 @@
+//! Public API of the ilo language.
+//!
+//! The lexer, parser, interpreter and tooling crates are re-exported, and [`run`] lexes, parses,
+//! checks and interprets a script in one call, returning the errors instead of displaying them.
+
+pub use bytecode;
+pub use checker;
+pub use error_manager;
+pub use interpreter;
+pub use lexer;
+pub use parser;
+pub use transpiler;
+
+pub use error_manager::{ErrorDetails as Diagnostic, ErrorType};
+pub use interpreter::{Archive, Host, Interpreter, NativePack, NullHost, Param, Value};
+
+use checker::{Checker, Type};
+use error_manager::capture_errors;
+use lexer::{Lexer, Symbol, Token};
+use parser::Parser;
+
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+	/// Disable the natives that can reach outside of the interpreter
+	pub sandbox: bool,
+}
+
+/// Runs a script and returns the value of its last statement, or the errors that stopped it.
+/// Lexical, syntax and type errors found before running are all collected, while the execution
+/// stops at the first runtime error.
+pub fn run(source: &str, options: &RunOptions) -> Result<Value, Vec<Diagnostic>> {
+	let mut interpreter = Interpreter::new();
+	interpreter.set_sandbox(options.sandbox);
+	run_in(&mut interpreter, source)
+}
+
+/// Runs a script in an existing interpreter, so that it can use and update the variables
+/// defined by previous runs
+pub fn run_in(interpreter: &mut Interpreter, source: &str) -> Result<Value, Vec<Diagnostic>> {
+	let (result, diagnostics) = capture_errors(|| {
+		let tokens = Lexer::new(source.to_string()).scan_tokens()?;
+		let statements = parser_for(interpreter, tokens).parse()?;
+		checker_for(interpreter).check(&statements)?;
+		interpreter.interpret_value(statements)
+	});
+
+	result.or(Err(diagnostics))
+}
+
+/// Parser that knows the modules already imported in the interpreter, whose functions are
+/// called with `.`
+pub fn parser_for(interpreter: &Interpreter, tokens: Vec<Token>) -> Parser {
+	let mut parser = Parser::new(tokens);
+	for (name, value) in interpreter.variables() {
+		if let Value::Module { .. } = value {
+			parser.define_module(Symbol::intern(&name));
+		}
+	}
+	parser
+}
+
+/// Checker that knows the variables and native functions already defined in the interpreter
+pub fn checker_for(interpreter: &Interpreter) -> Checker {
+	let mut checker = Checker::new();
+	for (name, value) in interpreter.variables() {
+		let variable_type = match value {
+			Value::Boolean(_) | Value::EmptyBoolean => Type::Boolean,
+			Value::Number(_) | Value::EmptyNumber => Type::Number,
+			Value::String(_) | Value::EmptyString => Type::String,
+			Value::List(_) => Type::List,
+			Value::Function {
+				args,
+				variadic: true,
+				..
+			} => Type::VariadicFunction(args.len() - 1),
+			Value::Function { args, .. } => Type::Function(args.len()),
+			// the checker cannot bound the arguments, so only the required ones are checked
+			Value::NativeFunction { params, .. }
+				if params.iter().any(|param| param.is_optional()) =>
+			{
+				Type::VariadicFunction(params.iter().filter(|param| !param.is_optional()).count())
+			}
+			Value::NativeFunction { params, .. } => Type::Function(params.len()),
+			Value::Empty | Value::Module { .. } => Type::Unknown,
+			Value::Enum { name, variants } => {
+				let variants: Vec<String> = variants.iter().map(|variant| variant.name()).collect();
+				checker.define_enum(&name, &variants);
+				continue;
+			}
+			Value::Variant { enumeration, .. } => Type::Variant(Symbol::intern(&enumeration)),
+		};
+		checker.define_global(&name, variable_type);
+	}
+	checker
+}
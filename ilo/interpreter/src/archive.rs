@@ -0,0 +1,78 @@
+use crate::Archive;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use std::{
+	fs::{self, File},
+	io::{self, Write},
+	path::Path,
+};
+use zip::{write::SimpleFileOptions, ZipArchive, ZipWriter};
+
+/// Creates an archive of a file or directory. The entries are named from the file or directory
+/// itself, so that extracting `src.zip` creates a `src` directory. No archive is left behind if
+/// it cannot be created.
+pub fn create(format: Archive, source: &Path, destination: &Path) -> io::Result<()> {
+	let name = source
+		.file_name()
+		.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no file name"))?;
+	fs::metadata(source)?;
+	let file = File::create(destination)?;
+	let written = write_archive(format, file, source, Path::new(name));
+	if written.is_err() {
+		_ = fs::remove_file(destination);
+	}
+	written
+}
+
+fn write_archive(format: Archive, file: File, source: &Path, name: &Path) -> io::Result<()> {
+	match format {
+		Archive::Zip => {
+			let mut writer = ZipWriter::new(file);
+			add_to_zip(&mut writer, source, name)?;
+			writer.finish()?;
+		}
+		Archive::TarGz => {
+			let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+			if source.is_dir() {
+				builder.append_dir_all(name, source)?;
+			} else {
+				builder.append_path_with_name(source, name)?;
+			}
+			builder.into_inner()?.finish()?;
+		}
+	}
+	Ok(())
+}
+
+fn add_to_zip(writer: &mut ZipWriter<File>, path: &Path, name: &Path) -> io::Result<()> {
+	// zip entries always use `/`, whatever the platform
+	let entry = name
+		.components()
+		.map(|component| component.as_os_str().to_string_lossy())
+		.collect::<Vec<_>>()
+		.join("/");
+	if path.is_dir() {
+		writer.add_directory(entry, SimpleFileOptions::default())?;
+		let mut children = fs::read_dir(path)?.collect::<io::Result<Vec<_>>>()?;
+		// sorted for the same archive to be created from the same files
+		children.sort_by_key(|child| child.file_name());
+		for child in children {
+			add_to_zip(writer, &child.path(), &name.join(child.file_name()))?;
+		}
+	} else {
+		writer.start_file(entry, SimpleFileOptions::default())?;
+		writer.write_all(&fs::read(path)?)?;
+	}
+	Ok(())
+}
+
+/// Extracts an archive into a directory, which is created if needed. Entries that would be
+/// written outside of the directory are rejected.
+pub fn extract(format: Archive, archive: &Path, destination: &Path) -> io::Result<()> {
+	let file = File::open(archive)?;
+	fs::create_dir_all(destination)?;
+	match format {
+		Archive::Zip => ZipArchive::new(file)?.extract(destination)?,
+		Archive::TarGz => tar::Archive::new(GzDecoder::new(file)).unpack(destination)?,
+	}
+	Ok(())
+}
@@ -0,0 +1,112 @@
+use alloc::{vec, vec::Vec};
+
+/// Checks whether a path matches a glob pattern (`glob_match`): `?` matches a character, `*` any
+/// characters within a path component, `**` any number of components, and `[abc]`, `[a-z]` or
+/// `[!abc]` one of a set of characters
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+	let pattern: Vec<char> = pattern.chars().collect();
+	let path: Vec<char> = path.chars().collect();
+	let mut matcher = Matcher {
+		pattern: &pattern,
+		path: &path,
+		results: vec![None; (pattern.len() + 1) * (path.len() + 1)],
+	};
+	matcher.matches(&pattern, &path)
+}
+
+/// Matches the ends of a pattern and of a path, remembering the result for each pair of ends,
+/// as the wildcards would otherwise try the same ones again an exponential number of times
+struct Matcher<'a> {
+	pattern: &'a [char],
+	path: &'a [char],
+	results: Vec<Option<bool>>,
+}
+
+impl<'a> Matcher<'a> {
+	fn matches(&mut self, pattern: &'a [char], path: &'a [char]) -> bool {
+		let index = (self.pattern.len() - pattern.len()) * (self.path.len() + 1)
+			+ (self.path.len() - path.len());
+		if let Some(result) = self.results[index] {
+			return result;
+		}
+		let result = self.unmemoized(pattern, path);
+		self.results[index] = Some(result);
+		result
+	}
+
+	fn unmemoized(&mut self, pattern: &'a [char], path: &'a [char]) -> bool {
+		match pattern {
+			[] => path.is_empty(),
+			['*', '*', rest @ ..] => {
+				// `a/**/b` also matches `a/b`
+				if let ['/', after @ ..] = rest {
+					if self.matches(after, path) {
+						return true;
+					}
+				}
+				(0..=path.len()).any(|skipped| self.matches(rest, &path[skipped..]))
+			}
+			['*', rest @ ..] => {
+				let component = path
+					.iter()
+					.position(|&character| character == '/')
+					.unwrap_or(path.len());
+				(0..=component).any(|skipped| self.matches(rest, &path[skipped..]))
+			}
+			['?', rest @ ..] => match path {
+				[character, path @ ..] if *character != '/' => self.matches(rest, path),
+				_ => false,
+			},
+			['[', class @ ..] => {
+				let Some(end) = class.iter().skip(1).position(|&character| character == ']') else {
+					// without a closing bracket, the bracket is a normal character
+					return path.first() == Some(&'[') && self.matches(class, &path[1..]);
+				};
+				let (class, rest) = (&class[..end + 1], &class[end + 2..]);
+				match path {
+					[character, path @ ..] if *character != '/' && in_class(class, *character) => {
+						self.matches(rest, path)
+					}
+					_ => false,
+				}
+			}
+			[expected, rest @ ..] => match path {
+				[character, path @ ..] if character == expected => self.matches(rest, path),
+				_ => false,
+			},
+		}
+	}
+}
+
+/// Directory of the first path component of a pattern with wildcards, or of its last component
+/// without them, from which the files matching the pattern can be listed (`glob`)
+pub fn base(pattern: &str) -> &str {
+	let wildcard = pattern.find(['*', '?', '[']).unwrap_or(pattern.len());
+	match pattern[..wildcard].rfind('/') {
+		Some(0) => "/",
+		Some(end) => &pattern[..end],
+		None => "",
+	}
+}
+
+/// Checks whether a character is in the set of characters between brackets
+fn in_class(class: &[char], character: char) -> bool {
+	let (negated, mut class) = match class {
+		['!' | '^', class @ ..] => (true, class),
+		class => (false, class),
+	};
+	let mut found = false;
+	while let Some((&first, rest)) = class.split_first() {
+		match rest {
+			['-', last, rest @ ..] => {
+				found |= (first..=*last).contains(&character);
+				class = rest;
+			}
+			_ => {
+				found |= first == character;
+				class = rest;
+			}
+		}
+	}
+	found != negated
+}
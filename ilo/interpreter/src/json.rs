@@ -0,0 +1,256 @@
+use crate::Value;
+use alloc::{
+	format,
+	rc::Rc,
+	string::{String, ToString},
+	vec::Vec,
+};
+use core::{iter::Peekable, str::Chars};
+
+/// Number of arrays that can be nested inside each other, the same limit as the parser's, so
+/// that deeply nested text or values give an error instead of overflowing the stack
+const MAX_DEPTH: usize = 64;
+
+/// Parses JSON text into a value (`json_parse`): arrays become lists and `null` an empty value.
+/// Objects cannot be parsed, as ilo has no maps.
+pub fn parse(text: &str) -> Result<Value, String> {
+	let mut characters = text.chars().peekable();
+	let value = parse_value(&mut characters, 0)?;
+	skip_whitespace(&mut characters);
+	match characters.next() {
+		None => Ok(value),
+		Some(character) => Err(format!("unexpected `{character}` after the value")),
+	}
+}
+
+fn parse_value(characters: &mut Peekable<Chars>, depth: usize) -> Result<Value, String> {
+	skip_whitespace(characters);
+	match characters.peek() {
+		None => Err(String::from("unexpected end of the text")),
+		Some('"') => {
+			characters.next();
+			parse_string(characters).map(Value::String)
+		}
+		Some('[') if depth == MAX_DEPTH => Err(format!(
+			"arrays cannot be nested more than {MAX_DEPTH} levels deep"
+		)),
+		Some('[') => {
+			characters.next();
+			parse_array(characters, depth + 1)
+		}
+		Some('{') => Err(String::from("objects cannot be parsed, as ilo has no maps")),
+		Some('-' | '0'..='9') => parse_number(characters),
+		Some(_) => {
+			let word: String = characters
+				.clone()
+				.take_while(char::is_ascii_alphabetic)
+				.collect();
+			let value = match word.as_str() {
+				"true" => Value::Boolean(true),
+				"false" => Value::Boolean(false),
+				"null" => Value::Empty,
+				"" => return Err(format!("unexpected `{}`", characters.peek().unwrap())),
+				word => return Err(format!("unexpected `{word}`")),
+			};
+			characters.nth(word.len() - 1);
+			Ok(value)
+		}
+	}
+}
+
+/// Parses an array after its opening bracket, `depth` arrays deep
+fn parse_array(characters: &mut Peekable<Chars>, depth: usize) -> Result<Value, String> {
+	let mut elements = Vec::new();
+	skip_whitespace(characters);
+	if characters.next_if_eq(&']').is_some() {
+		return Ok(Value::List(Rc::new(elements)));
+	}
+	loop {
+		elements.push(parse_value(characters, depth)?);
+		skip_whitespace(characters);
+		match characters.next() {
+			Some(',') => {}
+			Some(']') => return Ok(Value::List(Rc::new(elements))),
+			_ => {
+				return Err(String::from(
+					"expected `,` or `]` after an element of an array",
+				))
+			}
+		}
+	}
+}
+
+/// Parses a string after its opening quote
+fn parse_string(characters: &mut Peekable<Chars>) -> Result<String, String> {
+	let mut string = String::new();
+	loop {
+		match characters.next() {
+			None => return Err(String::from("unterminated string")),
+			Some('"') => return Ok(string),
+			Some('\\') => string.push(match characters.next() {
+				Some('"') => '"',
+				Some('\\') => '\\',
+				Some('/') => '/',
+				Some('b') => '\u{8}',
+				Some('f') => '\u{c}',
+				Some('n') => '\n',
+				Some('r') => '\r',
+				Some('t') => '\t',
+				Some('u') => parse_unicode_escape(characters)?,
+				_ => return Err(String::from("invalid escape in a string")),
+			}),
+			Some(character) if character < ' ' => {
+				return Err(String::from(
+					"control characters must be escaped in strings",
+				))
+			}
+			Some(character) => string.push(character),
+		}
+	}
+}
+
+/// Parses the hexadecimal digits of a `\u` escape, and of the low surrogate following it if it
+/// is a high surrogate
+fn parse_unicode_escape(characters: &mut Peekable<Chars>) -> Result<char, String> {
+	let code = parse_hexadecimal(characters)?;
+	if !(0xD800..0xDC00).contains(&code) {
+		return char::from_u32(code).ok_or_else(|| String::from("invalid unicode escape"));
+	}
+	if characters.next() != Some('\\') || characters.next() != Some('u') {
+		return Err(String::from("unpaired surrogate in a unicode escape"));
+	}
+	let low = parse_hexadecimal(characters)?;
+	if !(0xDC00..0xE000).contains(&low) {
+		return Err(String::from("unpaired surrogate in a unicode escape"));
+	}
+	char::from_u32(0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00))
+		.ok_or_else(|| String::from("invalid unicode escape"))
+}
+
+fn parse_hexadecimal(characters: &mut Peekable<Chars>) -> Result<u32, String> {
+	let digits: String = characters.take(4).collect();
+	match u32::from_str_radix(&digits, 16) {
+		Ok(code) if digits.len() == 4 => Ok(code),
+		_ => Err(String::from("invalid unicode escape")),
+	}
+}
+
+fn parse_number(characters: &mut Peekable<Chars>) -> Result<Value, String> {
+	let mut number = String::new();
+	while let Some(character) =
+		characters.next_if(|character| matches!(character, '-' | '+' | '.' | 'e' | 'E' | '0'..='9'))
+	{
+		number.push(character);
+	}
+	match number.parse() {
+		Ok(parsed) if is_json_number(&number) => Ok(Value::Number(parsed)),
+		_ => Err(format!("invalid number `{number}`")),
+	}
+}
+
+/// Whether a number is written as JSON requires, which is stricter than Rust (no `.5`, `1.` or
+/// `01`)
+fn is_json_number(number: &str) -> bool {
+	let digits = |text: &str| {
+		text.len()
+			- text
+				.trim_start_matches(|character: char| character.is_ascii_digit())
+				.len()
+	};
+	let rest = number.strip_prefix('-').unwrap_or(number);
+	let integer = digits(rest);
+	if integer == 0 || (integer > 1 && rest.starts_with('0')) {
+		return false;
+	}
+	let mut rest = &rest[integer..];
+	if let Some(fraction) = rest.strip_prefix('.') {
+		let length = digits(fraction);
+		if length == 0 {
+			return false;
+		}
+		rest = &fraction[length..];
+	}
+	if let Some(exponent) = rest.strip_prefix(['e', 'E']) {
+		let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+		let length = digits(exponent);
+		return length > 0 && length == exponent.len();
+	}
+	rest.is_empty()
+}
+
+fn skip_whitespace(characters: &mut Peekable<Chars>) {
+	while characters
+		.next_if(|character| matches!(character, ' ' | '\t' | '\n' | '\r'))
+		.is_some()
+	{}
+}
+
+/// Converts a value to JSON text (`json_stringify`), indented with tabs if it is pretty. Empty
+/// values become `null`, and the numbers that JSON cannot represent too, like in JavaScript.
+pub fn stringify(value: &Value, pretty: bool) -> Result<String, String> {
+	let mut json = String::new();
+	write_value(value, pretty, 0, &mut json)?;
+	Ok(json)
+}
+
+fn write_value(value: &Value, pretty: bool, depth: usize, json: &mut String) -> Result<(), String> {
+	match value {
+		Value::Boolean(boolean) => json.push_str(&boolean.to_string()),
+		Value::Number(number) if number.is_finite() => json.push_str(&value.to_string()),
+		Value::Number(_)
+		| Value::EmptyBoolean
+		| Value::EmptyNumber
+		| Value::EmptyString
+		| Value::Empty => json.push_str("null"),
+		Value::String(string) => write_string(string, json),
+		Value::List(elements) if elements.is_empty() => json.push_str("[]"),
+		Value::List(_) if depth == MAX_DEPTH => {
+			return Err(format!(
+				"lists cannot be nested more than {MAX_DEPTH} levels deep"
+			))
+		}
+		Value::List(elements) => {
+			json.push('[');
+			for (index, element) in elements.iter().enumerate() {
+				if index > 0 {
+					json.push(',');
+				}
+				if pretty {
+					json.push('\n');
+					json.push_str(&"\t".repeat(depth + 1));
+				}
+				write_value(element, pretty, depth + 1, json)?;
+			}
+			if pretty {
+				json.push('\n');
+				json.push_str(&"\t".repeat(depth));
+			}
+			json.push(']');
+		}
+		value => {
+			return Err(format!(
+				"a {} cannot be converted to JSON",
+				value.get_type()
+			))
+		}
+	}
+	Ok(())
+}
+
+fn write_string(string: &str, json: &mut String) {
+	json.push('"');
+	for character in string.chars() {
+		match character {
+			'"' => json.push_str("\\\""),
+			'\\' => json.push_str("\\\\"),
+			'\n' => json.push_str("\\n"),
+			'\r' => json.push_str("\\r"),
+			'\t' => json.push_str("\\t"),
+			character if character < ' ' => {
+				json.push_str(&format!("\\u{:04x}", character as u32));
+			}
+			character => json.push(character),
+		}
+	}
+	json.push('"');
+}
@@ -1,17 +1,152 @@
-use dialoguer::{theme::Theme, Input};
-use error_manager::{report_error, ErrorDetails, ErrorType};
-use lexer::{Token, TokenType};
-use parser::{Expr, Statement};
-use std::{
-	collections::HashMap,
+#![allow(clippy::result_unit_err)]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::{
+	borrow::ToOwned,
+	boxed::Box,
+	format,
+	rc::Rc,
+	string::{String, ToString},
+	sync::Arc,
+	vec,
+	vec::Vec,
+};
+use core::{
+	cell::RefCell,
 	fmt,
 	fmt::Display,
+	sync::atomic::{AtomicBool, Ordering},
+};
+#[cfg(feature = "std")]
+use dialoguer::{theme::Theme, Input, MultiSelect};
+use error_manager::{
+	capture_errors, current_file, paint, report_error, set_current_file, ErrorDetails, ErrorType,
+};
+use hashbrown::HashMap;
+use lexer::{Lexer, Symbol, Token, TokenType};
+#[cfg(feature = "std")]
+use notify_debouncer_mini::{
+	new_debouncer,
+	notify::{RecommendedWatcher, RecursiveMode},
+	DebounceEventResult, Debouncer,
+};
+use parser::{Expr, Iterable, MatchArm, Parser, Pattern, Statement};
+use smallvec::SmallVec;
+#[cfg(feature = "std")]
+use std::{
+	collections::VecDeque,
+	fs::{self, File},
+	io::{self, stdin, IsTerminal, Read, Write},
+	path::Path,
 	process::Command,
-	time::{SystemTime, UNIX_EPOCH},
+	sync::{
+		mpsc::{self, RecvTimeoutError},
+		Mutex, OnceLock, PoisonError,
+	},
+	thread,
+	time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
+#[cfg(feature = "std")]
+mod archive;
+mod glob;
+mod json;
+mod profile;
+#[cfg(feature = "std")]
+mod task;
+
+/// Float functions, which come from `std` or from `libm` without it
+mod math {
+	#[cfg(feature = "std")]
+	pub fn pow(base: f64, exponent: f64) -> f64 {
+		base.powf(exponent)
+	}
+
+	#[cfg(not(feature = "std"))]
+	pub fn pow(base: f64, exponent: f64) -> f64 {
+		libm::pow(base, exponent)
+	}
+
+	#[cfg(feature = "std")]
+	pub fn rem_euclid(value: f64, divisor: f64) -> f64 {
+		value.rem_euclid(divisor)
+	}
+
+	#[cfg(not(feature = "std"))]
+	pub fn rem_euclid(value: f64, divisor: f64) -> f64 {
+		let remainder = libm::fmod(value, divisor);
+		if remainder < 0.0 {
+			remainder + libm::fabs(divisor)
+		} else {
+			remainder
+		}
+	}
+
+	#[cfg(feature = "std")]
+	pub fn round(value: f64) -> f64 {
+		value.round()
+	}
+
+	#[cfg(not(feature = "std"))]
+	pub fn round(value: f64) -> f64 {
+		libm::round(value)
+	}
+
+	#[cfg(feature = "std")]
+	pub fn abs(value: f64) -> f64 {
+		value.abs()
+	}
+
+	#[cfg(not(feature = "std"))]
+	pub fn abs(value: f64) -> f64 {
+		libm::fabs(value)
+	}
+
+	#[cfg(feature = "std")]
+	pub fn floor(value: f64) -> f64 {
+		value.floor()
+	}
+
+	#[cfg(not(feature = "std"))]
+	pub fn floor(value: f64) -> f64 {
+		libm::floor(value)
+	}
+
+	#[cfg(feature = "std")]
+	pub fn ceil(value: f64) -> f64 {
+		value.ceil()
+	}
+
+	#[cfg(not(feature = "std"))]
+	pub fn ceil(value: f64) -> f64 {
+		libm::ceil(value)
+	}
+
+	#[cfg(feature = "std")]
+	pub fn sqrt(value: f64) -> f64 {
+		value.sqrt()
+	}
+
+	#[cfg(not(feature = "std"))]
+	pub fn sqrt(value: f64) -> f64 {
+		libm::sqrt(value)
+	}
+
+	#[cfg(feature = "std")]
+	pub fn trunc(value: f64) -> f64 {
+		value.trunc()
+	}
+
+	#[cfg(not(feature = "std"))]
+	pub fn trunc(value: f64) -> f64 {
+		libm::trunc(value)
+	}
+}
+
 #[derive(Clone, Debug, PartialEq)]
-enum Value {
+pub enum Value {
 	Empty,
 
 	EmptyBoolean,
@@ -20,63 +155,377 @@ enum Value {
 	EmptyNumber,
 	Number(f64),
 
+	EmptyString,
 	String(String),
 
+	/// Elements of a list, shared by its copies until one of them changes
+	List(Rc<Vec<Value>>),
+
 	Function {
 		name: String,
-		args: Vec<String>,
-		body: Vec<Statement>,
+		args: Rc<[Symbol]>,
+		/// Whether the last argument collects the extra values of the call in a list
+		variadic: bool,
+		body: Rc<[Statement]>,
+		/// Variables of the function that created the function, defined again when it is called
+		captured: Rc<Vec<(Symbol, Value)>>,
+		/// Whether the function was declared with `f` rather than being a lambda. Declared
+		/// functions use the variables of their caller first, so the captured ones only stand in
+		/// for the variables that went away with the function that created them
+		declared: bool,
 	},
 	NativeFunction {
 		name: String,
-		args: Vec<String>,
-		body: fn(Vec<Value>) -> Value,
+		params: Vec<Param>,
+		body: NativeBody,
+	},
+
+	/// Enum declared by the script, whose variants are read with `.`
+	Enum {
+		name: String,
+		variants: Rc<[Symbol]>,
+	},
+	/// Variant of an enum, only equal to itself
+	Variant {
+		enumeration: String,
+		name: String,
 	},
+	/// File imported with a name (`import "math.ilo" as math`), whose top-level variables are
+	/// read with `.`
+	Module {
+		path: String,
+		variables: Rc<Vec<(Symbol, Value)>>,
+	},
+}
+
+/// Type of value a native function takes for one of its parameters. The arguments are checked
+/// before the native is called, so natives do not have to validate their types themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Param {
+	/// Any value, empty ones included
+	Any,
+	Boolean,
+	Number,
+	String,
+	/// String or list, whose size can be measured
+	Sequence,
+	List,
+	/// Function of the script or native function, taking the given number of arguments
+	Function(usize),
+	/// Parameter that calls can leave out, after the ones they must give
+	Optional(&'static Param),
+}
+
+impl Param {
+	pub fn is_optional(self) -> bool {
+		matches!(self, Self::Optional(_))
+	}
+
+	fn accepts(self, value: &Value) -> bool {
+		match (self, value) {
+			(Self::Optional(param), _) => param.accepts(value),
+			(Self::Any, _)
+			| (Self::Boolean, Value::Boolean(_))
+			| (Self::Number, Value::Number(_))
+			| (Self::String, Value::String(_))
+			| (Self::Sequence, Value::String(_) | Value::List(_))
+			| (Self::List, Value::List(_)) => true,
+			(Self::Function(arity), Value::Function { .. } | Value::NativeFunction { .. }) => {
+				value.accepts_arguments(arity)
+			}
+			_ => false,
+		}
+	}
+}
+
+impl Display for Param {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Any => write!(f, "any value"),
+			Self::Boolean => write!(f, "a boolean"),
+			Self::Number => write!(f, "a number"),
+			Self::String => write!(f, "a string"),
+			Self::Sequence => write!(f, "a string or a list"),
+			Self::List => write!(f, "a list"),
+			Self::Optional(param) => write!(f, "{param}"),
+			Self::Function(arity) => write!(
+				f,
+				"a function of {arity} argument{}",
+				if *arity == 1 { "" } else { "s" }
+			),
+		}
+	}
 }
 
+type NativeFn = dyn Fn(&mut dyn Host, &[Value]) -> Value;
+
+/// Native function calling back functions of the script, with the closing parenthesis of its
+/// call to report their errors
+type CallbackFn = dyn Fn(&mut Interpreter, &Token, &[Value]) -> Result<Value, ErrorOrReturn>;
+
+/// Implementation of a native function, shared by the copies of its value
+#[derive(Clone)]
+pub struct NativeBody(NativeKind);
+
+#[derive(Clone)]
+enum NativeKind {
+	/// Native only using the host
+	Host(Rc<NativeFn>),
+	/// Native calling functions given as arguments (`watch`)
+	Callback(Rc<CallbackFn>),
+}
+
+impl PartialEq for NativeBody {
+	fn eq(&self, other: &Self) -> bool {
+		match (&self.0, &other.0) {
+			(NativeKind::Host(left), NativeKind::Host(right)) => Rc::ptr_eq(left, right),
+			(NativeKind::Callback(left), NativeKind::Callback(right)) => Rc::ptr_eq(left, right),
+			_ => false,
+		}
+	}
+}
+
+impl fmt::Debug for NativeBody {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "NativeBody")
+	}
+}
+
+/// Values given to a function call, stored inline for the usual small number of arguments
+type Arguments = SmallVec<[Value; 2]>;
+
 impl Value {
-	fn get_type(&self) -> String {
+	pub fn get_type(&self) -> String {
 		match self {
 			Self::EmptyBoolean | Self::Boolean(_) => String::from("boolean"),
 			Self::EmptyNumber | Self::Number(_) => String::from("number"),
-			Self::String(_) => String::from("string"),
-			Self::Function { args, .. } | Self::NativeFunction { args, .. } => {
-				format!("function({})", args.len())
+			Self::EmptyString | Self::String(_) => String::from("string"),
+			Self::List(_) => String::from("list"),
+			Self::Function { variadic: true, .. } => format!("function({}...)", self.arity() - 1),
+			Self::Function { .. } | Self::NativeFunction { .. } => {
+				format!("function({})", self.arity())
 			}
+			Self::Enum { .. } => String::from("enum"),
+			// variants of the same enum have the same type
+			Self::Variant { enumeration, .. } => enumeration.clone(),
+			Self::Module { .. } => String::from("module"),
 			Self::Empty => unreachable!("should not have to get type of empty"),
 		}
 	}
 
-	fn as_empty(&self) -> Self {
+	/// Number of arguments of a function
+	fn arity(&self) -> usize {
 		match self {
-			Self::Boolean(_) => Self::EmptyBoolean,
-			Self::Number(_) => Self::EmptyNumber,
-			_ => unreachable!(
-				"should not get empty type of something other than a boolean or a number"
-			),
+			Self::Function { args, .. } => args.len(),
+			Self::NativeFunction { params, .. } => params.len(),
+			_ => unreachable!("should not get the arity of something other than a function"),
+		}
+	}
+
+	/// Number of arguments that calls must give, which is the arity without the optional
+	/// parameters of natives
+	fn required_arity(&self) -> usize {
+		match self {
+			Self::NativeFunction { params, .. } => {
+				params.iter().filter(|param| !param.is_optional()).count()
+			}
+			_ => self.arity(),
+		}
+	}
+
+	/// Whether a function can be called with this number of arguments
+	fn accepts_arguments(&self, count: usize) -> bool {
+		match self {
+			Self::Function { variadic: true, .. } => count + 1 >= self.arity(),
+			_ => (self.required_arity()..=self.arity()).contains(&count),
+		}
+	}
+
+	/// Number of arguments a function takes, for humans: `2`, `at least 1` or `1 to 2`
+	fn describe_arity(&self) -> String {
+		let (required, arity) = (self.required_arity(), self.arity());
+		let arguments = match self {
+			Self::Function { variadic: true, .. } => format!("at least {}", arity - 1),
+			_ if required < arity => format!("{required} to {arity}"),
+			_ => arity.to_string(),
+		};
+		let last = match self {
+			Self::Function { variadic: true, .. } => arity - 1,
+			_ => arity,
+		};
+		format!("{arguments} argument{}", if last == 1 { "" } else { "s" })
+	}
+
+	/// Message of a call with the wrong number of arguments
+	fn arguments_count_error(&self, count: usize) -> String {
+		format!(
+			"Expected {} for {}, but found {count}",
+			self.describe_arity(),
+			self.get_type()
+		)
+	}
+
+	/// Argument given to a native with the wrong type, as reported: functions by their type, as
+	/// their number of arguments is what usually differs
+	fn describe_argument(&self) -> String {
+		match self {
+			Self::Function { .. } | Self::NativeFunction { .. } => self.get_type(),
+			_ => self.inspect(),
+		}
+	}
+
+	/// Number of an argument that the signature of its native checked to be a number
+	fn as_number(&self) -> f64 {
+		match self {
+			Self::Number(number) => *number,
+			_ => unreachable!("the arguments are checked before the native is called"),
+		}
+	}
+
+	/// String of an argument that the signature of its native checked to be a string
+	fn as_string(&self) -> &str {
+		match self {
+			Self::String(string) => string,
+			_ => unreachable!("the arguments are checked before the native is called"),
+		}
+	}
+
+	/// Elements of an argument that the signature of its native checked to be a list
+	fn as_list(&self) -> &[Value] {
+		match self {
+			Self::List(elements) => elements,
+			_ => unreachable!("the arguments are checked before the native is called"),
+		}
+	}
+
+	/// Description of the value with its type, for `inspect`: strings are quoted and empty values
+	/// are shown with their type (`empty(number)`), while `out` shows them as nothing
+	pub fn inspect(&self) -> String {
+		match self {
+			Self::Boolean(_) | Self::Number(_) | Self::List(_) => {
+				format!("{} {self}", self.get_type())
+			}
+			Self::String(string) => format!("string {}", quote(string)),
+			Self::EmptyBoolean | Self::EmptyNumber | Self::EmptyString => {
+				format!("empty({})", self.get_type())
+			}
+			Self::Empty => String::from("empty"),
+			Self::Function { .. }
+			| Self::NativeFunction { .. }
+			| Self::Enum { .. }
+			| Self::Variant { .. }
+			| Self::Module { .. } => self.to_string(),
+		}
+	}
+
+	/// Equality of `==`: values of different types are never equal, except the empty values of
+	/// the same type and `empty`, and functions are equal when they are the same function
+	fn equals(&self, other: &Self) -> bool {
+		match (self, other) {
+			(Self::Boolean(left), Self::Boolean(right)) => left == right,
+			(Self::Number(left), Self::Number(right)) => left == right,
+			(Self::String(left), Self::String(right)) => left == right,
+			(Self::List(left), Self::List(right)) => {
+				left.len() == right.len()
+					&& left
+						.iter()
+						.zip(right.iter())
+						.all(|(left, right)| left.equals(right))
+			}
+			(Self::EmptyBoolean, Self::EmptyBoolean | Self::Empty)
+			| (Self::EmptyNumber, Self::EmptyNumber | Self::Empty)
+			| (Self::EmptyString, Self::EmptyString | Self::Empty)
+			| (
+				Self::Empty,
+				Self::EmptyBoolean | Self::EmptyNumber | Self::EmptyString | Self::Empty,
+			) => true,
+			// lambdas all have the same name, so user-defined functions also need the same body
+			(
+				Self::Function {
+					name: left,
+					body: left_body,
+					..
+				},
+				Self::Function {
+					name: right,
+					body: right_body,
+					..
+				},
+			) => left == right && Rc::ptr_eq(left_body, right_body),
+			(Self::NativeFunction { name: left, .. }, Self::NativeFunction { name: right, .. })
+			| (Self::Enum { name: left, .. }, Self::Enum { name: right, .. })
+			| (Self::Module { path: left, .. }, Self::Module { path: right, .. }) => left == right,
+			(
+				Self::Variant {
+					enumeration: left_enumeration,
+					name: left,
+				},
+				Self::Variant {
+					enumeration: right_enumeration,
+					name: right,
+				},
+			) => left_enumeration == right_enumeration && left == right,
+			_ => false,
+		}
+	}
+
+	/// Empty value of the same type, if this type can be empty
+	fn as_empty(&self) -> Option<Self> {
+		match self {
+			Self::Boolean(_) | Self::EmptyBoolean => Some(Self::EmptyBoolean),
+			Self::Number(_) | Self::EmptyNumber => Some(Self::EmptyNumber),
+			Self::String(_) | Self::EmptyString => Some(Self::EmptyString),
+			_ => None,
 		}
 	}
 
 	fn call(
 		&self,
-		arguments: &[String],
-		arguments_values: Vec<Value>,
+		mut arguments_values: Arguments,
+		closing_paren: &Token,
 		interpreter: &mut Interpreter,
 	) -> Result<Value, ErrorOrReturn> {
 		match self {
-			Self::Function { body, .. } => {
-				interpreter.environment.enter_scope(true);
-
-				arguments.iter().enumerate().for_each(|(i, arg)| {
-					interpreter
-						.environment
-						.define_or_assign(arg.clone(), arguments_values[i].clone(), true)
-						// We can safely unwrap because a new scope was just entered,
-						// so every assignment is a new variable
-						.unwrap();
-				});
+			Self::Function {
+				args: arguments,
+				variadic,
+				body,
+				captured,
+				declared,
+				..
+			} => {
+				let scopes_count = interpreter.environment.scopes.len();
+				interpreter.environment.enter_call_scope();
+				interpreter.deferred.push(vec![]);
+
+				// the arguments are defined after, and hide the captured variables of their name
+				interpreter.environment.define_captured(captured, *declared);
+
+				if *variadic {
+					collect_extra_arguments(&mut arguments_values, arguments.len() - 1);
+				}
+				arguments
+					.iter()
+					.zip(arguments_values)
+					.for_each(|(arg, value)| {
+						interpreter
+							.environment
+							.define_or_assign(*arg, value, true)
+							// We can safely unwrap because a new scope was just entered,
+							// so every assignment is a new variable
+							.unwrap();
+					});
+
+				let block_result = interpreter.execute_block(body, false);
+				// a `return` inside nested blocks leaves them without closing their scopes
+				while interpreter.environment.scopes.len() > scopes_count + 1 {
+					interpreter.environment.leave_scope();
+				}
+				// deferred code runs even when the function fails, in the scope of the function
+				let deferred_result = interpreter.run_deferred();
+				interpreter.environment.leave_scope();
+				deferred_result?;
 
-				let block_result = interpreter.execute_block(body.to_vec(), false);
 				let mut return_value = Value::Empty;
 				if let Err(result) = block_result {
 					match result {
@@ -84,62 +533,148 @@ impl Value {
 							return Err(ErrorOrReturn::Error);
 						}
 						ErrorOrReturn::Return(value) => return_value = value,
+						ErrorOrReturn::Break(_) | ErrorOrReturn::Continue(_) => {
+							unreachable!("the parser only accepts `break` and `continue` in loops")
+						}
 					}
 				}
 
-				interpreter.environment.leave_scope();
-
 				Ok(return_value)
 			}
-			Self::NativeFunction { body, .. } => Ok(body(arguments_values)),
+			Self::NativeFunction { body, .. } => match &body.0 {
+				NativeKind::Host(function) => {
+					Ok(function(interpreter.host.as_mut(), &arguments_values))
+				}
+				NativeKind::Callback(function) => {
+					function(interpreter, closing_paren, &arguments_values)
+				}
+			},
 			_ => unreachable!("Should not try to call an uncallable expression"),
 		}
 	}
 }
 
 impl Display for Value {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		match self {
 			Self::Boolean(boolean) => write!(f, "{boolean}"),
 			Self::Number(number) => {
 				write!(f, "{}", if number == &0.0 { &0.0 } else { number })
 			}
 			Self::String(string) => write!(f, "{string}"),
-			Self::EmptyBoolean | Self::EmptyNumber | Self::Empty => write!(f, ""),
-			Self::Function { name, args, .. } | Self::NativeFunction { name, args, .. } => {
-				write!(
-					f,
-					"f {name}({} argument{}) {{{}}}",
-					args.len(),
-					if args.len() == 1 { "" } else { "s" },
-					match self {
-						Self::NativeFunction { .. } => " [native code] ",
-						_ => "",
-					}
-				)
+			// strings are quoted and empty values written out, to tell the elements apart
+			Self::List(elements) => {
+				let elements: Vec<String> = elements
+					.iter()
+					.map(|element| match element {
+						Self::String(string) => quote(string),
+						Self::EmptyBoolean
+						| Self::EmptyNumber
+						| Self::EmptyString
+						| Self::Empty => element.inspect(),
+						_ => element.to_string(),
+					})
+					.collect();
+				write!(f, "[{}]", elements.join(", "))
+			}
+			Self::EmptyBoolean | Self::EmptyNumber | Self::EmptyString | Self::Empty => {
+				write!(f, "")
+			}
+			Self::Function { name, .. } | Self::NativeFunction { name, .. } => write!(
+				f,
+				"f {name}({}) {{{}}}",
+				self.describe_arity(),
+				match self {
+					Self::NativeFunction { .. } => " [native code] ",
+					_ => "",
+				}
+			),
+			Self::Enum { name, variants } => {
+				let variants: Vec<String> = variants.iter().map(Symbol::name).collect();
+				write!(f, "enum {name} {{ {} }}", variants.join(", "))
 			}
+			Self::Variant { enumeration, name } => write!(f, "{enumeration}.{name}"),
+			Self::Module { path, .. } => write!(f, "module {}", quote(path)),
+		}
+	}
+}
+
+/// String between double quotes, with its quotes, backslashes and control characters escaped
+fn quote(string: &str) -> String {
+	let mut quoted = String::from("\"");
+	for character in string.chars() {
+		match character {
+			'"' => quoted.push_str("\\\""),
+			'\\' => quoted.push_str("\\\\"),
+			'\n' => quoted.push_str("\\n"),
+			'\t' => quoted.push_str("\\t"),
+			'\r' => quoted.push_str("\\r"),
+			character => quoted.push(character),
 		}
 	}
+	quoted.push('"');
+	quoted
 }
 
 #[derive(Debug)]
 struct Scope {
-	map: HashMap<String, Value>,
+	/// Variables defined in this scope, whose bindings are removed when leaving it
+	symbols: Vec<Symbol>,
+	/// Whether the scope is inside of a function
 	function: bool,
+	/// Whether the scope was entered by calling a function, and holds its arguments
+	call: bool,
 }
 
 impl Scope {
 	fn new(function: bool) -> Self {
 		Self {
-			map: HashMap::with_capacity(2),
+			symbols: Vec::with_capacity(2),
 			function,
+			call: false,
 		}
 	}
 }
 
+/// Kind of a scope in an environment snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeKind {
+	/// Scope of the script, where the native functions are defined
+	Global,
+	/// Scope of a function call, with its arguments
+	Function,
+	/// Scope of a block, a loop or a branch
+	Block,
+}
+
+/// Variables defined in a scope, in the order they were defined
+#[derive(Debug, Clone)]
+pub struct ScopeSnapshot {
+	pub kind: ScopeKind,
+	pub variables: Vec<(String, Value)>,
+}
+
+/// Counters of what a script did, since statistics were enabled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Statistics {
+	pub statements: u64,
+	/// Calls to functions of the script
+	pub function_calls: u64,
+	/// Calls to native functions
+	pub native_calls: u64,
+	/// Strings created by literals, operators and natives
+	pub strings: u64,
+	/// Largest number of nested scopes a statement was executed in, the global scope included
+	pub max_scope_depth: usize,
+}
+
 #[derive(Debug)]
 struct Environment {
 	scopes: Vec<Scope>,
+	/// Values of each variable, from the outermost scope to the innermost one. The visible
+	/// value is always the last one, so accessing a variable does not depend on the number
+	/// of scopes.
+	bindings: HashMap<Symbol, Vec<Value>>,
 }
 
 #[derive(Debug)]
@@ -152,50 +687,161 @@ impl Environment {
 	fn new() -> Self {
 		Self {
 			scopes: vec![Scope::new(false)],
+			bindings: HashMap::new(),
+		}
+	}
+
+	/// Variables of the innermost function call, or of the blocks of the script outside of
+	/// functions, with their current values. The variables of the script stay visible anyway.
+	fn locals(&self) -> Vec<(Symbol, Value)> {
+		let mut locals: Vec<(Symbol, Value)> = vec![];
+		for scope in self.scopes[1..].iter().rev() {
+			for &symbol in &scope.symbols {
+				if !locals.iter().any(|(local, _)| *local == symbol) {
+					if let Some(value) = self.get(symbol) {
+						locals.push((symbol, value));
+					}
+				}
+			}
+			if scope.call {
+				break;
+			}
 		}
+		locals
+	}
+
+	/// Defines the variables captured by a function, in the scope of its call, except the ones
+	/// the caller of a declared function already has
+	fn define_captured(&mut self, captured: &[(Symbol, Value)], declared: bool) {
+		for (name, value) in captured {
+			if declared && self.get(*name).is_some() {
+				continue;
+			}
+			self.define_or_assign(*name, value.clone(), true)
+				.expect("the variables are new in the scope of the call");
+		}
+	}
+
+	/// New environment with only the natives and the constants, in which imported files run
+	fn natives(&self) -> Self {
+		let mut environment = Self::new();
+		for (name, values) in self.bindings.iter() {
+			if let Some(native @ Value::NativeFunction { .. }) = values.first() {
+				_ = environment.define_or_assign(*name, native.clone(), true);
+			}
+		}
+		environment.define_constants();
+		environment
+	}
+
+	fn is_constant(symbol: Symbol) -> bool {
+		CONSTANTS.iter().any(|(name, _)| symbol.name() == *name)
+	}
+
+	fn define_constants(&mut self) {
+		for (name, value) in CONSTANTS {
+			_ = self.define_or_assign(Symbol::intern(name), Value::Number(value), true);
+		}
+	}
+
+	/// Variables defined in the scope of the script, other than the natives and the constants.
+	/// Its functions keep these variables, like lambdas keep the variables of the function
+	/// creating them, so that the functions of an imported file can use its variables wherever
+	/// they are called.
+	fn globals(&self) -> Vec<(Symbol, Value)> {
+		let globals: Vec<(Symbol, Value)> = self.scopes[0]
+			.symbols
+			.iter()
+			.filter(|&&symbol| !Self::is_constant(symbol))
+			.filter_map(|&symbol| match self.bindings.get(&symbol)?.first()? {
+				Value::NativeFunction { .. } => None,
+				value => Some((symbol, value.clone())),
+			})
+			.collect();
+		let shared = Rc::new(globals.clone());
+		globals
+			.into_iter()
+			.map(|(symbol, value)| match value {
+				Value::Function {
+					name,
+					args,
+					variadic,
+					body,
+					captured,
+					declared,
+				} if captured.is_empty() => (
+					symbol,
+					Value::Function {
+						name,
+						args,
+						variadic,
+						body,
+						captured: shared.clone(),
+						declared,
+					},
+				),
+				value => (symbol, value),
+			})
+			.collect()
 	}
 
 	fn enter_scope(&mut self, function_scope: bool) {
 		self.scopes.push(Scope::new(function_scope));
 	}
 
-	fn leave_scope(&mut self) {
-		self.scopes.pop();
+	fn enter_call_scope(&mut self) {
+		self.scopes.push(Scope {
+			call: true,
+			..Scope::new(true)
+		});
 	}
 
-	fn get(&self, name: String) -> Option<Value> {
-		for scope in self.scopes.iter().rev() {
-			if let Some(value) = scope.map.get(&name) {
-				return Some(value.to_owned());
+	fn leave_scope(&mut self) {
+		if let Some(scope) = self.scopes.pop() {
+			for symbol in scope.symbols {
+				if let Some(values) = self.bindings.get_mut(&symbol) {
+					values.pop();
+				}
 			}
 		}
-		None
+	}
+
+	fn get(&self, name: Symbol) -> Option<Value> {
+		self.bindings
+			.get(&name)
+			.and_then(|values| values.last())
+			.cloned()
+	}
+
+	fn get_mut(&mut self, name: Symbol) -> Option<&mut Value> {
+		self.bindings
+			.get_mut(&name)
+			.and_then(|values| values.last_mut())
 	}
 
 	fn define_or_assign(
 		&mut self,
-		name: String,
+		name: Symbol,
 		value: Value,
 		function_arg: bool,
 	) -> Result<(), EnvError> {
 		// We don’t want to check existing variables when assigning a function argument,
 		// because a function argument is always a new variable in its scope.
 		if !function_arg {
-			for scope in self.scopes.iter_mut().rev() {
-				if let Some(current_value) = scope.map.get(&name) {
-					let current_value = current_value.to_owned();
-
-					let mut value = value;
-					if value == Value::Empty {
-						value = current_value.as_empty();
+			if let Some(current_value) = self.get_mut(name) {
+				let mut value = value;
+				if value == Value::Empty {
+					match current_value.as_empty() {
+						Some(empty) => value = empty,
+						None => return Err(EnvError::InvalidType(current_value.clone())),
 					}
+				}
 
-					if current_value.get_type() == value.get_type() {
-						scope.map.insert(name, value);
-						return Ok(());
-					} else {
-						return Err(EnvError::InvalidType(current_value));
-					}
+				if current_value.get_type() == value.get_type() {
+					*current_value = value;
+					return Ok(());
+				} else {
+					return Err(EnvError::InvalidType(current_value.clone()));
 				}
 			}
 		}
@@ -205,8 +851,9 @@ impl Environment {
 			return Err(EnvError::EmptyDeclarationNoType);
 		}
 
-		if let Some(scope) = self.scopes.iter_mut().last() {
-			scope.map.insert(name, value);
+		if let Some(scope) = self.scopes.last_mut() {
+			scope.symbols.push(name);
+			self.bindings.entry(name).or_default().push(value);
 		} else {
 			unreachable!("scopes list should not be empty");
 		}
@@ -217,26 +864,75 @@ impl Environment {
 	fn define_native_function(
 		&mut self,
 		name: &str,
-		args: Vec<String>,
-		function: fn(Vec<Value>) -> Value,
+		params: Vec<Param>,
+		function: impl Fn(&mut dyn Host, &[Value]) -> Value + 'static,
 	) {
-		_ = self.define_or_assign(
-			name.to_string(),
-			Value::NativeFunction {
-				name: name.to_owned(),
-				args,
-				body: function,
-			},
+		self.define_native_body(name, params, NativeKind::Host(Rc::new(function)));
+	}
+
+	fn define_callback_function(
+		&mut self,
+		name: &str,
+		params: Vec<Param>,
+		function: impl Fn(&mut Interpreter, &Token, &[Value]) -> Result<Value, ErrorOrReturn> + 'static,
+	) {
+		self.define_native_body(name, params, NativeKind::Callback(Rc::new(function)));
+	}
+
+	fn define_native_body(&mut self, name: &str, params: Vec<Param>, body: NativeKind) {
+		let native = Value::NativeFunction {
+			name: name.to_owned(),
+			params,
+			body: NativeBody(body),
+		};
+		// natives defined by an embedding application replace any symbol with the same
+		// name, whatever its type
+		if let Some(value) = self.get_mut(Symbol::intern(name)) {
+			*value = native;
+		} else {
 			// this argument is set to `true` to skip unnecessary code in the function.
 			// either way, the behavior doesn’t change because we know there is
 			// no symbol using this name yet
-			true,
-		);
+			_ = self.define_or_assign(Symbol::intern(name), native, true);
+		}
+	}
+}
+
+/// Path of an imported file: relative paths start from the directory of the importing file.
+/// The `.` and `..` components are removed, so that a file has one path however it is
+/// imported.
+fn resolve_import(importer: &str, path: &str) -> String {
+	let joined;
+	let path = if path.starts_with('/') {
+		path
+	} else {
+		let directory = importer
+			.rfind(['/', '\\'])
+			.map_or("", |end| &importer[..=end]);
+		joined = format!("{directory}{path}");
+		&joined
+	};
+
+	let mut components: Vec<&str> = vec![];
+	for component in path.split('/') {
+		match component {
+			"." => {}
+			".." if components
+				.last()
+				.is_some_and(|last| !last.is_empty() && *last != "..") =>
+			{
+				components.pop();
+			}
+			_ => components.push(component),
+		}
 	}
+	components.join("/")
 }
 
+#[cfg(feature = "std")]
 struct AskTheme;
 
+#[cfg(feature = "std")]
 impl Theme for AskTheme {
 	fn format_prompt(&self, f: &mut dyn fmt::Write, prompt: &str) -> fmt::Result {
 		write!(f, "{}", prompt)
@@ -265,150 +961,1925 @@ impl Theme for AskTheme {
 	}
 }
 
-/// In order to use the `?` notation, return values are defined
-/// as an error state
-#[derive(Debug)]
-enum ErrorOrReturn {
-	Return(Value),
-	Error,
+/// Access of the natives to the outside world. The default host uses the standard input and
+/// output and the system, and can be replaced with `Interpreter::set_host` where they are not
+/// available or must be captured (browsers, tests, embedding applications)
+pub trait Host {
+	/// Writes a line to the output (`out` and errors of natives)
+	fn print(&mut self, text: &str);
+	/// Asks the user for a line of input after displaying a prompt (`ask`)
+	fn ask(&mut self, prompt: &str) -> String;
+	/// Asks the user for a line of input like `ask`, but gives up after some seconds (`ask` with
+	/// a timeout). Returns `None` if the user did not answer in time.
+	fn ask_timeout(&mut self, prompt: &str, seconds: f64) -> Option<String>;
+	/// Asks the user to choose any number of options after displaying a prompt (`multi_select`),
+	/// and returns the indexes of the chosen ones. Returns `None` if the user cannot be asked.
+	fn multi_select(&mut self, prompt: &str, options: &[String]) -> Option<Vec<usize>>;
+	/// Reads a line of the input, without its line ending (`read_line`). Returns `None` if the
+	/// input cannot be read, and `Some(None)` at the end of the input.
+	fn read_line(&mut self) -> Option<Option<String>>;
+	/// Reads the rest of the input (`read_all`)
+	fn read_all(&mut self) -> Option<String>;
+	/// Current time in nanoseconds since the Unix epoch (`time`)
+	fn time(&mut self) -> f64;
+	/// Milliseconds of a monotonic clock, which is not changed by the system time, since an
+	/// arbitrary point (`clock_ms` and the timers)
+	fn clock(&mut self) -> f64;
+	/// Runs a program and returns its standard output (`cmd`)
+	fn run_command(&mut self, program: &str, args: &[&str]) -> Option<String>;
+	/// Runs a command line with the shell of the system and returns its standard output
+	/// (`shell`)
+	fn run_shell(&mut self, command: &str) -> Option<String>;
+	/// Watches a file or directory, returning the paths of the files that change under it, as
+	/// they change (`watch`). Returns `None` if the path cannot be watched.
+	fn watch(&mut self, path: &str) -> Option<Box<dyn Iterator<Item = String>>>;
+	/// Downloads a URL to a file, returning the number of bytes written (`download`)
+	fn download(&mut self, url: &str, destination: &str) -> Option<u64>;
+	/// Creates an archive of a file or directory (`zip` and `tar`), returning whether it worked
+	fn create_archive(&mut self, format: Archive, source: &str, destination: &str) -> bool;
+	/// Extracts an archive into a directory (`unzip` and `untar`), returning whether it worked
+	fn extract_archive(&mut self, format: Archive, archive: &str, destination: &str) -> bool;
+	/// Reads the source code of an imported file (`import`)
+	fn read_file(&mut self, path: &str) -> Option<String>;
+	/// Whether a file or directory exists at the path (`file_exists`)
+	fn file_exists(&mut self, path: &str) -> bool;
+	/// Removes a file (`remove_file`), returning whether it worked
+	fn remove_file(&mut self, path: &str) -> bool;
+	/// Copies a file, replacing the destination if it exists (`copy_file`), returning whether it
+	/// worked
+	fn copy_file(&mut self, source: &str, destination: &str) -> bool;
+	/// Moves or renames a file, replacing the destination if it exists (`move_file`), returning
+	/// whether it worked
+	fn move_file(&mut self, source: &str, destination: &str) -> bool;
+	/// Lists the entries of a directory as sorted paths relative to it, separated by `/`, with
+	/// the entries of its subdirectories if it is recursive (`list_dir` and `glob`). Returns
+	/// `None` if the directory cannot be read.
+	fn list_dir(&mut self, path: &str, recursive: bool) -> Option<Vec<String>>;
+	/// Host of the tasks started by `spawn`, which run on other threads
+	fn task_host(&self) -> Box<dyn Host + Send>;
 }
 
-pub struct Interpreter {
-	environment: Environment,
+/// Format of the archives created and extracted by the natives
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Archive {
+	/// `zip` and `unzip`
+	Zip,
+	/// `tar` and `untar`, compressed with gzip
+	TarGz,
 }
 
-impl Interpreter {
-	pub fn new() -> Self {
-		let mut env = Environment::new();
+/// Host without any input or output, used by default without `std`
+pub struct NullHost;
 
-		env.define_native_function("out", vec![String::new()], |args| {
-			println!("{}", args[0]);
-			Value::Empty
-		});
-		env.define_native_function("ask", vec![String::new()], |args| {
-			let arg = args.first().unwrap();
-			match arg {
-				Value::String(prompt) => {
-					let input: String = Input::with_theme(&AskTheme)
-						.with_prompt(prompt)
-						.allow_empty(true)
-						.interact()
-						.unwrap_or_default();
-					Value::String(input)
-				}
-				_ => {
-					println!("error: `ask` can only take a string as argument");
-					Value::String(String::new())
-				}
-			}
-		});
-		env.define_native_function("size", vec![String::new()], |args| {
-			let arg = args.first().unwrap();
-			match arg {
-				Value::String(value) => Value::Number(value.len() as f64),
-				_ => {
-					println!("error: `size` can only take a string as argument");
-					Value::Number(0.0)
-				}
-			}
-		});
-		env.define_native_function("time", vec![], |_| {
-			let time = SystemTime::now()
-				.duration_since(UNIX_EPOCH)
-				.expect("error: could not get system time");
-			Value::Number(time.as_nanos() as f64)
-		});
-		env.define_native_function("cmd", vec![String::new()], |args| {
-			let arg = args.first().unwrap();
-			match arg {
-				Value::String(command) => {
-					if command.is_empty() {
-						return Value::String(String::new());
-					}
-					let split: Vec<&str> = command.split_whitespace().collect();
-					let args = &split[1..];
-					let output = Command::new(split.first().unwrap()).args(args).output();
-					if let Ok(output) = output {
-						return Value::String(String::from_utf8_lossy(&output.stdout).into_owned());
-					}
+impl Host for NullHost {
+	fn print(&mut self, _text: &str) {}
+
+	fn ask(&mut self, _prompt: &str) -> String {
+		String::new()
+	}
+
+	fn ask_timeout(&mut self, _prompt: &str, _seconds: f64) -> Option<String> {
+		None
+	}
+
+	fn multi_select(&mut self, _prompt: &str, _options: &[String]) -> Option<Vec<usize>> {
+		None
+	}
+
+	fn read_line(&mut self) -> Option<Option<String>> {
+		Some(None)
+	}
+
+	fn read_all(&mut self) -> Option<String> {
+		Some(String::new())
+	}
+
+	fn time(&mut self) -> f64 {
+		0.0
+	}
+
+	fn clock(&mut self) -> f64 {
+		0.0
+	}
+
+	fn run_command(&mut self, _program: &str, _args: &[&str]) -> Option<String> {
+		None
+	}
+
+	fn run_shell(&mut self, _command: &str) -> Option<String> {
+		None
+	}
+
+	fn watch(&mut self, _path: &str) -> Option<Box<dyn Iterator<Item = String>>> {
+		None
+	}
+
+	fn download(&mut self, _url: &str, _destination: &str) -> Option<u64> {
+		None
+	}
+
+	fn create_archive(&mut self, _format: Archive, _source: &str, _destination: &str) -> bool {
+		false
+	}
+
+	fn extract_archive(&mut self, _format: Archive, _archive: &str, _destination: &str) -> bool {
+		false
+	}
+
+	fn read_file(&mut self, _path: &str) -> Option<String> {
+		None
+	}
+
+	fn file_exists(&mut self, _path: &str) -> bool {
+		false
+	}
+
+	fn remove_file(&mut self, _path: &str) -> bool {
+		false
+	}
+
+	fn copy_file(&mut self, _source: &str, _destination: &str) -> bool {
+		false
+	}
+
+	fn move_file(&mut self, _source: &str, _destination: &str) -> bool {
+		false
+	}
+
+	fn list_dir(&mut self, _path: &str, _recursive: bool) -> Option<Vec<String>> {
+		None
+	}
+
+	fn task_host(&self) -> Box<dyn Host + Send> {
+		Box::new(NullHost)
+	}
+}
+
+/// Host using the standard input and output, the system clock and processes
+#[cfg(feature = "std")]
+pub struct StdHost;
+
+#[cfg(feature = "std")]
+impl Host for StdHost {
+	fn print(&mut self, text: &str) {
+		println!("{text}");
+	}
+
+	fn ask(&mut self, prompt: &str) -> String {
+		Input::with_theme(&AskTheme)
+			.with_prompt(prompt)
+			.allow_empty(true)
+			.interact()
+			.unwrap_or_default()
+	}
+
+	fn ask_timeout(&mut self, prompt: &str, seconds: f64) -> Option<String> {
+		let mut pending = PENDING_ANSWER
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner);
+		print!("{prompt}");
+		_ = io::stdout().flush();
+
+		// the input is read by another thread, as reading it cannot be stopped
+		let answer = pending.take().unwrap_or_else(|| {
+			let (sender, receiver) = mpsc::channel();
+			thread::spawn(move || _ = sender.send(read_line().flatten()));
+			receiver
+		});
+		let timeout = Duration::try_from_secs_f64(seconds).unwrap_or(Duration::MAX);
+		match answer.recv_timeout(timeout) {
+			Ok(line) => line,
+			Err(RecvTimeoutError::Timeout) => {
+				println!();
+				*pending = Some(answer);
+				None
+			}
+			Err(RecvTimeoutError::Disconnected) => None,
+		}
+	}
+
+	fn multi_select(&mut self, prompt: &str, options: &[String]) -> Option<Vec<usize>> {
+		MultiSelect::with_theme(&AskTheme)
+			.with_prompt(prompt)
+			.items(options)
+			.interact()
+			.ok()
+	}
+
+	fn read_line(&mut self) -> Option<Option<String>> {
+		read_line()
+	}
+
+	fn read_all(&mut self) -> Option<String> {
+		let mut input = String::new();
+		stdin().read_to_string(&mut input).ok()?;
+		Some(input)
+	}
+
+	fn time(&mut self) -> f64 {
+		let time = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.expect("error: could not get system time");
+		time.as_nanos() as f64
+	}
+
+	fn clock(&mut self) -> f64 {
+		CLOCK_START
+			.get_or_init(Instant::now)
+			.elapsed()
+			.as_secs_f64()
+			* 1000.0
+	}
+
+	fn run_command(&mut self, program: &str, args: &[&str]) -> Option<String> {
+		#[cfg(windows)]
+		let program = resolve_program(program);
+		let output = Command::new(program).args(args).output().ok()?;
+		Some(String::from_utf8_lossy(&output.stdout).into_owned())
+	}
+
+	fn run_shell(&mut self, command: &str) -> Option<String> {
+		#[cfg(windows)]
+		let output = {
+			use std::os::windows::process::CommandExt;
+			// `cmd.exe` has its own rules for quotes, so the command is given as it is
+			Command::new("cmd").arg("/C").raw_arg(command).output()
+		};
+		#[cfg(not(windows))]
+		let output = Command::new("sh").args(["-c", command]).output();
+		Some(String::from_utf8_lossy(&output.ok()?.stdout).into_owned())
+	}
+
+	fn watch(&mut self, path: &str) -> Option<Box<dyn Iterator<Item = String>>> {
+		let (sender, receiver) = mpsc::channel();
+		let mut debouncer = new_debouncer(WATCH_DEBOUNCE, sender).ok()?;
+		debouncer
+			.watcher()
+			.watch(Path::new(path), RecursiveMode::Recursive)
+			.ok()?;
+		Some(Box::new(Changes {
+			_debouncer: debouncer,
+			receiver,
+			pending: VecDeque::new(),
+		}))
+	}
+
+	fn download(&mut self, url: &str, destination: &str) -> Option<u64> {
+		let response = ureq::get(url).call().ok()?;
+		let total = response
+			.header("Content-Length")
+			.and_then(|length| length.parse().ok());
+		let mut file = File::create(destination).ok()?;
+		match save_download(&mut response.into_reader(), &mut file, total) {
+			Ok(written) => Some(written),
+			Err(_) => {
+				// a partial file would look like a successful download
+				drop(file);
+				_ = fs::remove_file(destination);
+				None
+			}
+		}
+	}
+
+	fn create_archive(&mut self, format: Archive, source: &str, destination: &str) -> bool {
+		archive::create(format, Path::new(source), Path::new(destination)).is_ok()
+	}
+
+	fn extract_archive(&mut self, format: Archive, archive: &str, destination: &str) -> bool {
+		archive::extract(format, Path::new(archive), Path::new(destination)).is_ok()
+	}
+
+	fn read_file(&mut self, path: &str) -> Option<String> {
+		fs::read_to_string(path).ok()
+	}
+
+	fn file_exists(&mut self, path: &str) -> bool {
+		Path::new(path).exists()
+	}
+
+	fn remove_file(&mut self, path: &str) -> bool {
+		fs::remove_file(path).is_ok()
+	}
+
+	fn copy_file(&mut self, source: &str, destination: &str) -> bool {
+		fs::copy(source, destination).is_ok()
+	}
+
+	fn move_file(&mut self, source: &str, destination: &str) -> bool {
+		// renaming does not work across file systems, where the file is copied instead
+		fs::rename(source, destination).is_ok()
+			|| (Path::new(source).is_file()
+				&& fs::copy(source, destination).is_ok()
+				&& fs::remove_file(source).is_ok())
+	}
+
+	fn list_dir(&mut self, path: &str, recursive: bool) -> Option<Vec<String>> {
+		let mut entries = vec![];
+		list_entries(Path::new(path), "", recursive, &mut entries).ok()?;
+		entries.sort();
+		Some(entries)
+	}
+
+	fn task_host(&self) -> Box<dyn Host + Send> {
+		Box::new(StdHost)
+	}
+}
+
+/// Adds the entries of a directory to the list, after the prefix of their parent directory.
+/// Symbolic links to directories are not followed, so that they cannot make cycles.
+#[cfg(feature = "std")]
+fn list_entries(
+	directory: &Path,
+	prefix: &str,
+	recursive: bool,
+	entries: &mut Vec<String>,
+) -> io::Result<()> {
+	for entry in fs::read_dir(directory)? {
+		let entry = entry?;
+		let path = format!("{prefix}{}", entry.file_name().to_string_lossy());
+		if recursive && entry.file_type()?.is_dir() {
+			list_entries(&entry.path(), &format!("{path}/"), recursive, entries)?;
+		}
+		entries.push(path);
+	}
+	Ok(())
+}
+
+/// Writes a downloaded body to a file, showing the progress on the error output when it is a
+/// terminal, and returns the number of bytes written
+#[cfg(feature = "std")]
+fn save_download(body: &mut dyn Read, file: &mut File, total: Option<u64>) -> io::Result<u64> {
+	let progress = io::stderr().is_terminal();
+	let mut buffer = [0; 8192];
+	let mut written = 0;
+	loop {
+		let read = body.read(&mut buffer)?;
+		if read == 0 {
+			break;
+		}
+		file.write_all(&buffer[..read])?;
+		written += read as u64;
+		if progress {
+			match total {
+				Some(total) if total > 0 => {
+					eprint!(
+						"\rDownloaded {written} of {total} bytes ({}%)",
+						written * 100 / total
+					)
+				}
+				_ => eprint!("\rDownloaded {written} bytes"),
+			}
+		}
+	}
+	if progress {
+		eprintln!();
+	}
+	Ok(written)
+}
+
+/// Answer of an `ask` that timed out, still being read. The next `ask` with a timeout waits for
+/// it instead of reading the input at the same time.
+#[cfg(feature = "std")]
+static PENDING_ANSWER: Mutex<Option<mpsc::Receiver<Option<String>>>> = Mutex::new(None);
+
+/// Start of the monotonic clock, when it is first read
+#[cfg(feature = "std")]
+static CLOCK_START: OnceLock<Instant> = OnceLock::new();
+
+/// Finds the file of a program in the `PATH` on Windows, where programs can also be batch files
+/// or scripts, whose extensions (`PATHEXT`) are not tried when running a program
+#[cfg(all(feature = "std", windows))]
+fn resolve_program(program: &str) -> std::path::PathBuf {
+	let path = Path::new(program);
+	if path.extension().is_some() || path.components().count() > 1 {
+		return path.to_path_buf();
+	}
+
+	let extensions =
+		std::env::var("PATHEXT").unwrap_or_else(|_| String::from(".COM;.EXE;.BAT;.CMD"));
+	let directories = std::env::var_os("PATH")
+		.map(|paths| std::env::split_paths(&paths).collect::<Vec<_>>())
+		.unwrap_or_default();
+	directories
+		.iter()
+		.flat_map(|directory| {
+			extensions
+				.split(';')
+				.map(move |extension| directory.join(format!("{program}{extension}")))
+		})
+		.find(|candidate| candidate.is_file())
+		.unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Splits the command line of `cmd` into a program and its arguments. They are separated by
+/// whitespace, except inside single or double quotes, and `\"` or `\'` is a quote in an argument.
+fn split_command(command: &str) -> Vec<String> {
+	let mut words = vec![];
+	let mut word: Option<String> = None;
+	let mut quote = None;
+	let mut characters = command.chars().peekable();
+	while let Some(character) = characters.next() {
+		match character {
+			'\\' if matches!(characters.peek(), Some('"' | '\'')) => {
+				word.get_or_insert_with(String::new)
+					.extend(characters.next());
+			}
+			character if Some(character) == quote => quote = None,
+			character if quote.is_some() => word.get_or_insert_with(String::new).push(character),
+			'"' | '\'' => {
+				quote = Some(character);
+				// `""` is an empty argument
+				word.get_or_insert_with(String::new);
+			}
+			character if character.is_whitespace() => words.extend(word.take()),
+			character => word.get_or_insert_with(String::new).push(character),
+		}
+	}
+	words.extend(word);
+	words
+}
+
+/// Reads a line of the standard input, without its line ending, or `Some(None)` at its end
+#[cfg(feature = "std")]
+fn read_line() -> Option<Option<String>> {
+	let mut line = String::new();
+	if stdin().read_line(&mut line).ok()? == 0 {
+		return Some(None);
+	}
+	if line.ends_with('\n') {
+		line.pop();
+		if line.ends_with('\r') {
+			line.pop();
+		}
+	}
+	Some(Some(line))
+}
+
+/// Time during which the changes of a file are grouped into one by `watch`, as editors often
+/// write a file in several steps
+#[cfg(feature = "std")]
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Paths changed under a watched path, waiting for the next change when all were returned
+#[cfg(feature = "std")]
+struct Changes {
+	/// Kept to keep watching
+	_debouncer: Debouncer<RecommendedWatcher>,
+	receiver: mpsc::Receiver<DebounceEventResult>,
+	pending: VecDeque<String>,
+}
+
+#[cfg(feature = "std")]
+impl Iterator for Changes {
+	type Item = String;
+
+	fn next(&mut self) -> Option<String> {
+		while self.pending.is_empty() {
+			// errors of the watcher are skipped, as the next changes can still be found
+			if let Ok(events) = self.receiver.recv().ok()? {
+				self.pending.extend(
+					events
+						.into_iter()
+						.map(|event| event.path.to_string_lossy().into_owned()),
+				);
+			}
+		}
+		self.pending.pop_front()
+	}
+}
+
+/// Prints a line through a host
+macro_rules! output {
+	($host:expr, $($arg:tt)*) => {
+		$host.print(&format!($($arg)*))
+	};
+}
+
+/// In order to use the `?` notation, return values are defined
+/// as an error state
+#[derive(Debug)]
+enum ErrorOrReturn {
+	Return(Value),
+	Error,
+	/// `break`, with the label of the loop to leave
+	Break(Option<Symbol>),
+	/// `continue`, with the label of the loop to continue
+	Continue(Option<Symbol>),
+}
+
+/// Converts a value to the type of a conversion keyword (`boolean`, `number` or `string`), or
+/// returns `None` if the value has no equivalent in that type
+fn convert(value: &Value, target: TokenType) -> Option<Value> {
+	match (target, value) {
+		(
+			_,
+			Value::Function { .. }
+			| Value::NativeFunction { .. }
+			| Value::Enum { .. }
+			| Value::Module { .. },
+		) => None,
+		// empty values stay empty
+		(
+			TokenType::String,
+			Value::EmptyBoolean | Value::EmptyNumber | Value::EmptyString | Value::Empty,
+		) => Some(Value::EmptyString),
+		(TokenType::String, value) => Some(Value::String(value.to_string())),
+		(TokenType::Number, Value::Number(number)) => Some(Value::Number(*number)),
+		(TokenType::Number, Value::Boolean(boolean)) => {
+			Some(Value::Number(if *boolean { 1.0 } else { 0.0 }))
+		}
+		(TokenType::Number, Value::String(string)) => string
+			.trim()
+			.parse::<f64>()
+			.ok()
+			.filter(|number| number.is_finite())
+			.map(Value::Number),
+		(TokenType::Boolean, Value::Boolean(boolean)) => Some(Value::Boolean(*boolean)),
+		(TokenType::Boolean, Value::Number(number)) if *number == 0.0 => {
+			Some(Value::Boolean(false))
+		}
+		(TokenType::Boolean, Value::Number(number)) if *number == 1.0 => Some(Value::Boolean(true)),
+		(TokenType::Boolean, Value::String(string)) => match string.as_str() {
+			"true" => Some(Value::Boolean(true)),
+			"false" => Some(Value::Boolean(false)),
+			_ => None,
+		},
+		(
+			TokenType::Boolean,
+			Value::EmptyBoolean | Value::EmptyNumber | Value::EmptyString | Value::Empty,
+		) => Some(Value::EmptyBoolean),
+		(
+			TokenType::Number,
+			Value::EmptyBoolean | Value::EmptyNumber | Value::EmptyString | Value::Empty,
+		) => Some(Value::EmptyNumber),
+		_ => None,
+	}
+}
+
+/// Position of the element of a list (or the character of a string) at an index, counted from
+/// the end when it is negative
+fn position(index: &Value, length: usize, sequence: &str, element: &str) -> Result<usize, String> {
+	let Value::Number(index) = *index else {
+		return Err(format!(
+			"Index of a {sequence} must be a number (found {})",
+			index.inspect()
+		));
+	};
+	if index != math::trunc(index) {
+		return Err(format!(
+			"Index of a {sequence} must be a whole number (found {index})"
+		));
+	}
+	let position = if index < 0.0 {
+		length as f64 + index
+	} else {
+		index
+	};
+	if position < 0.0 || position >= length as f64 {
+		return Err(format!(
+			"Index {index} is out of the bounds of a {sequence} of {length} {element}{}",
+			if length == 1 { "" } else { "s" }
+		));
+	}
+	Ok(position as usize)
+}
+
+/// Replaces the arguments after the given number by a list of them, for the last parameter of a
+/// variadic function
+fn collect_extra_arguments(arguments: &mut Arguments, count: usize) {
+	let extra = arguments.drain(count..).collect();
+	arguments.push(Value::List(Rc::new(extra)));
+}
+
+/// First argument of a call to a native that does not have the type of its parameter, with its
+/// position counted from 1
+fn mismatched_argument<'a>(
+	callee: &Value,
+	arguments: &'a [Value],
+) -> Option<(usize, Param, &'a Value)> {
+	let Value::NativeFunction { params, .. } = callee else {
+		return None;
+	};
+	params
+		.iter()
+		.zip(arguments)
+		.enumerate()
+		.find(|(_, (param, argument))| !param.accepts(argument))
+		.map(|(index, (param, argument))| (index + 1, *param, argument))
+}
+
+/// Formats a duration in milliseconds for humans (`format_duration`): `250ms` under a second,
+/// `1.5s` under a minute, and `1h 2m 3s` above
+fn format_duration(milliseconds: f64) -> String {
+	let milliseconds = math::round(milliseconds);
+	if milliseconds < 1000.0 {
+		return format!("{milliseconds}ms");
+	}
+	if milliseconds < 60_000.0 {
+		return format!("{}s", math::round(milliseconds / 10.0) / 100.0);
+	}
+
+	let seconds = math::round(milliseconds / 1000.0);
+	let hours = (seconds / 3600.0) as u64;
+	let minutes = (math::rem_euclid(seconds, 3600.0) / 60.0) as u64;
+	let seconds = math::rem_euclid(seconds, 60.0) as u64;
+	if hours > 0 {
+		format!("{hours}h {minutes}m {seconds}s")
+	} else {
+		format!("{minutes}m {seconds}s")
+	}
+}
+
+/// Native functions that can reach outside of the interpreter (shell, files,
+/// environment, network), and are therefore disabled in sandbox mode. The `--sandbox` flag of
+/// `ilo_cli` lists them too
+const SANDBOXED_NATIVES: [&str; 14] = [
+	"cmd",
+	"shell",
+	"watch",
+	"download",
+	"zip",
+	"unzip",
+	"tar",
+	"untar",
+	"remove_file",
+	"copy_file",
+	"move_file",
+	"file_exists",
+	"list_dir",
+	"glob",
+];
+
+/// Index in characters of a byte offset found in the text, or an empty number if it was not found
+fn character_index(text: &str, offset: Option<usize>) -> Value {
+	match offset {
+		Some(offset) => Value::Number(text[..offset].chars().count() as f64),
+		None => Value::EmptyNumber,
+	}
+}
+
+/// Repetitions of the fill, cut to make the text as long as the width in characters, with the
+/// length in bytes of the padded text (`None` if it does not even fit in memory)
+fn padding<'a>(
+	text: &str,
+	width: f64,
+	fill: &'a str,
+) -> (impl Iterator<Item = char> + 'a, Option<usize>) {
+	let missing = (width as usize).saturating_sub(text.chars().count());
+	let repeated = match fill.chars().count() {
+		0 => Some(0),
+		count => {
+			let rest: usize = fill.chars().take(missing % count).map(char::len_utf8).sum();
+			(missing / count)
+				.checked_mul(fill.len())
+				.and_then(|length| length.checked_add(rest))
+		}
+	};
+	// strings cannot be longer than `isize::MAX` bytes
+	let length = repeated
+		.and_then(|length| length.checked_add(text.len()))
+		.filter(|&length| length <= isize::MAX as usize);
+	(fill.chars().cycle().take(missing), length)
+}
+
+/// Numbers defined before the script runs, like the natives
+const CONSTANTS: [(&str, f64); 3] = [
+	("PI", core::f64::consts::PI),
+	("E", core::f64::consts::E),
+	("INFINITY", f64::INFINITY),
+];
+
+/// Default number of nested function calls, low enough for the interpreter to stay within the
+/// stack of a thread
+const DEFAULT_MAX_CALL_DEPTH: usize = 256;
+
+/// Hook into the execution of a script, used to build debuggers
+pub trait Debugger {
+	/// Called before each statement is executed. `depth` is the number of function calls in
+	/// progress. The interpreter can be used to inspect or evaluate code in the current frame.
+	fn on_statement(&mut self, interpreter: &mut Interpreter, line: i64, depth: usize);
+}
+
+/// Bundle of native functions kept outside of ilo (databases, cloud CLIs...), which an embedder
+/// registers and a project enables by name
+pub trait NativePack {
+	/// Name of the pack, as listed in the `plugins` of an `ilo.toml` file
+	fn name(&self) -> &str;
+	/// Defines the natives of the pack, usually with `define_typed_native`
+	fn define(&self, interpreter: &mut Interpreter);
+}
+
+pub struct Interpreter {
+	environment: Environment,
+	/// When enabled, calls to the natives listed in `SANDBOXED_NATIVES` are
+	/// reported as runtime errors
+	sandbox: bool,
+	debugger: Option<Box<dyn Debugger>>,
+	host: Box<dyn Host>,
+	/// Number of statements that can still be executed, if limited
+	fuel: Option<u64>,
+	/// Number of function calls in progress
+	call_depth: usize,
+	max_call_depth: usize,
+	max_string_length: Option<usize>,
+	/// When enabled, impossible conversions are reported as runtime errors instead of giving an
+	/// empty value
+	strict: bool,
+	/// Set from outside of the interpreter (on Ctrl-C) to stop the script at its next statement
+	interrupted: Arc<AtomicBool>,
+	/// Set in the interpreters of tasks, which leave the interrupt flag they share with the
+	/// script set, so that the script stops too
+	in_task: bool,
+	/// Function given to `on_interrupt`, called before stopping the script
+	interrupt_handler: Option<Value>,
+	/// Set when the script is interrupted or runs out of fuel, so that `attempt` does not
+	/// rescue it
+	stopping: bool,
+	/// Statements deferred by each function being called, and by the script, run in reverse
+	/// order when it exits
+	deferred: Vec<Vec<Statement>>,
+	/// Tasks started by `spawn`, whose handles are their indices. Tasks already waited for are
+	/// `None`.
+	#[cfg(feature = "std")]
+	tasks: Vec<Option<task::Task>>,
+	/// When enabled, the scopes are saved when the first runtime error is reported
+	snapshot_on_error: bool,
+	/// Scopes saved when the first runtime error was reported. The errors are reported from
+	/// methods that do not change the interpreter, hence the cell.
+	error_snapshot: RefCell<Option<Vec<ScopeSnapshot>>>,
+	/// Statements executed in each call stack, when profiling is enabled
+	profiler: Option<profile::Profiler>,
+	/// Counters of the execution, when statistics are enabled
+	statistics: Option<Statistics>,
+	/// Files whose imports are running, to report cycles of imports
+	importing: Vec<String>,
+	/// Top-level variables of the files already imported, by path, so that each file runs once
+	modules: HashMap<String, Rc<Vec<(Symbol, Value)>>>,
+	/// Command-line arguments given to the script, returned by `args`
+	arguments: Vec<String>,
+}
+
+impl Interpreter {
+	pub fn new() -> Self {
+		let mut env = Environment::new();
+
+		env.define_native_function("out", vec![Param::Any], |host, args| {
+			output!(host, "{}", args[0]);
+			Value::Empty
+		});
+		env.define_native_function("inspect", vec![Param::Any], |host, args| {
+			output!(host, "{}", args[0].inspect());
+			Value::Empty
+		});
+		env.define_callback_function(
+			"ask",
+			vec![Param::String, Param::Optional(&Param::Number)],
+			|interpreter, closing_paren, args| match args.get(1).map(Value::as_number) {
+				None => Ok(Value::String(interpreter.host.ask(args[0].as_string()))),
+				Some(seconds) if seconds > 0.0 => Ok(interpreter
+					.host
+					.ask_timeout(args[0].as_string(), seconds)
+					.map_or(Value::EmptyString, Value::String)),
+				Some(_) => interpreter.report_runtime_error(
+					closing_paren,
+					String::from("`ask` can only take a positive number of seconds"),
+				),
+			},
+		);
+		env.define_native_function(
+			"multi_select",
+			vec![Param::String, Param::List],
+			|host, args| {
+				let options = args[1].as_list();
+				let labels: Vec<String> = options.iter().map(Value::to_string).collect();
+				let Some(chosen) = host.multi_select(args[0].as_string(), &labels) else {
+					output!(
+						host,
+						"error: `multi_select` could not ask the user to choose"
+					);
+					return Value::List(Rc::new(vec![]));
+				};
+				let chosen = chosen.into_iter().filter_map(|index| options.get(index));
+				Value::List(Rc::new(chosen.cloned().collect()))
+			},
+		);
+		env.define_native_function("type", vec![Param::Any], |_, args| {
+			Value::String(match &args[0] {
+				Value::Empty => String::from("empty"),
+				value => value.get_type(),
+			})
+		});
+		env.define_native_function("size", vec![Param::Sequence], |_, args| {
+			Value::Number(match &args[0] {
+				Value::List(elements) => elements.len() as f64,
+				string => string.as_string().chars().count() as f64,
+			})
+		});
+		env.define_callback_function("args", vec![], |interpreter, _, _| {
+			let arguments = interpreter.arguments.iter().cloned().map(Value::String);
+			Ok(Value::List(Rc::new(arguments.collect())))
+		});
+		env.define_native_function("read_line", vec![], |host, _| match host.read_line() {
+			Some(Some(line)) => Value::String(line),
+			// the end of the input, which an empty line cannot be told apart from otherwise
+			Some(None) => Value::EmptyString,
+			None => {
+				output!(host, "error: `read_line` could not read the standard input");
+				Value::EmptyString
+			}
+		});
+		env.define_native_function("read_all", vec![], |host, _| {
+			let input = host.read_all();
+			if input.is_none() {
+				output!(host, "error: `read_all` could not read the standard input");
+			}
+			Value::String(input.unwrap_or_default())
+		});
+		env.define_native_function("time", vec![], |host, _| Value::Number(host.time()));
+		env.define_native_function("clock_ms", vec![], |host, _| Value::Number(host.clock()));
+		env.define_native_function("timer_start", vec![], |host, _| Value::Number(host.clock()));
+		env.define_native_function("timer_elapsed", vec![Param::Number], |host, args| {
+			Value::Number(host.clock() - args[0].as_number())
+		});
+		for name in ["format_duration", "duration"] {
+			env.define_callback_function(
+				name,
+				vec![Param::Number],
+				move |interpreter, closing_paren, args| match args[0].as_number() {
+					milliseconds if milliseconds.is_finite() && milliseconds >= 0.0 => {
+						Ok(Value::String(format_duration(milliseconds)))
+					}
+					_ => interpreter.report_runtime_error(
+						closing_paren,
+						format!(
+							"`{name}` can only take a finite non-negative number of milliseconds"
+						),
+					),
+				},
+			);
+		}
+		env.define_native_function("abs", vec![Param::Number], |_, args| {
+			Value::Number(math::abs(args[0].as_number()))
+		});
+		env.define_native_function("floor", vec![Param::Number], |_, args| {
+			Value::Number(math::floor(args[0].as_number()))
+		});
+		env.define_native_function("ceil", vec![Param::Number], |_, args| {
+			Value::Number(math::ceil(args[0].as_number()))
+		});
+		env.define_native_function("round", vec![Param::Number], |_, args| {
+			Value::Number(math::round(args[0].as_number()))
+		});
+		env.define_callback_function(
+			"sqrt",
+			vec![Param::Number],
+			|interpreter, closing_paren, args| match args[0].as_number() {
+				number if number >= 0.0 => Ok(Value::Number(math::sqrt(number))),
+				_ => interpreter.report_runtime_error(
+					closing_paren,
+					String::from("`sqrt` can only take a non-negative number"),
+				),
+			},
+		);
+		env.define_native_function("cmd", vec![Param::String], |host, args| {
+			let words = split_command(args[0].as_string());
+			let words: Vec<&str> = words.iter().map(String::as_str).collect();
+			if let Some((program, args)) = words.split_first() {
+				if let Some(output) = host.run_command(program, args) {
+					return Value::String(output);
+				}
+			}
+
+			Value::String(String::new())
+		});
+		env.define_native_function("shell", vec![Param::String], |host, args| {
+			Value::String(host.run_shell(args[0].as_string()).unwrap_or_default())
+		});
+		env.define_native_function(
+			"download",
+			vec![Param::String, Param::String],
+			|host, args| {
+				let (url, destination) = (args[0].as_string(), args[1].as_string());
+				match host.download(url, destination) {
+					Some(size) => Value::Number(size as f64),
+					None => {
+						output!(
+							host,
+							"error: `download` could not download `{url}` to `{destination}`"
+						);
+						Value::EmptyNumber
+					}
+				}
+			},
+		);
+		for (create, extract, format) in [
+			("zip", "unzip", Archive::Zip),
+			("tar", "untar", Archive::TarGz),
+		] {
+			env.define_native_function(
+				create,
+				vec![Param::String, Param::String],
+				move |host, args| {
+					let (source, destination) = (args[0].as_string(), args[1].as_string());
+					let created = host.create_archive(format, source, destination);
+					if !created {
+						output!(
+							host,
+							"error: `{create}` could not archive `{source}` to `{destination}`"
+						);
+					}
+					Value::Boolean(created)
+				},
+			);
+			env.define_native_function(
+				extract,
+				vec![Param::String, Param::String],
+				move |host, args| {
+					let (archive, destination) = (args[0].as_string(), args[1].as_string());
+					let extracted = host.extract_archive(format, archive, destination);
+					if !extracted {
+						output!(
+							host,
+							"error: `{extract}` could not extract `{archive}` to `{destination}`"
+						);
+					}
+					Value::Boolean(extracted)
+				},
+			);
+		}
+		env.define_native_function("file_exists", vec![Param::String], |host, args| {
+			Value::Boolean(host.file_exists(args[0].as_string()))
+		});
+		env.define_native_function("remove_file", vec![Param::String], |host, args| {
+			let path = args[0].as_string();
+			let removed = host.remove_file(path);
+			if !removed {
+				output!(host, "error: `remove_file` could not remove `{path}`");
+			}
+			Value::Boolean(removed)
+		});
+		env.define_native_function(
+			"copy_file",
+			vec![Param::String, Param::String],
+			|host, args| {
+				let (source, destination) = (args[0].as_string(), args[1].as_string());
+				let copied = host.copy_file(source, destination);
+				if !copied {
+					output!(
+						host,
+						"error: `copy_file` could not copy `{source}` to `{destination}`"
+					);
+				}
+				Value::Boolean(copied)
+			},
+		);
+		env.define_native_function(
+			"move_file",
+			vec![Param::String, Param::String],
+			|host, args| {
+				let (source, destination) = (args[0].as_string(), args[1].as_string());
+				let moved = host.move_file(source, destination);
+				if !moved {
+					output!(
+						host,
+						"error: `move_file` could not move `{source}` to `{destination}`"
+					);
+				}
+				Value::Boolean(moved)
+			},
+		);
+		env.define_native_function(
+			"list_dir",
+			vec![Param::String, Param::Boolean],
+			|host, args| {
+				let (path, recursive) = (args[0].as_string(), args[1] == Value::Boolean(true));
+				let Some(entries) = host.list_dir(path, recursive) else {
+					output!(
+						host,
+						"error: `list_dir` could not read the directory `{path}`"
+					);
+					return Value::List(Rc::new(vec![]));
+				};
+				Value::List(Rc::new(entries.into_iter().map(Value::String).collect()))
+			},
+		);
+		env.define_native_function("glob", vec![Param::String], |host, args| {
+			let pattern = args[0].as_string();
+			let base = glob::base(pattern);
+			// a missing directory has no files matching the pattern
+			let entries = host
+				.list_dir(if base.is_empty() { "." } else { base }, true)
+				.unwrap_or_default();
+			let paths = entries
+				.into_iter()
+				.map(|entry| match base {
+					"" => entry,
+					base if base.ends_with('/') => format!("{base}{entry}"),
+					base => format!("{base}/{entry}"),
+				})
+				.filter(|path| glob::glob_match(pattern, path))
+				.map(Value::String)
+				.collect();
+			Value::List(Rc::new(paths))
+		});
+		env.define_callback_function(
+			"json_parse",
+			vec![Param::String],
+			|interpreter, closing_paren, args| {
+				json::parse(args[0].as_string()).or_else(|error| {
+					interpreter.report_runtime_error(
+						closing_paren,
+						format!("`json_parse` could not parse the JSON ({error})"),
+					)
+				})
+			},
+		);
+		env.define_callback_function(
+			"json_stringify",
+			vec![Param::Any, Param::Boolean],
+			|interpreter, closing_paren, args| {
+				let pretty = args[1] == Value::Boolean(true);
+				match json::stringify(&args[0], pretty) {
+					Ok(json) => Ok(Value::String(json)),
+					Err(error) => interpreter.report_runtime_error(
+						closing_paren,
+						format!("`json_stringify` could not convert the value ({error})"),
+					),
+				}
+			},
+		);
+		env.define_native_function(
+			"glob_match",
+			vec![Param::String, Param::String],
+			|_, args| Value::Boolean(glob::glob_match(args[0].as_string(), args[1].as_string())),
+		);
+		env.define_native_function("style", vec![Param::String, Param::String], |host, args| {
+			let (text, styles) = (args[0].as_string(), args[1].as_string());
+			let mut codes: Vec<&str> = vec![];
+			for style in styles.split_whitespace() {
+				codes.push(match style {
+					"bold" => "1",
+					"dim" => "2",
+					"italic" => "3",
+					"underline" => "4",
+					"black" => "30",
+					"red" => "31",
+					"green" => "32",
+					"yellow" => "33",
+					"blue" => "34",
+					"magenta" => "35",
+					"cyan" => "36",
+					"white" => "37",
+					_ => {
+						output!(host, "error: unknown style `{style}` given to `style`");
+						return Value::String(text.to_string());
+					}
+				});
+			}
+			if codes.is_empty() {
+				return Value::String(text.to_string());
+			}
+			Value::String(paint(text, &codes.join(";")))
+		});
+		env.define_native_function("upper", vec![Param::String], |_, args| {
+			Value::String(args[0].as_string().to_uppercase())
+		});
+		env.define_native_function("lower", vec![Param::String], |_, args| {
+			Value::String(args[0].as_string().to_lowercase())
+		});
+		env.define_native_function("index_of", vec![Param::String, Param::String], |_, args| {
+			let (text, part) = (args[0].as_string(), args[1].as_string());
+			character_index(text, text.find(part))
+		});
+		env.define_native_function(
+			"last_index_of",
+			vec![Param::String, Param::String],
+			|_, args| {
+				let (text, part) = (args[0].as_string(), args[1].as_string());
+				character_index(text, text.rfind(part))
+			},
+		);
+		env.define_callback_function(
+			"pad_start",
+			vec![Param::String, Param::Number, Param::String],
+			|interpreter, closing_paren, args| {
+				let text = args[0].as_string();
+				let (padding, length) = padding(text, args[1].as_number(), args[2].as_string());
+				interpreter.check_string_length(closing_paren, length)?;
+				Ok(Value::String(padding.chain(text.chars()).collect()))
+			},
+		);
+		env.define_callback_function(
+			"pad_end",
+			vec![Param::String, Param::Number, Param::String],
+			|interpreter, closing_paren, args| {
+				let text = args[0].as_string();
+				let (padding, length) = padding(text, args[1].as_number(), args[2].as_string());
+				interpreter.check_string_length(closing_paren, length)?;
+				Ok(Value::String(text.chars().chain(padding).collect()))
+			},
+		);
+
+		env.define_callback_function(
+			"watch",
+			vec![Param::String, Param::Function(1)],
+			|interpreter, closing_paren, args| {
+				let (path, handler) = (args[0].as_string(), &args[1]);
+				let Some(changes) = interpreter.host.watch(path) else {
+					output!(interpreter.host, "error: `watch` could not watch `{path}`");
+					return Ok(Value::Empty);
+				};
+
+				for changed in changes {
+					let mut arguments = Arguments::new();
+					arguments.push(Value::String(changed));
+					// the handler returns `false` to stop watching
+					if interpreter.call(handler.clone(), closing_paren, arguments)?
+						== Value::Boolean(false)
+					{
+						break;
+					}
+				}
+				Ok(Value::Empty)
+			},
+		);
+
+		env.define_callback_function(
+			"on_interrupt",
+			vec![Param::Function(0)],
+			|interpreter, _, args| {
+				interpreter.interrupt_handler = Some(args[0].clone());
+				Ok(Value::Empty)
+			},
+		);
+
+		#[cfg(feature = "std")]
+		env.define_callback_function("spawn", vec![Param::Function(0)], |interpreter, _, args| {
+			match &args[0] {
+				function @ Value::Function { .. } => {
+					interpreter
+						.tasks
+						.push(Some(task::spawn(interpreter, function)));
+					Ok(Value::Number((interpreter.tasks.len() - 1) as f64))
+				}
+				_ => {
+					output!(
+						interpreter.host,
+						"error: `spawn` can only take a function defined by the script"
+					);
+					Ok(Value::EmptyNumber)
+				}
+			}
+		});
+		#[cfg(feature = "std")]
+		env.define_callback_function(
+			"wait",
+			vec![Param::Number],
+			|interpreter, closing_paren, args| {
+				let task = match args[0].as_number() {
+					handle if handle >= 0.0 && handle.fract() == 0.0 => interpreter
+						.tasks
+						.get_mut(handle as usize)
+						.and_then(Option::take),
+					_ => None,
+				};
+				let Some(task) = task else {
+					output!(
+						interpreter.host,
+						"error: `wait` can only take a task from `spawn` that was not waited for yet"
+					);
+					return Ok(Value::Empty);
+				};
+				match task::wait(task) {
+					Some(value) => Ok(value),
+					None => interpreter.report_runtime_error(
+						closing_paren,
+						"The task waited for failed".to_string(),
+					),
+				}
+			},
+		);
+		env.define_constants();
+
+		Self {
+			environment: env,
+			sandbox: false,
+			debugger: None,
+			#[cfg(feature = "std")]
+			host: Box::new(StdHost),
+			#[cfg(not(feature = "std"))]
+			host: Box::new(NullHost),
+			fuel: None,
+			call_depth: 0,
+			max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+			max_string_length: None,
+			strict: false,
+			interrupted: Arc::new(AtomicBool::new(false)),
+			in_task: false,
+			interrupt_handler: None,
+			stopping: false,
+			deferred: vec![],
+			#[cfg(feature = "std")]
+			tasks: vec![],
+			snapshot_on_error: false,
+			error_snapshot: RefCell::new(None),
+			profiler: None,
+			statistics: None,
+			importing: vec![],
+			modules: HashMap::new(),
+			arguments: vec![],
+		}
+	}
+
+	/// Gives the script its command-line arguments, which it gets with `args`
+	pub fn set_arguments(&mut self, arguments: Vec<String>) {
+		self.arguments = arguments;
+	}
+
+	pub fn set_sandbox(&mut self, sandbox: bool) {
+		self.sandbox = sandbox;
+	}
+
+	pub fn set_strict(&mut self, strict: bool) {
+		self.strict = strict;
+	}
+
+	/// Flag to set from another thread or a signal handler to interrupt the script: it stops at
+	/// its next statement with a runtime error, after calling the function given to
+	/// `on_interrupt`
+	pub fn interrupt_flag(&self) -> Arc<AtomicBool> {
+		self.interrupted.clone()
+	}
+
+	pub fn set_debugger(&mut self, debugger: Box<dyn Debugger>) {
+		self.debugger = Some(debugger);
+	}
+
+	/// Replaces the host used by the natives
+	pub fn set_host(&mut self, host: Box<dyn Host>) {
+		self.host = host;
+	}
+
+	/// Limits the number of statements (and loop iterations) that can be executed, after which a
+	/// runtime error is reported, so that untrusted scripts cannot run forever
+	pub fn set_fuel(&mut self, fuel: Option<u64>) {
+		self.fuel = fuel;
+	}
+
+	/// Limits the number of nested function calls, so that deep recursion is reported as a
+	/// runtime error instead of overflowing the stack
+	pub fn set_max_call_depth(&mut self, depth: usize) {
+		self.max_call_depth = depth;
+	}
+
+	/// Limits the length in bytes of the strings that scripts can build
+	pub fn set_max_string_length(&mut self, length: Option<usize>) {
+		self.max_string_length = length;
+	}
+
+	/// Defines a native function that scripts can call, replacing any variable with the same name
+	pub fn define_native(
+		&mut self,
+		name: &str,
+		arity: usize,
+		function: impl Fn(&[Value]) -> Value + 'static,
+	) {
+		self.define_typed_native(name, vec![Param::Any; arity], function);
+	}
+
+	/// Defines a native function like `define_native`, whose arguments are checked to have the
+	/// types of its parameters before it is called
+	pub fn define_typed_native(
+		&mut self,
+		name: &str,
+		params: Vec<Param>,
+		function: impl Fn(&[Value]) -> Value + 'static,
+	) {
+		self.environment
+			.define_native_function(name, params, move |_, args| function(args));
+	}
+
+	/// Defines the natives of a pack, replacing any variables with the same names
+	pub fn load_pack(&mut self, pack: &dyn NativePack) {
+		pack.define(self);
+	}
+
+	/// Value of a variable that can be accessed from the current scope
+	pub fn get_variable(&self, name: &str) -> Option<Value> {
+		self.environment.get(Symbol::intern(name))
+	}
+
+	/// Lists the variables that can be accessed from the current scope, as
+	/// `(name, type, value)`, sorted by name. Native functions and constants are not included.
+	pub fn visible_variables(&self) -> Vec<(String, String, String)> {
+		let mut variables = vec![];
+		for (name, values) in self.environment.bindings.iter() {
+			match values.last() {
+				None | Some(Value::NativeFunction { .. }) => {}
+				Some(_) if values.len() == 1 && Environment::is_constant(*name) => {}
+				Some(value) => variables.push((name.name(), value.get_type(), value.to_string())),
+			}
+		}
+		variables.sort();
+		variables
+	}
+
+	/// Saves the scopes when a runtime error is reported, to be read with `take_error_snapshot`
+	pub fn set_snapshot_on_error(&mut self, enabled: bool) {
+		self.snapshot_on_error = enabled;
+	}
+
+	/// Counts the statements executed in each stack of function calls, to be read with
+	/// `folded_stacks`
+	pub fn enable_profiling(&mut self) {
+		self.profiler = Some(profile::Profiler::new());
+	}
+
+	/// Number of statements executed in each call stack since profiling was enabled, in the
+	/// folded stacks format read by flamegraph tools (`script;caller;function 12` on each line)
+	pub fn folded_stacks(&self) -> Option<String> {
+		self.profiler.as_ref().map(profile::Profiler::folded_stacks)
+	}
+
+	/// Counts the statements, calls and strings of the script, to be read with `statistics`
+	pub fn enable_statistics(&mut self) {
+		self.statistics = Some(Statistics::default());
+	}
+
+	/// Counters of the execution since statistics were enabled
+	pub fn statistics(&self) -> Option<Statistics> {
+		self.statistics
+	}
+
+	/// Scopes as they were when the first runtime error was reported, if
+	/// `set_snapshot_on_error` was enabled
+	pub fn take_error_snapshot(&mut self) -> Option<Vec<ScopeSnapshot>> {
+		self.error_snapshot.take()
+	}
+
+	/// Variables of every scope, from the global scope to the current one. Native functions
+	/// and constants are left out, and so are the variables of the callers hidden by a
+	/// function call.
+	pub fn environment_snapshot(&self) -> Vec<ScopeSnapshot> {
+		// the values of a variable are stacked in the order of the scopes defining it
+		let mut depths: HashMap<Symbol, usize> = HashMap::new();
+		self.environment
+			.scopes
+			.iter()
+			.enumerate()
+			.map(|(index, scope)| {
+				let variables = scope
+					.symbols
+					.iter()
+					.filter_map(|symbol| {
+						let depth = depths.entry(*symbol).or_insert(0);
+						let value = self.environment.bindings.get(symbol)?.get(*depth)?;
+						*depth += 1;
+						if index == 0 && Environment::is_constant(*symbol) {
+							return None;
+						}
+						Some((symbol.name(), value.clone()))
+					})
+					.filter(|(_, value)| !matches!(value, Value::NativeFunction { .. }))
+					.collect();
+				let kind = if index == 0 {
+					ScopeKind::Global
+				} else if scope.call {
+					ScopeKind::Function
+				} else {
+					ScopeKind::Block
+				};
+				ScopeSnapshot { kind, variables }
+			})
+			.collect()
+	}
+
+	fn save_error_snapshot(&self) {
+		if self.snapshot_on_error && self.error_snapshot.borrow().is_none() {
+			*self.error_snapshot.borrow_mut() = Some(self.environment_snapshot());
+		}
+	}
+
+	/// Values of the variables that can be accessed from the current scope, native functions
+	/// included
+	pub fn variables(&self) -> Vec<(String, Value)> {
+		self.environment
+			.bindings
+			.iter()
+			.filter_map(|(name, values)| Some((name.name(), values.last()?.clone())))
+			.collect()
+	}
+
+	/// Runs source code in the current scope, as if it was written where the execution
+	/// currently is
+	pub fn evaluate_source(&mut self, source: String) -> Result<String, ()> {
+		let tokens = Lexer::new(source).scan_tokens()?;
+		let statements = Parser::new(tokens).parse()?;
+		self.interpret(statements)
+	}
+
+	fn notify_debugger(&mut self, statement: &Statement) {
+		if let Some(mut debugger) = self.debugger.take() {
+			let depth = self
+				.environment
+				.scopes
+				.iter()
+				.filter(|scope| scope.function)
+				.count();
+			debugger.on_statement(self, statement.first_token().line(), depth);
+			self.debugger = Some(debugger);
+		}
+	}
+
+	/// Runs the statements and returns the displayed value of the last one
+	pub fn interpret(&mut self, statements: Vec<Statement>) -> Result<String, ()> {
+		Ok(self.interpret_value(statements)?.to_string())
+	}
+
+	/// Runs the statements and returns the value of the last one
+	pub fn interpret_value(&mut self, statements: Vec<Statement>) -> Result<Value, ()> {
+		let scopes_count = self.environment.scopes.len();
+		let deferred_count = self.deferred.len();
+		self.stopping = false;
+		self.deferred.push(vec![]);
+		let mut result = Ok(Value::String(String::new()));
+		for statement in &statements {
+			result = self.execute(statement);
+			if result.is_err() {
+				break;
+			}
+		}
+
+		// an error can happen while inside nested scopes or function calls, which must be left
+		// so that the interpreter can be reused (e.g. in the REPL)
+		while self.environment.scopes.len() > scopes_count {
+			self.environment.leave_scope();
+		}
+		let mut deferred_result = Ok(());
+		while self.deferred.len() > deferred_count {
+			deferred_result = deferred_result.and(self.run_deferred());
+		}
+		match (result, deferred_result) {
+			(Ok(value), Ok(())) => Ok(value),
+			_ => Err(()),
+		}
+	}
+
+	/// Runs the statements deferred by the function or script that exits, the last one first.
+	/// They all run even if one fails.
+	fn run_deferred(&mut self) -> Result<(), ErrorOrReturn> {
+		let mut result = Ok(());
+		for statement in self.deferred.pop().unwrap_or_default().iter().rev() {
+			if let Err(error) = self.execute(statement) {
+				result = Err(error);
+			}
+		}
+		result
+	}
+
+	fn report_runtime_error(&self, token: &Token, message: String) -> Result<Value, ErrorOrReturn> {
+		self.save_error_snapshot();
+		report_error(ErrorDetails::new(
+			ErrorType::RuntimeError,
+			message,
+			token.line(),
+			token.column(),
+		));
+		Err(ErrorOrReturn::Error)
+	}
+
+	fn report_type_error(&self, token: &Token, message: String) -> Result<Value, ErrorOrReturn> {
+		self.save_error_snapshot();
+		report_error(ErrorDetails::new(
+			ErrorType::TypeError,
+			message,
+			token.line(),
+			token.column(),
+		));
+		Err(ErrorOrReturn::Error)
+	}
+
+	/// Uses one unit of fuel, or reports an error if there is none left
+	fn consume_fuel(&mut self, token: &Token) -> Result<(), ErrorOrReturn> {
+		match self.fuel {
+			Some(0) => {
+				self.stopping = true;
+				self.report_runtime_error(token, "Execution limit reached".to_string())?;
+			}
+			Some(ref mut fuel) => *fuel -= 1,
+			None => {}
+		}
+		Ok(())
+	}
+
+	/// Stops the script if it was interrupted, after calling its interrupt handler
+	fn check_interrupt(&mut self, token: &Token) -> Result<(), ErrorOrReturn> {
+		let interrupted = if self.in_task {
+			self.interrupted.load(Ordering::Relaxed)
+		} else {
+			self.interrupted.swap(false, Ordering::Relaxed)
+		};
+		if interrupted {
+			// taken so that an interrupted handler is not called again
+			if let Some(handler) = self.interrupt_handler.take() {
+				self.call(handler, token, Arguments::new())?;
+			}
+			self.stopping = true;
+			self.report_runtime_error(token, "The script was interrupted".to_string())?;
+		}
+		Ok(())
+	}
+
+	/// Reports an error if a string of this length cannot be built. `None` means that the length
+	/// does not even fit in memory.
+	fn check_string_length(
+		&self,
+		token: &Token,
+		length: Option<usize>,
+	) -> Result<(), ErrorOrReturn> {
+		match (length, self.max_string_length) {
+			(Some(length), Some(max_length)) if length <= max_length => Ok(()),
+			(Some(_), None) => Ok(()),
+			_ => {
+				self.report_runtime_error(token, "String is too long".to_string())?;
+				Ok(())
+			}
+		}
+	}
+
+	/// Counts a statement for the profiler and the statistics, when they are enabled
+	fn record_statement(&mut self) {
+		if let Some(profiler) = self.profiler.as_mut() {
+			profiler.sample();
+		}
+		if let Some(statistics) = self.statistics.as_mut() {
+			statistics.statements += 1;
+			statistics.max_scope_depth = statistics
+				.max_scope_depth
+				.max(self.environment.scopes.len());
+		}
+	}
+
+	fn record_string(&mut self) {
+		if let Some(statistics) = self.statistics.as_mut() {
+			statistics.strings += 1;
+		}
+	}
+
+	fn execute(&mut self, statement: &Statement) -> Result<Value, ErrorOrReturn> {
+		if !matches!(statement, Statement::Block { .. }) {
+			self.consume_fuel(statement.first_token())?;
+			self.check_interrupt(statement.first_token())?;
+			if self.debugger.is_some() {
+				self.notify_debugger(statement);
+			}
+			self.record_statement();
+		}
+
+		match statement {
+			Statement::Expr { expr } => self.evaluate(expr),
+			Statement::Assignment { ident, value } => self.execute_assignment(ident, value),
+			Statement::MultipleAssignment { idents, values } => {
+				self.execute_multiple_assignment(idents, values)
+			}
+			Statement::Increment { ident, operator } => self.execute_increment(ident, operator),
+			Statement::IndexAssignment {
+				object,
+				bracket,
+				index,
+				value,
+			} => self.execute_index_assignment(object, bracket, index, value),
+			Statement::Block { statements } => self.execute_block(statements, true),
+			Statement::If {
+				condition,
+				then,
+				otherwise,
+			} => self.execute_if(condition, then, otherwise.as_deref()),
+			Statement::While {
+				label,
+				condition,
+				body,
+			} => self.execute_while(label.as_ref(), condition, body),
+			Statement::Repeat {
+				label,
+				keyword,
+				body,
+				condition,
+			} => self.execute_repeat(label.as_ref(), keyword, body, condition),
+			Statement::For {
+				label,
+				variable,
+				iterable,
+				body,
+			} => self.execute_for(label.as_ref(), variable, iterable, body),
+			Statement::FunctionDeclaration {
+				ident,
+				params,
+				variadic,
+				body,
+			} => self.execute_function_declaration(ident, params, *variadic, body),
+			Statement::Return { expr } => self.execute_return(expr),
+			Statement::Fail { keyword, message } => self.execute_fail(keyword, message),
+			Statement::Assert {
+				keyword,
+				condition,
+				message,
+			} => self.execute_assert(keyword, condition, message.as_ref()),
+			Statement::Break { label, .. } => {
+				Err(ErrorOrReturn::Break(label.as_ref().map(Token::symbol)))
+			}
+			Statement::Continue { label, .. } => {
+				Err(ErrorOrReturn::Continue(label.as_ref().map(Token::symbol)))
+			}
+			Statement::Defer { body, .. } => {
+				if let Some(deferred) = self.deferred.last_mut() {
+					deferred.push((**body).clone());
+				}
+				Ok(Value::Empty)
+			}
+			Statement::Attempt {
+				body,
+				variable,
+				handler,
+				..
+			} => self.execute_attempt(body, variable, handler),
+			Statement::Import {
+				keyword,
+				path,
+				alias,
+			} => self.execute_import(keyword, path, alias.as_ref()),
+			Statement::Match { value, arms, .. } => self.execute_match(value, arms),
+			Statement::Enum { ident, variants } => self.execute_enum_declaration(ident, variants),
+		}
+	}
+
+	/// Runs the imported file, unless it was already imported, and defines its top-level
+	/// variables in the script, or a module holding them when it has an alias
+	fn execute_import(
+		&mut self,
+		keyword: &Token,
+		path: &Token,
+		alias: Option<&Token>,
+	) -> Result<Value, ErrorOrReturn> {
+		if self.sandbox {
+			return self.report_runtime_error(
+				keyword,
+				"`import` is not available in sandbox mode".to_string(),
+			);
+		}
+		let TokenType::StringLiteral(path) = path.token_type() else {
+			unreachable!("the path of an import should be a string");
+		};
+		let importer = current_file();
+		let path = resolve_import(importer.as_deref().unwrap_or_default(), &path);
+		if importer.as_ref() == Some(&path) || self.importing.contains(&path) {
+			return self.report_runtime_error(
+				keyword,
+				format!("`{path}` cannot be imported while it is being imported (import cycle)"),
+			);
+		}
+
+		let globals = match self.modules.get(&path) {
+			Some(globals) => globals.clone(),
+			None => {
+				let globals = Rc::new(self.run_module(keyword, importer, &path)?);
+				self.modules.insert(path.clone(), globals.clone());
+				globals
+			}
+		};
+		let globals = match alias {
+			Some(alias) => vec![(
+				alias.symbol(),
+				Value::Module {
+					path,
+					variables: globals,
+				},
+			)],
+			None => globals.to_vec(),
+		};
+		for (name, value) in globals {
+			if let Err(EnvError::InvalidType(current)) =
+				self.environment
+					.define_or_assign(name, value.clone(), false)
+			{
+				return self.report_type_error(
+					keyword,
+					format!(
+						"Imported variable `{}` has type `{}`, but it is already defined with type `{}`",
+						name.name(),
+						value.get_type(),
+						current.get_type()
+					),
+				);
+			}
+		}
+		Ok(Value::Empty)
+	}
+
+	/// Runs an imported file in an environment of its own, and returns its top-level variables.
+	/// Its errors are reported in its file.
+	fn run_module(
+		&mut self,
+		keyword: &Token,
+		importer: Option<String>,
+		path: &str,
+	) -> Result<Vec<(Symbol, Value)>, ErrorOrReturn> {
+		let Some(source) = self.host.read_file(path) else {
+			return Err(self
+				.report_runtime_error(keyword, format!("Cannot read the imported file `{path}`"))
+				.unwrap_err());
+		};
+
+		set_current_file(Some(path.to_string()));
+		self.importing.push(importer.clone().unwrap_or_default());
+		let natives = self.environment.natives();
+		let environment = core::mem::replace(&mut self.environment, natives);
+		let result = Lexer::new(source)
+			.scan_tokens()
+			.and_then(|tokens| Parser::new(tokens).parse())
+			.and_then(|statements| self.interpret_value(statements));
+		let module = core::mem::replace(&mut self.environment, environment);
+		self.importing.pop();
+		set_current_file(importer);
+
+		match result {
+			Ok(_) => Ok(module.globals()),
+			Err(()) => Err(ErrorOrReturn::Error),
+		}
+	}
+
+	/// Reports the message as a runtime error, at the `fail` keyword
+	fn execute_fail(&mut self, keyword: &Token, message: &Expr) -> Result<Value, ErrorOrReturn> {
+		let message = self.evaluate(message)?;
+		self.report_runtime_error(keyword, message.to_string())
+	}
+
+	fn execute_assert(
+		&mut self,
+		keyword: &Token,
+		condition: &Expr,
+		message: Option<&Expr>,
+	) -> Result<Value, ErrorOrReturn> {
+		if self.evaluate(condition)? == Value::Boolean(true) {
+			return Ok(Value::Empty);
+		}
+		let message = match message {
+			Some(message) => format!("Assertion failed: {}", self.evaluate(message)?),
+			None => String::from("Assertion failed"),
+		};
+		self.report_runtime_error(keyword, message)
+	}
 
-					Value::String(String::new())
-				}
-				_ => {
-					println!("error: `cmd` can only take a string as argument");
-					Value::String(String::new())
-				}
-			}
-		});
+	/// Runs the body, and the handler if the body fails, with the error as a string
+	/// (`Runtime error at line 2, column 5: ...`). The error is not displayed when it is rescued.
+	fn execute_attempt(
+		&mut self,
+		body: &Statement,
+		variable: &Token,
+		handler: &Statement,
+	) -> Result<Value, ErrorOrReturn> {
+		let scopes_count = self.environment.scopes.len();
+		let (result, mut errors) = capture_errors(|| self.execute(body));
+		let rescued = match result {
+			Err(ErrorOrReturn::Error) if !self.stopping => errors.pop(),
+			_ => None,
+		};
+		for error in errors {
+			report_error(error);
+		}
+		let Some(error) = rescued else {
+			return result;
+		};
 
-		Self { environment: env }
+		// the blocks that failed did not close their scopes
+		while self.environment.scopes.len() > scopes_count {
+			self.environment.leave_scope();
+		}
+		self.environment
+			.enter_scope(self.environment.scopes.last().unwrap().function);
+		self.environment
+			.define_or_assign(variable.symbol(), Value::String(error.to_string()), true)
+			.unwrap();
+		let result = self.execute(handler);
+		self.environment.leave_scope();
+		result
 	}
 
-	pub fn interpret(&mut self, statements: Vec<Statement>) -> Result<String, ()> {
-		let mut result = String::new();
-		for statement in statements {
-			result = format!("{}", self.execute(statement).or(Err(()))?);
+	fn execute_match(
+		&mut self,
+		value: &Expr,
+		arms: &[MatchArm<Statement>],
+	) -> Result<Value, ErrorOrReturn> {
+		let value = self.evaluate(value)?;
+		match self.matching_arm(&value, arms)? {
+			Some(arm) => self.execute(&arm.body),
+			None => Ok(Value::Empty),
 		}
-		Ok(result)
 	}
 
-	fn report_runtime_error(&self, token: &Token, message: String) -> Result<Value, ErrorOrReturn> {
-		report_error(ErrorDetails::new(
-			ErrorType::RuntimeError,
-			message,
-			token.line(),
-			token.column(),
-		));
-		Err(ErrorOrReturn::Error)
+	/// First arm with a pattern matching the value, and whose guard is `true`
+	fn matching_arm<'a, T>(
+		&mut self,
+		value: &Value,
+		arms: &'a [MatchArm<T>],
+	) -> Result<Option<&'a MatchArm<T>>, ErrorOrReturn> {
+		for arm in arms {
+			for pattern in &arm.patterns {
+				let matches = match pattern {
+					Pattern::Default(_) => true,
+					Pattern::Type(token) => match token.token_type() {
+						TokenType::Boolean => matches!(value, Value::Boolean(_)),
+						TokenType::Number => matches!(value, Value::Number(_)),
+						TokenType::String => matches!(value, Value::String(_)),
+						_ => matches!(
+							value,
+							Value::EmptyBoolean
+								| Value::EmptyNumber | Value::EmptyString
+								| Value::Empty
+						),
+					},
+					Pattern::Value(expr) => self.evaluate(expr)?.equals(value),
+				};
+				if matches {
+					if self.passes_guard(arm.guard.as_ref())? {
+						return Ok(Some(arm));
+					}
+					break;
+				}
+			}
+		}
+		Ok(None)
 	}
 
-	fn report_type_error(&self, token: &Token, message: String) -> Result<Value, ErrorOrReturn> {
-		report_error(ErrorDetails::new(
-			ErrorType::TypeError,
-			message,
-			token.line(),
-			token.column(),
-		));
-		Err(ErrorOrReturn::Error)
+	fn passes_guard(&mut self, guard: Option<&Expr>) -> Result<bool, ErrorOrReturn> {
+		match guard {
+			Some(guard) => Ok(self.evaluate(guard)? == Value::Boolean(true)),
+			None => Ok(true),
+		}
 	}
 
-	fn execute(&mut self, statement: Statement) -> Result<Value, ErrorOrReturn> {
-		match statement {
-			Statement::Expr { expr } => self.evaluate(expr),
-			Statement::Assignment { ident, value } => self.execute_assignment(ident, value),
-			Statement::Block { statements } => self.execute_block(statements, true),
-			Statement::If {
-				condition,
-				then,
-				otherwise,
-			} => self.execute_if(condition, *then, otherwise),
-			Statement::While { condition, body } => self.execute_while(condition, *body),
-			Statement::FunctionDeclaration {
+	fn execute_enum_declaration(
+		&mut self,
+		ident: &Token,
+		variants: &[Token],
+	) -> Result<Value, ErrorOrReturn> {
+		let enumeration = Value::Enum {
+			name: ident.lexeme().into(),
+			variants: variants.iter().map(Token::symbol).collect(),
+		};
+
+		match self
+			.environment
+			.define_or_assign(ident.symbol(), enumeration, false)
+		{
+			Ok(()) => Ok(Value::Empty),
+			Err(_) => self.report_type_error(
 				ident,
-				params,
-				body,
-			} => self.execute_function_declaration(ident, params, body),
-			Statement::Return { expr } => self.execute_return(expr),
+				format!("Identifier `{}` has already been declared", ident.lexeme()),
+			),
 		}
 	}
 
-	fn execute_assignment(&mut self, ident: Token, value: Expr) -> Result<Value, ErrorOrReturn> {
+	fn execute_assignment(&mut self, ident: &Token, value: &Expr) -> Result<Value, ErrorOrReturn> {
+		if let Some(result) = self.execute_string_append(ident, value) {
+			return result;
+		}
+
 		let value = self.evaluate(value)?;
+		self.assign(ident, value)
+	}
 
-		if let Err(error) =
-			self.environment
-				.define_or_assign(ident.lexeme().into(), value.clone(), false)
+	/// Evaluates all the values before assigning them, so that `a, b = b, a` swaps the variables
+	fn execute_multiple_assignment(
+		&mut self,
+		idents: &[Token],
+		values: &[Expr],
+	) -> Result<Value, ErrorOrReturn> {
+		let mut evaluated = Vec::with_capacity(values.len());
+		for value in values {
+			evaluated.push(self.evaluate(value)?);
+		}
+		for (ident, value) in idents.iter().zip(evaluated) {
+			self.assign(ident, value)?;
+		}
+		Ok(Value::Empty)
+	}
+
+	fn assign(&mut self, ident: &Token, value: Value) -> Result<Value, ErrorOrReturn> {
+		if let Err(error) = self
+			.environment
+			.define_or_assign(ident.symbol(), value.clone(), false)
 		{
 			match error {
 				EnvError::EmptyDeclarationNoType => self.report_runtime_error(
-					&ident,
+					ident,
 					format!(
 						"Variable `{}` cannot be initialized as `empty`, type must be specified",
 						ident.lexeme()
 					),
 				),
 				EnvError::InvalidType(current_value) => self.report_type_error(
-					&ident,
+					ident,
 					format!(
 						"Variable `{}` already exists, but has a different type (tried to replace `{}` with `{}`)",
 						ident.lexeme(),
@@ -421,9 +2892,95 @@ impl Interpreter {
 		}
 	}
 
+	fn execute_increment(
+		&mut self,
+		ident: &Token,
+		operator: &Token,
+	) -> Result<Value, ErrorOrReturn> {
+		let step = if operator.token_type() == TokenType::PlusPlus {
+			1.0
+		} else {
+			-1.0
+		};
+		let value = match self.environment.get_mut(ident.symbol()) {
+			Some(Value::Number(number)) => {
+				*number += step;
+				return Ok(Value::Empty);
+			}
+			Some(value) => value.inspect(),
+			None => {
+				return self
+					.report_runtime_error(ident, format!("Undefined symbol `{}`", ident.lexeme()))
+			}
+		};
+		let name = if step > 0.0 { "Increment" } else { "Decrement" };
+		self.report_type_error(
+			operator,
+			format!(
+				"{name} (`{}`) must be applied to a number variable (found {value})",
+				operator.lexeme()
+			),
+		)
+	}
+
+	/// Assignments like `text = text + "abc" + other` on a string variable append to the string in
+	/// place, instead of copying it for each addition and again for the assignment, so that
+	/// building a string in a loop stays linear. Returns `None` when the assignment does not have
+	/// this shape, or when an operand is not a string so that the error is reported as usual.
+	fn execute_string_append(
+		&mut self,
+		ident: &Token,
+		value: &Expr,
+	) -> Option<Result<Value, ErrorOrReturn>> {
+		let mut appended = vec![];
+		let mut expr = value;
+		while let Expr::Binary {
+			left_expr,
+			operator,
+			right_expr,
+		} = expr
+		{
+			// a call could modify the variable before the right side is computed, while the
+			// addition must use its previous value
+			if operator.token_type() != TokenType::Plus || right_expr.contains_call() {
+				return None;
+			}
+			appended.push((operator, right_expr.as_ref()));
+			expr = left_expr;
+		}
+		match expr {
+			Expr::Variable { name } if !appended.is_empty() && name.symbol() == ident.symbol() => {}
+			_ => return None,
+		}
+		let mut length = match self.environment.get_mut(ident.symbol()) {
+			Some(Value::String(string)) => string.len(),
+			_ => return None,
+		};
+
+		let mut strings = vec![];
+		for (operator, expr) in appended.into_iter().rev() {
+			match self.evaluate(expr) {
+				Ok(Value::String(string)) => {
+					let new_length = length.checked_add(string.len());
+					if let Err(error) = self.check_string_length(operator, new_length) {
+						return Some(Err(error));
+					}
+					length += string.len();
+					strings.push(string);
+				}
+				Ok(_) => return None,
+				Err(error) => return Some(Err(error)),
+			}
+		}
+		if let Some(Value::String(string)) = self.environment.get_mut(ident.symbol()) {
+			string.extend(strings);
+		}
+		Some(Ok(Value::Empty))
+	}
+
 	fn execute_block(
 		&mut self,
-		statements: Vec<Statement>,
+		statements: &[Statement],
 		create_scope: bool,
 	) -> Result<Value, ErrorOrReturn> {
 		if create_scope {
@@ -434,6 +2991,10 @@ impl Interpreter {
 		let mut result: Option<Value> = None;
 		for statement in statements {
 			if let Statement::Return { expr } = statement {
+				if self.debugger.is_some() {
+					self.notify_debugger(statement);
+				}
+				self.record_statement();
 				// can be only Ok(value) or Err(ErrorOrReturn::Error)
 				let statement_result = self.execute_return(expr);
 				if let Ok(value) = statement_result {
@@ -459,17 +3020,17 @@ impl Interpreter {
 
 	fn execute_if(
 		&mut self,
-		condition: Expr,
-		then: Statement,
-		otherwise: Option<Box<Statement>>,
+		condition: &Expr,
+		then: &Statement,
+		otherwise: Option<&Statement>,
 	) -> Result<Value, ErrorOrReturn> {
-		let condition_value = self.evaluate(condition.clone())?;
+		let condition_value = self.evaluate(condition)?;
 
 		if condition_value == Value::Boolean(true) {
 			self.execute(then)?;
 		} else if condition_value == Value::Boolean(false) {
 			if let Some(else_branch) = otherwise {
-				self.execute(*else_branch)?;
+				self.execute(else_branch)?;
 			}
 		} else {
 			self.report_type_error(
@@ -481,33 +3042,198 @@ impl Interpreter {
 		Ok(Value::String(String::from("")))
 	}
 
-	fn execute_while(&mut self, condition: Expr, body: Statement) -> Result<Value, ErrorOrReturn> {
-		while self.evaluate(condition.clone())? == Value::Boolean(true) {
-			self.execute(body.clone())?;
+	fn execute_while(
+		&mut self,
+		label: Option<&Token>,
+		condition: &Expr,
+		body: &Statement,
+	) -> Result<Value, ErrorOrReturn> {
+		let label = label.map(Token::symbol);
+		while self.evaluate(condition)? == Value::Boolean(true) {
+			self.consume_fuel(condition.first_token())?;
+			self.check_interrupt(condition.first_token())?;
+			if self.execute_loop_body(label, body)? {
+				break;
+			}
+		}
+
+		Ok(Value::String(String::from("")))
+	}
+
+	/// Runs the body until the condition is `true`, or is not a boolean
+	fn execute_repeat(
+		&mut self,
+		label: Option<&Token>,
+		keyword: &Token,
+		body: &Statement,
+		condition: &Expr,
+	) -> Result<Value, ErrorOrReturn> {
+		let label = label.map(Token::symbol);
+		loop {
+			self.consume_fuel(keyword)?;
+			self.check_interrupt(keyword)?;
+			if self.execute_loop_body(label, body)?
+				|| self.evaluate(condition)? != Value::Boolean(false)
+			{
+				break;
+			}
+		}
+
+		Ok(Value::Empty)
+	}
+
+	fn execute_for(
+		&mut self,
+		label: Option<&Token>,
+		variable: &Token,
+		iterable: &Iterable,
+		body: &Statement,
+	) -> Result<Value, ErrorOrReturn> {
+		let label = label.map(Token::symbol);
+		match iterable {
+			Iterable::Value(expr) => {
+				let string = match self.evaluate(expr)? {
+					Value::String(string) => string,
+					value => {
+						return self.report_type_error(
+							expr.first_token(),
+							format!(
+								"Only strings can be iterated over with `for` (found `{value}`)"
+							),
+						)
+					}
+				};
+				for character in string.chars() {
+					let value = Value::String(character.to_string());
+					if self.execute_for_iteration(label, variable, value, body)? {
+						break;
+					}
+				}
+			}
+			Iterable::Range {
+				start,
+				operator,
+				end,
+				step,
+			} => {
+				let start = self.evaluate_range_bound(start)?;
+				let end = self.evaluate_range_bound(end)?;
+				let step = match step {
+					Some(step) => self.evaluate_range_bound(step)?,
+					None if start <= end => 1.0,
+					None => -1.0,
+				};
+				if step == 0.0 || !step.is_finite() {
+					return self.report_runtime_error(
+						operator,
+						format!("The step of a range must be a finite number other than 0 (found `{step}`)"),
+					);
+				}
+
+				// computing each number from the start avoids accumulating rounding errors
+				let mut index = 0.0;
+				loop {
+					let number = start + index * step;
+					let in_range = if step > 0.0 {
+						number <= end
+					} else {
+						number >= end
+					};
+					if !in_range
+						|| self.execute_for_iteration(
+							label,
+							variable,
+							Value::Number(number),
+							body,
+						)? {
+						break;
+					}
+					index += 1.0;
+				}
+			}
 		}
 
 		Ok(Value::String(String::from("")))
 	}
 
+	fn evaluate_range_bound(&mut self, expr: &Expr) -> Result<f64, ErrorOrReturn> {
+		match self.evaluate(expr)? {
+			Value::Number(number) => Ok(number),
+			value => Err(self
+				.report_type_error(
+					expr.first_token(),
+					format!("The bounds and the step of a range must be numbers (found `{value}`)"),
+				)
+				.unwrap_err()),
+		}
+	}
+
+	/// Runs the body of a `for` loop with its variable set to the value, and returns whether a
+	/// `break` stopped the loop
+	fn execute_for_iteration(
+		&mut self,
+		label: Option<Symbol>,
+		variable: &Token,
+		value: Value,
+		body: &Statement,
+	) -> Result<bool, ErrorOrReturn> {
+		self.consume_fuel(variable)?;
+		self.check_interrupt(variable)?;
+		// each iteration has its own variable
+		self.environment
+			.enter_scope(self.environment.scopes.last().unwrap().function);
+		self.environment
+			.define_or_assign(variable.symbol(), value, true)
+			.unwrap();
+		let stop = self.execute_loop_body(label, body)?;
+		self.environment.leave_scope();
+		Ok(stop)
+	}
+
+	/// Runs the body of the loop with the given label, and returns whether a `break` stopped the
+	/// loop
+	fn execute_loop_body(
+		&mut self,
+		label: Option<Symbol>,
+		body: &Statement,
+	) -> Result<bool, ErrorOrReturn> {
+		let scopes_count = self.environment.scopes.len();
+		let stop = match self.execute(body) {
+			Err(ErrorOrReturn::Break(target)) if target.is_none() || target == label => true,
+			Err(ErrorOrReturn::Continue(target)) if target.is_none() || target == label => false,
+			result => return result.map(|_| false),
+		};
+
+		// the blocks that were left early did not close their scopes
+		while self.environment.scopes.len() > scopes_count {
+			self.environment.leave_scope();
+		}
+		Ok(stop)
+	}
+
 	fn execute_function_declaration(
 		&mut self,
-		ident: Token,
-		params: Vec<Token>,
-		body: Vec<Statement>,
+		ident: &Token,
+		params: &[Token],
+		variadic: bool,
+		body: &[Statement],
 	) -> Result<Value, ErrorOrReturn> {
 		let function = Value::Function {
 			name: ident.lexeme().into(),
-			args: params.iter().map(|t| t.lexeme().into()).collect(),
-			body,
+			args: params.iter().map(Token::symbol).collect(),
+			variadic,
+			body: body.into(),
+			captured: Rc::new(self.environment.locals()),
+			declared: true,
 		};
 
-		if let Err(error) =
-			self.environment
-				.define_or_assign(ident.lexeme().into(), function, false)
+		if let Err(error) = self
+			.environment
+			.define_or_assign(ident.symbol(), function, false)
 		{
 			match error {
 				EnvError::InvalidType(_) => self.report_type_error(
-					&ident,
+					ident,
 					format!("Identifier `{}` has already been declared", ident.lexeme(),),
 				),
 				_ => unreachable!("No other error should happen"),
@@ -517,7 +3243,7 @@ impl Interpreter {
 		}
 	}
 
-	fn execute_return(&mut self, expr: Expr) -> Result<Value, ErrorOrReturn> {
+	fn execute_return(&mut self, expr: &Expr) -> Result<Value, ErrorOrReturn> {
 		// if one of the parent scope is a function scope, then `return` is allowed
 		let mut in_function = false;
 		for scope in self.environment.scopes.iter().rev() {
@@ -537,67 +3263,375 @@ impl Interpreter {
 		}
 	}
 
-	fn evaluate(&mut self, expr: Expr) -> Result<Value, ErrorOrReturn> {
+	fn evaluate(&mut self, expr: &Expr) -> Result<Value, ErrorOrReturn> {
 		match expr {
 			Expr::Primary { value } => self.evaluate_primary(value),
-			Expr::Unary { operator, expr } => self.evaluate_unary(operator, *expr),
+			Expr::Unary { operator, expr } => self.evaluate_unary(operator, expr),
+			// `?` is the only postfix operator
+			Expr::Postfix { expr, .. } => Ok(Value::Boolean(matches!(
+				self.evaluate(expr)?,
+				Value::EmptyBoolean | Value::EmptyNumber | Value::EmptyString | Value::Empty
+			))),
 			Expr::Binary {
 				left_expr,
 				operator,
 				right_expr,
-			} => self.evaluate_binary(*left_expr, operator, *right_expr),
-			Expr::Grouping { expr } => self.evaluate(*expr),
+			} => self.evaluate_binary(left_expr, operator, right_expr),
+			Expr::Grouping { expr } => self.evaluate(expr),
+			Expr::Conversion { target, expr, .. } => self.evaluate_conversion(target, expr),
 			Expr::Variable { name } => self.evaluate_variable(name),
 			Expr::Call {
 				callee,
 				closing_paren,
 				arguments,
-			} => self.evaluate_call(*callee, closing_paren, arguments),
+			} => self.evaluate_call(callee, closing_paren, arguments),
+			Expr::SafeCall {
+				receiver,
+				name,
+				closing_paren,
+				arguments,
+			} => self.evaluate_safe_call(receiver, name, closing_paren, arguments),
+			Expr::Match {
+				keyword,
+				value,
+				arms,
+			} => self.evaluate_match(keyword, value, arms),
+			Expr::Field { object, name } => self.evaluate_field(object, name),
+			Expr::List { elements, .. } => self.evaluate_list(elements),
+			Expr::Lambda { params, body, .. } => Ok(self.evaluate_lambda(params, body)),
+			Expr::Index {
+				object,
+				bracket,
+				index,
+			} => self.evaluate_index(object, bracket, index),
+		}
+	}
+
+	/// Function named `lambda`, whose body returns the value of the expression. It keeps a copy
+	/// of the variables of the function creating it, to still use them once the function returned.
+	fn evaluate_lambda(&self, params: &[Token], body: &Expr) -> Value {
+		Value::Function {
+			name: String::from("lambda"),
+			args: params.iter().map(Token::symbol).collect(),
+			variadic: false,
+			body: Rc::from([Statement::Return { expr: body.clone() }]),
+			captured: Rc::new(self.environment.locals()),
+			declared: false,
+		}
+	}
+
+	fn evaluate_match(
+		&mut self,
+		keyword: &Token,
+		value: &Expr,
+		arms: &[MatchArm<Expr>],
+	) -> Result<Value, ErrorOrReturn> {
+		let value = self.evaluate(value)?;
+		match self.matching_arm(&value, arms)? {
+			Some(arm) => self.evaluate(&arm.body),
+			None => self.report_runtime_error(
+				keyword,
+				format!(
+					"No arm of the `match` matches {}, it needs a `default` arm",
+					value.inspect()
+				),
+			),
+		}
+	}
+
+	fn evaluate_index(
+		&mut self,
+		object: &Expr,
+		bracket: &Token,
+		index: &Expr,
+	) -> Result<Value, ErrorOrReturn> {
+		let safe = bracket.token_type() == TokenType::InterrogationBracket;
+		let object_value = self.evaluate(object)?;
+		if safe
+			&& matches!(
+				object_value,
+				Value::EmptyBoolean | Value::EmptyNumber | Value::EmptyString | Value::Empty
+			) {
+			return Ok(Value::Empty);
+		}
+		let index_value = self.evaluate(index)?;
+
+		match object_value {
+			Value::List(elements) => {
+				match position(&index_value, elements.len(), "list", "element") {
+					Ok(position) => Ok(elements[position].clone()),
+					Err(_) if safe => Ok(Value::Empty),
+					Err(message) => self.report_runtime_error(index.first_token(), message),
+				}
+			}
+			Value::String(string) => {
+				let length = string.chars().count();
+				match position(&index_value, length, "string", "character") {
+					Ok(position) => Ok(Value::String(
+						string.chars().nth(position).unwrap_or_default().to_string(),
+					)),
+					Err(_) if safe => Ok(Value::Empty),
+					Err(message) => self.report_runtime_error(bracket, message),
+				}
+			}
+			_ => self.report_type_error(
+				bracket,
+				format!(
+					"Only lists and strings can be indexed with `[]` (found {})",
+					object_value.inspect()
+				),
+			),
+		}
+	}
+
+	/// Replaces an element of a list stored in a variable, or in the lists it contains. Lists
+	/// share their elements until they change, so the other copies of the list keep their value.
+	fn execute_index_assignment(
+		&mut self,
+		object: &Expr,
+		bracket: &Token,
+		index: &Expr,
+		value: &Expr,
+	) -> Result<Value, ErrorOrReturn> {
+		// the indexes from the list in the variable to the element, written from the last one
+		let mut path = vec![(bracket, index)];
+		let mut list = object;
+		while let Expr::Index {
+			object,
+			bracket,
+			index,
+		} = list
+		{
+			path.push((bracket, index));
+			list = object;
+		}
+		let Expr::Variable { name } = list else {
+			unreachable!("the parser only accepts assignments to lists in variables");
+		};
+
+		let mut indexes = Vec::with_capacity(path.len());
+		for (bracket, index) in path.into_iter().rev() {
+			indexes.push((bracket, index, self.evaluate(index)?));
+		}
+		let value = self.evaluate(value)?;
+
+		let Some(mut element) = self.environment.get_mut(name.symbol()) else {
+			return self
+				.report_runtime_error(name, format!("Undefined symbol `{}`", name.lexeme()));
+		};
+		for (bracket, index, index_value) in indexes {
+			let Value::List(elements) = element else {
+				let message = format!(
+					"Only the elements of lists can be assigned with `[]` (found {})",
+					element.inspect()
+				);
+				return self.report_type_error(bracket, message);
+			};
+			let elements = Rc::make_mut(elements);
+			match position(&index_value, elements.len(), "list", "element") {
+				Ok(position) => element = &mut elements[position],
+				Err(message) => return self.report_runtime_error(index.first_token(), message),
+			}
+		}
+		*element = value;
+		Ok(Value::Empty)
+	}
+
+	fn evaluate_list(&mut self, elements: &[Expr]) -> Result<Value, ErrorOrReturn> {
+		let mut values = Vec::with_capacity(elements.len());
+		for element in elements {
+			values.push(self.evaluate(element)?);
 		}
+		Ok(Value::List(Rc::new(values)))
 	}
 
-	fn evaluate_variable(&mut self, name: Token) -> Result<Value, ErrorOrReturn> {
-		if let Some(value) = self.environment.get(name.lexeme().into()) {
+	/// Variant of an enum (`Color.red`), or variable of a module (`math.clamp`)
+	fn evaluate_field(&mut self, object: &Expr, name: &Token) -> Result<Value, ErrorOrReturn> {
+		match self.evaluate(object)? {
+			Value::Enum {
+				name: enumeration,
+				variants,
+			} => {
+				if variants.contains(&name.symbol()) {
+					Ok(Value::Variant {
+						enumeration,
+						name: name.lexeme().into(),
+					})
+				} else {
+					self.report_type_error(
+						name,
+						format!("Enum `{enumeration}` has no variant `{}`", name.lexeme()),
+					)
+				}
+			}
+			Value::Module { path, variables } => {
+				match variables
+					.iter()
+					.find(|(variable, _)| *variable == name.symbol())
+				{
+					Some((_, value)) => Ok(value.clone()),
+					None => self.report_runtime_error(
+						name,
+						format!("Module `{path}` has no variable `{}`", name.lexeme()),
+					),
+				}
+			}
+			value => self.report_type_error(
+				name,
+				format!(
+					"Only the variants of an enum and the variables of a module can be read with `.` (found {})",
+					value.inspect()
+				),
+			),
+		}
+	}
+
+	fn evaluate_conversion(&mut self, target: &Token, expr: &Expr) -> Result<Value, ErrorOrReturn> {
+		let value = self.evaluate(expr)?;
+		if let Some(converted) = convert(&value, target.token_type()) {
+			return Ok(converted);
+		}
+
+		if self.strict {
+			return self.report_runtime_error(
+				target,
+				format!("Cannot convert `{value}` to a {}", target.lexeme()),
+			);
+		}
+		Ok(match target.token_type() {
+			TokenType::Boolean => Value::EmptyBoolean,
+			TokenType::Number => Value::EmptyNumber,
+			_ => Value::EmptyString,
+		})
+	}
+
+	fn evaluate_variable(&mut self, name: &Token) -> Result<Value, ErrorOrReturn> {
+		if let Some(value) = self.environment.get(name.symbol()) {
 			Ok(value)
 		} else {
-			self.report_runtime_error(&name, format!("Undefined symbol `{}`", name.lexeme()))
+			self.report_runtime_error(name, format!("Undefined symbol `{}`", name.lexeme()))
 		}
 	}
 
 	fn evaluate_call(
 		&mut self,
-		callee: Expr,
-		closing_paren: Token,
-		arguments: Vec<Expr>,
+		callee: &Expr,
+		closing_paren: &Token,
+		arguments: &[Expr],
 	) -> Result<Value, ErrorOrReturn> {
 		let callee_value = self.evaluate(callee)?;
-		let mut arguments_values: Vec<Value> = vec![];
+		let mut arguments_values = Arguments::new();
+		for argument in arguments {
+			arguments_values.push(self.evaluate(argument)?);
+		}
+		self.call(callee_value, closing_paren, arguments_values)
+	}
+
+	/// The function and the arguments are not evaluated when the receiver is empty, and the
+	/// result is the receiver itself
+	fn evaluate_safe_call(
+		&mut self,
+		receiver: &Expr,
+		name: &Token,
+		closing_paren: &Token,
+		arguments: &[Expr],
+	) -> Result<Value, ErrorOrReturn> {
+		let receiver_value = self.evaluate(receiver)?;
+		if matches!(
+			receiver_value,
+			Value::EmptyBoolean | Value::EmptyNumber | Value::EmptyString | Value::Empty
+		) {
+			return Ok(receiver_value);
+		}
+
+		let callee_value = self.evaluate_variable(name)?;
+		let mut arguments_values = Arguments::new();
+		arguments_values.push(receiver_value);
 		for argument in arguments {
 			arguments_values.push(self.evaluate(argument)?);
 		}
+		self.call(callee_value, closing_paren, arguments_values)
+	}
+
+	fn call(
+		&mut self,
+		callee_value: Value,
+		closing_paren: &Token,
+		arguments_values: Arguments,
+	) -> Result<Value, ErrorOrReturn> {
 		match callee_value {
-			Value::Function { ref args, .. } | Value::NativeFunction { ref args, .. } => {
-				let args_length = args.len();
+			Value::NativeFunction { ref name, .. }
+				if self.sandbox && SANDBOXED_NATIVES.contains(&name.as_str()) =>
+			{
+				self.report_runtime_error(
+					closing_paren,
+					format!("Native function `{name}` is not available in sandbox mode"),
+				)
+			}
+			Value::Function { .. } | Value::NativeFunction { .. } => {
 				let provided_args_length = arguments_values.len();
-				if args_length == provided_args_length {
-					callee_value.call(args, arguments_values, self)
-				} else {
+				if !callee_value.accepts_arguments(provided_args_length) {
+					self.report_type_error(
+						closing_paren,
+						callee_value.arguments_count_error(provided_args_length),
+					)
+				} else if let Some((position, param, argument)) =
+					mismatched_argument(&callee_value, &arguments_values)
+				{
+					let Value::NativeFunction { name, .. } = &callee_value else {
+						unreachable!("only the arguments of natives are checked");
+					};
 					self.report_type_error(
-						&closing_paren,
+						closing_paren,
+						format!(
+							"Argument {position} of `{name}` should be {param} (found {})",
+							argument.describe_argument()
+						),
+					)
+				} else if self.call_depth == self.max_call_depth {
+					self.report_runtime_error(
+						closing_paren,
 						format!(
-							"Expected {} argument{}, but found {}",
-							args_length,
-							if args_length == 1 { "" } else { "s" },
-							provided_args_length
+							"Function calls cannot be nested more than {} levels deep",
+							self.max_call_depth
 						),
 					)
+				} else {
+					if let (
+						Some(profiler),
+						Value::Function { name, .. } | Value::NativeFunction { name, .. },
+					) = (self.profiler.as_mut(), &callee_value)
+					{
+						profiler.enter(name);
+					}
+					let native = matches!(callee_value, Value::NativeFunction { .. });
+					if let Some(statistics) = self.statistics.as_mut() {
+						if native {
+							statistics.native_calls += 1;
+						} else {
+							statistics.function_calls += 1;
+						}
+					}
+					self.call_depth += 1;
+					let result = callee_value.call(arguments_values, closing_paren, self);
+					self.call_depth -= 1;
+					if let Some(profiler) = self.profiler.as_mut() {
+						profiler.leave();
+					}
+					if native && matches!(result, Ok(Value::String(_))) {
+						self.record_string();
+					}
+					result
 				}
 			}
-			_ => self.report_type_error(&closing_paren, "Expression not callable".to_string()),
+			_ => self.report_type_error(closing_paren, "Expression not callable".to_string()),
 		}
 	}
 
-	fn evaluate_primary(&self, value: Token) -> Result<Value, ErrorOrReturn> {
+	fn evaluate_primary(&mut self, value: &Token) -> Result<Value, ErrorOrReturn> {
+		if let TokenType::StringLiteral(_) = value.token_type() {
+			self.record_string();
+		}
 		match value.token_type() {
 			TokenType::True => Ok(Value::Boolean(true)),
 			TokenType::False => Ok(Value::Boolean(false)),
@@ -605,12 +3639,13 @@ impl Interpreter {
 			TokenType::StringLiteral(string) => Ok(Value::String(string)),
 			TokenType::Boolean => Ok(Value::EmptyBoolean),
 			TokenType::Number => Ok(Value::EmptyNumber),
+			TokenType::String => Ok(Value::EmptyString),
 			TokenType::Empty => Ok(Value::Empty),
 			_ => unreachable!("Value cannot be anything else"),
 		}
 	}
 
-	fn evaluate_unary(&mut self, operator: Token, expr: Expr) -> Result<Value, ErrorOrReturn> {
+	fn evaluate_unary(&mut self, operator: &Token, expr: &Expr) -> Result<Value, ErrorOrReturn> {
 		let value = self.evaluate(expr)?;
 		match operator.token_type() {
 			TokenType::Bang => {
@@ -618,7 +3653,7 @@ impl Interpreter {
 					Ok(Value::Boolean(!value))
 				} else {
 					self.report_type_error(
-						&operator,
+						operator,
 						format!("Unary not (`!`) must be applied to a boolean (found `{value}`)",),
 					)
 				}
@@ -628,7 +3663,7 @@ impl Interpreter {
 					Ok(Value::Number(-value))
 				} else {
 					self.report_type_error(
-						&operator,
+						operator,
 						format!("Unary minus (`-`) must be applied to a number (found `{value}`)",),
 					)
 				}
@@ -639,9 +3674,9 @@ impl Interpreter {
 
 	fn evaluate_binary(
 		&mut self,
-		left_expr: Expr,
-		operator: Token,
-		right_expr: Expr,
+		left_expr: &Expr,
+		operator: &Token,
+		right_expr: &Expr,
 	) -> Result<Value, ErrorOrReturn> {
 		let left_value = self.evaluate(left_expr)?;
 
@@ -672,8 +3707,8 @@ impl Interpreter {
 	fn evaluate_binary_logic(
 		&mut self,
 		left_value: Value,
-		operator: Token,
-		right_expr: Expr,
+		operator: &Token,
+		right_expr: &Expr,
 	) -> Result<Value, ErrorOrReturn> {
 		match operator.token_type() {
 			TokenType::And => {
@@ -700,40 +3735,10 @@ impl Interpreter {
 	fn evaluate_equality(
 		&self,
 		left_value: Value,
-		operator: Token,
+		operator: &Token,
 		right_value: Value,
 	) -> Result<Value, ErrorOrReturn> {
-		let equality = match left_value {
-			Value::Boolean(left_value) => match right_value {
-				Value::Boolean(right_value) => left_value == right_value,
-				_ => false,
-			},
-			Value::Number(left_value) => match right_value {
-				Value::Number(right_value) => left_value == right_value,
-				_ => false,
-			},
-			Value::String(left_value) => match right_value {
-				Value::String(right_value) => left_value == right_value,
-				_ => false,
-			},
-			Value::EmptyBoolean => {
-				right_value == Value::EmptyBoolean || right_value == Value::Empty
-			}
-			Value::EmptyNumber => right_value == Value::EmptyNumber || right_value == Value::Empty,
-			Value::Empty => {
-				right_value == Value::EmptyBoolean
-					|| right_value == Value::EmptyNumber
-					|| right_value == Value::Empty
-			}
-			Value::NativeFunction { name: lf, .. } | Value::Function { name: lf, .. } => {
-				match right_value {
-					Value::NativeFunction { name: rf, .. } | Value::Function { name: rf, .. } => {
-						lf == rf
-					}
-					_ => false,
-				}
-			}
-		};
+		let equality = left_value.equals(&right_value);
 
 		Ok(Value::Boolean(
 			if operator.token_type() == TokenType::EqualEqual {
@@ -747,12 +3752,12 @@ impl Interpreter {
 	fn evaluate_comparison(
 		&self,
 		left_value: Value,
-		operator: Token,
+		operator: &Token,
 		right_value: Value,
 	) -> Result<Value, ErrorOrReturn> {
 		let error = || {
 			self.report_type_error(
-				&operator,
+				operator,
 				format!(
 					"Comparison can only be performed between two numbers (found `{}` and `{}`)",
 					left_value, right_value
@@ -775,14 +3780,14 @@ impl Interpreter {
 	}
 
 	fn evaluate_math_operation(
-		&self,
+		&mut self,
 		left_value: Value,
-		operator: Token,
+		operator: &Token,
 		right_value: Value,
 	) -> Result<Value, ErrorOrReturn> {
 		let error = || {
 			self.report_type_error(
-				&operator,
+				operator,
 				format!(
 					"{} (`{}`) can only be performed between two numbers{} (found `{}` and `{}`)",
 					match operator.token_type() {
@@ -814,8 +3819,8 @@ impl Interpreter {
 					TokenType::Minus => left_value - right_value,
 					TokenType::Star => left_value * right_value,
 					TokenType::Slash => left_value / right_value,
-					TokenType::Percent => left_value.rem_euclid(right_value),
-					TokenType::Caret => left_value.powf(right_value),
+					TokenType::Percent => math::rem_euclid(left_value, right_value),
+					TokenType::Caret => math::pow(left_value, right_value),
 					_ => unreachable!("Operator cannot be anything else"),
 				})),
 				_ => error(),
@@ -823,19 +3828,24 @@ impl Interpreter {
 			Value::String(left_value) => match right_value {
 				Value::String(right_value) => {
 					if operator.token_type() == TokenType::Plus {
+						self.check_string_length(
+							operator,
+							left_value.len().checked_add(right_value.len()),
+						)?;
+						self.record_string();
 						Ok(Value::String(format!("{left_value}{right_value}")))
 					} else {
 						self.report_runtime_error(
-							&operator,
+							operator,
 							"Only addition (`+`) can be used between two strings".to_string(),
 						)
 					}
 				}
 				Value::Number(right_value) => {
 					if operator.token_type() == TokenType::Star {
-						if right_value.round() != right_value {
+						if math::round(right_value) != right_value {
 							return self.report_runtime_error(
-								&operator,
+								operator,
 								format!(
 									"Multiplication (`*`) between a string and a number requires a positive integer (found `{right_value}`)"
 								),
@@ -846,21 +3856,21 @@ impl Interpreter {
 
 						if right_value < 0 {
 							return self.report_runtime_error(
-								&operator,
+								operator,
 								format!(
 									"Multiplication (`*`) between a string and a number requires a positive integer (found `{right_value}`)"
 								),
 							);
 						}
 
-						let mut result = String::new();
-						for _ in 0..right_value {
-							result.push_str(&left_value);
-						}
-						Ok(Value::String(result))
+						let count = usize::try_from(right_value).unwrap_or(usize::MAX);
+						self.check_string_length(operator, left_value.len().checked_mul(count))?;
+
+						self.record_string();
+						Ok(Value::String(left_value.repeat(count)))
 					} else {
 						self.report_runtime_error(
-							&operator,
+							operator,
 							"Only multiplication (`*`) can be used between a string and a number"
 								.into(),
 						)
@@ -0,0 +1,55 @@
+use alloc::{collections::BTreeMap, format, string::String, vec::Vec};
+
+/// Name of the frame at the root of every stack, for the statements of the script itself
+const SCRIPT_FRAME: &str = "script";
+
+/// Counts the statements executed in each stack of function calls, to find where a script
+/// spends its time
+pub struct Profiler {
+	/// Stack of the calls in progress, from the script to the innermost call, separated by `;`
+	stack: String,
+	/// Length of `stack` before each call in progress was entered
+	lengths: Vec<usize>,
+	samples: BTreeMap<String, u64>,
+}
+
+impl Profiler {
+	pub fn new() -> Self {
+		Self {
+			stack: String::from(SCRIPT_FRAME),
+			lengths: Vec::new(),
+			samples: BTreeMap::new(),
+		}
+	}
+
+	pub fn enter(&mut self, function: &str) {
+		self.lengths.push(self.stack.len());
+		self.stack.push(';');
+		self.stack.push_str(function);
+	}
+
+	pub fn leave(&mut self) {
+		if let Some(length) = self.lengths.pop() {
+			self.stack.truncate(length);
+		}
+	}
+
+	/// Counts a statement executed in the current stack
+	pub fn sample(&mut self) {
+		match self.samples.get_mut(&self.stack) {
+			Some(count) => *count += 1,
+			None => {
+				self.samples.insert(self.stack.clone(), 1);
+			}
+		}
+	}
+
+	/// Samples in the folded stacks format of flamegraph tools (`script;caller;function 12`),
+	/// one stack per line
+	pub fn folded_stacks(&self) -> String {
+		self.samples
+			.iter()
+			.map(|(stack, count)| format!("{stack} {count}\n"))
+			.collect()
+	}
+}
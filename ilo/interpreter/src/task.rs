@@ -0,0 +1,210 @@
+use crate::{Arguments, Interpreter, Value};
+use lexer::{Symbol, Token, TokenType};
+use std::{rc::Rc, thread};
+
+/// Value sent between the threads of `spawn` and `wait`. Values cannot be sent as they are, as
+/// functions share their body and symbols are only known to the thread that interned them.
+pub enum SentValue {
+	Empty,
+	EmptyBoolean,
+	Boolean(bool),
+	EmptyNumber,
+	Number(f64),
+	EmptyString,
+	String(String),
+	List(Vec<SentValue>),
+	/// User-defined function, with its body compiled to bytecode
+	Function {
+		name: String,
+		args: Vec<String>,
+		variadic: bool,
+		body: Vec<u8>,
+		/// Variables captured by a function, except the native functions
+		captured: Vec<(String, SentValue)>,
+		declared: bool,
+	},
+	Enum {
+		name: String,
+		variants: Vec<String>,
+	},
+	Variant {
+		enumeration: String,
+		name: String,
+	},
+	/// Module, with its variables except the native functions
+	Module {
+		path: String,
+		variables: Vec<(String, SentValue)>,
+	},
+}
+
+impl SentValue {
+	/// Returns `None` for native functions, which every interpreter defines itself
+	fn new(value: &Value) -> Option<Self> {
+		Some(match value {
+			Value::Empty => Self::Empty,
+			Value::EmptyBoolean => Self::EmptyBoolean,
+			Value::Boolean(boolean) => Self::Boolean(*boolean),
+			Value::EmptyNumber => Self::EmptyNumber,
+			Value::Number(number) => Self::Number(*number),
+			Value::EmptyString => Self::EmptyString,
+			Value::String(string) => Self::String(string.clone()),
+			Value::List(elements) => {
+				Self::List(elements.iter().map(Self::new).collect::<Option<_>>()?)
+			}
+			Value::Function {
+				name,
+				args,
+				variadic,
+				body,
+				captured,
+				declared,
+			} => Self::Function {
+				name: name.clone(),
+				args: args.iter().map(Symbol::name).collect(),
+				variadic: *variadic,
+				body: bytecode::compile(name, body),
+				captured: captured
+					.iter()
+					.filter_map(|(name, value)| Some((name.name(), Self::new(value)?)))
+					.collect(),
+				declared: *declared,
+			},
+			Value::NativeFunction { .. } => return None,
+			Value::Enum { name, variants } => Self::Enum {
+				name: name.clone(),
+				variants: variants.iter().map(Symbol::name).collect(),
+			},
+			Value::Variant { enumeration, name } => Self::Variant {
+				enumeration: enumeration.clone(),
+				name: name.clone(),
+			},
+			Value::Module { path, variables } => Self::Module {
+				path: path.clone(),
+				variables: variables
+					.iter()
+					.filter_map(|(name, value)| Some((name.name(), Self::new(value)?)))
+					.collect(),
+			},
+		})
+	}
+
+	fn into_value(self) -> Value {
+		match self {
+			Self::Empty => Value::Empty,
+			Self::EmptyBoolean => Value::EmptyBoolean,
+			Self::Boolean(boolean) => Value::Boolean(boolean),
+			Self::EmptyNumber => Value::EmptyNumber,
+			Self::Number(number) => Value::Number(number),
+			Self::EmptyString => Value::EmptyString,
+			Self::String(string) => Value::String(string),
+			Self::List(elements) => Value::List(Rc::new(
+				elements.into_iter().map(Self::into_value).collect(),
+			)),
+			Self::Function {
+				name,
+				args,
+				variadic,
+				body,
+				captured,
+				declared,
+			} => Value::Function {
+				name,
+				args: args.iter().map(|arg| Symbol::intern(arg)).collect(),
+				variadic,
+				body: Rc::from(
+					bytecode::load(&body)
+						.expect("the body was compiled by the spawning thread")
+						.statements,
+				),
+				captured: Rc::new(
+					captured
+						.into_iter()
+						.map(|(name, value)| (Symbol::intern(&name), value.into_value()))
+						.collect(),
+				),
+				declared,
+			},
+			Self::Enum { name, variants } => Value::Enum {
+				name,
+				variants: variants
+					.iter()
+					.map(|variant| Symbol::intern(variant))
+					.collect(),
+			},
+			Self::Variant { enumeration, name } => Value::Variant { enumeration, name },
+			Self::Module { path, variables } => Value::Module {
+				path,
+				variables: Rc::new(
+					variables
+						.into_iter()
+						.map(|(name, value)| (Symbol::intern(&name), value.into_value()))
+						.collect(),
+				),
+			},
+		}
+	}
+}
+
+/// Task started by `spawn`, giving the return value of its function, or `None` if it failed
+pub type Task = thread::JoinHandle<Option<SentValue>>;
+
+/// Runs a user-defined function on another thread, in a new interpreter with the same settings,
+/// arguments and interrupt flag, the fuel left and a copy of the variables visible from the call
+/// to `spawn`. Changes to the variables are not seen by the other threads, and only the natives of
+/// ilo are available, using the task host of the interpreter.
+pub fn spawn(interpreter: &Interpreter, function: &Value) -> Task {
+	let variables: Vec<(String, SentValue)> = interpreter
+		.environment
+		.bindings
+		.iter()
+		.filter_map(|(name, values)| Some((name.name(), SentValue::new(values.last()?)?)))
+		.collect();
+	let function = SentValue::new(function).expect("only user-defined functions are spawned");
+	let (sandbox, strict, max_call_depth, max_string_length, fuel) = (
+		interpreter.sandbox,
+		interpreter.strict,
+		interpreter.max_call_depth,
+		interpreter.max_string_length,
+		interpreter.fuel,
+	);
+	let (host, interrupted, arguments) = (
+		interpreter.host.task_host(),
+		interpreter.interrupted.clone(),
+		interpreter.arguments.clone(),
+	);
+
+	thread::spawn(move || {
+		let mut interpreter = Interpreter::new();
+		interpreter.set_sandbox(sandbox);
+		interpreter.set_strict(strict);
+		interpreter.set_max_call_depth(max_call_depth);
+		interpreter.set_max_string_length(max_string_length);
+		interpreter.set_fuel(fuel);
+		interpreter.set_host(host);
+		interpreter.set_arguments(arguments);
+		interpreter.interrupted = interrupted;
+		interpreter.in_task = true;
+		for (name, value) in variables {
+			_ = interpreter.environment.define_or_assign(
+				Symbol::intern(&name),
+				value.into_value(),
+				true,
+			);
+		}
+
+		let function = function.into_value();
+		let Value::Function { name, .. } = &function else {
+			unreachable!("only user-defined functions are spawned");
+		};
+		// errors in the function are reported at its name
+		let token = Token::new(TokenType::Identifier, name.clone(), 0, 0);
+		let value = interpreter.call(function, &token, Arguments::new()).ok()?;
+		Some(SentValue::new(&value).unwrap_or(SentValue::Empty))
+	})
+}
+
+/// Waits for a task to end, and returns the return value of its function
+pub fn wait(task: Task) -> Option<Value> {
+	Some(task.join().ok()??.into_value())
+}
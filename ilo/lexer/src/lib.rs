@@ -1,47 +1,64 @@
-use std::fmt::Debug;
+#![allow(clippy::result_unit_err)]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::{
+	format,
+	string::{String, ToString},
+	vec,
+	vec::Vec,
+};
+use core::fmt::Debug;
+use hashbrown::HashMap;
+#[cfg(feature = "std")]
+use std::cell::RefCell;
 use substring::Substring;
 
 use error_manager::{report_error, ErrorDetails, ErrorType};
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum TokenType {
 	// Single character tokens
-	LeftBrace,     // }
-	RightBrace,    // }
-	LeftBracket,   // [
-	RightBracket,  // ]
-	Comma,         // ,
-	Colon,         // :
-	Interrogation, // ?
-	LeftParen,     // (
-	RightParen,    // )
+	LeftBrace,    // }
+	RightBrace,   // }
+	LeftBracket,  // [
+	RightBracket, // ]
+	Comma,        // ,
+	Colon,        // :
+	LeftParen,    // (
+	RightParen,   // )
 
 	// 1-2-3 character tokens
-	Arrow,        // ->
-	Bang,         // !
-	BangEqual,    // !=
-	Caret,        // ^
-	CaretEqual,   // ^=
-	Dot,          // .
-	DotDotDot,    // ...
-	Equal,        // =
-	EqualEqual,   // ==
-	Greater,      // >
-	GreaterEqual, // >=
-	Less,         // <
-	LessEqual,    // <=
-	Minus,        // -
-	MinusEqual,   // -=
-	MinusMinus,   // --
-	Percent,      // %
-	PercentEqual, // %=
-	Plus,         // +
-	PlusEqual,    // +=
-	PlusPlus,     // ++
-	Slash,        // /
-	SlashEqual,   // /=
-	Star,         // *
-	StarEqual,    // *=
+	Interrogation,        // ?
+	InterrogationDot,     // ?.
+	InterrogationBracket, // ?[
+	Arrow,                // ->
+	Bang,                 // !
+	BangEqual,            // !=
+	Caret,                // ^
+	CaretEqual,           // ^=
+	Dot,                  // .
+	DotDotDot,            // ...
+	Equal,                // =
+	EqualEqual,           // ==
+	Greater,              // >
+	GreaterEqual,         // >=
+	Less,                 // <
+	LessEqual,            // <=
+	Minus,                // -
+	MinusEqual,           // -=
+	MinusMinus,           // --
+	Percent,              // %
+	PercentEqual,         // %=
+	Plus,                 // +
+	PlusEqual,            // +=
+	PlusPlus,             // ++
+	Slash,                // /
+	SlashEqual,           // /=
+	Star,                 // *
+	StarEqual,            // *=
 
 	// Literals
 	Identifier,
@@ -50,34 +67,92 @@ pub enum TokenType {
 
 	// Reserved keywords
 	And,      // and
+	As,       // as
+	Assert,   // assert
+	Attempt,  // attempt
 	Boolean,  // boolean
 	Break,    // break
 	Continue, // continue
 	Default,  // default
+	Defer,    // defer
 	Else,     // else
 	Empty,    // empty
+	Enum,     // enum
+	Fail,     // fail
 	False,    // false
 	For,      // for
 	Function, // function
 	If,       // if
+	Import,   // import
 	In,       // in
 	Match,    // match
 	Number,   // number
 	Or,       // or
+	Repeat,   // repeat
+	Rescue,   // rescue
 	Return,   // return
 	String,   // string
 	True,     // true
+	Until,    // until
 	While,    // while
 
 	EOL, // End of line (\n)
 	EOF, // End of file
 }
 
+/// Identifier name interned when lexing, so that variables can be looked up
+/// without hashing their name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+#[derive(Default)]
+struct SymbolTable {
+	ids: HashMap<String, Symbol>,
+	names: Vec<String>,
+}
+
+#[cfg(feature = "std")]
+thread_local! {
+	static SYMBOLS: RefCell<SymbolTable> = RefCell::new(SymbolTable::default());
+}
+
+/// Without `std` there are no threads to keep the tables apart, so it is shared
+#[cfg(not(feature = "std"))]
+static SYMBOLS: spin::Mutex<Option<SymbolTable>> = spin::Mutex::new(None);
+
+fn with_symbols<T>(f: impl FnOnce(&mut SymbolTable) -> T) -> T {
+	#[cfg(feature = "std")]
+	return SYMBOLS.with(|symbols| f(&mut symbols.borrow_mut()));
+	#[cfg(not(feature = "std"))]
+	f(SYMBOLS.lock().get_or_insert_with(SymbolTable::default))
+}
+
+impl Symbol {
+	/// Returns the symbol of a name, the same name always giving the same symbol
+	pub fn intern(name: &str) -> Self {
+		with_symbols(|symbols| {
+			if let Some(symbol) = symbols.ids.get(name) {
+				return *symbol;
+			}
+			let symbol = Symbol(symbols.names.len() as u32);
+			symbols.names.push(name.to_string());
+			symbols.ids.insert(name.to_string(), symbol);
+			symbol
+		})
+	}
+
+	pub fn name(&self) -> String {
+		with_symbols(|symbols| symbols.names[self.0 as usize].clone())
+	}
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token {
 	token_type: TokenType,
 	/// Textual representation of the token, as is in the source code
 	lexeme: String,
+	/// Interned lexeme of identifiers
+	symbol: Option<Symbol>,
 	/// Line of the start of the token
 	line: i64,
 	/// Column of the start of the token
@@ -85,6 +160,16 @@ pub struct Token {
 }
 
 impl Token {
+	pub fn new(token_type: TokenType, lexeme: String, line: i64, column: i64) -> Self {
+		Self {
+			symbol: (token_type == TokenType::Identifier).then(|| Symbol::intern(&lexeme)),
+			token_type,
+			lexeme,
+			line,
+			column,
+		}
+	}
+
 	pub fn token_type(&self) -> TokenType {
 		self.token_type.clone()
 	}
@@ -93,6 +178,12 @@ impl Token {
 		self.lexeme.as_ref()
 	}
 
+	/// Symbol of an identifier token
+	pub fn symbol(&self) -> Symbol {
+		self.symbol
+			.expect("only identifiers should be used as symbols")
+	}
+
 	pub fn line(&self) -> i64 {
 		self.line
 	}
@@ -103,7 +194,8 @@ impl Token {
 }
 
 pub struct Lexer {
-	source: String,
+	/// Characters of the source code, collected once so that they can be accessed by index
+	source: Vec<char>,
 	tokens: Vec<Token>,
 	/// Offset of the start of the current lexeme
 	start: i64,
@@ -118,7 +210,7 @@ pub struct Lexer {
 impl Lexer {
 	pub fn new(source: String) -> Self {
 		Self {
-			source,
+			source: source.chars().collect(),
 			tokens: vec![],
 			start: 0,
 			current: 0,
@@ -145,12 +237,12 @@ impl Lexer {
 			}
 		}
 
-		self.tokens.push(Token {
-			token_type: TokenType::EOF,
-			lexeme: String::new(),
-			line: self.line,
-			column: self.column,
-		});
+		self.tokens.push(Token::new(
+			TokenType::EOF,
+			String::new(),
+			self.line,
+			self.column,
+		));
 
 		if has_error {
 			Err(())
@@ -160,7 +252,7 @@ impl Lexer {
 	}
 
 	fn is_at_end(&self) -> bool {
-		self.current >= self.source.chars().count() as i64
+		self.current >= self.source.len() as i64
 	}
 
 	fn scan_token(&mut self) -> Result<(), ()> {
@@ -175,7 +267,15 @@ impl Lexer {
 			']' => self.add_token(TokenType::RightBracket),
 			',' => self.add_token(TokenType::Comma),
 			':' => self.add_token(TokenType::Colon),
-			'?' => self.add_token(TokenType::Interrogation),
+			'?' => {
+				if self.match_char('.') {
+					self.add_token(TokenType::InterrogationDot)
+				} else if self.match_char('[') {
+					self.add_token(TokenType::InterrogationBracket)
+				} else {
+					self.add_token(TokenType::Interrogation)
+				}
+			}
 			'(' => self.add_token(TokenType::LeftParen),
 			')' => self.add_token(TokenType::RightParen),
 			'-' => {
@@ -303,7 +403,9 @@ impl Lexer {
 			},
 			c => {
 				if c.is_ascii_digit() {
-					self.number();
+					if let Err(()) = self.number() {
+						has_error = true;
+					}
 				} else if is_alpha(c) {
 					self.identifier();
 				} else {
@@ -349,7 +451,7 @@ impl Lexer {
 	}
 
 	fn peek_next(&self) -> char {
-		if self.current + 1 >= self.source.chars().count() as i64 {
+		if self.current + 1 >= self.source.len() as i64 {
 			return '\0';
 		}
 		self.char_at(self.current + 1)
@@ -363,20 +465,22 @@ impl Lexer {
 	}
 
 	fn char_at(&self, index: i64) -> char {
-		self.source.chars().collect::<Vec<char>>()[index as usize]
+		self.source[index as usize]
+	}
+
+	fn substring(&self, start: i64, end: i64) -> String {
+		self.source[start as usize..end as usize].iter().collect()
 	}
 
 	fn add_token(&mut self, token_type: TokenType) {
-		let lexeme = self
-			.source
-			.substring(self.start as usize, self.current as usize);
-
-		self.tokens.push(Token {
-			token_type: token_type.clone(),
-			lexeme: lexeme.into(),
-			line: self.line,
-			column: self.column,
-		});
+		let lexeme = self.substring(self.start, self.current);
+
+		self.tokens.push(Token::new(
+			token_type.clone(),
+			lexeme.clone(),
+			self.line,
+			self.column,
+		));
 
 		match token_type {
 			TokenType::EOL => {
@@ -384,10 +488,10 @@ impl Lexer {
 				self.column = 1;
 			}
 			TokenType::StringLiteral(lit) => {
-				let newlines = lit.match_indices('\n');
+				let mut newlines = lit.match_indices('\n');
 				let count = newlines.clone().count();
 				self.line += count as i64;
-				if let Some(last) = newlines.last() {
+				if let Some(last) = newlines.next_back() {
 					self.column = lit
 						.substring(last.0 + 1, lit.chars().count())
 						.chars()
@@ -411,34 +515,69 @@ impl Lexer {
 
 		self.advance();
 
-		let literal = self
-			.source
-			.substring(self.start as usize + 1, self.current as usize - 1)
-			.into();
+		let literal = self.substring(self.start + 1, self.current - 1);
 		self.add_token(TokenType::StringLiteral(literal));
 
 		Ok(())
 	}
 
-	fn number(&mut self) {
-		while self.peek().is_ascii_digit() {
-			self.advance();
-		}
+	/// Number literal, with an optional fractional part and exponent (`2.5e-3`). Its digits can
+	/// be separated by `_` (`1_000_000`).
+	fn number(&mut self) -> Result<(), ()> {
+		self.digits();
 
 		if self.peek() == '.' && self.peek_next().is_ascii_digit() {
 			self.advance();
+			self.digits();
+		}
 
-			while self.peek().is_ascii_digit() {
-				self.advance();
+		if matches!(self.peek(), 'e' | 'E') {
+			let sign = matches!(self.peek_next(), '+' | '-');
+			let first_digit = if sign {
+				self.current + 2
+			} else {
+				self.current + 1
+			};
+			if first_digit < self.source.len() as i64 && self.char_at(first_digit).is_ascii_digit()
+			{
+				self.current = first_digit;
+				self.digits();
 			}
 		}
 
-		self.add_token(TokenType::NumberLiteral(
-			self.source
-				.substring(self.start as usize, self.current as usize)
-				.parse()
-				.unwrap(),
-		));
+		let lexeme = self.substring(self.start, self.current);
+		let characters: Vec<char> = lexeme.chars().collect();
+		let misplaced_separator = characters.iter().enumerate().any(|(index, &c)| {
+			c == '_'
+				&& !(characters[index - 1].is_ascii_digit()
+					&& characters
+						.get(index + 1)
+						.is_some_and(|next| next.is_ascii_digit()))
+		});
+		let number: f64 = lexeme.replace('_', "").parse().unwrap();
+		let error = if misplaced_separator {
+			Some(format!(
+				"Misplaced `_` in number `{lexeme}`, separators must be between two digits"
+			))
+		} else if number.is_infinite() {
+			Some(format!("Number `{lexeme}` is too large"))
+		} else {
+			None
+		};
+		if let Some(message) = error {
+			self.report_lexical_error(message, self.line, self.column);
+			self.column += characters.len() as i64;
+			return Err(());
+		}
+		self.add_token(TokenType::NumberLiteral(number));
+		Ok(())
+	}
+
+	/// Digits of a number, with their separators
+	fn digits(&mut self) {
+		while self.peek().is_ascii_digit() || self.peek() == '_' {
+			self.advance();
+		}
 	}
 
 	fn identifier(&mut self) {
@@ -446,30 +585,37 @@ impl Lexer {
 			self.advance();
 		}
 
-		let ident: String = self
-			.source
-			.substring(self.start as usize, self.current as usize)
-			.into();
+		let ident = self.substring(self.start, self.current);
 
 		match ident.as_str() {
 			"and" => self.add_token(TokenType::And),
+			"as" => self.add_token(TokenType::As),
+			"assert" => self.add_token(TokenType::Assert),
+			"attempt" => self.add_token(TokenType::Attempt),
 			"boolean" => self.add_token(TokenType::Boolean),
 			"break" => self.add_token(TokenType::Break),
 			"continue" => self.add_token(TokenType::Continue),
 			"default" => self.add_token(TokenType::Default),
+			"defer" => self.add_token(TokenType::Defer),
 			"else" => self.add_token(TokenType::Else),
 			"empty" => self.add_token(TokenType::Empty),
+			"enum" => self.add_token(TokenType::Enum),
 			"f" => self.add_token(TokenType::Function),
+			"fail" => self.add_token(TokenType::Fail),
 			"false" => self.add_token(TokenType::False),
 			"for" => self.add_token(TokenType::For),
 			"if" => self.add_token(TokenType::If),
+			"import" => self.add_token(TokenType::Import),
 			"in" => self.add_token(TokenType::In),
 			"match" => self.add_token(TokenType::Match),
 			"number" => self.add_token(TokenType::Number),
 			"or" => self.add_token(TokenType::Or),
+			"repeat" => self.add_token(TokenType::Repeat),
+			"rescue" => self.add_token(TokenType::Rescue),
 			"return" => self.add_token(TokenType::Return),
 			"string" => self.add_token(TokenType::String),
 			"true" => self.add_token(TokenType::True),
+			"until" => self.add_token(TokenType::Until),
 			"while" => self.add_token(TokenType::While),
 			_ => self.add_token(TokenType::Identifier),
 		}
@@ -1,5 +1,18 @@
+#![allow(clippy::result_unit_err)]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::{
+	borrow::ToOwned,
+	boxed::Box,
+	format,
+	string::{String, ToString},
+	vec,
+	vec::Vec,
+};
 use error_manager::{report_error, ErrorDetails, ErrorType};
-use lexer::{Token, TokenType};
+use lexer::{Symbol, Token, TokenType};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Statement {
@@ -10,6 +23,23 @@ pub enum Statement {
 		ident: Token,
 		value: Expr,
 	},
+	/// `a, b = b, a`, assigning the values once they are all evaluated
+	MultipleAssignment {
+		idents: Vec<Token>,
+		values: Vec<Expr>,
+	},
+	/// `i++` or `i--`, on a number variable
+	Increment {
+		ident: Token,
+		operator: Token,
+	},
+	/// `list[index] = value`, where the list is a variable or an element of a list in a variable
+	IndexAssignment {
+		object: Box<Expr>,
+		bracket: Token,
+		index: Box<Expr>,
+		value: Expr,
+	},
 	Block {
 		statements: Vec<Statement>,
 	},
@@ -19,17 +49,134 @@ pub enum Statement {
 		otherwise: Option<Box<Statement>>,
 	},
 	While {
+		/// Name given to the loop, that `break` and `continue` can use to refer to it from an
+		/// inner loop
+		label: Option<Token>,
+		condition: Expr,
+		body: Box<Statement>,
+	},
+	/// Runs the body once, then again until the condition is `true`
+	Repeat {
+		label: Option<Token>,
+		keyword: Token,
+		body: Box<Statement>,
 		condition: Expr,
+	},
+	/// Runs the body for each element of the iterable, bound to `variable`
+	For {
+		label: Option<Token>,
+		variable: Token,
+		iterable: Iterable,
 		body: Box<Statement>,
 	},
 	FunctionDeclaration {
 		ident: Token,
 		params: Vec<Token>,
+		/// Whether the last parameter collects the extra arguments in a list (`values...`)
+		variadic: bool,
 		body: Vec<Statement>,
 	},
 	Return {
 		expr: Expr,
 	},
+	/// Leaves the labeled loop, or the innermost one
+	Break {
+		keyword: Token,
+		label: Option<Token>,
+	},
+	/// Skips to the next iteration of the labeled loop, or of the innermost one
+	Continue {
+		keyword: Token,
+		label: Option<Token>,
+	},
+	/// Runs the body (an expression or a block) when the enclosing function or script exits,
+	/// even because of an error
+	Defer {
+		keyword: Token,
+		body: Box<Statement>,
+	},
+	/// Reports a runtime error with the message given by the script
+	Fail {
+		keyword: Token,
+		message: Expr,
+	},
+	/// Reports a runtime error when the condition is not `true`, with the optional message
+	Assert {
+		keyword: Token,
+		condition: Expr,
+		message: Option<Expr>,
+	},
+	/// Runs the script at `path` (a string literal), relative to the importing file, and defines
+	/// its top-level variables, or a module named `alias` whose variables are read with `.`
+	Import {
+		keyword: Token,
+		path: Token,
+		alias: Option<Token>,
+	},
+	/// Runs the body, and the handler with the error bound to `variable` if the body fails
+	Attempt {
+		keyword: Token,
+		body: Box<Statement>,
+		variable: Token,
+		handler: Box<Statement>,
+	},
+	/// Runs the body of the first arm with a pattern matching the value. Bodies are blocks, or
+	/// expression statements for the arms written with `->`.
+	Match {
+		keyword: Token,
+		value: Expr,
+		arms: Vec<MatchArm<Statement>>,
+	},
+	/// Declares an enum, whose variants are only equal to themselves
+	Enum {
+		ident: Token,
+		variants: Vec<Token>,
+	},
+}
+
+/// Arm of a `match`, whose body runs when one of its patterns matches the value and its guard,
+/// if any, is `true`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchArm<T> {
+	pub patterns: Vec<Pattern>,
+	/// Condition after the patterns (`default if n > 10 -> ...`)
+	pub guard: Option<Expr>,
+	pub body: T,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+	/// Value compared to the matched value with `==`, like a literal or an enum variant
+	Value(Expr),
+	/// `boolean`, `number` or `string`, matching the values of the type that are not empty, or
+	/// `empty`, matching all the empty values
+	Type(Token),
+	/// `default`, matching any value
+	Default(Token),
+}
+
+impl Pattern {
+	pub fn first_token(&self) -> &Token {
+		match self {
+			Self::Value(expr) => expr.first_token(),
+			Self::Type(token) | Self::Default(token) => token,
+		}
+	}
+}
+
+/// What a `for` loop iterates over
+#[derive(Debug, Clone, PartialEq)]
+pub enum Iterable {
+	/// Characters of a string
+	Value(Expr),
+	/// Numbers from `start` to `end` included, `step` apart. Without a step, the range counts up
+	/// or down by 1 depending on its bounds.
+	Range {
+		start: Box<Expr>,
+		operator: Token,
+		end: Box<Expr>,
+		step: Option<Box<Expr>>,
+	},
 }
 
 impl Statement {
@@ -41,9 +188,43 @@ impl Statement {
 			}
 			| Self::Return { expr }
 			| Self::While {
-				condition: expr, ..
+				label: None,
+				condition: expr,
+				..
 			} => expr.first_token(),
-			Self::Assignment { ident, .. } | Self::FunctionDeclaration { ident, .. } => ident,
+			Self::IndexAssignment { object, .. } => object.first_token(),
+			Self::While {
+				label: Some(token), ..
+			}
+			| Self::Repeat {
+				label: Some(token), ..
+			}
+			| Self::Repeat {
+				label: None,
+				keyword: token,
+				..
+			}
+			| Self::For {
+				label: Some(token), ..
+			}
+			| Self::For {
+				label: None,
+				variable: token,
+				..
+			}
+			| Self::Break { keyword: token, .. }
+			| Self::Continue { keyword: token, .. }
+			| Self::Defer { keyword: token, .. }
+			| Self::Attempt { keyword: token, .. }
+			| Self::Fail { keyword: token, .. }
+			| Self::Assert { keyword: token, .. }
+			| Self::Import { keyword: token, .. }
+			| Self::Match { keyword: token, .. } => token,
+			Self::Assignment { ident, .. }
+			| Self::Increment { ident, .. }
+			| Self::FunctionDeclaration { ident, .. }
+			| Self::Enum { ident, .. } => ident,
+			Self::MultipleAssignment { idents, .. } => &idents[0],
 			Self::Block { .. } => {
 				unreachable!("`first_token` should not be accessed on a block")
 			}
@@ -51,6 +232,9 @@ impl Statement {
 	}
 }
 
+/// Expression nodes own their children through `Box`es instead of living in an arena: function
+/// values keep their body after the parser is dropped, and the REPL runs statements coming from
+/// several parses in the same interpreter, so each tree has to stand on its own.
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
 	Primary {
@@ -60,6 +244,11 @@ pub enum Expr {
 		operator: Token,
 		expr: Box<Expr>,
 	},
+	/// Operator written after its operand: `value?` tells if the value is empty
+	Postfix {
+		expr: Box<Expr>,
+		operator: Token,
+	},
 	Binary {
 		left_expr: Box<Expr>,
 		operator: Token,
@@ -76,6 +265,50 @@ pub enum Expr {
 		closing_paren: Token,
 		arguments: Vec<Expr>,
 	},
+	/// `number(expr)`, `string(expr)` or `boolean(expr)`, converting the value of the expression
+	/// to the type of the keyword
+	Conversion {
+		target: Token,
+		expr: Box<Expr>,
+		closing_paren: Token,
+	},
+	/// `receiver?.name(arguments)`, which calls `name` with the receiver as its first argument,
+	/// unless the receiver is empty
+	SafeCall {
+		receiver: Box<Expr>,
+		name: Token,
+		closing_paren: Token,
+		arguments: Vec<Expr>,
+	},
+	/// `match` whose arms are all written with `->`, giving the value of the arm that matches
+	Match {
+		keyword: Token,
+		value: Box<Expr>,
+		arms: Vec<MatchArm<Expr>>,
+	},
+	/// `object.name` without a call, which reads a variant of an enum or a variable of a module
+	Field {
+		object: Box<Expr>,
+		name: Token,
+	},
+	/// `object[index]`, or `object?[index]` giving an empty value instead of failing when the
+	/// object is empty or the index is out of bounds
+	Index {
+		object: Box<Expr>,
+		bracket: Token,
+		index: Box<Expr>,
+	},
+	/// `[elements]`, whose elements can be written on several lines
+	List {
+		opening_bracket: Token,
+		elements: Vec<Expr>,
+	},
+	/// `(params) -> body`, an anonymous function returning the value of its body
+	Lambda {
+		opening_paren: Token,
+		params: Vec<Token>,
+		body: Box<Expr>,
+	},
 }
 
 impl Expr {
@@ -85,24 +318,119 @@ impl Expr {
 			| Expr::Unary {
 				operator: token, ..
 			}
-			| Expr::Variable { name: token } => token,
+			| Expr::Variable { name: token }
+			| Expr::Conversion { target: token, .. }
+			| Expr::Match { keyword: token, .. }
+			| Expr::List {
+				opening_bracket: token,
+				..
+			}
+			| Expr::Lambda {
+				opening_paren: token,
+				..
+			} => token,
 			Expr::Binary {
 				left_expr: expr, ..
 			}
 			| Expr::Grouping { expr }
-			| Expr::Call { callee: expr, .. } => expr.first_token(),
+			| Expr::Postfix { expr, .. }
+			| Expr::Call { callee: expr, .. }
+			| Expr::SafeCall { receiver: expr, .. }
+			| Expr::Field { object: expr, .. }
+			| Expr::Index { object: expr, .. } => expr.first_token(),
+		}
+	}
+
+	/// Whether evaluating the expression can call a function, and therefore have side effects
+	pub fn contains_call(&self) -> bool {
+		match self {
+			// the body of a lambda only runs when it is called
+			Expr::Primary { .. } | Expr::Variable { .. } | Expr::Lambda { .. } => false,
+			Expr::Unary { expr, .. }
+			| Expr::Postfix { expr, .. }
+			| Expr::Grouping { expr }
+			| Expr::Conversion { expr, .. }
+			| Expr::Field { object: expr, .. } => expr.contains_call(),
+			Expr::Binary {
+				left_expr,
+				right_expr,
+				..
+			} => left_expr.contains_call() || right_expr.contains_call(),
+			Expr::Call { .. } | Expr::SafeCall { .. } => true,
+			Expr::List { elements, .. } => elements.iter().any(Expr::contains_call),
+			Expr::Index { object, index, .. } => object.contains_call() || index.contains_call(),
+			Expr::Match { value, arms, .. } => {
+				value.contains_call()
+					|| arms.iter().any(|arm| {
+						arm.body.contains_call()
+							|| arm.guard.as_ref().is_some_and(Expr::contains_call)
+							|| arm.patterns.iter().any(|pattern| match pattern {
+								Pattern::Value(expr) => expr.contains_call(),
+								Pattern::Type(_) | Pattern::Default(_) => false,
+							})
+					})
+			}
 		}
 	}
 }
 
+/// Number of nested blocks and expressions the parser accepts, so that deeply nested code is
+/// reported instead of overflowing the stack
+pub const MAX_NESTING: usize = 64;
+
 pub struct Parser {
 	tokens: Vec<Token>,
 	current: i64,
+	/// Number of blocks and expressions currently being parsed inside each other
+	nesting: usize,
+	/// Labels of the loops around the statement being parsed, in the current function
+	loops: Vec<Option<Symbol>>,
+	/// Whether the statement being parsed is deferred, and cannot return
+	deferred: bool,
+	/// Names of the modules imported with `as`, whose functions are called with `.` instead of
+	/// being called on them (`math.clamp(x)` is not `clamp(math, x)`)
+	modules: Vec<Symbol>,
 }
 
 impl Parser {
-	pub fn new(tokens: Vec<Token>) -> Self {
-		Self { tokens, current: 0 }
+	pub fn new(mut tokens: Vec<Token>) -> Self {
+		// the parser relies on the tokens ending with `EOF`, which is not the case of tokens
+		// that were not produced by the lexer
+		if tokens.last().map(Token::token_type) != Some(TokenType::EOF) {
+			let (line, column) = tokens
+				.last()
+				.map_or((1, 1), |token| (token.line(), token.column()));
+			tokens.push(Token::new(TokenType::EOF, String::new(), line, column));
+		}
+		// `f` only declares a function when a name follows it, and is a variable otherwise, like
+		// the parameter of `f apply(f, value)`
+		for index in 1..tokens.len() {
+			if tokens[index - 1].token_type() == TokenType::Function
+				&& tokens[index].token_type() != TokenType::Identifier
+			{
+				let keyword = &tokens[index - 1];
+				tokens[index - 1] = Token::new(
+					TokenType::Identifier,
+					keyword.lexeme().to_string(),
+					keyword.line(),
+					keyword.column(),
+				);
+			}
+		}
+		Self {
+			tokens: join_parenthesized_lines(tokens),
+			current: 0,
+			nesting: 0,
+			loops: vec![],
+			deferred: false,
+			modules: vec![],
+		}
+	}
+
+	/// Declares a module imported with `as` before the parsed code, like in a previous input of
+	/// the REPL
+	pub fn define_module(&mut self, name: Symbol) {
+		self.modules.push(name);
 	}
 
 	pub fn parse(&mut self) -> Result<Vec<Statement>, ()> {
@@ -145,8 +473,12 @@ impl Parser {
 				| TokenType::For
 				| TokenType::If
 				| TokenType::While
+				| TokenType::Repeat
+				| TokenType::Attempt
+				| TokenType::Import
 				| TokenType::Return
-				| TokenType::Match => {
+				| TokenType::Match
+				| TokenType::Enum => {
 					return;
 				}
 				_ => self.advance(),
@@ -189,9 +521,9 @@ impl Parser {
 		false
 	}
 
-	fn match_any(&mut self, types: Vec<TokenType>) -> bool {
+	fn match_any(&mut self, types: &[TokenType]) -> bool {
 		for token_type in types {
-			if self.next_is(token_type) {
+			if self.next_is(token_type.clone()) {
 				self.advance();
 				return true;
 			}
@@ -243,10 +575,41 @@ impl Parser {
 		Err(())
 	}
 
+	/// Runs a parsing function one nesting level deeper, or reports an error if the code is
+	/// nested too deeply
+	fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, ()>) -> Result<T, ()> {
+		if self.nesting == MAX_NESTING {
+			self.report_parsing_error(
+				format!("Code cannot be nested more than {MAX_NESTING} levels deep"),
+				self.peek(),
+			);
+			return Err(());
+		}
+		self.nesting += 1;
+		let result = parse(self);
+		self.nesting -= 1;
+		result
+	}
+
 	fn statement(&mut self) -> Result<Statement, ()> {
+		self.nested(Self::unnested_statement)
+	}
+
+	fn unnested_statement(&mut self) -> Result<Statement, ()> {
 		if self.match_one(TokenType::Identifier) {
 			if self.peek().token_type() == TokenType::Equal {
 				return self.assign_statement();
+			} else if matches!(
+				self.peek().token_type(),
+				TokenType::PlusPlus | TokenType::MinusMinus
+			) {
+				return self.increment_statement();
+			} else if compound_operator(self.peek().token_type()).is_some() {
+				return self.compound_assign_statement();
+			} else if self.peek().token_type() == TokenType::Colon {
+				return self.labeled_statement();
+			} else if self.peek().token_type() == TokenType::Comma {
+				return self.multiple_assign_statement();
 			} else {
 				// if we are at an expression statement using an identifier,
 				// it is already consumed by now, so we backtrack
@@ -259,11 +622,31 @@ impl Parser {
 		} else if self.match_one(TokenType::If) {
 			return self.if_statement();
 		} else if self.match_one(TokenType::While) {
-			return self.while_statement();
+			return self.while_statement(None);
+		} else if self.match_one(TokenType::Repeat) {
+			return self.repeat_statement(None);
+		} else if self.match_one(TokenType::For) {
+			return self.for_statement(None);
 		} else if self.match_one(TokenType::Function) {
 			return self.function_statement();
 		} else if self.match_one(TokenType::Return) {
 			return self.return_statement();
+		} else if self.match_any(&[TokenType::Break, TokenType::Continue]) {
+			return self.loop_control_statement();
+		} else if self.match_one(TokenType::Defer) {
+			return self.defer_statement();
+		} else if self.match_one(TokenType::Attempt) {
+			return self.attempt_statement();
+		} else if self.match_one(TokenType::Fail) {
+			return self.fail_statement();
+		} else if self.match_one(TokenType::Assert) {
+			return self.assert_statement();
+		} else if self.match_one(TokenType::Import) {
+			return self.import_statement();
+		} else if self.match_one(TokenType::Match) {
+			return self.match_statement();
+		} else if self.match_one(TokenType::Enum) {
+			return self.enum_statement();
 		}
 
 		self.expression_statement()
@@ -274,15 +657,145 @@ impl Parser {
 
 		self.advance();
 
-		let value = if self.match_one(TokenType::Empty) {
+		let value = self.assigned_value()?;
+
+		self.consume_eol_or_report("Line must end after an assignment".to_string())?;
+
+		Ok(Statement::Assignment { ident, value })
+	}
+
+	fn assigned_value(&mut self) -> Result<Expr, ()> {
+		if self.match_one(TokenType::Empty) {
 			self.empty_type()
 		} else {
 			self.expression()
-		}?;
+		}
+	}
+
+	fn multiple_assign_statement(&mut self) -> Result<Statement, ()> {
+		let mut idents = vec![self.previous()];
+		while self.match_one(TokenType::Comma) {
+			let ident = self.consume_or_report(
+				TokenType::Identifier,
+				"Expected the name of a variable after `,`".to_string(),
+			)?;
+			if idents.iter().any(|other| other.symbol() == ident.symbol()) {
+				self.report_parsing_error(
+					format!("Variable `{}` cannot be assigned twice", ident.lexeme()),
+					ident,
+				);
+				return Err(());
+			}
+			idents.push(ident);
+		}
+		let equal = self.consume_or_report(
+			TokenType::Equal,
+			"Expected `=` after the variables of an assignment".to_string(),
+		)?;
+
+		let mut values = vec![self.assigned_value()?];
+		while self.match_one(TokenType::Comma) {
+			values.push(self.assigned_value()?);
+		}
+		if values.len() != idents.len() {
+			self.report_parsing_error(
+				format!(
+					"Expected {} values to assign to {} variables, but found {}",
+					idents.len(),
+					idents.len(),
+					values.len()
+				),
+				equal,
+			);
+			return Err(());
+		}
 
 		self.consume_eol_or_report("Line must end after an assignment".to_string())?;
 
-		Ok(Statement::Assignment { ident, value })
+		Ok(Statement::MultipleAssignment { idents, values })
+	}
+
+	/// Desugars `a += b` to `a = a + b`, and likewise for the other compound assignments
+	fn compound_assign_statement(&mut self) -> Result<Statement, ()> {
+		let ident = self.previous();
+		let compound = self.advance();
+		let operator_type =
+			compound_operator(compound.token_type()).expect("checked by `unnested_statement`");
+		// the operator keeps the position of the compound assignment for errors
+		let operator = Token::new(
+			operator_type,
+			compound.lexeme()[..compound.lexeme().len() - 1].to_string(),
+			compound.line(),
+			compound.column(),
+		);
+
+		let value = self.expression()?;
+
+		self.consume_eol_or_report("Line must end after an assignment".to_string())?;
+
+		Ok(Statement::Assignment {
+			value: Expr::Binary {
+				left_expr: Box::new(Expr::Variable {
+					name: ident.clone(),
+				}),
+				operator,
+				right_expr: Box::new(value),
+			},
+			ident,
+		})
+	}
+
+	fn increment_statement(&mut self) -> Result<Statement, ()> {
+		let ident = self.previous();
+		let operator = self.advance();
+
+		self.consume_eol_or_report(format!("Line must end after `{}`", operator.lexeme()))?;
+
+		Ok(Statement::Increment { ident, operator })
+	}
+
+	fn index_assign_statement(&mut self, target: Expr) -> Result<Statement, ()> {
+		let equal = self.previous();
+		let Expr::Index {
+			object,
+			bracket,
+			index,
+		} = target
+		else {
+			self.report_parsing_error(
+				"Only variables and the elements of lists can be assigned".to_string(),
+				equal,
+			);
+			return Err(());
+		};
+		// the list has to be stored in a variable for the assignment to change it
+		let mut safe = bracket.token_type() == TokenType::InterrogationBracket;
+		let mut list = object.as_ref();
+		while let Expr::Index {
+			object, bracket, ..
+		} = list
+		{
+			safe |= bracket.token_type() == TokenType::InterrogationBracket;
+			list = object;
+		}
+		if safe || !matches!(list, Expr::Variable { .. }) {
+			self.report_parsing_error(
+				"Only the elements of lists stored in variables can be assigned, with `[]`"
+					.to_string(),
+				equal,
+			);
+			return Err(());
+		}
+
+		let value = self.expression()?;
+		self.consume_eol_or_report("Line must end after an assignment".to_string())?;
+
+		Ok(Statement::IndexAssignment {
+			object,
+			bracket,
+			index,
+			value,
+		})
 	}
 
 	fn empty_type(&mut self) -> Result<Expr, ()> {
@@ -292,21 +805,14 @@ impl Parser {
 			});
 		}
 
-		if !self.match_any(vec![TokenType::Boolean, TokenType::Number]) {
-			if self.peek().token_type() == TokenType::String {
-				self.report_parsing_error(
-					"Empty string variables must be initialized with `\"\"̀ ".to_string(),
-					self.peek(),
-				)
-			} else {
-				self.report_parsing_error(
-					format!(
-						"Empty variables must be initialized with `empty(boolean)`, {}",
-						"`empty(number)`, or an empty string `\"\"`"
-					),
-					self.peek(),
-				);
-			}
+		if !self.match_any(&[TokenType::Boolean, TokenType::Number, TokenType::String]) {
+			self.report_parsing_error(
+				format!(
+					"Empty variables must be initialized with `empty(boolean)`, {}",
+					"`empty(number)` or `empty(string)`"
+				),
+				self.peek(),
+			);
 			return Err(());
 		}
 
@@ -366,7 +872,7 @@ impl Parser {
 
 		if self.match_one(TokenType::Else) {
 			if self.match_one(TokenType::If) {
-				else_branch = Some(Box::new(self.if_statement()?));
+				else_branch = Some(Box::new(self.nested(Self::if_statement)?));
 			} else {
 				self.consume_or_report(
 					TokenType::LeftBrace,
@@ -385,7 +891,36 @@ impl Parser {
 		})
 	}
 
-	fn while_statement(&mut self) -> Result<Statement, ()> {
+	fn labeled_statement(&mut self) -> Result<Statement, ()> {
+		let label = self.previous();
+		self.advance();
+
+		if !self.match_any(&[TokenType::For, TokenType::While, TokenType::Repeat]) {
+			self.report_parsing_error(
+				format!("Expected a loop after the label `{}`", label.lexeme()),
+				self.peek(),
+			);
+			return Err(());
+		}
+		if self.loops.contains(&Some(label.symbol())) {
+			self.report_parsing_error(
+				format!(
+					"Label `{}` is already used by an enclosing loop",
+					label.lexeme()
+				),
+				label,
+			);
+			return Err(());
+		}
+
+		match self.previous().token_type() {
+			TokenType::For => self.for_statement(Some(label)),
+			TokenType::While => self.while_statement(Some(label)),
+			_ => self.repeat_statement(Some(label)),
+		}
+	}
+
+	fn while_statement(&mut self, label: Option<Token>) -> Result<Statement, ()> {
 		let condition = self.expression()?;
 
 		self.consume_or_report(
@@ -393,13 +928,129 @@ impl Parser {
 			"Expected an opening `{{` after the condition in a `while` statement".to_string(),
 		)?;
 
-		let body = Statement::Block {
-			statements: self.block_statement()?,
-		};
+		self.loops.push(label.as_ref().map(Token::symbol));
+		let statements = self.block_statement();
+		self.loops.pop();
 
 		Ok(Statement::While {
+			label,
 			condition,
-			body: Box::new(body),
+			body: Box::new(Statement::Block {
+				statements: statements?,
+			}),
+		})
+	}
+
+	fn repeat_statement(&mut self, label: Option<Token>) -> Result<Statement, ()> {
+		let keyword = self.previous();
+
+		self.consume_or_report(
+			TokenType::LeftBrace,
+			"Expected an opening `{` after `repeat`".to_string(),
+		)?;
+
+		self.loops.push(label.as_ref().map(Token::symbol));
+		let statements = self.block_statement();
+		self.loops.pop();
+		let body = Box::new(Statement::Block {
+			statements: statements?,
+		});
+
+		self.consume_or_report(
+			TokenType::Until,
+			"Expected `until` and a condition after the body of a `repeat` statement".to_string(),
+		)?;
+		let condition = self.expression()?;
+
+		Ok(Statement::Repeat {
+			label,
+			keyword,
+			body,
+			condition,
+		})
+	}
+
+	fn for_statement(&mut self, label: Option<Token>) -> Result<Statement, ()> {
+		let variable = self.consume_or_report(
+			TokenType::Identifier,
+			"Expected a variable name after `for`".to_string(),
+		)?;
+		self.consume_or_report(
+			TokenType::In,
+			format!("Expected `in` after `for {}`", variable.lexeme()),
+		)?;
+		let value = self.expression()?;
+		let iterable = if self.match_one(TokenType::DotDotDot) {
+			let operator = self.previous();
+			let end = self.expression()?;
+			let step = if self.peek().token_type() == TokenType::Identifier
+				&& self.peek().lexeme() == "step"
+			{
+				self.advance();
+				Some(Box::new(self.expression()?))
+			} else {
+				None
+			};
+			Iterable::Range {
+				start: Box::new(value),
+				operator,
+				end: Box::new(end),
+				step,
+			}
+		} else {
+			Iterable::Value(value)
+		};
+
+		self.consume_or_report(
+			TokenType::LeftBrace,
+			"Expected an opening `{` after the value in a `for` statement".to_string(),
+		)?;
+
+		self.loops.push(label.as_ref().map(Token::symbol));
+		let statements = self.block_statement();
+		self.loops.pop();
+
+		Ok(Statement::For {
+			label,
+			variable,
+			iterable,
+			body: Box::new(Statement::Block {
+				statements: statements?,
+			}),
+		})
+	}
+
+	fn loop_control_statement(&mut self) -> Result<Statement, ()> {
+		let keyword = self.previous();
+		let label = if self.match_one(TokenType::Identifier) {
+			Some(self.previous())
+		} else {
+			None
+		};
+
+		if self.loops.is_empty() {
+			self.report_parsing_error(
+				format!("`{}` can only be used inside a loop", keyword.lexeme()),
+				keyword,
+			);
+			return Err(());
+		}
+		if let Some(label) = &label {
+			if !self.loops.contains(&Some(label.symbol())) {
+				self.report_parsing_error(
+					format!("No loop labeled `{}` around this statement", label.lexeme()),
+					label.clone(),
+				);
+				return Err(());
+			}
+		}
+
+		self.consume_eol_or_report(format!("Line must end after `{}`", keyword.lexeme()))?;
+
+		Ok(if keyword.token_type() == TokenType::Break {
+			Statement::Break { keyword, label }
+		} else {
+			Statement::Continue { keyword, label }
 		})
 	}
 
@@ -417,12 +1068,23 @@ impl Parser {
 		)?;
 
 		let mut parameters: Vec<Token> = vec![];
+		let mut variadic = false;
 		if !self.next_is(TokenType::RightParen) {
 			let error_message = format!("Expected parameter name for function {}", name.lexeme());
 			parameters.push(self.consume_or_report(TokenType::Identifier, error_message.clone())?);
-			while self.match_one(TokenType::Comma) {
+			variadic = self.match_one(TokenType::DotDotDot);
+			while !variadic && self.match_one(TokenType::Comma) {
 				parameters
 					.push(self.consume_or_report(TokenType::Identifier, error_message.clone())?);
+				variadic = self.match_one(TokenType::DotDotDot);
+			}
+			if variadic && self.next_is(TokenType::Comma) {
+				self.report_parsing_error(
+					"Only the last parameter can collect the extra arguments with `...`"
+						.to_string(),
+					self.peek(),
+				);
+				return Err(());
 			}
 		}
 		self.consume_or_report(
@@ -447,16 +1109,30 @@ impl Parser {
 			),
 		)?;
 
-		let body = self.block_statement()?;
+		// loops around the declaration cannot be controlled from the function body
+		let loops = core::mem::take(&mut self.loops);
+		let deferred = core::mem::replace(&mut self.deferred, false);
+		let body = self.block_statement();
+		self.loops = loops;
+		self.deferred = deferred;
+		let body = body?;
 
 		Ok(Statement::FunctionDeclaration {
 			ident: name,
 			params: parameters,
+			variadic,
 			body,
 		})
 	}
 
 	fn return_statement(&mut self) -> Result<Statement, ()> {
+		if self.deferred {
+			self.report_parsing_error(
+				"`return` cannot be used in deferred code".to_string(),
+				self.previous(),
+			);
+			return Err(());
+		}
 		let expr_stmt = self.expression_statement()?;
 		match expr_stmt {
 			Statement::Expr { expr } => Ok(Statement::Return { expr }),
@@ -464,8 +1140,312 @@ impl Parser {
 		}
 	}
 
+	fn fail_statement(&mut self) -> Result<Statement, ()> {
+		let keyword = self.previous();
+		let message = self.expression()?;
+		self.consume_eol_or_report("Line must end after the message of `fail`".to_string())?;
+		Ok(Statement::Fail { keyword, message })
+	}
+
+	fn assert_statement(&mut self) -> Result<Statement, ()> {
+		let keyword = self.previous();
+		let condition = self.expression()?;
+		let message = if self.match_one(TokenType::Comma) {
+			Some(self.expression()?)
+		} else {
+			None
+		};
+		self.consume_eol_or_report("Line must end after an `assert` statement".to_string())?;
+		Ok(Statement::Assert {
+			keyword,
+			condition,
+			message,
+		})
+	}
+
+	fn import_statement(&mut self) -> Result<Statement, ()> {
+		let keyword = self.previous();
+		// the imported variables are defined in the scope of the script
+		if self.nesting > 1 {
+			self.report_parsing_error(
+				"`import` can only be used at the top level of the script".to_string(),
+				keyword,
+			);
+			return Err(());
+		}
+		if !matches!(self.peek().token_type(), TokenType::StringLiteral(_)) {
+			self.report_parsing_error(
+				"Expected the path of the imported file as a string after `import`".to_string(),
+				self.peek(),
+			);
+			return Err(());
+		}
+		let path = self.advance();
+		let alias = if self.match_one(TokenType::As) {
+			let alias = self.consume_or_report(
+				TokenType::Identifier,
+				"Expected the name of the module after `as`".to_string(),
+			)?;
+			self.modules.push(alias.symbol());
+			Some(alias)
+		} else {
+			None
+		};
+		self.consume_eol_or_report("Line must end after an `import` statement".to_string())?;
+		Ok(Statement::Import {
+			keyword,
+			path,
+			alias,
+		})
+	}
+
+	fn defer_statement(&mut self) -> Result<Statement, ()> {
+		let keyword = self.previous();
+
+		// the deferred code runs once the loops around it are over
+		let loops = core::mem::take(&mut self.loops);
+		let deferred = core::mem::replace(&mut self.deferred, true);
+		let body = if self.match_one(TokenType::LeftBrace) {
+			self.block_statement()
+				.map(|statements| Statement::Block { statements })
+		} else {
+			self.expression_statement()
+		};
+		self.loops = loops;
+		self.deferred = deferred;
+
+		Ok(Statement::Defer {
+			keyword,
+			body: Box::new(body?),
+		})
+	}
+
+	fn attempt_statement(&mut self) -> Result<Statement, ()> {
+		let keyword = self.previous();
+
+		self.consume_or_report(
+			TokenType::LeftBrace,
+			"Expected an opening `{` after `attempt`".to_string(),
+		)?;
+		let body = Box::new(Statement::Block {
+			statements: self.block_statement()?,
+		});
+
+		self.consume_or_report(
+			TokenType::Rescue,
+			"Expected `rescue` after the body of an `attempt` statement".to_string(),
+		)?;
+		let variable = self.consume_or_report(
+			TokenType::Identifier,
+			"Expected the name of the error after `rescue`".to_string(),
+		)?;
+		self.consume_or_report(
+			TokenType::LeftBrace,
+			format!(
+				"Expected an opening `{{` after `rescue {}`",
+				variable.lexeme()
+			),
+		)?;
+		let handler = Box::new(Statement::Block {
+			statements: self.block_statement()?,
+		});
+
+		Ok(Statement::Attempt {
+			keyword,
+			body,
+			variable,
+			handler,
+		})
+	}
+
+	fn match_statement(&mut self) -> Result<Statement, ()> {
+		let keyword = self.previous();
+		let value = self.expression()?;
+		let arms = self.match_arms(&keyword, |parser, arrow| match arrow {
+			Some(_) => Ok(Statement::Expr {
+				expr: parser.expression()?,
+			}),
+			None => Ok(Statement::Block {
+				statements: parser.block_statement()?,
+			}),
+		})?;
+		self.consume_eol_or_report("Line must end after a `match` statement".to_string())?;
+
+		Ok(Statement::Match {
+			keyword,
+			value,
+			arms,
+		})
+	}
+
+	fn match_expression(&mut self) -> Result<Expr, ()> {
+		let keyword = self.previous();
+		let value = self.expression()?;
+		let arms = self.match_arms(&keyword, |parser, arrow| match arrow {
+			Some(_) => parser.expression(),
+			None => {
+				parser.report_parsing_error(
+					"Arms of a `match` used as a value must give their value with `->`".to_string(),
+					parser.previous(),
+				);
+				Err(())
+			}
+		})?;
+
+		Ok(Expr::Match {
+			keyword,
+			value: Box::new(value),
+			arms,
+		})
+	}
+
+	/// Arms of a `match` between braces, separated by commas or new lines. Each arm has patterns
+	/// separated by commas, and a body parsed after its `->`, or after its `{` when it has no
+	/// arrow.
+	fn match_arms<T>(
+		&mut self,
+		keyword: &Token,
+		mut body: impl FnMut(&mut Self, Option<Token>) -> Result<T, ()>,
+	) -> Result<Vec<MatchArm<T>>, ()> {
+		self.consume_or_report(
+			TokenType::LeftBrace,
+			"Expected an opening `{` after the value in a `match`".to_string(),
+		)?;
+
+		let mut arms = vec![];
+		loop {
+			while self.match_any(&[TokenType::EOL, TokenType::Comma]) {}
+			if self.match_one(TokenType::RightBrace) {
+				break;
+			}
+			if self.is_at_end() {
+				self.report_parsing_error(
+					"Expected a closing `}` after the arms of the `match`".to_string(),
+					keyword.clone(),
+				);
+				return Err(());
+			}
+
+			let mut patterns = vec![self.pattern()?];
+			while self.match_one(TokenType::Comma) {
+				patterns.push(self.pattern()?);
+			}
+			let guard = if self.match_one(TokenType::If) {
+				Some(self.expression()?)
+			} else {
+				None
+			};
+			let arrow = if self.match_one(TokenType::Arrow) {
+				Some(self.previous())
+			} else {
+				self.consume_or_report(
+					TokenType::LeftBrace,
+					"Expected `->` or an opening `{` after the patterns of a `match` arm"
+						.to_string(),
+				)?;
+				None
+			};
+			// arms with a block can be followed by the next one on the same line
+			let block_arm = arrow.is_none();
+			arms.push(MatchArm {
+				patterns,
+				guard,
+				body: self.nested(|parser| body(parser, arrow))?,
+			});
+
+			if !block_arm
+				&& !matches!(
+					self.peek().token_type(),
+					TokenType::EOL | TokenType::Comma | TokenType::RightBrace
+				) {
+				self.report_parsing_error(
+					"Expected a new line or a `,` after an arm of a `match`".to_string(),
+					self.peek(),
+				);
+				return Err(());
+			}
+		}
+
+		Ok(arms)
+	}
+
+	fn pattern(&mut self) -> Result<Pattern, ()> {
+		if self.match_one(TokenType::Default) {
+			return Ok(Pattern::Default(self.previous()));
+		}
+		// `number(value)` is a conversion, while `number` alone is the type
+		if matches!(
+			self.peek().token_type(),
+			TokenType::Boolean | TokenType::Number | TokenType::String | TokenType::Empty
+		) && self
+			.tokens
+			.get(self.current as usize + 1)
+			.map(Token::token_type)
+			!= Some(TokenType::LeftParen)
+		{
+			return Ok(Pattern::Type(self.advance()));
+		}
+
+		Ok(Pattern::Value(self.expression()?))
+	}
+
+	fn enum_statement(&mut self) -> Result<Statement, ()> {
+		let ident = self.consume_or_report(
+			TokenType::Identifier,
+			"Expected the name of the enum after `enum`".to_string(),
+		)?;
+		self.consume_or_report(
+			TokenType::LeftBrace,
+			format!(
+				"Expected an opening `{{` for the variants of `{}`",
+				ident.lexeme()
+			),
+		)?;
+
+		let mut variants: Vec<Token> = vec![];
+		loop {
+			while self.match_any(&[TokenType::EOL, TokenType::Comma]) {}
+			if self.match_one(TokenType::RightBrace) {
+				break;
+			}
+			let variant = self.consume_or_report(
+				TokenType::Identifier,
+				format!("Expected the name of a variant of `{}`", ident.lexeme()),
+			)?;
+			if variants
+				.iter()
+				.any(|declared| declared.symbol() == variant.symbol())
+			{
+				self.report_parsing_error(
+					format!(
+						"Variant `{}` is declared twice in `{}`",
+						variant.lexeme(),
+						ident.lexeme()
+					),
+					variant,
+				);
+				return Err(());
+			}
+			variants.push(variant);
+		}
+		if variants.is_empty() {
+			self.report_parsing_error(
+				format!("Enum `{}` must have at least one variant", ident.lexeme()),
+				ident,
+			);
+			return Err(());
+		}
+
+		self.consume_eol_or_report("Line must end after the declaration of an enum".to_string())?;
+
+		Ok(Statement::Enum { ident, variants })
+	}
+
 	fn expression_statement(&mut self) -> Result<Statement, ()> {
 		let expr = self.expression()?;
+		if self.match_one(TokenType::Equal) {
+			return self.index_assign_statement(expr);
+		}
 
 		self.consume_eol_or_report("Line must end after an expression statement".to_string())?;
 
@@ -473,7 +1453,7 @@ impl Parser {
 	}
 
 	fn expression(&mut self) -> Result<Expr, ()> {
-		self.or()
+		self.nested(Self::or)
 	}
 
 	fn or(&mut self) -> Result<Expr, ()> {
@@ -513,7 +1493,7 @@ impl Parser {
 	fn equality(&mut self) -> Result<Expr, ()> {
 		let mut expr = self.comparison()?;
 
-		while self.match_any(vec![TokenType::BangEqual, TokenType::EqualEqual]) {
+		while self.match_any(&[TokenType::BangEqual, TokenType::EqualEqual]) {
 			let operator = self.previous();
 			let right = self.comparison()?;
 			expr = Expr::Binary {
@@ -535,7 +1515,7 @@ impl Parser {
 
 		let mut expr = self.term()?;
 
-		while self.match_any(vec![
+		while self.match_any(&[
 			TokenType::GreaterEqual,
 			TokenType::Greater,
 			TokenType::LessEqual,
@@ -556,7 +1536,7 @@ impl Parser {
 	fn term(&mut self) -> Result<Expr, ()> {
 		let mut expr = self.modulo()?;
 
-		while self.match_any(vec![TokenType::Minus, TokenType::Plus]) {
+		while self.match_any(&[TokenType::Minus, TokenType::Plus]) {
 			let operator = self.previous();
 			let right = self.modulo()?;
 			expr = Expr::Binary {
@@ -588,7 +1568,7 @@ impl Parser {
 	fn factor(&mut self) -> Result<Expr, ()> {
 		let mut expr = self.exponentiation()?;
 
-		while self.match_any(vec![TokenType::Slash, TokenType::Star]) {
+		while self.match_any(&[TokenType::Slash, TokenType::Star]) {
 			let operator = self.previous();
 			let right = self.exponentiation()?;
 			expr = Expr::Binary {
@@ -618,9 +1598,9 @@ impl Parser {
 	}
 
 	fn unary(&mut self) -> Result<Expr, ()> {
-		if self.match_any(vec![TokenType::Minus, TokenType::Bang]) {
+		if self.match_any(&[TokenType::Minus, TokenType::Bang]) {
 			let operator = self.previous();
-			let expr = self.unary()?;
+			let expr = self.nested(Self::unary)?;
 			return Ok(Expr::Unary {
 				operator,
 				expr: Box::new(expr),
@@ -635,7 +1615,31 @@ impl Parser {
 
 		loop {
 			if self.match_one(TokenType::LeftParen) {
-				expr = self.finish_call(expr)?;
+				let (arguments, closing_paren) = self.arguments()?;
+				expr = Expr::Call {
+					callee: Box::new(expr),
+					closing_paren,
+					arguments,
+				};
+			} else if self.match_any(&[TokenType::Dot, TokenType::InterrogationDot]) {
+				expr = self.method_call(expr)?;
+			} else if self.match_any(&[TokenType::LeftBracket, TokenType::InterrogationBracket]) {
+				let bracket = self.previous();
+				let index = self.expression()?;
+				self.consume_or_report(
+					TokenType::RightBracket,
+					"Expected a closing `]` after the index".to_string(),
+				)?;
+				expr = Expr::Index {
+					object: Box::new(expr),
+					bracket,
+					index: Box::new(index),
+				};
+			} else if self.match_one(TokenType::Interrogation) {
+				expr = Expr::Postfix {
+					expr: Box::new(expr),
+					operator: self.previous(),
+				};
 			} else {
 				break;
 			}
@@ -644,7 +1648,88 @@ impl Parser {
 		Ok(expr)
 	}
 
-	fn finish_call(&mut self, expr: Expr) -> Result<Expr, ()> {
+	/// `value.f(args)` is the same call as `f(value, args)`, and `value?.f(args)` skips the call
+	/// when the value is empty. On a module imported with `as`, `module.f(args)` calls the `f`
+	/// of the module.
+	fn method_call(&mut self, receiver: Expr) -> Result<Expr, ()> {
+		let dot = self.previous();
+		let name = self.consume_or_report(
+			TokenType::Identifier,
+			format!("Expected the name of a function after `{}`", dot.lexeme()),
+		)?;
+		if dot.token_type() == TokenType::Dot && !self.next_is(TokenType::LeftParen) {
+			return Ok(Expr::Field {
+				object: Box::new(receiver),
+				name,
+			});
+		}
+		self.consume_or_report(
+			TokenType::LeftParen,
+			format!(
+				"Expected a `(` after `{}{}`, only functions can be used after a `{}`",
+				dot.lexeme(),
+				name.lexeme(),
+				dot.lexeme()
+			),
+		)?;
+
+		let (mut arguments, closing_paren) = self.arguments()?;
+		if let Expr::Variable { name: module } = &receiver {
+			if dot.token_type() == TokenType::Dot && self.modules.contains(&module.symbol()) {
+				return Ok(Expr::Call {
+					callee: Box::new(Expr::Field {
+						object: Box::new(receiver),
+						name,
+					}),
+					closing_paren,
+					arguments,
+				});
+			}
+		}
+		if dot.token_type() == TokenType::InterrogationDot {
+			return Ok(Expr::SafeCall {
+				receiver: Box::new(receiver),
+				name,
+				closing_paren,
+				arguments,
+			});
+		}
+
+		arguments.insert(0, receiver);
+		Ok(Expr::Call {
+			callee: Box::new(Expr::Variable { name }),
+			closing_paren,
+			arguments,
+		})
+	}
+
+	fn conversion(&mut self) -> Result<Expr, ()> {
+		let target = self.previous();
+		self.consume_or_report(
+			TokenType::LeftParen,
+			format!(
+				"Expected a `(` after `{}`, to convert a value",
+				target.lexeme()
+			),
+		)?;
+		let expr = self.expression()?;
+		let closing_paren = self.consume_or_report(
+			TokenType::RightParen,
+			format!(
+				"Expected a closing `)` after the value converted with `{}`",
+				target.lexeme()
+			),
+		)?;
+
+		Ok(Expr::Conversion {
+			target,
+			expr: Box::new(expr),
+			closing_paren,
+		})
+	}
+
+	/// Arguments of a call after its opening `(`, and its closing `)`
+	fn arguments(&mut self) -> Result<(Vec<Expr>, Token), ()> {
 		let mut arguments: Vec<Expr> = vec![];
 
 		if !self.next_is(TokenType::RightParen) {
@@ -668,15 +1753,77 @@ impl Parser {
 			),
 		)?;
 
-		Ok(Expr::Call {
-			callee: Box::new(expr),
-			closing_paren,
-			arguments,
+		Ok((arguments, closing_paren))
+	}
+
+	fn list(&mut self) -> Result<Expr, ()> {
+		let opening_bracket = self.previous();
+		let mut elements = vec![];
+		loop {
+			while self.match_one(TokenType::EOL) {}
+			if self.match_one(TokenType::RightBracket) {
+				break;
+			}
+			elements.push(self.expression()?);
+			while self.match_one(TokenType::EOL) {}
+			if !self.match_one(TokenType::Comma) {
+				self.consume_or_report(
+					TokenType::RightBracket,
+					"Expected a `,` or a closing `]` after an element of the list".to_string(),
+				)?;
+				break;
+			}
+		}
+
+		Ok(Expr::List {
+			opening_bracket,
+			elements,
+		})
+	}
+
+	/// Whether the `(` at the current token starts the parameters of a lambda, like `(a, b) ->`,
+	/// rather than a grouping
+	fn is_lambda(&self) -> bool {
+		let mut current = self.current as usize + 1;
+		let mut expects_name = true;
+		loop {
+			match self.tokens.get(current).map(Token::token_type) {
+				Some(TokenType::Identifier) if expects_name => expects_name = false,
+				Some(TokenType::Comma) if !expects_name => expects_name = true,
+				// `()` has no parameters, but `(a,)` is not a list of parameters
+				Some(TokenType::RightParen)
+					if !expects_name || current == self.current as usize + 1 =>
+				{
+					break
+				}
+				_ => return false,
+			}
+			current += 1;
+		}
+		self.tokens.get(current + 1).map(Token::token_type) == Some(TokenType::Arrow)
+	}
+
+	fn lambda(&mut self) -> Result<Expr, ()> {
+		let opening_paren = self.advance();
+		let mut params = vec![];
+		while !self.match_one(TokenType::RightParen) {
+			params.push(self.advance());
+			self.match_one(TokenType::Comma);
+		}
+		// the `->`, checked by `is_lambda`
+		self.advance();
+
+		let body = self.expression()?;
+
+		Ok(Expr::Lambda {
+			opening_paren,
+			params,
+			body: Box::new(body),
 		})
 	}
 
 	fn primary(&mut self) -> Result<Expr, ()> {
-		if self.match_any(vec![TokenType::False, TokenType::True]) {
+		if self.match_any(&[TokenType::False, TokenType::True]) {
 			return Ok(Expr::Primary {
 				value: self.previous(),
 			});
@@ -698,6 +1845,22 @@ impl Parser {
 			_ => (),
 		}
 
+		if self.match_any(&[TokenType::Boolean, TokenType::Number, TokenType::String]) {
+			return self.conversion();
+		}
+
+		if self.match_one(TokenType::Match) {
+			return self.match_expression();
+		}
+
+		if self.match_one(TokenType::LeftBracket) {
+			return self.list();
+		}
+
+		if self.next_is(TokenType::LeftParen) && self.is_lambda() {
+			return self.lambda();
+		}
+
 		if self.match_one(TokenType::LeftParen) {
 			let expr = self.expression()?;
 			self.consume_or_report(
@@ -732,3 +1895,78 @@ impl Parser {
 		Err(())
 	}
 }
+
+/// Binary operator applied by a compound assignment, like `+` for `+=`
+fn compound_operator(token_type: TokenType) -> Option<TokenType> {
+	match token_type {
+		TokenType::PlusEqual => Some(TokenType::Plus),
+		TokenType::MinusEqual => Some(TokenType::Minus),
+		TokenType::StarEqual => Some(TokenType::Star),
+		TokenType::SlashEqual => Some(TokenType::Slash),
+		TokenType::PercentEqual => Some(TokenType::Percent),
+		TokenType::CaretEqual => Some(TokenType::Caret),
+		_ => None,
+	}
+}
+
+/// Removes the line breaks directly inside parentheses, so that long expressions and argument
+/// lists can be split across lines. Parentheses that are never closed keep their line breaks, to
+/// report the missing `)` on the right line.
+fn join_parenthesized_lines(tokens: Vec<Token>) -> Vec<Token> {
+	// indexes of the brackets that are open, and of the line breaks inside parentheses
+	let mut open: Vec<usize> = vec![];
+	let mut line_breaks: Vec<(usize, usize)> = vec![];
+	let mut closed = vec![false; tokens.len()];
+	for (index, token) in tokens.iter().enumerate() {
+		match token.token_type() {
+			TokenType::LeftParen
+			| TokenType::LeftBracket
+			| TokenType::InterrogationBracket
+			| TokenType::LeftBrace => open.push(index),
+			TokenType::EOL => {
+				if let Some(&opening) = open.last() {
+					if tokens[opening].token_type() == TokenType::LeftParen {
+						line_breaks.push((index, opening));
+					}
+				}
+			}
+			TokenType::RightParen | TokenType::RightBracket => {
+				let opening = if token.token_type() == TokenType::RightParen {
+					TokenType::LeftParen
+				} else {
+					TokenType::LeftBracket
+				};
+				if let Some(&index) = open.last() {
+					let token_type = tokens[index].token_type();
+					if token_type == opening
+						|| opening == TokenType::LeftBracket
+							&& token_type == TokenType::InterrogationBracket
+					{
+						closed[index] = true;
+						open.pop();
+					}
+				}
+			}
+			TokenType::RightBrace => {
+				// brackets left open in a block are never closed
+				while let Some(index) = open.pop() {
+					if tokens[index].token_type() == TokenType::LeftBrace {
+						break;
+					}
+				}
+			}
+			_ => {}
+		}
+	}
+
+	let mut removed = vec![false; tokens.len()];
+	for (index, opening) in line_breaks {
+		removed[index] = closed[opening];
+	}
+	tokens
+		.into_iter()
+		.zip(removed)
+		.filter(|(_, removed)| !removed)
+		.map(|(token, _)| token)
+		.collect()
+}
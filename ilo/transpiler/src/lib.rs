@@ -0,0 +1,1532 @@
+//! Transpilation of parsed scripts to JavaScript.
+//!
+//! The generated code keeps the structure of the script (one JavaScript statement per ilo
+//! statement), declares variables with `let` where ilo would create them, and replaces the
+//! native functions with small helpers (`out` becomes `console.log`). Runtime type checks are
+//! not reproduced: a script with type errors produces JavaScript that runs with JavaScript's
+//! semantics.
+//!
+//! `transpile_with_source_map` also gives a source map, mapping each line of JavaScript to the
+//! statement it comes from, so that errors in the generated code point to the script.
+
+use lexer::{Token, TokenType};
+use parser::{Expr, Iterable, Pattern, Statement};
+use std::collections::{BTreeMap, HashSet};
+
+/// Identifiers that cannot be used as variable names in JavaScript
+const RESERVED_WORDS: [&str; 44] = [
+	"arguments",
+	"await",
+	"case",
+	"catch",
+	"class",
+	"const",
+	"debugger",
+	"delete",
+	"do",
+	"enum",
+	"eval",
+	"export",
+	"extends",
+	"finally",
+	"function",
+	"implements",
+	"import",
+	"instanceof",
+	"interface",
+	"let",
+	"new",
+	"null",
+	"package",
+	"private",
+	"protected",
+	"public",
+	"static",
+	"super",
+	"switch",
+	"this",
+	"throw",
+	"try",
+	"typeof",
+	"undefined",
+	"var",
+	"void",
+	"with",
+	"yield",
+	"console",
+	"require",
+	"process",
+	"prompt",
+	"Math",
+	"performance",
+];
+
+/// JavaScript replacement of each native function and constant, and the code of the helper it needs, if any
+fn native_function(name: &str) -> Option<(&'static str, Option<&'static str>)> {
+	Some(match name {
+		"out" => ("console.log", None),
+		"ask" => ("$ilo_ask", Some(ASK_HELPER)),
+		"multi_select" => ("$ilo_multi_select", Some(MULTI_SELECT_HELPER)),
+		"args" => (
+			"$ilo_args",
+			Some(
+				r#"function $ilo_args() {
+	return typeof process === "undefined" ? [] : process.argv.slice(2);
+}"#,
+			),
+		),
+		"read_line" => (
+			"$ilo_read_line",
+			Some(
+				r#"function $ilo_read_line() {
+	const fs = require("fs");
+	const buffer = Buffer.alloc(1);
+	const bytes = [];
+	let ended = true;
+	try {
+		while (fs.readSync(0, buffer, 0, 1) === 1) {
+			ended = false;
+			if (buffer[0] === 10) {
+				break;
+			}
+			bytes.push(buffer[0]);
+		}
+	} catch {}
+	return ended ? null : Buffer.from(bytes).toString().replace(/\r$/, "");
+}"#,
+			),
+		),
+		"read_all" => (
+			"$ilo_read_all",
+			Some(
+				r#"function $ilo_read_all() {
+	try {
+		return require("fs").readFileSync(0).toString();
+	} catch {
+		return "";
+	}
+}"#,
+			),
+		),
+		"size" => (
+			"$ilo_size",
+			Some(
+				r#"function $ilo_size(value) {
+	if (Array.isArray(value)) {
+		return value.length;
+	}
+	return typeof value === "string" ? [...value].length : 0;
+}"#,
+			),
+		),
+		"time" => (
+			"$ilo_time",
+			Some(
+				r#"function $ilo_time() {
+	return Date.now() * 1e6;
+}"#,
+			),
+		),
+		"inspect" => (
+			"$ilo_inspect",
+			Some(
+				r#"function $ilo_inspect(value) {
+	if (value === null) {
+		console.log("empty");
+	} else if (typeof value === "string") {
+		console.log(`string ${JSON.stringify(value)}`);
+	} else if (typeof value === "function") {
+		console.log(`f ${value.name}(${value.length} argument${value.length === 1 ? "" : "s"}) {}`);
+	} else if (Array.isArray(value)) {
+		console.log(`list ${JSON.stringify(value)}`);
+	} else {
+		console.log(`${typeof value} ${value}`);
+	}
+	return null;
+}"#,
+			),
+		),
+		"clock_ms" | "timer_start" => ("performance.now", None),
+		"timer_elapsed" => (
+			"$ilo_timer_elapsed",
+			Some(
+				r#"function $ilo_timer_elapsed(start) {
+	return performance.now() - start;
+}"#,
+			),
+		),
+		"format_duration" | "duration" => (
+			"$ilo_format_duration",
+			Some(
+				r#"function $ilo_format_duration(milliseconds) {
+	if (typeof milliseconds !== "number" || !isFinite(milliseconds) || milliseconds < 0) {
+		throw new Error("`format_duration` can only take a finite non-negative number of milliseconds");
+	}
+	milliseconds = Math.round(milliseconds);
+	if (milliseconds < 1000) {
+		return `${milliseconds}ms`;
+	}
+	if (milliseconds < 60000) {
+		return `${Math.round(milliseconds / 10) / 100}s`;
+	}
+	const seconds = Math.round(milliseconds / 1000);
+	const hours = Math.floor(seconds / 3600);
+	const rest = `${Math.floor((seconds % 3600) / 60)}m ${seconds % 60}s`;
+	return hours > 0 ? `${hours}h ${rest}` : rest;
+}"#,
+			),
+		),
+		"cmd" => (
+			"$ilo_cmd",
+			Some(
+				r#"function $ilo_cmd(command) {
+	const words = [];
+	let word = null;
+	let quote = null;
+	for (let i = 0; i < command.length; i++) {
+		const character = command[i];
+		if (character === "\\" && (command[i + 1] === '"' || command[i + 1] === "'")) {
+			word = (word ?? "") + command[++i];
+		} else if (character === quote) {
+			quote = null;
+		} else if (quote !== null) {
+			word += character;
+		} else if (character === '"' || character === "'") {
+			quote = character;
+			word = word ?? "";
+		} else if (/\s/.test(character)) {
+			if (word !== null) {
+				words.push(word);
+			}
+			word = null;
+		} else {
+			word = (word ?? "") + character;
+		}
+	}
+	if (word !== null) {
+		words.push(word);
+	}
+	const [program, ...args] = words;
+	if (program === undefined) {
+		return "";
+	}
+	try {
+		return require("child_process").execFileSync(program, args).toString();
+	} catch {
+		return "";
+	}
+}"#,
+			),
+		),
+		"shell" => (
+			"$ilo_shell",
+			Some(
+				r#"function $ilo_shell(command) {
+	try {
+		return require("child_process").execSync(command).toString();
+	} catch {
+		return "";
+	}
+}"#,
+			),
+		),
+		"download" => (
+			"$ilo_download",
+			Some(
+				r#"function $ilo_download(url, destination) {
+	try {
+		require("child_process").execFileSync("curl", ["-fsSL", "-o", destination, url]);
+		return require("fs").statSync(destination).size;
+	} catch {
+		return null;
+	}
+}"#,
+			),
+		),
+		"watch" => (
+			"$ilo_watch",
+			Some(
+				r#"function $ilo_watch(path, handler) {
+	const watcher = require("fs").watch(path, { recursive: true }, (_, file) => {
+		if (handler(require("path").join(path, file ?? "")) === false) {
+			watcher.close();
+		}
+	});
+	return null;
+}"#,
+			),
+		),
+		"zip" | "tar" => (
+			"$ilo_archive",
+			Some(
+				r#"function $ilo_archive(source, destination) {
+	const path = require("path");
+	const [program, options] = destination.endsWith(".zip") ? ["zip", ["-qr"]] : ["tar", ["-czf"]];
+	try {
+		require("child_process").execFileSync(
+			program,
+			[...options, path.resolve(destination), path.basename(source)],
+			{ cwd: path.dirname(source) },
+		);
+		return true;
+	} catch {
+		return false;
+	}
+}"#,
+			),
+		),
+		"unzip" | "untar" => (
+			"$ilo_extract",
+			Some(
+				r#"function $ilo_extract(archive, destination) {
+	const [program, options] = archive.endsWith(".zip") ? ["unzip", ["-qo", archive, "-d"]] : ["tar", ["-xzf", archive, "-C"]];
+	try {
+		require("fs").mkdirSync(destination, { recursive: true });
+		require("child_process").execFileSync(program, [...options, destination]);
+		return true;
+	} catch {
+		return false;
+	}
+}"#,
+			),
+		),
+		"file_exists" => (
+			"$ilo_file_exists",
+			Some(
+				r#"function $ilo_file_exists(path) {
+	return require("fs").existsSync(path);
+}"#,
+			),
+		),
+		"remove_file" => (
+			"$ilo_remove_file",
+			Some(
+				r#"function $ilo_remove_file(path) {
+	try {
+		require("fs").unlinkSync(path);
+		return true;
+	} catch {
+		return false;
+	}
+}"#,
+			),
+		),
+		"copy_file" => (
+			"$ilo_copy_file",
+			Some(
+				r#"function $ilo_copy_file(source, destination) {
+	try {
+		require("fs").copyFileSync(source, destination);
+		return true;
+	} catch {
+		return false;
+	}
+}"#,
+			),
+		),
+		"move_file" => (
+			"$ilo_move_file",
+			Some(
+				r#"function $ilo_move_file(source, destination) {
+	const fs = require("fs");
+	try {
+		fs.renameSync(source, destination);
+		return true;
+	} catch {
+		try {
+			fs.copyFileSync(source, destination);
+			fs.unlinkSync(source);
+			return true;
+		} catch {
+			return false;
+		}
+	}
+}"#,
+			),
+		),
+		"list_dir" => (
+			"$ilo_list_dir",
+			Some(
+				r#"function $ilo_list_dir(path, recursive) {
+	try {
+		return require("fs")
+			.readdirSync(path, { recursive })
+			.map((entry) => entry.replaceAll("\\", "/"))
+			.sort();
+	} catch {
+		console.log(`error: \`list_dir\` could not read the directory \`${path}\``);
+		return [];
+	}
+}"#,
+			),
+		),
+		"json_parse" => (
+			"$ilo_json_parse",
+			Some(
+				r#"function $ilo_json_parse(text) {
+	try {
+		return JSON.parse(text, (_, value) => {
+			if (value !== null && typeof value === "object" && !Array.isArray(value)) {
+				throw new Error("objects cannot be parsed, as ilo has no maps");
+			}
+			return value;
+		});
+	} catch (error) {
+		throw new Error(`\`json_parse\` could not parse the JSON (${error.message})`);
+	}
+}"#,
+			),
+		),
+		"json_stringify" => (
+			"$ilo_json_stringify",
+			Some(
+				r#"function $ilo_json_stringify(value, pretty) {
+	return JSON.stringify(value, null, pretty ? "\t" : undefined) ?? "";
+}"#,
+			),
+		),
+		"glob" => ("$ilo_glob", Some(GLOB_HELPER)),
+		"glob_match" => ("$ilo_glob_match", Some(GLOB_MATCH_HELPER)),
+		"spawn" => (
+			"$ilo_spawn",
+			Some(
+				r#"function $ilo_spawn(task) {
+	// JavaScript cannot run the function in parallel without making it asynchronous
+	return { result: task() };
+}"#,
+			),
+		),
+		"wait" => (
+			"$ilo_wait",
+			Some(
+				r#"function $ilo_wait(task) {
+	return task.result;
+}"#,
+			),
+		),
+		"on_interrupt" => (
+			"$ilo_on_interrupt",
+			Some(
+				r#"function $ilo_on_interrupt(handler) {
+	process.removeAllListeners("SIGINT");
+	process.once("SIGINT", () => {
+		handler();
+		process.exit(130);
+	});
+	return null;
+}"#,
+			),
+		),
+		"style" => (
+			"$ilo_style",
+			Some(
+				r#"function $ilo_style(text, styles) {
+	return text;
+}"#,
+			),
+		),
+		"type" => (
+			"$ilo_type",
+			Some(
+				r#"function $ilo_type(value) {
+	if (value === null || value === undefined) {
+		return "empty";
+	} else if (Array.isArray(value)) {
+		return "list";
+	} else if (typeof value === "function") {
+		return `function(${value.length})`;
+	} else if (typeof value === "object") {
+		const text = String(value);
+		return text.startsWith("enum ") ? "enum" : text.split(".")[0];
+	}
+	return typeof value;
+}"#,
+			),
+		),
+		"upper" => (
+			"$ilo_upper",
+			Some(
+				r#"function $ilo_upper(text) {
+	return text.toUpperCase();
+}"#,
+			),
+		),
+		"lower" => (
+			"$ilo_lower",
+			Some(
+				r#"function $ilo_lower(text) {
+	return text.toLowerCase();
+}"#,
+			),
+		),
+		"index_of" => (
+			"$ilo_index_of",
+			Some(
+				r#"function $ilo_index_of(text, part) {
+	const index = text.indexOf(part);
+	return index < 0 ? null : Array.from(text.slice(0, index)).length;
+}"#,
+			),
+		),
+		"last_index_of" => (
+			"$ilo_last_index_of",
+			Some(
+				r#"function $ilo_last_index_of(text, part) {
+	const index = text.lastIndexOf(part);
+	return index < 0 ? null : Array.from(text.slice(0, index)).length;
+}"#,
+			),
+		),
+		"pad_start" => (
+			"$ilo_pad_start",
+			Some(
+				r#"function $ilo_pad_start(text, width, fill) {
+	const missing = Math.max(Math.floor(width) - Array.from(text).length, 0);
+	return Array.from(fill.repeat(missing)).slice(0, missing).join("") + text;
+}"#,
+			),
+		),
+		"pad_end" => (
+			"$ilo_pad_end",
+			Some(
+				r#"function $ilo_pad_end(text, width, fill) {
+	const missing = Math.max(Math.floor(width) - Array.from(text).length, 0);
+	return text + Array.from(fill.repeat(missing)).slice(0, missing).join("");
+}"#,
+			),
+		),
+		"abs" => ("Math.abs", None),
+		"floor" => ("Math.floor", None),
+		"ceil" => ("Math.ceil", None),
+		"round" => ("Math.round", None),
+		"sqrt" => ("Math.sqrt", None),
+		"PI" => ("Math.PI", None),
+		"E" => ("Math.E", None),
+		"INFINITY" => ("Infinity", None),
+		_ => return None,
+	})
+}
+
+/// Reads an answer, or `null` if there is none after the optional number of seconds
+const ASK_HELPER: &str = r#"function $ilo_ask(question, seconds) {
+	if (seconds !== undefined && !(seconds > 0)) {
+		throw new Error("`ask` can only take a positive number of seconds");
+	}
+	if (typeof prompt === "function") {
+		return prompt(question) ?? "";
+	}
+	process.stdout.write(question);
+	if (seconds !== undefined) {
+		try {
+			return require("child_process")
+				.execFileSync("bash", ["-c", 'read -r -t "$1" line && printf %s "$line"', "bash", String(seconds)], {
+					stdio: ["inherit", "pipe", "inherit"],
+				})
+				.toString();
+		} catch {
+			console.log();
+			return null;
+		}
+	}
+	const fs = require("fs");
+	const buffer = Buffer.alloc(1);
+	const bytes = [];
+	try {
+		while (fs.readSync(0, buffer, 0, 1) === 1 && buffer[0] !== 10) {
+			bytes.push(buffer[0]);
+		}
+	} catch {}
+	return Buffer.from(bytes).toString();
+}"#;
+
+/// Lists the options to choose them by their numbers. Needs `ASK_HELPER` too
+const MULTI_SELECT_HELPER: &str = r#"function $ilo_multi_select(question, options) {
+	options.forEach((option, index) => console.log(`${index + 1}. ${option}`));
+	const chosen = $ilo_ask(`${question} (numbers separated by spaces) `).split(/[\s,]+/);
+	return options.filter((_, index) => chosen.includes(String(index + 1)));
+}"#;
+
+const GLOB_MATCH_HELPER: &str = r#"function $ilo_glob_match(pattern, path) {
+	let regex = "";
+	for (let i = 0; i < pattern.length; i++) {
+		const end = pattern[i] === "[" ? pattern.indexOf("]", i + 2) : -1;
+		if (pattern.startsWith("**/", i)) {
+			regex += "(?:.*/)?";
+			i += 2;
+		} else if (pattern.startsWith("**", i)) {
+			regex += ".*";
+			i++;
+		} else if (pattern[i] === "*") {
+			regex += "[^/]*";
+		} else if (pattern[i] === "?") {
+			regex += "[^/]";
+		} else if (end !== -1) {
+			const set = pattern.slice(i + 1, end).replace(/^!/, "^").replace(/[\\\]]/g, "\\$&");
+			regex += `(?!/)[${set}]`;
+			i = end;
+		} else {
+			regex += pattern[i].replace(/[.*+?^${}()|[\]\\/]/g, "\\$&");
+		}
+	}
+	return new RegExp(`^${regex}$`).test(path);
+}"#;
+
+/// Needs `GLOB_MATCH_HELPER` too
+const GLOB_HELPER: &str = r#"function $ilo_glob(pattern) {
+	const wildcard = pattern.search(/[*?[]/);
+	const end = pattern.lastIndexOf("/", wildcard < 0 ? pattern.length : wildcard);
+	const base = end < 0 ? "" : end === 0 ? "/" : pattern.slice(0, end);
+	let entries;
+	try {
+		entries = require("fs").readdirSync(base || ".", { recursive: true });
+	} catch {
+		return [];
+	}
+	return entries
+		.map((entry) => (base === "" ? "" : base.endsWith("/") ? base : `${base}/`) + entry.replaceAll("\\", "/"))
+		.filter((path) => $ilo_glob_match(pattern, path))
+		.sort();
+}"#;
+
+const MODULO_HELPER: &str = r#"function $ilo_mod(left, right) {
+	const remainder = left % right;
+	return remainder < 0 ? remainder + Math.abs(right) : remainder;
+}"#;
+
+const RANGE_HELPER: &str = r#"function* $ilo_range(start, end, step) {
+	step ??= start <= end ? 1 : -1;
+	if (step === 0 || !Number.isFinite(step)) {
+		throw new Error(`The step of a range must be a finite number other than 0 (found ${step})`);
+	}
+	for (let index = 0; ; index++) {
+		const number = start + index * step;
+		if (!(step > 0 ? number <= end : number >= end)) {
+			return;
+		}
+		yield number;
+	}
+}"#;
+
+const NUMBER_HELPER: &str = r#"function $ilo_number(value) {
+	if (typeof value === "number" || value === null) {
+		return value;
+	}
+	if (typeof value === "boolean") {
+		return value ? 1 : 0;
+	}
+	const number = typeof value === "string" && value.trim() !== "" ? Number(value) : NaN;
+	return Number.isFinite(number) ? number : null;
+}"#;
+
+const STRING_HELPER: &str = r#"function $ilo_string(value) {
+	return value === null || typeof value === "function" ? "" : String(value);
+}"#;
+
+const BOOLEAN_HELPER: &str = r#"function $ilo_boolean(value) {
+	if (typeof value === "boolean" || value === null) {
+		return value;
+	}
+	if (value === 0 || value === "false") {
+		return false;
+	}
+	return value === 1 || value === "true" ? true : null;
+}"#;
+
+const MULTIPLICATION_HELPER: &str = r#"function $ilo_mul(left, right) {
+	return typeof left === "string" ? left.repeat(right) : left * right;
+}"#;
+
+/// Enums and their variants are frozen objects, only equal to themselves, and displayed like in
+/// ilo, including by `console.log` in Node.js
+const ENUM_HELPER: &str = r#"function $ilo_enum(name, variants) {
+	const displayed = (text) => ({
+		toString: () => text,
+		[Symbol.for("nodejs.util.inspect.custom")]: () => text,
+	});
+	const enumeration = displayed(`enum ${name} { ${variants.join(", ")} }`);
+	for (const variant of variants) {
+		enumeration[variant] = Object.freeze(displayed(`${name}.${variant}`));
+	}
+	return Object.freeze(enumeration);
+}"#;
+
+/// Imported files must be transpiled next to the script, to a file with the `.js` extension. Its
+/// code runs in a function returning its top-level variables and functions in an object, which
+/// is the module, or whose properties become global variables.
+const IMPORT_HELPER: &str = r#"function $ilo_import(path) {
+	const file = require("path").join(__dirname, path.replace(/\.ilo$/, "") + ".js");
+	const code = require("fs").readFileSync(file).toString();
+	const names = [...code.matchAll(/^(?:let|const|function) ([A-Za-z_]\w*)/gm)].map((match) => match[1]);
+	const module = new Function("require", "__dirname", `${code}\nreturn { ${[...new Set(names)].join(", ")} };`);
+	return module(require, require("path").dirname(file));
+}"#;
+
+/// Lists are equal when their elements are, while `===` compares arrays by reference
+const EQUALS_HELPER: &str = r#"function $ilo_equals(left, right) {
+	if (Array.isArray(left) && Array.isArray(right)) {
+		return (
+			left.length === right.length &&
+			left.every((element, index) => $ilo_equals(element, right[index]))
+		);
+	}
+	return left === right;
+}"#;
+
+/// Negative indexes count from the end of the list, like in ilo
+const INDEX_HELPER: &str = r#"function $ilo_elements(sequence) {
+	return typeof sequence === "string" ? Array.from(sequence) : sequence;
+}
+
+function $ilo_position(sequence, index) {
+	const length = $ilo_elements(sequence).length;
+	const position = index < 0 ? length + index : index;
+	if (!Number.isInteger(index) || position < 0 || position >= length) {
+		const bounds = typeof sequence === "string" ? `a string of ${length} characters` : `a list of ${length} elements`;
+		throw new Error(`Index ${index} is out of the bounds of ${bounds}`);
+	}
+	return position;
+}
+
+function $ilo_index(sequence, index) {
+	return $ilo_elements(sequence)[$ilo_position(sequence, index)];
+}
+
+function $ilo_safe_index(sequence, index) {
+	if (sequence === null) {
+		return null;
+	}
+	const elements = $ilo_elements(sequence);
+	const position = index < 0 ? elements.length + index : index;
+	return Number.isInteger(index) && position >= 0 && position < elements.length ? elements[position] : null;
+}
+
+function $ilo_set(list, index, value) {
+	list[$ilo_position(list, index)] = value;
+}"#;
+
+const UNMATCHED_HELPER: &str = r#"function $ilo_unmatched(value) {
+	throw new Error(`No arm of the \`match\` matches ${value}, it needs a \`default\` arm`);
+}"#;
+
+pub fn transpile(statements: &[Statement]) -> String {
+	transpile_with_source_map(statements, "", "").0
+}
+
+/// Transpiles the statements, and gives the JavaScript code with its source map (version 3),
+/// `source` being the path of the script and `file` the name of the JavaScript file
+pub fn transpile_with_source_map(
+	statements: &[Statement],
+	source: &str,
+	file: &str,
+) -> (String, String) {
+	let mut transpiler = Transpiler {
+		output: String::new(),
+		indentation: 0,
+		scopes: vec![HashSet::new()],
+		helpers: BTreeMap::new(),
+		position: None,
+		positions: vec![],
+	};
+
+	if statements.iter().any(defers) {
+		transpiler.deferring_statements(statements, &[]);
+	} else {
+		for statement in statements {
+			transpiler.statement(statement);
+		}
+	}
+
+	let mut result = String::new();
+	for helper in transpiler.helpers.values() {
+		result.push_str(helper);
+		result.push_str("\n\n");
+	}
+	// the helpers do not come from the script
+	let mut positions = vec![None; result.matches('\n').count()];
+	positions.extend(transpiler.positions);
+	result.push_str(&transpiler.output);
+
+	let source_map = format!(
+		r#"{{"version":3,"file":{},"sources":[{}],"names":[],"mappings":{}}}"#,
+		json_string(file),
+		json_string(source),
+		json_string(&mappings(&positions))
+	);
+	(result, source_map)
+}
+
+/// Mappings of a source map, from the position in the script of each line of JavaScript. Each
+/// line gets a segment for its first column, with the fields encoded as differences with the
+/// previous segment.
+fn mappings(positions: &[Option<(i64, i64)>]) -> String {
+	let mut mappings = String::new();
+	let (mut previous_line, mut previous_column) = (0, 0);
+	for (index, position) in positions.iter().enumerate() {
+		if index > 0 {
+			mappings.push(';');
+		}
+		if let Some((line, column)) = position {
+			// lines and columns start at 0 in source maps, and at 1 in tokens
+			let (line, column) = (line - 1, column - 1);
+			for field in [0, 0, line - previous_line, column - previous_column] {
+				push_vlq(&mut mappings, field);
+			}
+			(previous_line, previous_column) = (line, column);
+		}
+	}
+	mappings
+}
+
+/// Encodes a number as a base 64 variable-length quantity, the sign being the lowest bit
+fn push_vlq(output: &mut String, value: i64) {
+	const DIGITS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+	let mut value = if value < 0 {
+		(-value << 1) | 1
+	} else {
+		value << 1
+	};
+	loop {
+		let mut digit = value & 0b11111;
+		value >>= 5;
+		if value > 0 {
+			// more digits follow
+			digit |= 0b100000;
+		}
+		output.push(DIGITS[digit as usize] as char);
+		if value == 0 {
+			break;
+		}
+	}
+}
+
+struct Transpiler {
+	output: String,
+	indentation: usize,
+	/// Names of the variables declared in each scope, to know when `let` is needed
+	scopes: Vec<HashSet<String>>,
+	/// Helper functions needed by the generated code, by name
+	helpers: BTreeMap<&'static str, &'static str>,
+	/// Line and column in the script of the statement being transpiled
+	position: Option<(i64, i64)>,
+	/// Position in the script of each line of `output`
+	positions: Vec<Option<(i64, i64)>>,
+}
+
+impl Transpiler {
+	fn line(&mut self, line: &str) {
+		self.positions.push(self.position);
+		self.output.push_str(&"\t".repeat(self.indentation));
+		self.output.push_str(line);
+		self.output.push('\n');
+	}
+
+	fn is_declared(&self, name: &str) -> bool {
+		self.scopes.iter().any(|scope| scope.contains(name))
+	}
+
+	/// Declares the name in the current scope, and returns whether it was not declared yet
+	fn declare(&mut self, name: &str) -> bool {
+		if self.is_declared(name) {
+			return false;
+		}
+		self.scopes.last_mut().unwrap().insert(name.to_string());
+		true
+	}
+
+	fn identifier(&self, name: &str) -> String {
+		if RESERVED_WORDS.contains(&name) || name.starts_with("$ilo_") {
+			format!("{name}_")
+		} else {
+			name.to_string()
+		}
+	}
+
+	fn block(&mut self, statements: &[Statement], parameters: &[Token]) {
+		self.indentation += 1;
+		self.scopes.push(
+			parameters
+				.iter()
+				.map(|parameter| parameter.lexeme().to_string())
+				.collect(),
+		);
+		for statement in statements {
+			self.statement(statement);
+		}
+		self.scopes.pop();
+		self.indentation -= 1;
+	}
+
+	/// Writes a function body that contains `defer` statements, indented like a block
+	fn deferring_block(&mut self, statements: &[Statement], parameters: &[Token]) {
+		self.indentation += 1;
+		self.deferring_statements(statements, parameters);
+		self.indentation -= 1;
+	}
+
+	/// Writes the statements in a block that runs the deferred functions when it is left, the
+	/// last one first, including when an exception is thrown
+	fn deferring_statements(&mut self, statements: &[Statement], parameters: &[Token]) {
+		self.line("const $ilo_deferred = [];");
+		self.line("try {");
+		self.block(statements, parameters);
+		self.line("} finally {");
+		self.line("\t$ilo_deferred.reverse().forEach((deferred) => deferred());");
+		self.line("}");
+	}
+
+	fn statement(&mut self, statement: &Statement) {
+		// blocks do not have a token, and their lines belong to the statement around them
+		let previous_position = self.position;
+		if !matches!(statement, Statement::Block { .. }) {
+			let token = statement.first_token();
+			self.position = Some((token.line(), token.column()));
+		}
+		self.statement_code(statement);
+		self.position = previous_position;
+	}
+
+	fn statement_code(&mut self, statement: &Statement) {
+		match statement {
+			Statement::Expr { expr } => {
+				let expr = self.expr(expr);
+				self.line(&format!("{expr};"));
+			}
+			Statement::Assignment { ident, value } => {
+				let value = self.expr(value);
+				let name = self.identifier(ident.lexeme());
+				if self.declare(ident.lexeme()) {
+					self.line(&format!("let {name} = {value};"));
+				} else {
+					self.line(&format!("{name} = {value};"));
+				}
+			}
+			Statement::MultipleAssignment { idents, values } => {
+				let values: Vec<String> = values.iter().map(|value| self.expr(value)).collect();
+				let names: Vec<String> = idents
+					.iter()
+					.map(|ident| self.identifier(ident.lexeme()))
+					.collect();
+				let declared: Vec<bool> = idents
+					.iter()
+					.map(|ident| self.declare(ident.lexeme()))
+					.collect();
+				let assignment = format!("[{}] = [{}];", names.join(", "), values.join(", "));
+				if declared.iter().all(|&declared| declared) {
+					self.line(&format!("let {assignment}"));
+				} else {
+					let new_names: Vec<&str> = names
+						.iter()
+						.zip(declared)
+						.filter(|(_, declared)| *declared)
+						.map(|(name, _)| name.as_str())
+						.collect();
+					if !new_names.is_empty() {
+						self.line(&format!("let {};", new_names.join(", ")));
+					}
+					self.line(&assignment);
+				}
+			}
+			Statement::Increment { ident, operator } => {
+				let name = self.identifier(ident.lexeme());
+				self.line(&format!("{name}{};", operator.lexeme()));
+			}
+			Statement::IndexAssignment {
+				object,
+				index,
+				value,
+				..
+			} => {
+				self.helpers.insert("$ilo_index", INDEX_HELPER);
+				let object = self.expr(object);
+				let index = self.expr(index);
+				let value = self.expr(value);
+				self.line(&format!("$ilo_set({object}, {index}, {value});"));
+			}
+			Statement::Block { statements } => {
+				self.line("{");
+				self.block(statements, &[]);
+				self.line("}");
+			}
+			Statement::If { .. } => {
+				self.if_statement(statement, "");
+				self.line("}");
+			}
+			Statement::While {
+				label,
+				condition,
+				body,
+			} => {
+				// loops stop as soon as the condition is not `true`
+				let condition = boolean(self.expr(condition), condition);
+				let label = match label {
+					Some(label) => format!("{}: ", self.identifier(label.lexeme())),
+					None => String::new(),
+				};
+				self.line(&format!("{label}while ({condition}) {{"));
+				self.block(block_statements(body), &[]);
+				self.line("}");
+			}
+			Statement::Repeat {
+				label,
+				body,
+				condition,
+				..
+			} => {
+				// the body runs again only while the condition is `false`
+				let condition = operand_of_equality(self.expr(condition), condition);
+				let label = match label {
+					Some(label) => format!("{}: ", self.identifier(label.lexeme())),
+					None => String::new(),
+				};
+				self.line(&format!("{label}do {{"));
+				self.block(block_statements(body), &[]);
+				self.line(&format!("}} while ({condition} === false);"));
+			}
+			Statement::For {
+				label,
+				variable,
+				iterable,
+				body,
+			} => {
+				let iterable = match iterable {
+					// like in ilo, strings are iterated over by Unicode scalar values
+					Iterable::Value(expr) => self.expr(expr),
+					Iterable::Range {
+						start, end, step, ..
+					} => {
+						self.helpers.insert("$ilo_range", RANGE_HELPER);
+						let start = self.expr(start);
+						let end = self.expr(end);
+						match step {
+							Some(step) => {
+								let step = self.expr(step);
+								format!("$ilo_range({start}, {end}, {step})")
+							}
+							None => format!("$ilo_range({start}, {end})"),
+						}
+					}
+				};
+				let label = match label {
+					Some(label) => format!("{}: ", self.identifier(label.lexeme())),
+					None => String::new(),
+				};
+				let variable_name = self.identifier(variable.lexeme());
+				self.line(&format!(
+					"{label}for (let {variable_name} of {iterable}) {{"
+				));
+				self.block(block_statements(body), core::slice::from_ref(variable));
+				self.line("}");
+			}
+			Statement::FunctionDeclaration {
+				ident,
+				params,
+				variadic,
+				body,
+			} => {
+				self.declare(ident.lexeme());
+				let mut parameters: Vec<String> = params
+					.iter()
+					.map(|param| self.identifier(param.lexeme()))
+					.collect();
+				if let (true, Some(last)) = (*variadic, parameters.last_mut()) {
+					last.insert_str(0, "...");
+				}
+				self.line(&format!(
+					"function {}({}) {{",
+					self.identifier(ident.lexeme()),
+					parameters.join(", ")
+				));
+				if body.iter().any(defers) {
+					self.deferring_block(body, params);
+				} else {
+					self.block(body, params);
+				}
+				self.line("}");
+			}
+			Statement::Attempt {
+				body,
+				variable,
+				handler,
+				..
+			} => {
+				self.line("try {");
+				self.block(block_statements(body), &[]);
+				self.line("} catch ($ilo_error) {");
+				// JavaScript errors only give their message, without their type and position
+				self.indentation += 1;
+				self.line(&format!(
+					"let {} = String($ilo_error?.message ?? $ilo_error);",
+					self.identifier(variable.lexeme())
+				));
+				self.indentation -= 1;
+				self.block(block_statements(handler), core::slice::from_ref(variable));
+				self.line("}");
+			}
+			Statement::Defer { body, .. } => {
+				self.line("$ilo_deferred.push(() => {");
+				self.block(block_statements(body), &[]);
+				self.line("});");
+			}
+			Statement::Match { value, arms, .. } => {
+				let value = self.expr(value);
+				self.line("{");
+				self.indentation += 1;
+				self.line(&format!("const $ilo_match = {value};"));
+				for (index, arm) in arms.iter().enumerate() {
+					let condition = self.arm_condition(&arm.patterns, arm.guard.as_ref());
+					if index == 0 {
+						self.line(&format!("if ({condition}) {{"));
+					} else {
+						self.line(&format!("}} else if ({condition}) {{"));
+					}
+					self.block(block_statements(&arm.body), &[]);
+				}
+				if !arms.is_empty() {
+					self.line("}");
+				}
+				self.indentation -= 1;
+				self.line("}");
+			}
+			Statement::Enum { ident, variants } => {
+				self.helpers.insert("$ilo_enum", ENUM_HELPER);
+				self.declare(ident.lexeme());
+				let variants: Vec<String> = variants
+					.iter()
+					.map(|variant| string_literal(variant.lexeme()))
+					.collect();
+				self.line(&format!(
+					"const {} = $ilo_enum({}, [{}]);",
+					self.identifier(ident.lexeme()),
+					string_literal(ident.lexeme()),
+					variants.join(", ")
+				));
+			}
+			Statement::Return { expr } => {
+				let expr = self.expr(expr);
+				self.line(&format!("return {expr};"));
+			}
+			Statement::Fail { message, .. } => {
+				let message = self.expr(message);
+				self.line(&format!("throw new Error({message});"));
+			}
+			Statement::Assert {
+				condition, message, ..
+			} => {
+				let condition = operand_of_equality(self.expr(condition), condition);
+				let message = match message {
+					Some(message) => format!("\"Assertion failed: \" + {}", self.expr(message)),
+					None => String::from("\"Assertion failed\""),
+				};
+				self.line(&format!(
+					"if ({condition} !== true) throw new Error({message});"
+				));
+			}
+			Statement::Import { path, alias, .. } => {
+				self.helpers.insert("$ilo_import", IMPORT_HELPER);
+				let TokenType::StringLiteral(path) = path.token_type() else {
+					unreachable!("the path of an import should be a string");
+				};
+				let module = format!("$ilo_import({})", string_literal(&path));
+				match alias {
+					Some(alias) => {
+						let name = self.identifier(alias.lexeme());
+						if self.declare(alias.lexeme()) {
+							self.line(&format!("let {name} = {module};"));
+						} else {
+							self.line(&format!("{name} = {module};"));
+						}
+					}
+					None => self.line(&format!("Object.assign(globalThis, {module});")),
+				}
+			}
+			Statement::Break { keyword, label } | Statement::Continue { keyword, label } => {
+				match label {
+					Some(label) => {
+						let label = self.identifier(label.lexeme());
+						self.line(&format!("{} {label};", keyword.lexeme()))
+					}
+					None => self.line(&format!("{};", keyword.lexeme())),
+				}
+			}
+		}
+	}
+
+	/// Writes an `if` statement and its `else if` branches, without the final closing brace
+	fn if_statement(&mut self, statement: &Statement, prefix: &str) {
+		if let Statement::If {
+			condition,
+			then,
+			otherwise,
+		} = statement
+		{
+			let condition = self.expr(condition);
+			self.line(&format!("{prefix}if ({condition}) {{"));
+			self.block(block_statements(then), &[]);
+			match otherwise.as_deref() {
+				Some(otherwise @ Statement::If { .. }) => self.if_statement(otherwise, "} else "),
+				Some(otherwise) => {
+					self.line("} else {");
+					self.block(block_statements(otherwise), &[]);
+				}
+				None => (),
+			}
+		}
+	}
+
+	/// Condition on `$ilo_match` that is true when one of the patterns matches it
+	/// Condition of an arm of a `match`: one of its patterns matches, and its guard is `true`
+	fn arm_condition(&mut self, patterns: &[Pattern], guard: Option<&Expr>) -> String {
+		let condition = self.patterns_condition(patterns);
+		match guard {
+			Some(guard) => {
+				let guard = boolean(self.expr(guard), guard);
+				format!("({condition}) && ({guard})")
+			}
+			None => condition,
+		}
+	}
+
+	fn patterns_condition(&mut self, patterns: &[Pattern]) -> String {
+		let conditions: Vec<String> = patterns
+			.iter()
+			.map(|pattern| match pattern {
+				Pattern::Default(_) => String::from("true"),
+				Pattern::Type(token) => match token.token_type() {
+					TokenType::Boolean => String::from(r#"typeof $ilo_match === "boolean""#),
+					TokenType::Number => String::from(r#"typeof $ilo_match === "number""#),
+					TokenType::String => String::from(r#"typeof $ilo_match === "string""#),
+					// empty values are `null`
+					_ => String::from("$ilo_match === null"),
+				},
+				Pattern::Value(expr) => {
+					let value = self.expr(expr);
+					if is_never_list(expr) {
+						format!("$ilo_match === {}", operand_of_equality(value, expr))
+					} else {
+						self.helpers.insert("$ilo_equals", EQUALS_HELPER);
+						format!("$ilo_equals($ilo_match, {value})")
+					}
+				}
+			})
+			.collect();
+		if conditions.len() == 1 {
+			conditions.concat()
+		} else {
+			conditions
+				.iter()
+				.map(|condition| format!("({condition})"))
+				.collect::<Vec<_>>()
+				.join(" || ")
+		}
+	}
+
+	fn expr(&mut self, expr: &Expr) -> String {
+		match expr {
+			Expr::Primary { value } => match value.token_type() {
+				TokenType::NumberLiteral(number) => number.to_string(),
+				TokenType::StringLiteral(string) => string_literal(&string),
+				TokenType::True => String::from("true"),
+				TokenType::False => String::from("false"),
+				// `empty` and the types of typed empty values
+				_ => String::from("null"),
+			},
+			Expr::Unary { operator, expr } => {
+				let operand = self.expr(expr);
+				if matches!(**expr, Expr::Unary { .. } | Expr::Binary { .. }) {
+					format!("{}({operand})", operator.lexeme())
+				} else {
+					format!("{}{operand}", operator.lexeme())
+				}
+			}
+			Expr::Conversion { target, expr, .. } => {
+				let (name, helper) = match target.token_type() {
+					TokenType::Boolean => ("$ilo_boolean", BOOLEAN_HELPER),
+					TokenType::Number => ("$ilo_number", NUMBER_HELPER),
+					_ => ("$ilo_string", STRING_HELPER),
+				};
+				self.helpers.insert(name, helper);
+				format!("{name}({})", self.expr(expr))
+			}
+			// empty values are `null`
+			Expr::Postfix { expr, .. } => format!("({} === null)", self.expr(expr)),
+			Expr::Binary {
+				left_expr,
+				operator,
+				right_expr,
+			} => {
+				let plain_left = self.expr(left_expr);
+				let plain_right = self.expr(right_expr);
+				let left = operand(plain_left.clone(), left_expr, operator);
+				let right = operand(plain_right.clone(), right_expr, operator);
+				match operator.token_type() {
+					TokenType::And | TokenType::Or => {
+						let left = boolean(left, left_expr);
+						let right = boolean(right, right_expr);
+						if operator.token_type() == TokenType::And {
+							format!("{left} && {right}")
+						} else {
+							format!("{left} || {right}")
+						}
+					}
+					TokenType::EqualEqual | TokenType::BangEqual
+						if !is_never_list(left_expr) && !is_never_list(right_expr) =>
+					{
+						self.helpers.insert("$ilo_equals", EQUALS_HELPER);
+						let negation = match operator.token_type() {
+							TokenType::BangEqual => "!",
+							_ => "",
+						};
+						format!("{negation}$ilo_equals({plain_left}, {plain_right})")
+					}
+					TokenType::EqualEqual => format!("{left} === {right}"),
+					TokenType::BangEqual => format!("{left} !== {right}"),
+					TokenType::Caret => format!("{left} ** {right}"),
+					TokenType::Percent => {
+						self.helpers.insert("$ilo_mod", MODULO_HELPER);
+						format!("$ilo_mod({plain_left}, {plain_right})")
+					}
+					TokenType::Star if is_number(left_expr) && is_number(right_expr) => {
+						format!("{left} * {right}")
+					}
+					TokenType::Star if is_string(left_expr) => format!("{left}.repeat({right})"),
+					TokenType::Star => {
+						self.helpers.insert("$ilo_mul", MULTIPLICATION_HELPER);
+						format!("$ilo_mul({plain_left}, {plain_right})")
+					}
+					_ => format!("{left} {} {right}", operator.lexeme()),
+				}
+			}
+			Expr::Grouping { expr } => format!("({})", self.expr(expr)),
+			Expr::Variable { name } => {
+				let name = name.lexeme();
+				if !self.is_declared(name) {
+					if let Some((replacement, helper)) = native_function(name) {
+						if let Some(helper) = helper {
+							self.helpers.insert(replacement, helper);
+						}
+						// helpers using other helpers
+						match name {
+							"glob" => {
+								self.helpers.insert("$ilo_glob_match", GLOB_MATCH_HELPER);
+							}
+							"multi_select" => {
+								self.helpers.insert("$ilo_ask", ASK_HELPER);
+							}
+							_ => {}
+						}
+						return replacement.to_string();
+					}
+				}
+				self.identifier(name)
+			}
+			Expr::Call {
+				callee, arguments, ..
+			} => {
+				let callee = self.expr(callee);
+				let arguments: Vec<String> = arguments
+					.iter()
+					.map(|argument| self.expr(argument))
+					.collect();
+				format!("{callee}({})", arguments.join(", "))
+			}
+			Expr::SafeCall {
+				receiver,
+				name,
+				arguments,
+				..
+			} => {
+				let receiver = self.expr(receiver);
+				let callee = self.expr(&Expr::Variable { name: name.clone() });
+				let arguments: Vec<String> = arguments
+					.iter()
+					.map(|argument| format!(", {}", self.expr(argument)))
+					.collect();
+				// empty values are `null`
+				format!(
+					"(($receiver) => $receiver === null ? null : {callee}($receiver{}))({receiver})",
+					arguments.concat()
+				)
+			}
+			Expr::Match { value, arms, .. } => {
+				let value = self.expr(value);
+				let mut branches = String::new();
+				for arm in arms {
+					let condition = self.arm_condition(&arm.patterns, arm.guard.as_ref());
+					let body = self.expr(&arm.body);
+					branches.push_str(&format!("{condition} ? {body} : "));
+				}
+				self.helpers.insert("$ilo_unmatched", UNMATCHED_HELPER);
+				format!("(($ilo_match) => {branches}$ilo_unmatched($ilo_match))({value})")
+			}
+			// the variants of an enum are the properties of its object
+			Expr::Field { object, name } => format!("{}.{}", self.expr(object), name.lexeme()),
+			Expr::Index {
+				object,
+				bracket,
+				index,
+			} => {
+				self.helpers.insert("$ilo_index", INDEX_HELPER);
+				let object = self.expr(object);
+				let index = self.expr(index);
+				if bracket.token_type() == TokenType::InterrogationBracket {
+					format!("$ilo_safe_index({object}, {index})")
+				} else {
+					format!("$ilo_index({object}, {index})")
+				}
+			}
+			Expr::List { elements, .. } => {
+				let elements: Vec<String> =
+					elements.iter().map(|element| self.expr(element)).collect();
+				format!("[{}]", elements.join(", "))
+			}
+			Expr::Lambda { params, body, .. } => {
+				let parameters: Vec<String> = params
+					.iter()
+					.map(|param| self.identifier(param.lexeme()))
+					.collect();
+				format!("(({}) => {})", parameters.join(", "), self.expr(body))
+			}
+		}
+	}
+}
+
+/// Operands that are operations are wrapped in parentheses, so that JavaScript’s precedence
+/// rules don’t apply
+fn operand(operand: String, expr: &Expr, operator: &Token) -> String {
+	match expr {
+		Expr::Binary { .. } => format!("({operand})"),
+		Expr::Unary { .. } if operator.token_type() == TokenType::Caret => format!("({operand})"),
+		_ => operand,
+	}
+}
+
+/// Operands of `===` that are operations are wrapped in parentheses
+fn operand_of_equality(operand: String, expr: &Expr) -> String {
+	match expr {
+		Expr::Binary { .. } => format!("({operand})"),
+		_ => operand,
+	}
+}
+
+/// In ilo, only `true` is truthy
+fn boolean(operand: String, expr: &Expr) -> String {
+	if is_boolean(expr) {
+		operand
+	} else {
+		format!("{operand} === true")
+	}
+}
+
+/// Whether the statement defers code to the end of the function or script it is in
+fn defers(statement: &Statement) -> bool {
+	match statement {
+		Statement::Defer { .. } => true,
+		Statement::Block { statements } => statements.iter().any(defers),
+		Statement::If {
+			then, otherwise, ..
+		} => defers(then) || otherwise.as_deref().is_some_and(defers),
+		Statement::While { body, .. }
+		| Statement::Repeat { body, .. }
+		| Statement::For { body, .. } => defers(body),
+		Statement::Match { arms, .. } => arms.iter().any(|arm| defers(&arm.body)),
+		Statement::Attempt { body, handler, .. } => defers(body) || defers(handler),
+		_ => false,
+	}
+}
+
+fn block_statements(statement: &Statement) -> &[Statement] {
+	match statement {
+		Statement::Block { statements } => statements,
+		_ => std::slice::from_ref(statement),
+	}
+}
+
+/// Whether the expression is known to be a number without running it
+fn is_number(expr: &Expr) -> bool {
+	match expr {
+		Expr::Primary { value } => matches!(value.token_type(), TokenType::NumberLiteral(_)),
+		Expr::Unary { operator, .. } => operator.token_type() == TokenType::Minus,
+		Expr::Binary {
+			left_expr,
+			operator,
+			right_expr,
+		} => match operator.token_type() {
+			TokenType::Minus | TokenType::Slash | TokenType::Percent | TokenType::Caret => true,
+			TokenType::Plus | TokenType::Star => is_number(left_expr) && is_number(right_expr),
+			_ => false,
+		},
+		Expr::Grouping { expr } => is_number(expr),
+		_ => false,
+	}
+}
+
+/// Whether the expression is known to be a boolean without running it
+fn is_boolean(expr: &Expr) -> bool {
+	match expr {
+		Expr::Primary { value } => matches!(value.token_type(), TokenType::True | TokenType::False),
+		Expr::Unary { operator, .. } => operator.token_type() == TokenType::Bang,
+		Expr::Binary { operator, .. } => matches!(
+			operator.token_type(),
+			TokenType::And
+				| TokenType::Or
+				| TokenType::EqualEqual
+				| TokenType::BangEqual
+				| TokenType::Greater
+				| TokenType::GreaterEqual
+				| TokenType::Less
+				| TokenType::LessEqual
+		),
+		Expr::Grouping { expr } => is_boolean(expr),
+		_ => false,
+	}
+}
+
+/// Whether the expression is known not to be a list, so that `===` compares it like ilo does
+fn is_never_list(expr: &Expr) -> bool {
+	match expr {
+		Expr::Primary { .. } | Expr::Field { .. } => true,
+		Expr::Grouping { expr } => is_never_list(expr),
+		_ => is_number(expr) || is_boolean(expr),
+	}
+}
+
+/// Whether the expression is known to be a string without running it
+fn is_string(expr: &Expr) -> bool {
+	match expr {
+		Expr::Primary { value } => matches!(value.token_type(), TokenType::StringLiteral(_)),
+		Expr::Grouping { expr } => is_string(expr),
+		_ => false,
+	}
+}
+
+/// String literal of JSON, whose escapes of control characters differ from JavaScript's
+fn json_string(string: &str) -> String {
+	let mut json = String::from("\"");
+	for character in string.chars() {
+		match character {
+			'"' => json.push_str("\\\""),
+			'\\' => json.push_str("\\\\"),
+			c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+			c => json.push(c),
+		}
+	}
+	json.push('"');
+	json
+}
+
+fn string_literal(string: &str) -> String {
+	let mut literal = String::from("\"");
+	for character in string.chars() {
+		match character {
+			'"' => literal.push_str("\\\""),
+			'\\' => literal.push_str("\\\\"),
+			'\n' => literal.push_str("\\n"),
+			'\r' => literal.push_str("\\r"),
+			'\t' => literal.push_str("\\t"),
+			c if c.is_control() => literal.push_str(&format!("\\u{{{:x}}}", c as u32)),
+			c => literal.push(c),
+		}
+	}
+	literal.push('"');
+	literal
+}
@@ -0,0 +1,169 @@
+//! WebAssembly bindings of ilo, powering the in-browser playground.
+//!
+//! Build with `wasm-pack build ilo/wasm --target web`, then call `run(source)` from JavaScript:
+//! the output of the script and its errors are returned instead of being displayed.
+
+use ilo::{Archive, Host, Interpreter, NullHost};
+use std::{cell::RefCell, rc::Rc};
+use wasm_bindgen::prelude::*;
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+extern "C" {
+	#[wasm_bindgen(js_namespace = Date)]
+	fn now() -> f64;
+
+	#[wasm_bindgen(js_namespace = performance, js_name = now)]
+	fn performance_now() -> f64;
+
+	#[wasm_bindgen(js_name = prompt)]
+	fn browser_prompt(message: &str) -> Option<String>;
+}
+
+#[wasm_bindgen]
+pub struct RunResult {
+	output: String,
+	errors: Vec<String>,
+}
+
+#[wasm_bindgen]
+impl RunResult {
+	/// Lines printed by the script, each ending with a line break
+	#[wasm_bindgen(getter)]
+	pub fn output(&self) -> String {
+		self.output.clone()
+	}
+
+	/// Errors that stopped the script, as displayed by the CLI
+	#[wasm_bindgen(getter)]
+	pub fn errors(&self) -> Vec<String> {
+		self.errors.clone()
+	}
+}
+
+/// Host of the playground: the output is collected, the input comes from the browser prompt,
+/// and commands, files and the network cannot be used
+struct PlaygroundHost {
+	output: Rc<RefCell<String>>,
+}
+
+impl Host for PlaygroundHost {
+	fn print(&mut self, text: &str) {
+		let mut output = self.output.borrow_mut();
+		output.push_str(text);
+		output.push('\n');
+	}
+
+	fn ask(&mut self, prompt: &str) -> String {
+		self.print(prompt);
+		#[cfg(target_arch = "wasm32")]
+		return browser_prompt(prompt).unwrap_or_default();
+		#[cfg(not(target_arch = "wasm32"))]
+		String::new()
+	}
+
+	fn ask_timeout(&mut self, prompt: &str, _seconds: f64) -> Option<String> {
+		// the browser prompt cannot time out
+		Some(self.ask(prompt))
+	}
+
+	fn multi_select(&mut self, _prompt: &str, _options: &[String]) -> Option<Vec<usize>> {
+		// the browser has no prompt to choose options
+		None
+	}
+
+	fn read_line(&mut self) -> Option<Option<String>> {
+		Some(None)
+	}
+
+	fn read_all(&mut self) -> Option<String> {
+		Some(String::new())
+	}
+
+	fn time(&mut self) -> f64 {
+		#[cfg(target_arch = "wasm32")]
+		return now() * 1_000_000.0;
+		#[cfg(not(target_arch = "wasm32"))]
+		ilo::interpreter::StdHost.time()
+	}
+
+	fn clock(&mut self) -> f64 {
+		#[cfg(target_arch = "wasm32")]
+		return performance_now();
+		#[cfg(not(target_arch = "wasm32"))]
+		ilo::interpreter::StdHost.clock()
+	}
+
+	fn run_command(&mut self, _program: &str, _args: &[&str]) -> Option<String> {
+		None
+	}
+
+	fn run_shell(&mut self, _command: &str) -> Option<String> {
+		None
+	}
+
+	fn watch(&mut self, _path: &str) -> Option<Box<dyn Iterator<Item = String>>> {
+		None
+	}
+
+	fn download(&mut self, _url: &str, _destination: &str) -> Option<u64> {
+		None
+	}
+
+	fn create_archive(&mut self, _format: Archive, _source: &str, _destination: &str) -> bool {
+		false
+	}
+
+	fn extract_archive(&mut self, _format: Archive, _archive: &str, _destination: &str) -> bool {
+		false
+	}
+
+	fn read_file(&mut self, _path: &str) -> Option<String> {
+		None
+	}
+
+	fn file_exists(&mut self, _path: &str) -> bool {
+		false
+	}
+
+	fn remove_file(&mut self, _path: &str) -> bool {
+		false
+	}
+
+	fn copy_file(&mut self, _source: &str, _destination: &str) -> bool {
+		false
+	}
+
+	fn move_file(&mut self, _source: &str, _destination: &str) -> bool {
+		false
+	}
+
+	fn list_dir(&mut self, _path: &str, _recursive: bool) -> Option<Vec<String>> {
+		None
+	}
+
+	// threads are not available in browsers
+	fn task_host(&self) -> Box<dyn Host + Send> {
+		Box::new(NullHost)
+	}
+}
+
+/// Runs a script in a sandboxed interpreter
+#[wasm_bindgen]
+pub fn run(source: &str) -> RunResult {
+	let output = Rc::new(RefCell::new(String::new()));
+	let mut interpreter = Interpreter::new();
+	interpreter.set_sandbox(true);
+	interpreter.set_host(Box::new(PlaygroundHost {
+		output: output.clone(),
+	}));
+	let result = ilo::run_in(&mut interpreter, source);
+
+	RunResult {
+		output: output.take(),
+		errors: match result {
+			Ok(_) => vec![],
+			Err(diagnostics) => diagnostics.iter().map(ToString::to_string).collect(),
+		},
+	}
+}
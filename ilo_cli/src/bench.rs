@@ -0,0 +1,149 @@
+use crate::display_command_error;
+use clap::Args as CLIArgs;
+use ilo::interpreter::Interpreter;
+use ilo::lexer::Lexer;
+use ilo::parser::{Parser, Statement};
+use std::{
+	fs,
+	process::exit,
+	time::{Duration, Instant},
+};
+
+#[derive(CLIArgs)]
+pub struct BenchArgs {
+	/// Path to the file to benchmark, ending in .ilo
+	pub file: String,
+	#[clap(short, long)]
+	/// Name of a function (without arguments) to benchmark. The script is run once to declare
+	/// it, then only the call is measured
+	function: Option<String>,
+	#[clap(short = 'n', long, default_value_t = 10)]
+	/// Number of measured runs
+	iterations: usize,
+	#[clap(short, long, default_value_t = 3)]
+	/// Number of runs executed before measuring, to warm up caches
+	warmup: usize,
+}
+
+pub fn run_bench(args: BenchArgs) {
+	let source = match fs::read_to_string(&args.file) {
+		Ok(source) => source,
+		Err(_) => {
+			display_command_error(format!("no file found at path `{}`", args.file));
+			exit(66);
+		}
+	};
+
+	if args.iterations == 0 {
+		display_command_error("the number of iterations must be at least 1".to_string());
+		exit(64);
+	}
+
+	let statements = parse(source).unwrap_or_else(|()| exit(65));
+
+	let durations = match args.function {
+		Some(ref function) => bench_function(statements, function, &args),
+		None => bench_script(statements, &args),
+	}
+	.unwrap_or_else(|()| exit(70));
+
+	display_statistics(&args, &durations);
+}
+
+fn parse(source: String) -> Result<Vec<Statement>, ()> {
+	let mut lexer = Lexer::new(source);
+	let tokens = lexer.scan_tokens()?;
+
+	let mut parser = Parser::new(tokens);
+	parser.parse()
+}
+
+/// Each run interprets the whole script in a fresh interpreter, so that runs do not share
+/// any state
+fn bench_script(statements: Vec<Statement>, args: &BenchArgs) -> Result<Vec<Duration>, ()> {
+	let mut durations = Vec::with_capacity(args.iterations);
+
+	for run in 0..args.warmup + args.iterations {
+		let mut interpreter = Interpreter::new();
+		let statements = statements.clone();
+
+		let start = Instant::now();
+		interpreter.interpret(statements)?;
+		let elapsed = start.elapsed();
+
+		if run >= args.warmup {
+			durations.push(elapsed);
+		}
+	}
+
+	Ok(durations)
+}
+
+/// The script is interpreted once, then only the call to the function is measured, in the
+/// same interpreter
+fn bench_function(
+	statements: Vec<Statement>,
+	function: &str,
+	args: &BenchArgs,
+) -> Result<Vec<Duration>, ()> {
+	let mut interpreter = Interpreter::new();
+	interpreter.interpret(statements)?;
+
+	let call = parse(format!("{function}()"))?;
+
+	let mut durations = Vec::with_capacity(args.iterations);
+
+	for run in 0..args.warmup + args.iterations {
+		let call = call.clone();
+
+		let start = Instant::now();
+		interpreter.interpret(call)?;
+		let elapsed = start.elapsed();
+
+		if run >= args.warmup {
+			durations.push(elapsed);
+		}
+	}
+
+	Ok(durations)
+}
+
+fn display_statistics(args: &BenchArgs, durations: &[Duration]) {
+	let mut milliseconds: Vec<f64> = durations.iter().map(|d| d.as_secs_f64() * 1e3).collect();
+	milliseconds.sort_by(|a, b| a.total_cmp(b));
+
+	let count = milliseconds.len() as f64;
+	let mean = milliseconds.iter().sum::<f64>() / count;
+	let median = if milliseconds.len().is_multiple_of(2) {
+		let middle = milliseconds.len() / 2;
+		(milliseconds[middle - 1] + milliseconds[middle]) / 2.0
+	} else {
+		milliseconds[milliseconds.len() / 2]
+	};
+	let variance = milliseconds
+		.iter()
+		.map(|ms| (ms - mean).powi(2))
+		.sum::<f64>()
+		/ count;
+
+	println!(
+		"Benchmark of {}{} ({} run{}, {} warmup run{})",
+		args.file,
+		match args.function {
+			Some(ref function) => format!(", function `{function}`"),
+			None => String::new(),
+		},
+		args.iterations,
+		if args.iterations == 1 { "" } else { "s" },
+		args.warmup,
+		if args.warmup == 1 { "" } else { "s" },
+	);
+	println!("  mean:   {mean:.3} ms");
+	println!("  median: {median:.3} ms");
+	println!("  stddev: {:.3} ms", variance.sqrt());
+	println!(
+		"  min:    {:.3} ms, max: {:.3} ms",
+		milliseconds[0],
+		milliseconds[milliseconds.len() - 1]
+	);
+}
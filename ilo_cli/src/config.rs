@@ -0,0 +1,66 @@
+use crate::{display_command_error, plugins};
+use std::{fs, path::Path, process::exit};
+
+const CONFIG_FILE: &str = "ilo.toml";
+
+/// Settings of a project, read from the `ilo.toml` file next to its scripts. They can also be
+/// enabled with the command line options of the same name.
+#[derive(Default)]
+pub struct Config {
+	pub strict: bool,
+	/// Names of the packs of natives to define before running the scripts
+	pub plugins: Vec<String>,
+}
+
+impl Config {
+	/// Reads the `ilo.toml` file of a directory, or returns the default settings if there is none.
+	/// An invalid file stops the program.
+	pub fn load(directory: &Path) -> Self {
+		let path = directory.join(CONFIG_FILE);
+		let Ok(source) = fs::read_to_string(&path) else {
+			return Self::default();
+		};
+
+		parse(&source).unwrap_or_else(|error| {
+			display_command_error(format!("invalid `{}`: {error}", path.display()));
+			exit(78);
+		})
+	}
+
+	/// Settings of the project containing a script
+	pub fn for_script(path: &str) -> Self {
+		let directory = Path::new(path)
+			.parent()
+			.filter(|directory| !directory.as_os_str().is_empty())
+			.unwrap_or(Path::new("."));
+		Self::load(directory)
+	}
+}
+
+fn parse(source: &str) -> Result<Config, String> {
+	let table: toml::Table = source
+		.parse()
+		.map_err(|error: toml::de::Error| error.message().trim().to_string())?;
+
+	let mut config = Config::default();
+	for (key, value) in table {
+		match (key.as_str(), value) {
+			("strict", toml::Value::Boolean(strict)) => config.strict = strict,
+			("strict", _) => return Err("`strict` must be `true` or `false`".to_string()),
+			("plugins", toml::Value::Array(names)) => {
+				for name in names {
+					let toml::Value::String(name) = name else {
+						return Err("`plugins` must be a list of names".to_string());
+					};
+					if !plugins::is_known(&name) {
+						return Err(format!("unknown plugin `{name}`"));
+					}
+					config.plugins.push(name);
+				}
+			}
+			("plugins", _) => return Err("`plugins` must be a list of names".to_string()),
+			_ => return Err(format!("unknown setting `{key}`")),
+		}
+	}
+	Ok(config)
+}
@@ -0,0 +1,156 @@
+use crate::{display_command_error, PromptTheme};
+use dialoguer::Input;
+use ilo::interpreter::{Debugger, Interpreter};
+use std::{collections::BTreeSet, process::exit};
+
+const HELP: &str = "Commands:
+  s, step            run until the next statement
+  n, next            run until the next statement, without entering function calls
+  c, continue        run until the next breakpoint
+  b, break [line]    add a breakpoint at a line, or list the breakpoints
+  d, delete <line>   remove the breakpoint at a line
+  p, print <expr>    evaluate an expression in the current frame and display its value
+  v, vars            display the variables of the current frame
+  l, list            display the code around the current line
+  h, help            display this message
+  q, quit            stop the program";
+
+enum Mode {
+	Step,
+	/// Stop at the next statement whose call depth is at most the given depth
+	Next(usize),
+	Continue,
+}
+
+pub struct CLIDebugger {
+	lines: Vec<String>,
+	breakpoints: BTreeSet<i64>,
+	mode: Mode,
+}
+
+impl CLIDebugger {
+	pub fn new(source: &str) -> Self {
+		println!("Type h to display the available commands.");
+		Self {
+			lines: source.lines().map(String::from).collect(),
+			breakpoints: BTreeSet::new(),
+			mode: Mode::Step,
+		}
+	}
+
+	fn display_lines(&self, current_line: i64, context: i64) {
+		let first = (current_line - context).max(1);
+		let last = (current_line + context).min(self.lines.len() as i64);
+		for line in first..=last {
+			println!(
+				"{}{} {:>4} | {}",
+				if line == current_line { ">" } else { " " },
+				if self.breakpoints.contains(&line) {
+					"*"
+				} else {
+					" "
+				},
+				line,
+				self.lines[line as usize - 1]
+			);
+		}
+	}
+
+	fn parse_line(&self, argument: &str) -> Option<i64> {
+		match argument.parse::<i64>() {
+			Ok(line) if line >= 1 && line <= self.lines.len() as i64 => Some(line),
+			_ => {
+				display_command_error(format!(
+					"expected a line number between 1 and {}",
+					self.lines.len()
+				));
+				None
+			}
+		}
+	}
+}
+
+impl Debugger for CLIDebugger {
+	fn on_statement(&mut self, interpreter: &mut Interpreter, line: i64, depth: usize) {
+		let should_stop = self.breakpoints.contains(&line)
+			|| match self.mode {
+				Mode::Step => true,
+				Mode::Next(next_depth) => depth <= next_depth,
+				Mode::Continue => false,
+			};
+
+		if !should_stop {
+			return;
+		}
+
+		self.display_lines(line, 0);
+
+		loop {
+			let input: String = Input::with_theme(&PromptTheme)
+				.with_prompt("debug> ")
+				.allow_empty(true)
+				.interact()
+				.unwrap_or_default();
+			let input = input.trim();
+			let (command, argument) = input.split_once(' ').unwrap_or((input, ""));
+			let argument = argument.trim();
+
+			match command {
+				"s" | "step" => {
+					self.mode = Mode::Step;
+					return;
+				}
+				"n" | "next" => {
+					self.mode = Mode::Next(depth);
+					return;
+				}
+				"c" | "continue" => {
+					self.mode = Mode::Continue;
+					return;
+				}
+				"b" | "break" if argument.is_empty() => {
+					if self.breakpoints.is_empty() {
+						println!("No breakpoints.");
+					}
+					for breakpoint in self.breakpoints.iter() {
+						self.display_lines(*breakpoint, 0);
+					}
+				}
+				"b" | "break" => {
+					if let Some(line) = self.parse_line(argument) {
+						self.breakpoints.insert(line);
+						println!("Breakpoint added at line {line}.");
+					}
+				}
+				"d" | "delete" => {
+					if let Some(line) = self.parse_line(argument) {
+						if self.breakpoints.remove(&line) {
+							println!("Breakpoint removed at line {line}.");
+						} else {
+							display_command_error(format!("no breakpoint at line {line}"));
+						}
+					}
+				}
+				"p" | "print" => {
+					if argument.is_empty() {
+						display_command_error("`print` expects an expression".to_string());
+					} else if let Ok(result) = interpreter.evaluate_source(argument.to_string()) {
+						println!("{result}");
+					}
+				}
+				"v" | "vars" => {
+					for (name, value_type, value) in interpreter.visible_variables() {
+						println!("{name}: {value_type} = {value}");
+					}
+				}
+				"l" | "list" => self.display_lines(line, 3),
+				"h" | "help" => println!("{HELP}"),
+				"q" | "quit" => exit(0),
+				"" => (),
+				_ => display_command_error(format!(
+					"unknown command `{command}`, type h to display the available commands"
+				)),
+			}
+		}
+	}
+}
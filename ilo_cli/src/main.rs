@@ -1,48 +1,452 @@
-use clap::Parser as CLIParser;
+mod bench;
+mod config;
+mod debug;
+mod plugins;
+
+use clap::{Args as CLIArgs, Parser as CLIParser, Subcommand, ValueEnum};
+use config::Config;
 use dialoguer::{theme::Theme, Input};
-use interpreter::Interpreter;
-use lexer::Lexer;
-use parser::Parser;
-use std::{fmt, fs, path::PathBuf, process::exit};
+use ilo::error_manager::{set_color_choice, set_current_file, ColorChoice};
+use ilo::interpreter::{Interpreter, ScopeKind};
+use ilo::lexer::Lexer;
+use ilo::parser::{Parser, Statement};
+use std::{
+	env, fmt,
+	fs::{self, File},
+	io::Write,
+	path::{Path, PathBuf},
+	process::exit,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc,
+	},
+};
 
 #[derive(CLIParser)]
+#[clap(args_conflicts_with_subcommands = true)]
 struct Args {
-	/// Path to the file to run, ending in .ilo. If this is not provided, the REPL will be
-	/// executed instead.
+	#[clap(subcommand)]
+	command: Option<Command>,
+	/// Path to the file to run, ending in .ilo or .iloc. If this is not provided, the REPL will be
+	/// executed instead, after running the `~/.ilorc` file (or the file at `ILO_RC`) if it exists.
 	file: Option<String>,
+	#[clap(long, value_name = "FILE", conflicts_with = "file")]
+	/// Replay the inputs of a REPL session recorded with `:record`, one input per line,
+	/// without waiting for user input
+	replay: Option<String>,
+	#[clap(flatten)]
+	options: RunOptions,
+}
+
+#[derive(CLIArgs)]
+struct RunOptions {
 	#[clap(short, long)]
 	/// Display the lexed tokens before running the script
 	tokens: bool,
 	#[clap(short, long)]
 	/// Display the parsed Abstract Syntax Tree (AST) before running the script
 	ast: bool,
+	#[clap(short, long)]
+	/// Disable what can reach outside of the interpreter (`cmd`, `shell`, `watch`, `download`,
+	/// the archives, `file_exists`, `remove_file`, `copy_file`, `move_file`, `list_dir`, `glob`,
+	/// `import` and the plugins), to run untrusted scripts
+	sandbox: bool,
+	#[clap(long, value_enum, default_value_t = ColorMode::Auto)]
+	/// Use colors in errors and in the `style` native. With `auto`, colors are used only when
+	/// the output is a terminal and the `NO_COLOR` environment variable is not set
+	color: ColorMode,
+	#[clap(long)]
+	/// Report warnings as errors, require `empty` to be given a type, forbid comparing values
+	/// of different types, and make impossible conversions runtime errors. Also enabled by
+	/// `strict = true` in an `ilo.toml` file next to the script
+	strict: bool,
+	#[clap(long)]
+	/// Display the variables of every scope when a runtime error stops the script
+	dump_env: bool,
+	#[clap(long, value_name = "FILE")]
+	/// Count the statements executed in each stack of function calls, and write the counts to
+	/// FILE as folded stacks, to be turned into a flamegraph by tools such as inferno
+	profile: Option<String>,
+	#[clap(long)]
+	/// Display the number of statements executed, function and native calls, strings created
+	/// and the deepest nesting of scopes once the script ends
+	stats: bool,
+	#[clap(trailing_var_arg = true, allow_hyphen_values = true)]
+	/// Arguments given to the script, as a list of strings returned by the `args` native
+	arguments: Vec<String>,
+	#[clap(skip)]
+	/// Packs of natives enabled by `plugins = [...]` in the `ilo.toml` file of the project
+	plugins: Vec<String>,
+}
+
+impl RunOptions {
+	/// Applies the settings of the `ilo.toml` file of the project
+	fn configure(&mut self, config: Config) {
+		self.strict |= config.strict;
+		self.plugins = config.plugins;
+	}
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ColorMode {
+	Auto,
+	Always,
+	Never,
+}
+
+impl From<ColorMode> for ColorChoice {
+	fn from(mode: ColorMode) -> Self {
+		match mode {
+			ColorMode::Auto => Self::Auto,
+			ColorMode::Always => Self::Always,
+			ColorMode::Never => Self::Never,
+		}
+	}
+}
+
+#[derive(Subcommand)]
+enum Command {
+	/// Run a script or a compiled `.iloc` file. If the path is a directory, its `main.ilo` file is used as the entry point
+	Run {
+		/// Path to the file to run, ending in .ilo or .iloc, or to a directory containing a `main.ilo` file
+		path: String,
+		#[clap(flatten)]
+		options: RunOptions,
+	},
+	/// Check a script for errors without running it, and display warnings about likely mistakes
+	Check {
+		/// Path to the file to check, ending in .ilo
+		file: String,
+		#[clap(long)]
+		/// Report warnings as errors, and check the rules of strict mode (see `ilo run --help`)
+		strict: bool,
+		#[clap(long)]
+		/// Only check the types (mixed-type operations, conditions that are not booleans, calls
+		/// with the wrong number of arguments...), without displaying warnings
+		types: bool,
+	},
+	/// Compile a script to a `.iloc` file, which can be run without being lexed and parsed again
+	Compile {
+		/// Path to the file to compile, ending in .ilo
+		file: String,
+		#[clap(short, long)]
+		/// Path of the compiled file. Defaults to the path of the script, with the `.iloc`
+		/// extension
+		output: Option<String>,
+	},
+	/// Display the opcodes of a compiled `.iloc` file, or of a script once compiled, with the
+	/// lines of the source code they come from
+	Disasm {
+		/// Path to the file to disassemble, ending in .ilo or .iloc
+		file: String,
+	},
+	/// Transpile a script to JavaScript
+	Transpile {
+		/// Path to the file to transpile, ending in .ilo
+		file: String,
+		#[clap(short, long)]
+		/// Path of the JavaScript file to write, next to its source map (`.js.map`), which maps
+		/// errors back to the script. If not provided, the code is displayed instead
+		output: Option<String>,
+	},
+	/// Run a script step by step, with breakpoints and variable inspection
+	Debug {
+		/// Path to the file to debug, ending in .ilo
+		file: String,
+		#[clap(flatten)]
+		options: RunOptions,
+	},
+	/// Run a script (or one of its functions) several times and display timing statistics
+	Bench(bench::BenchArgs),
 }
 
 fn main() {
 	let args = Args::parse();
 
-	if let Some(path) = args.file {
-		if let Some(ext) = PathBuf::from(path.clone()).extension() {
-			if ext != "ilo" {
-				display_command_error("file name must have `.ilo` extension.".to_string());
-				exit(64);
+	match args.command {
+		Some(Command::Run { path, mut options }) => {
+			set_color_choice(options.color.into());
+			let path = resolve_entry_point(path);
+			check_extension(&path, &["ilo", "iloc"]);
+			options.configure(Config::for_script(&path));
+			run_file(&path, &options);
+		}
+		Some(Command::Check {
+			file,
+			strict,
+			types,
+		}) => {
+			check_extension(&file, &["ilo"]);
+			let config = Config::for_script(&file);
+			check_file(&file, strict || config.strict, types, &config.plugins);
+		}
+		Some(Command::Compile { file, output }) => {
+			check_extension(&file, &["ilo"]);
+			compile_file(&file, output);
+		}
+		Some(Command::Disasm { file }) => {
+			check_extension(&file, &["ilo", "iloc"]);
+			disassemble_file(&file);
+		}
+		Some(Command::Transpile { file, output }) => {
+			check_extension(&file, &["ilo"]);
+			transpile_file(&file, output);
+		}
+		Some(Command::Debug { file, mut options }) => {
+			set_color_choice(options.color.into());
+			check_extension(&file, &["ilo"]);
+			options.configure(Config::for_script(&file));
+			debug_file(&file, &options);
+		}
+		Some(Command::Bench(bench_args)) => {
+			check_extension(&bench_args.file, &["ilo"]);
+			bench::run_bench(bench_args);
+		}
+		None => {
+			let mut options = args.options;
+			set_color_choice(options.color.into());
+			if let Some(path) = args.file {
+				check_extension(&path, &["ilo", "iloc"]);
+				options.configure(Config::for_script(&path));
+				run_file(&path, &options);
+			} else {
+				// REPL inputs belong to the project of the current directory
+				options.configure(Config::load(Path::new(".")));
+				match args.replay {
+					Some(path) => replay_session(&path, &options),
+					None => run_repl(&options),
+				}
 			}
-			run_file(&path, args.tokens, args.ast);
-		} else {
-			display_command_error("file name must have `.ilo` extension.".to_string());
-			exit(64);
 		}
+	}
+}
+
+/// Directories are run through their `main.ilo` file
+fn resolve_entry_point(path: String) -> String {
+	if !Path::new(&path).is_dir() {
+		return path;
+	}
+
+	let entry_point = Path::new(&path).join("main.ilo");
+	if !entry_point.is_file() {
+		display_command_error(format!(
+			"no `main.ilo` entry point found in directory `{path}`"
+		));
+		exit(66);
+	}
+
+	entry_point.to_string_lossy().into_owned()
+}
+
+fn check_extension(path: &str, extensions: &[&str]) {
+	if PathBuf::from(path)
+		.extension()
+		.is_none_or(|ext| !extensions.iter().any(|extension| ext == *extension))
+	{
+		let extensions: Vec<String> = extensions.iter().map(|ext| format!("`.{ext}`")).collect();
+		display_command_error(format!(
+			"file name must have {} extension.",
+			extensions.join(" or ")
+		));
+		exit(64);
+	}
+}
+
+fn run_file(path: &String, options: &RunOptions) {
+	if path.ends_with(".iloc") {
+		run_compiled_file(path, options);
+		return;
+	}
+
+	match fs::read_to_string(path) {
+		Ok(source) => {
+			set_current_file(Some(path.clone()));
+			let mut interpreter = new_interpreter(options);
+			let interrupted = handle_interrupts(&interpreter);
+			let result = run(source, options, &mut interpreter);
+			write_profile(&interpreter, options);
+			display_statistics(&interpreter);
+			if let Err(()) = result {
+				dump_environment(&mut interpreter);
+				exit(if interrupted.load(Ordering::Relaxed) {
+					130
+				} else {
+					70
+				});
+			}
+		}
+		Err(_) => {
+			display_command_error(format!("no file found at path `{path}`"));
+		}
+	}
+}
+
+/// Stops the script at its next statement on Ctrl-C, after calling its `on_interrupt` handler,
+/// instead of killing the process. A second Ctrl-C exits right away, for scripts waiting in a
+/// native. Returns whether the script was interrupted.
+fn handle_interrupts(interpreter: &Interpreter) -> Arc<AtomicBool> {
+	let flag = interpreter.interrupt_flag();
+	let interrupted = Arc::new(AtomicBool::new(false));
+	let handler_interrupted = interrupted.clone();
+	let handled = ctrlc::set_handler(move || {
+		if handler_interrupted.swap(true, Ordering::Relaxed) {
+			exit(130);
+		}
+		flag.store(true, Ordering::Relaxed);
+	});
+	if let Err(error) = handled {
+		display_command_error(format!("could not handle Ctrl-C ({error})"));
+	}
+	interrupted
+}
+
+fn run_compiled_file(path: &String, options: &RunOptions) {
+	let bytes = match fs::read(path) {
+		Ok(bytes) => bytes,
+		Err(_) => {
+			display_command_error(format!("no file found at path `{path}`"));
+			exit(66);
+		}
+	};
+
+	match ilo::bytecode::load(&bytes) {
+		Ok(chunk) => {
+			// lines and columns in errors refer to the source file
+			set_current_file(Some(chunk.source_path));
+			if options.ast {
+				display_ast(&chunk.statements);
+			}
+			let mut interpreter = new_interpreter(options);
+			let interrupted = handle_interrupts(&interpreter);
+			let result = interpreter.interpret(chunk.statements);
+			write_profile(&interpreter, options);
+			display_statistics(&interpreter);
+			if let Err(()) = result {
+				dump_environment(&mut interpreter);
+				exit(if interrupted.load(Ordering::Relaxed) {
+					130
+				} else {
+					70
+				});
+			}
+		}
+		Err(error) => {
+			display_command_error(format!("could not load `{path}`: {error}"));
+			exit(65);
+		}
+	}
+}
+
+/// Lexes and parses a file, exiting if it cannot be read or contains errors
+fn parse_file(path: &String) -> Vec<Statement> {
+	let source = match fs::read_to_string(path) {
+		Ok(source) => source,
+		Err(_) => {
+			display_command_error(format!("no file found at path `{path}`"));
+			exit(66);
+		}
+	};
+
+	set_current_file(Some(path.clone()));
+	Lexer::new(source)
+		.scan_tokens()
+		.and_then(|tokens| Parser::new(tokens).parse())
+		.unwrap_or_else(|()| exit(65))
+}
+
+fn check_file(path: &String, strict: bool, types: bool, enabled_plugins: &[String]) {
+	let statements = parse_file(path);
+	let linted = if types {
+		Ok(())
 	} else {
-		run_repl(args.tokens, args.ast);
+		ilo::checker::lint(&statements, strict)
+	};
+	// the natives of the plugins are known to the checker
+	let mut interpreter = Interpreter::new();
+	plugins::load(&mut interpreter, enabled_plugins);
+	let mut checker = ilo::checker_for(&interpreter);
+	checker.set_strict(strict);
+	checker.set_warnings(!types);
+	if let Err(()) = linted.and(checker.check(&statements)) {
+		exit(65);
+	}
+}
+
+fn compile_file(path: &String, output: Option<String>) {
+	let statements = parse_file(path);
+
+	let output = output.unwrap_or_else(|| {
+		PathBuf::from(path)
+			.with_extension("iloc")
+			.display()
+			.to_string()
+	});
+	if let Err(error) = fs::write(&output, ilo::bytecode::compile(path, &statements)) {
+		display_command_error(format!("could not write `{output}` ({error})"));
+		exit(73);
+	}
+}
+
+fn disassemble_file(path: &String) {
+	let bytes = if path.ends_with(".iloc") {
+		fs::read(path).unwrap_or_else(|_| {
+			display_command_error(format!("no file found at path `{path}`"));
+			exit(66);
+		})
+	} else {
+		ilo::bytecode::compile(path, &parse_file(path))
+	};
+
+	match ilo::bytecode::disassemble(&bytes) {
+		Ok(listing) => print!("{listing}"),
+		Err(error) => {
+			display_command_error(format!("could not load `{path}`: {error}"));
+			exit(65);
+		}
+	}
+}
+
+fn transpile_file(path: &String, output: Option<String>) {
+	let statements = parse_file(path);
+
+	let Some(output) = output else {
+		print!("{}", ilo::transpiler::transpile(&statements));
+		return;
+	};
+	let file_name = |path: &str| {
+		Path::new(path)
+			.file_name()
+			.map(|name| name.to_string_lossy().into_owned())
+			.unwrap_or_default()
+	};
+	let map_path = format!("{output}.map");
+	// the map is read from the directory of the JavaScript file, so the script is found by
+	// its absolute path
+	let source = fs::canonicalize(path)
+		.map(|source| source.display().to_string())
+		.unwrap_or_else(|_| path.clone());
+	let (mut javascript, source_map) =
+		ilo::transpiler::transpile_with_source_map(&statements, &source, &file_name(&output));
+	javascript.push_str(&format!("//# sourceMappingURL={}\n", file_name(&map_path)));
+
+	for (path, content) in [(&output, javascript), (&map_path, source_map)] {
+		if let Err(error) = fs::write(path, content) {
+			display_command_error(format!("could not write `{path}` ({error})"));
+			exit(73);
+		}
 	}
 }
 
-fn run_file(path: &String, show_tokens: bool, show_ast: bool) {
+fn debug_file(path: &String, options: &RunOptions) {
 	match fs::read_to_string(path) {
 		Ok(source) => {
-			if let Err(()) = run(source, show_tokens, show_ast) {
+			set_current_file(Some(path.clone()));
+			let mut interpreter = new_interpreter(options);
+			interpreter.set_debugger(Box::new(debug::CLIDebugger::new(&source)));
+			if let Err(()) = run(source, options, &mut interpreter) {
 				exit(70);
 			}
+			println!("Program finished.");
 		}
 		Err(_) => {
 			display_command_error(format!("no file found at path `{path}`"));
@@ -84,8 +488,13 @@ impl Theme for PromptTheme {
 	}
 }
 
-fn run_repl(show_tokens: bool, show_ast: bool) {
+fn run_repl(options: &RunOptions) {
 	println!("Type exit to stop the REPL.");
+	println!("Type :record <file> to save the next inputs to a script, and :stop to stop.");
+
+	let mut interpreter = new_interpreter(options);
+	run_startup_file(options, &mut interpreter);
+	let mut recording: Option<File> = None;
 
 	loop {
 		let input: String = Input::with_theme(&PromptTheme)
@@ -99,16 +508,185 @@ fn run_repl(show_tokens: bool, show_ast: bool) {
 			break;
 		}
 
-		let result = run(input, show_tokens, show_ast);
+		if let Some(path) = input.strip_prefix(":record") {
+			let path = path.trim();
+			if path.is_empty() {
+				display_command_error("`:record` expects a file path".to_string());
+				continue;
+			}
+			match File::create(path) {
+				Ok(file) => {
+					recording = Some(file);
+					println!("Recording inputs to `{path}`.");
+				}
+				Err(error) => display_command_error(format!("could not create `{path}` ({error})")),
+			}
+			continue;
+		}
+
+		if input == ":stop" {
+			if recording.take().is_some() {
+				println!("Recording stopped.");
+			} else {
+				display_command_error("no recording in progress".to_string());
+			}
+			continue;
+		}
+
+		let result = run(input.clone(), options, &mut interpreter);
 		if let Ok(result) = result {
 			if !result.is_empty() {
 				println!("{result}");
 			}
+			// only the inputs that ran successfully are recorded, so that the
+			// recording can be run as a script
+			if let Some(file) = recording.as_mut() {
+				if let Err(error) = writeln!(file, "{input}") {
+					display_command_error(format!("could not record input ({error})"));
+					recording = None;
+				}
+			}
 		}
 	}
 }
 
-fn run(source: String, show_tokens: bool, show_ast: bool) -> Result<String, ()> {
+/// Runs the file at `ILO_RC`, or `~/.ilorc`, in the REPL session so that the functions and
+/// variables it defines can be used. Its errors are displayed, but do not stop the REPL.
+fn run_startup_file(options: &RunOptions, interpreter: &mut Interpreter) {
+	let path = match env::var_os("ILO_RC") {
+		Some(path) => PathBuf::from(path),
+		None => match env::var_os("HOME").or_else(|| env::var_os("USERPROFILE")) {
+			Some(home) => Path::new(&home).join(".ilorc"),
+			None => return,
+		},
+	};
+	let source = match fs::read_to_string(&path) {
+		Ok(source) => source,
+		// the default file is optional, but a file given explicitly must exist
+		Err(_) if env::var_os("ILO_RC").is_none() => return,
+		Err(error) => {
+			display_command_error(format!(
+				"could not read startup file `{}` ({error})",
+				path.display()
+			));
+			return;
+		}
+	};
+
+	set_current_file(Some(path.display().to_string()));
+	// the errors are already reported
+	_ = run(source, options, interpreter);
+	set_current_file(None);
+}
+
+/// Runs every line of the file as a REPL input, in the same interpreter, displaying the
+/// inputs and their results as if they had been typed
+fn replay_session(path: &String, options: &RunOptions) {
+	let session = match fs::read_to_string(path) {
+		Ok(session) => session,
+		Err(_) => {
+			display_command_error(format!("no file found at path `{path}`"));
+			exit(66);
+		}
+	};
+
+	let mut interpreter = new_interpreter(options);
+	let mut has_error = false;
+
+	for input in session.lines().filter(|line| !line.trim().is_empty()) {
+		println!("ilo> {input}");
+		match run(input.to_string(), options, &mut interpreter) {
+			Ok(result) if !result.is_empty() => println!("{result}"),
+			Ok(_) => (),
+			Err(()) => has_error = true,
+		}
+	}
+
+	if has_error {
+		exit(70);
+	}
+}
+
+fn new_interpreter(options: &RunOptions) -> Interpreter {
+	let mut interpreter = Interpreter::new();
+	interpreter.set_sandbox(options.sandbox);
+	interpreter.set_strict(options.strict);
+	interpreter.set_snapshot_on_error(options.dump_env);
+	interpreter.set_arguments(options.arguments.clone());
+	// plugins can reach outside of the interpreter, so they are not loaded in sandbox mode
+	if !options.sandbox {
+		plugins::load(&mut interpreter, &options.plugins);
+	}
+	if options.profile.is_some() {
+		interpreter.enable_profiling();
+	}
+	if options.stats {
+		interpreter.enable_statistics();
+	}
+	interpreter
+}
+
+/// Writes the folded stacks counted with `--profile`, even if the script failed
+fn write_profile(interpreter: &Interpreter, options: &RunOptions) {
+	let (Some(path), Some(stacks)) = (&options.profile, interpreter.folded_stacks()) else {
+		return;
+	};
+	if let Err(error) = fs::write(path, stacks) {
+		display_command_error(format!("could not write `{path}` ({error})"));
+	}
+}
+
+/// Displays the counters of the execution enabled with `--stats`, even if the script failed
+fn display_statistics(interpreter: &Interpreter) {
+	let Some(statistics) = interpreter.statistics() else {
+		return;
+	};
+
+	println!("{SEPARATOR}");
+	println!("Statements executed: {}", statistics.statements);
+	println!("Function calls:      {}", statistics.function_calls);
+	println!("Native calls:        {}", statistics.native_calls);
+	println!("Strings created:     {}", statistics.strings);
+	println!("Max scope depth:     {}", statistics.max_scope_depth);
+	println!("{SEPARATOR}");
+}
+
+/// Displays the scopes saved when the script failed, with `--dump-env`
+fn dump_environment(interpreter: &mut Interpreter) {
+	let Some(scopes) = interpreter.take_error_snapshot() else {
+		return;
+	};
+
+	println!("{SEPARATOR}");
+	println!("Environment at the error:");
+	for scope in scopes {
+		println!(
+			"{} scope",
+			match scope.kind {
+				ScopeKind::Global => "Global",
+				ScopeKind::Function => "Function",
+				ScopeKind::Block => "Block",
+			}
+		);
+		if scope.variables.is_empty() {
+			println!("\t(no variables)");
+		}
+		for (name, value) in scope.variables {
+			println!("\t{name} = {}", value.inspect());
+		}
+	}
+	println!("{SEPARATOR}");
+}
+
+const SEPARATOR: &str = "----------------------------------";
+
+fn display_ast(statements: &[Statement]) {
+	println!("AST:");
+	println!("{:#?}", statements);
+	println!("{SEPARATOR}");
+}
+
+fn run(source: String, options: &RunOptions, interpreter: &mut Interpreter) -> Result<String, ()> {
 	let mut lexer = Lexer::new(source);
 	let tokens = lexer.scan_tokens();
 
@@ -118,16 +696,14 @@ fn run(source: String, show_tokens: bool, show_ast: bool) -> Result<String, ()>
 
 	let tokens = tokens.unwrap();
 
-	let separator = "----------------------------------";
-
-	if show_tokens {
-		println!("{separator}");
+	if options.tokens {
+		println!("{SEPARATOR}");
 		println!("Tokens:");
 		println!("{:#?}", tokens);
-		println!("{separator}");
+		println!("{SEPARATOR}");
 	}
 
-	let mut parser = Parser::new(tokens);
+	let mut parser = ilo::parser_for(interpreter, tokens);
 	let expr = parser.parse();
 
 	if let Err(()) = expr {
@@ -136,13 +712,19 @@ fn run(source: String, show_tokens: bool, show_ast: bool) -> Result<String, ()>
 
 	let statements = expr.unwrap();
 
-	if show_ast {
-		println!("AST:");
-		println!("{:#?}", statements);
-		println!("{separator}");
+	if options.ast {
+		display_ast(&statements);
 	}
 
-	let mut interpreter = Interpreter::new();
+	let mut checker = ilo::checker_for(interpreter);
+	checker.set_strict(options.strict);
+	// outside of strict mode, warnings are only displayed by `ilo check`
+	let linted = if options.strict {
+		ilo::checker::lint(&statements, true)
+	} else {
+		Ok(())
+	};
+	linted.and(checker.check(&statements))?;
 
 	interpreter.interpret(statements)
 }
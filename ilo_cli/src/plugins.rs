@@ -0,0 +1,22 @@
+use ilo::{Interpreter, NativePack};
+
+/// Packs of natives compiled into this binary, which projects enable with
+/// `plugins = ["name"]` in their `ilo.toml`. A pack is added by depending on the crate that
+/// implements it and listing it here.
+pub fn packs() -> Vec<Box<dyn NativePack>> {
+	Vec::new()
+}
+
+pub fn is_known(name: &str) -> bool {
+	packs().iter().any(|pack| pack.name() == name)
+}
+
+/// Defines the natives of the enabled packs. Unknown names are rejected when the `ilo.toml`
+/// file is read.
+pub fn load(interpreter: &mut Interpreter, enabled: &[String]) {
+	for pack in packs() {
+		if enabled.iter().any(|name| name == pack.name()) {
+			interpreter.load_pack(pack.as_ref());
+		}
+	}
+}